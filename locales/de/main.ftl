@@ -54,6 +54,7 @@ chat-footer = CX-5.8 ist eine KI und kann Fehler machen.
 # Must be captured via move_tr!().get() BEFORE async boundary, passed as ChatI18n
 chat-error-connection = ⚠ Verbindungsfehler: { $error }
 chat-error-request-failed = Anfrage fehlgeschlagen: { $error }
+rate-limited = Zu viele Anfragen. Bitte versuchen Sie es in { $seconds } s erneut.
 chat-error-invalid-reader = Ungültiger Stream-Reader
 chat-error-read-error = Lesefehler: { $error }
 chat-error-no-chunk-value = Kein Wert im Stream-Chunk
@@ -85,12 +86,40 @@ carousel-no-data = Keine Daten verfügbar
 # --- Comparison component (show_comparison) ---
 comparison-changes-from = Änderungen von
 comparison-changes-to = bis
+timeline-reports = { $count } Berichte
+pinned-reports = Angeheftet ({ $count }):
+pinned-timeline = Zeitverlauf
+pinned-timeline-prompt = Zeige, wie sich die angehefteten Berichte im Lauf der Zeit verändert haben
+pin-report = Für Zeitverlauf anheften
+unpin-report = Lösen
+pin-limit = Es können keine weiteren Berichte angeheftet werden
+compare-open = Bilder vergleichen
+compare-pin-two = Zwei Berichte anheften, um ihre Bilder zu vergleichen
+compare-viewer = Bildvergleich
+compare-mode-side-by-side = Nebeneinander
+compare-mode-swipe = Wischen
+compare-mode-overlay = Überlagern
+compare-swipe-position = Position des Schiebers
+compare-overlay-opacity = Deckkraft des neueren Bildes
+compare-zoom-in = Vergrößern
+compare-zoom-out = Verkleinern
+compare-zoom-reset = Zoom zurücksetzen
+compare-close = Schließen
+lightbox = Bildbetrachter
+lightbox-previous = Vorheriges Bild
+lightbox-next = Nächstes Bild
+lightbox-date = Datum
+lightbox-size = Größe
+lightbox-mime-type = Typ
+lightbox-download = Herunterladen
+annotations-show = Erkannte Elemente anzeigen
+annotations-layer = Erkannte Elemente
 
 # --- Description component (show_description.rs) ---
 description-report-label = Bericht
 
 # --- Shared detail labels ---
-# Used in: show_comparison (ComparisonDetailItem) and show_description.rs (DetailItem)
+# Used in: annotations.rs (AnnotatedDetail, AnnotationToggles) and show_timeline.rs
 detail-label-windows = Fenster:
 detail-label-doors = Türen:
 detail-label-radiators = Heizkörper:
@@ -99,20 +128,91 @@ detail-label-openings = Öffnungen:
 # --- Shared download button tooltip ---
 # Used in: show_comparison and show_description.rs
 download-as-markdown = Als Markdown herunterladen
+copy-link = Link kopieren
+copy-link-done = Link kopiert
+deep-link-compare-prompt = Vergleiche diese beiden Berichte
+context-restored-stale = Kontext von einem früheren Besuch wiederhergestellt; er ist möglicherweise veraltet
+context-restored-dropped = Kontext von einem früheren Besuch wiederhergestellt; { $count } gelöschte Einträge wurden entfernt
+context-restored-dismiss = Ausblenden
 
 # --- Tree component (show_tree.rs) ---
 # Fallback label for nodes where name is None
 tree-node-unnamed = (unbenannt)
+tree-filter = Objekte filtern
+tree-filter-empty = Keine passenden Objekte
+tree-label = Objekte
+tree-branch-new = Neues Objekt
+tree-branch-add = Unterobjekt hinzufügen
+tree-branch-rename = Umbenennen
+tree-branch-delete = Löschen
+tree-branch-delete-confirm = „{ $name }“ löschen?
+tree-branch-name = Objektname
+tree-branch-save = Speichern
+tree-branch-cancel = Abbrechen
+branch-not-found = Das Objekt existiert nicht mehr
+branch-not-owned = Das Objekt gehört einem anderen Benutzer
+branch-not-a-branch = Hier können nur Objekte geändert werden, keine Bilder
+branch-not-empty = Nur leere Objekte können gelöscht werden
+branch-invalid-name = Geben Sie einen Namen mit höchstens 120 Zeichen ein
+branch-invalid-move = Ein Objekt kann nicht in sich selbst oder unter seine Unterobjekte verschoben werden
+branch-no-change = Nichts zu ändern
+tree-shared-with-me = Mit mir geteilt
+share-open = Teilen
+share-title = „{ $name }“ teilen
+share-close = Schließen
+share-loading = Freigaben werden geladen...
+share-none = Noch mit niemandem geteilt
+share-kind = Teilen mit
+share-kind-user = Benutzer
+share-kind-group = Gruppe
+share-grantee = E-Mail-Adresse oder Gruppenname
+share-permission = Berechtigung
+share-permission-read = Darf ansehen
+share-permission-edit = Darf bearbeiten
+share-add = Teilen
+share-remove = Nicht mehr teilen
+share-not-found = Das Objekt existiert nicht mehr
+share-not-owned = Nur der Eigentümer kann dieses Objekt teilen
+share-not-a-branch = Nur Objekte können geteilt werden, keine Bilder
+share-invalid-grantee = Geben Sie eine E-Mail-Adresse oder einen Gruppennamen ohne Leerzeichen ein
+share-self = Dieses Objekt gehört Ihnen bereits
+share-read-only = Dieses Objekt ist nur zum Lesen für Sie freigegeben
 
 reports = Berichte
 reports-select-object = Objekt auswählen, um Berichte anzuzeigen
 reports-loading = Berichte werden geladen...
 reports-empty = Noch keine Berichte
+reports-read-only = Dieses Objekt ist nur zum Ansehen mit Ihnen geteilt
 reports-upload = Bericht hochladen
 reports-delete = Bericht löschen
 reports-image = Bericht
 reports-use-context = Diesen Bericht im Chat-Kontext verwenden
 reports-datetime = Datum und Uhrzeit des Berichts
+reports-exif-missing = Dieses Foto enthält keinen Aufnahmezeitpunkt; die aktuelle Zeit wird verwendet, sofern Sie sie nicht ändern.
+reports-drop-hint = Fotos hier ablegen
+reports-choose-files = Dateien auswählen
+reports-upload-all = Alle hochladen
+reports-retry-failed = Fehlgeschlagene wiederholen
+reports-clear-finished = Hochgeladene entfernen
+reports-remove-file = Aus dem Upload entfernen
+reports-file-reading = Aufnahmedatum wird gelesen…
+reports-file-pending = Wartet
+reports-file-done = Hochgeladen
+reports-file-checking = Suche nach Duplikaten…
+reports-duplicate-exact = Bereits hochgeladen als „{ $name }“
+reports-duplicate-similar = Ähnelt „{ $name }“
+reports-duplicate-skip = Überspringen
+reports-duplicate-replace = Ersetzen
+reports-duplicate-keep = Beide behalten
+reports-duplicate-replace-failed = Hochgeladen, aber das vorherige Bild konnte nicht entfernt werden.
+reports-upload-network-error = Netzwerkfehler, der Upload hat den Server nicht erreicht.
+reports-batch-summary = { $uploaded } von { $total } hochgeladen, { $failed } fehlgeschlagen
+upload-invalid-form = Der Upload konnte nicht gelesen werden.
+upload-missing-file = Bitte wählen Sie ein Bild zum Hochladen aus.
+upload-unsupported-type = Es können nur JPEG-, PNG-, WebP- und HEIC-Bilder hochgeladen werden.
+upload-too-large = Das Bild ist größer als { $max_mb } MB.
+upload-too-many-pixels = Die Bildauflösung überschreitet { $max_megapixels } Megapixel.
+upload-invalid-image = Die Bilddatei ist beschädigt oder unvollständig.
 reports-file = Berichtsbild
 reports-save-date = Berichtsdatum speichern
 reports-prev-page = Vorherige Berichtsseite
@@ -121,4 +221,47 @@ selected-context-unnamed = Unbenannt
 selected-context-remove = Ausgewähltes Objekt entfernen
 selected-report-open = Bild des ausgewählten Berichts öffnen
 selected-report-remove = Ausgewählten Bericht entfernen
-selected-report-close = Berichtsbild schließen
+
+sessions-title = Aktive Sitzungen
+sessions-loading = Sitzungen werden geladen...
+sessions-empty = Keine aktiven Sitzungen
+sessions-current = Dieses Gerät
+sessions-unknown-device = Unbekanntes Gerät
+sessions-created = Angemeldet:
+sessions-last-active = Zuletzt aktiv:
+sessions-ip = IP:
+sessions-revoke = Abmelden
+sessions-error = Sitzungen konnten nicht geladen werden: { $error }
+sessions-revoke-error = Sitzung konnte nicht beendet werden: { $error }
+
+admin-title = Benutzer und Sitzungen
+admin-refresh = Liste aktualisieren
+admin-loading = Sitzungen werden geladen...
+admin-empty = Keine angemeldeten Benutzer
+admin-forbidden = Diese Seite ist nur für Administratoren verfügbar.
+admin-expires-in = Token läuft in { $minutes } Min. ab
+admin-expired = Token abgelaufen
+admin-refresh-token = Refresh-Token vorhanden
+admin-no-refresh-token = Kein Refresh-Token
+admin-refreshing = Token wird erneuert...
+admin-stream-active = Streaming
+admin-stop-stream = Stream stoppen
+admin-terminate = Sitzung beenden
+admin-terminate-hint = Meldet dieses Gerät ab, API-Tokens bleiben gültig
+admin-revoke-access = Gesamten Zugriff entziehen
+admin-revoke-access-hint = Beendet alle Sitzungen und löscht alle API-Tokens dieses Benutzers
+admin-access-revoked = { $sessions } Sitzungen beendet und { $tokens } API-Tokens gelöscht
+admin-error = Aktion fehlgeschlagen: { $error }
+
+tokens-title = API-Tokens
+tokens-name = Tokenname
+tokens-lifetime = Gültigkeit des Tokens
+tokens-days = { $days } Tage
+tokens-create = Token erstellen
+tokens-copy-now = Kopieren Sie das Token jetzt, es wird nicht erneut angezeigt:
+tokens-loading = Tokens werden geladen...
+tokens-empty = Keine API-Tokens
+tokens-expires = Läuft ab:
+tokens-last-used = Zuletzt verwendet:
+tokens-revoke = Widerrufen
+tokens-error = API-Token-Anfrage fehlgeschlagen: { $error }
@@ -54,6 +54,7 @@ chat-footer = CX-5.8 is AI and can make mistakes.
 # Must be captured via move_tr!().get() BEFORE async boundary, passed as ChatI18n
 chat-error-connection = Connection error: { $error }
 chat-error-request-failed = Request failed: { $error }
+rate-limited = Too many requests. Please try again in { $seconds } s.
 chat-error-invalid-reader = Invalid stream reader
 chat-error-read-error = Read error: { $error }
 chat-error-no-chunk-value = No value in stream chunk
@@ -84,12 +85,40 @@ carousel-no-data = No data available
 # --- Comparison component (show_comparison) ---
 comparison-changes-from = Changes from
 comparison-changes-to = to
+timeline-reports = { $count } reports
+pinned-reports = Pinned ({ $count }):
+pinned-timeline = Timeline
+pinned-timeline-prompt = Show how the pinned reports changed over time
+pin-report = Pin for timeline
+unpin-report = Unpin
+pin-limit = No more reports can be pinned
+compare-open = Compare images
+compare-pin-two = Pin two reports to compare their images
+compare-viewer = Image comparison
+compare-mode-side-by-side = Side by side
+compare-mode-swipe = Swipe
+compare-mode-overlay = Overlay
+compare-swipe-position = Swipe position
+compare-overlay-opacity = Opacity of the newer image
+compare-zoom-in = Zoom in
+compare-zoom-out = Zoom out
+compare-zoom-reset = Reset zoom
+compare-close = Close
+lightbox = Image viewer
+lightbox-previous = Previous image
+lightbox-next = Next image
+lightbox-date = Date
+lightbox-size = Size
+lightbox-mime-type = Type
+lightbox-download = Download
+annotations-show = Show detected elements
+annotations-layer = Detected elements
 
 # --- Description component (show_description.rs) ---
 description-report-label = Report
 
 # --- Shared detail labels ---
-# Used in: show_comparison (ComparisonDetailItem) and show_description.rs (DetailItem)
+# Used in: annotations.rs (AnnotatedDetail, AnnotationToggles) and show_timeline.rs
 # Both files render Windows / Doors / Radiators / Openings — same domain, shared keys
 detail-label-windows = Windows:
 detail-label-doors = Doors:
@@ -99,20 +128,91 @@ detail-label-openings = Openings:
 # --- Shared download button tooltip ---
 # Used in: show_comparison and show_description.rs
 download-as-markdown = Download as Markdown
+copy-link = Copy link
+copy-link-done = Link copied
+deep-link-compare-prompt = Compare these two reports
+context-restored-stale = Context restored from an earlier visit; it may be outdated
+context-restored-dropped = Context restored from an earlier visit; { $count } deleted items were removed
+context-restored-dismiss = Dismiss
 
 # --- Tree component (show_tree.rs) ---
 # Fallback label for nodes where name is None
 tree-node-unnamed = (unnamed)
+tree-filter = Filter objects
+tree-filter-empty = No matching objects
+tree-label = Objects
+tree-branch-new = New object
+tree-branch-add = Add sub-object
+tree-branch-rename = Rename
+tree-branch-delete = Delete
+tree-branch-delete-confirm = Delete "{ $name }"?
+tree-branch-name = Object name
+tree-branch-save = Save
+tree-branch-cancel = Cancel
+branch-not-found = The object no longer exists
+branch-not-owned = The object belongs to another user
+branch-not-a-branch = Only objects can be changed here, not images
+branch-not-empty = Only empty objects can be deleted
+branch-invalid-name = Enter a name of at most 120 characters
+branch-invalid-move = An object cannot be moved into itself or below its own sub-objects
+branch-no-change = Nothing to change
+tree-shared-with-me = Shared with me
+share-open = Share
+share-title = Share "{ $name }"
+share-close = Close
+share-loading = Loading shares...
+share-none = Not shared with anyone yet
+share-kind = Share with
+share-kind-user = User
+share-kind-group = Group
+share-grantee = E-mail address or group name
+share-permission = Permission
+share-permission-read = Can view
+share-permission-edit = Can edit
+share-add = Share
+share-remove = Stop sharing
+share-not-found = The object no longer exists
+share-not-owned = Only the owner can share this object
+share-not-a-branch = Only objects can be shared, not images
+share-invalid-grantee = Enter an e-mail address or a group name without spaces
+share-self = You already own this object
+share-read-only = This object is shared with you read-only
 
 reports = Reports
 reports-select-object = Select an object to show reports
 reports-loading = Loading reports...
 reports-empty = No reports yet
+reports-read-only = This object is shared with you for viewing only
 reports-upload = Upload report
 reports-delete = Delete report
 reports-image = Report
 reports-use-context = Use this report in chat context
 reports-datetime = Report date and time
+reports-exif-missing = This photo has no capture time; the current time is used unless you change it.
+reports-drop-hint = Drop photos here
+reports-choose-files = Choose files
+reports-upload-all = Upload all
+reports-retry-failed = Retry failed
+reports-clear-finished = Clear uploaded
+reports-remove-file = Remove from upload
+reports-file-reading = Reading capture date…
+reports-file-pending = Waiting
+reports-file-done = Uploaded
+reports-file-checking = Checking for duplicates…
+reports-duplicate-exact = Already uploaded as “{ $name }”
+reports-duplicate-similar = Looks like “{ $name }”
+reports-duplicate-skip = Skip
+reports-duplicate-replace = Replace
+reports-duplicate-keep = Keep both
+reports-duplicate-replace-failed = Uploaded, but the previous image could not be removed.
+reports-upload-network-error = Network error, the upload did not reach the server.
+reports-batch-summary = { $uploaded } of { $total } uploaded, { $failed } failed
+upload-invalid-form = The upload could not be read.
+upload-missing-file = Please choose an image to upload.
+upload-unsupported-type = Only JPEG, PNG, WebP and HEIC images can be uploaded.
+upload-too-large = The image is larger than { $max_mb } MB.
+upload-too-many-pixels = The image resolution exceeds { $max_megapixels } megapixels.
+upload-invalid-image = The image file is damaged or incomplete.
 reports-file = Report image
 reports-save-date = Save report date
 reports-prev-page = Previous reports page
@@ -121,4 +221,47 @@ selected-context-unnamed = Unnamed
 selected-context-remove = Remove selected object
 selected-report-open = Open selected report image
 selected-report-remove = Remove selected report
-selected-report-close = Close report image
+
+sessions-title = Active sessions
+sessions-loading = Loading sessions...
+sessions-empty = No active sessions
+sessions-current = This device
+sessions-unknown-device = Unknown device
+sessions-created = Signed in:
+sessions-last-active = Last active:
+sessions-ip = IP:
+sessions-revoke = Sign out
+sessions-error = Sessions could not be loaded: { $error }
+sessions-revoke-error = Session could not be ended: { $error }
+
+admin-title = Users and sessions
+admin-refresh = Refresh list
+admin-loading = Loading sessions...
+admin-empty = No signed-in users
+admin-forbidden = This page is only available to administrators.
+admin-expires-in = Token expires in { $minutes } min
+admin-expired = Token expired
+admin-refresh-token = Refresh token available
+admin-no-refresh-token = No refresh token
+admin-refreshing = Refreshing token...
+admin-stream-active = Streaming
+admin-stop-stream = Stop stream
+admin-terminate = Terminate session
+admin-terminate-hint = Signs this device out, API tokens stay valid
+admin-revoke-access = Revoke all access
+admin-revoke-access-hint = Ends every session of this user and deletes their API tokens
+admin-access-revoked = Ended { $sessions } sessions and deleted { $tokens } API tokens
+admin-error = Action failed: { $error }
+
+tokens-title = API tokens
+tokens-name = Token name
+tokens-lifetime = Token lifetime
+tokens-days = { $days } days
+tokens-create = Create token
+tokens-copy-now = Copy the token now, it will not be shown again:
+tokens-loading = Loading tokens...
+tokens-empty = No API tokens
+tokens-expires = Expires:
+tokens-last-used = Last used:
+tokens-revoke = Revoke
+tokens-error = API token request failed: { $error }
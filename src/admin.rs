@@ -0,0 +1,286 @@
+use crate::sessions::ActiveSession;
+use serde::{Deserialize, Serialize};
+
+/// Session row of the admin console.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdminSession {
+    pub session: ActiveSession,
+    pub email: Option<String>,
+    pub roles: Vec<String>,
+    /// Seconds until the id_token expires, negative once expired.
+    pub expires_in_secs: Option<i64>,
+    pub has_refresh_token: bool,
+    pub refreshing: bool,
+    /// Agent request currently streamed to this session, if any.
+    pub stream_request_id: Option<String>,
+}
+
+/// Result of revoking all access of a user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevokedAccess {
+    pub sessions: usize,
+    pub api_tokens: usize,
+}
+
+/// Groups sessions by subject, users sorted by name.
+pub fn group_by_user(sessions: Vec<AdminSession>) -> Vec<(String, Vec<AdminSession>)> {
+    let mut users: Vec<(String, Vec<AdminSession>)> = Vec::new();
+    for session in sessions {
+        match users
+            .iter_mut()
+            .find(|(subject, _)| *subject == session.session.subject)
+        {
+            Some((_, list)) => list.push(session),
+            None => users.push((session.session.subject.clone(), vec![session])),
+        }
+    }
+    users.sort_by_key(|(subject, list)| {
+        list[0]
+            .session
+            .name
+            .clone()
+            .unwrap_or_else(|| subject.clone())
+            .to_lowercase()
+    });
+    users
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::{AdminSession, RevokedAccess};
+    use crate::auth::AuthenticatedUser;
+    use crate::auth_ssr::session_user;
+    use crate::sessions::{active_session, error_response, session_handle};
+    use crate::ssr::terminate_session;
+    use crate::state::AppState;
+    use axum::{
+        Json,
+        extract::{Path, State},
+        http::StatusCode,
+        response::{IntoResponse, Response},
+    };
+    use axum_extra::extract::CookieJar;
+    use std::time::Instant;
+
+    pub(crate) async fn admin_user(
+        state: &AppState,
+        jar: &CookieJar,
+    ) -> Result<(String, AuthenticatedUser), Response> {
+        match session_user(state, jar).await {
+            Some((session_id, user)) if user.is_admin() => Ok((session_id, user)),
+            Some(_) => Err(error_response(StatusCode::FORBIDDEN, "Admin role required")),
+            None => Err(error_response(
+                StatusCode::UNAUTHORIZED,
+                "Not authenticated",
+            )),
+        }
+    }
+
+    async fn find_session_id(state: &AppState, handle: &str) -> Option<String> {
+        state
+            .sessions
+            .lock()
+            .await
+            .keys()
+            .find(|id| session_handle(id) == handle)
+            .cloned()
+    }
+
+    pub async fn admin_sessions_handler(State(state): State<AppState>, jar: CookieJar) -> Response {
+        let current_id = match admin_user(&state, &jar).await {
+            Ok((session_id, _)) => session_id,
+            Err(response) => return response,
+        };
+
+        let streams: Vec<(String, Option<String>)> = {
+            let chat_sessions = state.chat_sessions.lock().await.clone();
+            let mut streams = Vec::with_capacity(chat_sessions.len());
+            for (session_id, chat_session) in chat_sessions {
+                let request_id = chat_session.current_request_id.read().await.clone();
+                streams.push((session_id, request_id));
+            }
+            streams
+        };
+
+        let now = Instant::now();
+        let sessions: Vec<AdminSession> = state
+            .sessions
+            .lock()
+            .await
+            .iter()
+            .filter_map(|(id, session)| {
+                let active = active_session(id, session, &current_id)?;
+                let mut roles: Vec<String> = session
+                    .roles
+                    .iter()
+                    .map(|role| role.as_str().to_string())
+                    .collect();
+                roles.sort();
+                Some(AdminSession {
+                    session: active,
+                    email: session.email.clone(),
+                    roles,
+                    expires_in_secs: session.id_token_expires_at.map(|exp_at| {
+                        if exp_at >= now {
+                            (exp_at - now).as_secs() as i64
+                        } else {
+                            -((now - exp_at).as_secs() as i64)
+                        }
+                    }),
+                    has_refresh_token: session.refresh_token.is_some(),
+                    refreshing: session
+                        .is_refreshing
+                        .try_lock()
+                        .map(|flag| *flag)
+                        .unwrap_or(true),
+                    stream_request_id: streams
+                        .iter()
+                        .find(|(session_id, _)| session_id == id)
+                        .and_then(|(_, request_id)| request_id.clone()),
+                })
+            })
+            .collect();
+
+        Json(sessions).into_response()
+    }
+
+    /// Cancels the agent request streamed to the session, the session stays signed in.
+    pub async fn admin_stop_stream_handler(
+        State(state): State<AppState>,
+        jar: CookieJar,
+        Path(handle): Path<String>,
+    ) -> Response {
+        let admin = match admin_user(&state, &jar).await {
+            Ok((_, user)) => user,
+            Err(response) => return response,
+        };
+        let Some(session_id) = find_session_id(&state, &handle).await else {
+            return error_response(StatusCode::NOT_FOUND, "Session not found");
+        };
+        let chat_session = state.chat_sessions.lock().await.get(&session_id).cloned();
+        let request_id = match chat_session {
+            Some(chat_session) => chat_session.current_request_id.read().await.clone(),
+            None => None,
+        };
+        let Some(request_id) = request_id else {
+            return error_response(StatusCode::NOT_FOUND, "No active stream");
+        };
+
+        crate::stop::cancel_request(&state, &request_id);
+        tracing::info!(admin = %admin.subject, request_id = %request_id, "admin: stream stopped");
+        StatusCode::NO_CONTENT.into_response()
+    }
+
+    pub async fn admin_terminate_session_handler(
+        State(state): State<AppState>,
+        jar: CookieJar,
+        Path(handle): Path<String>,
+    ) -> Response {
+        let admin = match admin_user(&state, &jar).await {
+            Ok((_, user)) => user,
+            Err(response) => return response,
+        };
+        let Some(session_id) = find_session_id(&state, &handle).await else {
+            return error_response(StatusCode::NOT_FOUND, "Session not found");
+        };
+
+        let session = terminate_session(&state, &session_id).await;
+        tracing::info!(
+            admin = %admin.subject,
+            subject = ?session.and_then(|session| session.subject),
+            "admin: session terminated"
+        );
+        StatusCode::NO_CONTENT.into_response()
+    }
+
+    /// Ends every session of the session's user and deletes their API tokens.
+    pub async fn admin_revoke_access_handler(
+        State(state): State<AppState>,
+        jar: CookieJar,
+        Path(handle): Path<String>,
+    ) -> Response {
+        let admin = match admin_user(&state, &jar).await {
+            Ok((_, user)) => user,
+            Err(response) => return response,
+        };
+        let (subject, session_ids) = {
+            let sessions = state.sessions.lock().await;
+            let Some(subject) = sessions
+                .iter()
+                .find(|(id, _)| session_handle(id) == handle)
+                .and_then(|(_, session)| session.subject.clone())
+            else {
+                return error_response(StatusCode::NOT_FOUND, "Session not found");
+            };
+            let session_ids: Vec<String> = sessions
+                .iter()
+                .filter(|(_, session)| session.subject.as_ref() == Some(&subject))
+                .map(|(id, _)| id.clone())
+                .collect();
+            (subject, session_ids)
+        };
+
+        for session_id in &session_ids {
+            terminate_session(&state, session_id).await;
+        }
+        let revoked = RevokedAccess {
+            sessions: session_ids.len(),
+            api_tokens: state.api_tokens.revoke_subject(&subject).await,
+        };
+        tracing::info!(
+            admin = %admin.subject,
+            subject = %subject,
+            sessions = revoked.sessions,
+            api_tokens = revoked.api_tokens,
+            "admin: access revoked"
+        );
+        Json(revoked).into_response()
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) use ssr::admin_user;
+#[cfg(feature = "ssr")]
+pub use ssr::{
+    admin_revoke_access_handler, admin_sessions_handler, admin_stop_stream_handler,
+    admin_terminate_session_handler,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(subject: &str, name: &str, handle: &str) -> AdminSession {
+        AdminSession {
+            session: ActiveSession {
+                handle: handle.to_string(),
+                subject: subject.to_string(),
+                name: Some(name.to_string()),
+                created_at: 0,
+                last_activity: 0,
+                user_agent: None,
+                ip: None,
+                current: false,
+            },
+            email: None,
+            roles: Vec::new(),
+            expires_in_secs: None,
+            has_refresh_token: false,
+            refreshing: false,
+            stream_request_id: None,
+        }
+    }
+
+    #[test]
+    fn groups_sessions_per_user_sorted_by_name() {
+        let users = group_by_user(vec![
+            session("s-2", "zoe", "a"),
+            session("s-1", "Anna", "b"),
+            session("s-2", "zoe", "c"),
+        ]);
+
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[0].0, "s-1");
+        assert_eq!(users[1].1.len(), 2);
+    }
+}
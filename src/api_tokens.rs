@@ -0,0 +1,588 @@
+use serde::{Deserialize, Serialize};
+
+/// Scopes a personal API token can be granted.
+pub const API_TOKEN_SCOPES: [&str; 4] = ["reports:read", "reports:write", "chat", "models"];
+pub const MAX_TOKEN_LIFETIME_DAYS: u32 = 365;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: u32,
+}
+
+/// Returned once on creation, the plain token is never stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    pub token: String,
+    pub info: ApiTokenInfo,
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::*;
+    use crate::auth::{AuthenticatedUser, Role};
+    use crate::auth_ssr::session_user;
+    use crate::sessions::error_response;
+    use crate::state::AppState;
+    use axum::{
+        Json,
+        extract::{Path, State},
+        http::{Method, StatusCode, header::AUTHORIZATION},
+        middleware::Next,
+        response::{IntoResponse, Response},
+    };
+    use axum_extra::extract::CookieJar;
+    use sha2::{Digest, Sha256};
+    use std::path::PathBuf;
+    use tokio::sync::Mutex;
+    use uuid::Uuid;
+
+    const TOKEN_PREFIX: &str = "gmr_";
+    /// `last_used_at` is persisted at this resolution, so busy tokens don't rewrite the store.
+    const LAST_USED_RESOLUTION_SECS: i64 = 60 * 60;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct StoredApiToken {
+        id: String,
+        label: String,
+        subject: String,
+        name: String,
+        email: Option<String>,
+        roles: Vec<String>,
+        scopes: Vec<String>,
+        secret_hash: String,
+        created_at: i64,
+        expires_at: i64,
+        last_used_at: Option<i64>,
+    }
+
+    impl StoredApiToken {
+        fn info(&self) -> ApiTokenInfo {
+            ApiTokenInfo {
+                id: self.id.clone(),
+                name: self.label.clone(),
+                scopes: self.scopes.clone(),
+                created_at: self.created_at,
+                expires_at: self.expires_at,
+                last_used_at: self.last_used_at,
+            }
+        }
+    }
+
+    /// Identity of a request authenticated by `Authorization: Bearer`.
+    #[derive(Debug, Clone)]
+    pub struct ApiPrincipal {
+        pub token_id: String,
+        pub user: AuthenticatedUser,
+        pub scopes: Vec<String>,
+    }
+
+    impl ApiPrincipal {
+        /// Key used in place of the session cookie for per-client state.
+        pub fn session_key(&self) -> String {
+            format!("api-token:{}", self.token_id)
+        }
+
+        /// User id the agent knows this user by (the UI uses the e-mail).
+        pub fn user_id(&self) -> String {
+            self.user
+                .email
+                .clone()
+                .unwrap_or_else(|| self.user.subject.clone())
+        }
+    }
+
+    /// User id the agent knows the caller by, from the token or else the session.
+    pub async fn agent_user_id(
+        state: &AppState,
+        jar: &CookieJar,
+        principal: Option<&ApiPrincipal>,
+    ) -> Option<String> {
+        match principal {
+            Some(principal) => Some(principal.user_id()),
+            None => {
+                let (_, user) = session_user(state, jar).await?;
+                Some(user.email.unwrap_or(user.subject))
+            }
+        }
+    }
+
+    /// Token metadata persisted as JSON, secrets only as SHA-256 digests.
+    pub struct ApiTokenStore {
+        path: PathBuf,
+        tokens: Mutex<Vec<StoredApiToken>>,
+    }
+
+    fn hash_secret(secret: &str) -> String {
+        hex::encode(Sha256::digest(secret.as_bytes()))
+    }
+
+    fn split_token(token: &str) -> Option<(&str, &str)> {
+        token.strip_prefix(TOKEN_PREFIX)?.split_once('.')
+    }
+
+    impl ApiTokenStore {
+        pub fn load(path: impl Into<PathBuf>) -> Self {
+            let path = path.into();
+            let tokens = match std::fs::read(&path) {
+                Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                    tracing::error!(path = %path.display(), error = %e, "api tokens: invalid store file");
+                    Vec::new()
+                }),
+                Err(_) => Vec::new(),
+            };
+            Self {
+                path,
+                tokens: Mutex::new(tokens),
+            }
+        }
+
+        async fn persist(&self, tokens: &[StoredApiToken]) -> anyhow::Result<()> {
+            if let Some(parent) = self.path.parent()
+                && !parent.as_os_str().is_empty()
+            {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let tmp = self.path.with_extension("json.tmp");
+            tokio::fs::write(&tmp, serde_json::to_vec_pretty(tokens)?).await?;
+            tokio::fs::rename(&tmp, &self.path).await?;
+            Ok(())
+        }
+
+        pub async fn create(
+            &self,
+            user: &AuthenticatedUser,
+            request: CreateApiTokenRequest,
+            now: i64,
+        ) -> Result<CreatedApiToken, String> {
+            let label = request.name.trim().to_string();
+            if label.is_empty() || label.chars().count() > 64 {
+                return Err("Token name must be 1-64 characters".to_string());
+            }
+            if request.scopes.is_empty()
+                || request
+                    .scopes
+                    .iter()
+                    .any(|scope| !API_TOKEN_SCOPES.contains(&scope.as_str()))
+            {
+                return Err("Unknown or missing scope".to_string());
+            }
+            if request.expires_in_days == 0 || request.expires_in_days > MAX_TOKEN_LIFETIME_DAYS {
+                return Err(format!("Lifetime must be 1-{MAX_TOKEN_LIFETIME_DAYS} days"));
+            }
+
+            let id = Uuid::now_v7().simple().to_string();
+            let mut secret_bytes = [0_u8; 32];
+            getrandom::fill(&mut secret_bytes).map_err(|e| e.to_string())?;
+            let secret = hex::encode(secret_bytes);
+            let mut roles: Vec<String> = user
+                .roles
+                .iter()
+                .map(|role| role.as_str().to_string())
+                .collect();
+            roles.sort();
+            let mut scopes = request.scopes;
+            scopes.sort();
+            scopes.dedup();
+
+            let stored = StoredApiToken {
+                id: id.clone(),
+                label,
+                subject: user.subject.clone(),
+                name: user.name.clone(),
+                email: user.email.clone(),
+                roles,
+                scopes,
+                secret_hash: hash_secret(&secret),
+                created_at: now,
+                expires_at: now + i64::from(request.expires_in_days) * 24 * 60 * 60,
+                last_used_at: None,
+            };
+            let info = stored.info();
+
+            let mut tokens = self.tokens.lock().await;
+            tokens.push(stored);
+            if let Err(e) = self.persist(&tokens).await {
+                tokens.pop();
+                tracing::error!(error = %e, "api tokens: failed to persist store");
+                return Err("Failed to store token".to_string());
+            }
+
+            Ok(CreatedApiToken {
+                token: format!("{TOKEN_PREFIX}{id}.{secret}"),
+                info,
+            })
+        }
+
+        pub async fn list(&self, subject: &str) -> Vec<ApiTokenInfo> {
+            self.tokens
+                .lock()
+                .await
+                .iter()
+                .filter(|token| token.subject == subject)
+                .map(StoredApiToken::info)
+                .collect()
+        }
+
+        /// Removes a token owned by `subject`, or any token when `any_owner` is set.
+        pub async fn revoke(&self, subject: &str, id: &str, any_owner: bool) -> bool {
+            let mut tokens = self.tokens.lock().await;
+            let Some(index) = tokens
+                .iter()
+                .position(|token| token.id == id && (any_owner || token.subject == subject))
+            else {
+                return false;
+            };
+            tokens.remove(index);
+            if let Err(e) = self.persist(&tokens).await {
+                tracing::error!(error = %e, "api tokens: failed to persist store");
+            }
+            true
+        }
+
+        /// Removes every token of `subject`, when its sessions are ended by the IdP or an admin.
+        pub async fn revoke_subject(&self, subject: &str) -> usize {
+            let mut tokens = self.tokens.lock().await;
+            let before = tokens.len();
+            tokens.retain(|token| token.subject != subject);
+            let revoked = before - tokens.len();
+            if revoked > 0
+                && let Err(e) = self.persist(&tokens).await
+            {
+                tracing::error!(error = %e, "api tokens: failed to persist store");
+            }
+            revoked
+        }
+
+        pub async fn authenticate(&self, token: &str, now: i64) -> Option<ApiPrincipal> {
+            let (id, secret) = split_token(token)?;
+            let mut tokens = self.tokens.lock().await;
+            let stored = tokens.iter_mut().find(|stored| stored.id == id)?;
+            if stored.secret_hash != hash_secret(secret) || stored.expires_at <= now {
+                return None;
+            }
+            let stale = stored
+                .last_used_at
+                .is_none_or(|at| now - at >= LAST_USED_RESOLUTION_SECS);
+            if stale {
+                stored.last_used_at = Some(now);
+            }
+            let stored = stored.clone();
+            if stale && let Err(e) = self.persist(&tokens).await {
+                tracing::error!(error = %e, "api tokens: failed to persist store");
+            }
+
+            Some(ApiPrincipal {
+                token_id: stored.id.clone(),
+                user: AuthenticatedUser {
+                    subject: stored.subject.clone(),
+                    name: stored.name.clone(),
+                    email: stored.email.clone(),
+                    roles: stored
+                        .roles
+                        .iter()
+                        .map(|role| Role::from_string(role))
+                        .collect(),
+                },
+                scopes: stored.scopes.clone(),
+            })
+        }
+    }
+
+    /// Scope a bearer token needs for an API route, `None` for routes tokens cannot use.
+    pub fn required_scope(method: &Method, path: &str) -> Option<&'static str> {
+        if path.starts_with("/api/proxy/tree/") {
+            (method == Method::GET).then_some("reports:read")
+        } else if path.starts_with("/api/proxy/reports/") || path.starts_with("/api/proxy/shares/")
+        {
+            match *method {
+                Method::GET => Some("reports:read"),
+                _ => Some("reports:write"),
+            }
+        } else if path.starts_with("/api/proxy/images/") || path.starts_with("/api/proxy/branches/")
+        {
+            Some("reports:write")
+        } else if path == "/api/chat_stream"
+            || path == "/api/stop"
+            || path.starts_with("/api/chat_context/")
+        {
+            Some("chat")
+        } else if path.starts_with("/api/models/") {
+            Some("models")
+        } else {
+            None
+        }
+    }
+
+    /// Per-user routes (`/api/proxy/tree/{user_id}`, `/api/models/{user_id}`)
+    /// only accept the token owner's id unless the owner is an admin.
+    fn owns_path(principal: &ApiPrincipal, path: &str) -> bool {
+        let user_id = path
+            .strip_prefix("/api/proxy/tree/")
+            .or_else(|| path.strip_prefix("/api/models/"));
+        match user_id {
+            Some(user_id) => {
+                let user_id = percent_encoding::percent_decode_str(user_id).decode_utf8_lossy();
+                principal.user.is_admin() || user_id == principal.user_id()
+            }
+            None => true,
+        }
+    }
+
+    /// Token of an `Authorization: Bearer …` header; other schemes are ignored
+    /// and the request is authenticated by its session.
+    pub(crate) fn bearer_token(headers: &axum::http::HeaderMap) -> Option<&str> {
+        headers
+            .get(AUTHORIZATION)?
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")
+            .map(str::trim)
+    }
+
+    /// Accepts either a signed-in browser session or a bearer API token
+    /// with the scope the route requires.
+    pub async fn api_auth(
+        State(state): State<AppState>,
+        mut req: axum::extract::Request,
+        next: Next,
+    ) -> Response {
+        let Some(token) = bearer_token(req.headers()).map(str::to_string) else {
+            let jar = CookieJar::from_headers(req.headers());
+            return match session_user(&state, &jar).await {
+                Some(_) => next.run(req).await,
+                None => error_response(StatusCode::UNAUTHORIZED, "Not authenticated"),
+            };
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        let Some(principal) = state.api_tokens.authenticate(&token, now).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Invalid or expired API token");
+        };
+        let path = req.uri().path();
+        let allowed = required_scope(req.method(), path)
+            .is_some_and(|scope| principal.scopes.iter().any(|granted| granted == scope));
+        if !allowed || !owns_path(&principal, path) {
+            return error_response(
+                StatusCode::FORBIDDEN,
+                "API token scope does not allow this request",
+            );
+        }
+
+        req.extensions_mut().insert(principal);
+        next.run(req).await
+    }
+
+    pub async fn list_api_tokens_handler(
+        State(state): State<AppState>,
+        jar: CookieJar,
+    ) -> Response {
+        let Some((_, user)) = session_user(&state, &jar).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        Json(state.api_tokens.list(&user.subject).await).into_response()
+    }
+
+    pub async fn create_api_token_handler(
+        State(state): State<AppState>,
+        jar: CookieJar,
+        Json(request): Json<CreateApiTokenRequest>,
+    ) -> Response {
+        let Some((_, user)) = session_user(&state, &jar).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        let now = chrono::Utc::now().timestamp();
+        match state.api_tokens.create(&user, request, now).await {
+            Ok(created) => {
+                tracing::info!(subject = %user.subject, token_id = %created.info.id, "api tokens: token created");
+                (StatusCode::CREATED, Json(created)).into_response()
+            }
+            Err(e) => error_response(StatusCode::BAD_REQUEST, &e),
+        }
+    }
+
+    pub async fn revoke_api_token_handler(
+        State(state): State<AppState>,
+        jar: CookieJar,
+        Path(id): Path<String>,
+    ) -> Response {
+        let Some((_, user)) = session_user(&state, &jar).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        if state
+            .api_tokens
+            .revoke(&user.subject, &id, user.is_admin())
+            .await
+        {
+            tracing::info!(subject = %user.subject, token_id = %id, "api tokens: token revoked");
+            StatusCode::NO_CONTENT.into_response()
+        } else {
+            error_response(StatusCode::NOT_FOUND, "Token not found")
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::collections::HashSet;
+
+        fn user() -> AuthenticatedUser {
+            AuthenticatedUser {
+                subject: "sub-1".to_string(),
+                name: "Anna".to_string(),
+                email: Some("anna@example.test".to_string()),
+                roles: HashSet::from([Role::User]),
+            }
+        }
+
+        fn request(scopes: &[&str]) -> CreateApiTokenRequest {
+            CreateApiTokenRequest {
+                name: "camera".to_string(),
+                scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+                expires_in_days: 30,
+            }
+        }
+
+        fn store() -> ApiTokenStore {
+            let path = std::env::temp_dir()
+                .join(format!("gmr-api-tokens-{}", Uuid::now_v7()))
+                .join("tokens.json");
+            ApiTokenStore::load(path)
+        }
+
+        #[tokio::test]
+        async fn token_round_trip_and_revoke() {
+            let store = store();
+            let created = store
+                .create(&user(), request(&["reports:write"]), 1_000)
+                .await
+                .unwrap();
+
+            let principal = store.authenticate(&created.token, 2_000).await.unwrap();
+            assert_eq!(principal.user.subject, "sub-1");
+            assert_eq!(principal.scopes, vec!["reports:write".to_string()]);
+            assert_eq!(principal.user_id(), "anna@example.test");
+
+            let reloaded = ApiTokenStore::load(store.path.clone());
+            assert!(reloaded.authenticate(&created.token, 2_000).await.is_some());
+            let on_disk = std::fs::read_to_string(&store.path).unwrap();
+            assert!(!on_disk.contains(split_token(&created.token).unwrap().1));
+
+            assert!(store.revoke("sub-1", &created.info.id, false).await);
+            assert!(store.authenticate(&created.token, 2_000).await.is_none());
+            let _ = std::fs::remove_dir_all(store.path.parent().unwrap());
+        }
+
+        #[tokio::test]
+        async fn subject_revocation_and_persisted_last_use() {
+            let store = store();
+            let first = store
+                .create(&user(), request(&["chat"]), 1_000)
+                .await
+                .unwrap();
+            let second = store
+                .create(&user(), request(&["models"]), 1_000)
+                .await
+                .unwrap();
+
+            store.authenticate(&first.token, 2_000).await.unwrap();
+            store.authenticate(&first.token, 2_100).await.unwrap();
+            let reloaded = ApiTokenStore::load(store.path.clone());
+            assert_eq!(reloaded.list("sub-1").await[0].last_used_at, Some(2_000));
+
+            assert_eq!(store.revoke_subject("other").await, 0);
+            assert_eq!(store.revoke_subject("sub-1").await, 2);
+            assert!(store.authenticate(&first.token, 2_000).await.is_none());
+            assert!(store.authenticate(&second.token, 2_000).await.is_none());
+            assert!(
+                ApiTokenStore::load(store.path.clone())
+                    .list("sub-1")
+                    .await
+                    .is_empty()
+            );
+            let _ = std::fs::remove_dir_all(store.path.parent().unwrap());
+        }
+
+        #[tokio::test]
+        async fn expired_or_tampered_tokens_are_rejected() {
+            let store = store();
+            let created = store
+                .create(&user(), request(&["chat"]), 1_000)
+                .await
+                .unwrap();
+
+            assert!(
+                store
+                    .authenticate(&created.token, created.info.expires_at)
+                    .await
+                    .is_none()
+            );
+            let tampered = format!("{}0", created.token);
+            assert!(store.authenticate(&tampered, 2_000).await.is_none());
+            assert!(!store.revoke("other-user", &created.info.id, false).await);
+            let _ = std::fs::remove_dir_all(store.path.parent().unwrap());
+        }
+
+        #[tokio::test]
+        async fn unknown_scope_is_rejected() {
+            assert!(
+                store()
+                    .create(&user(), request(&["admin"]), 1_000)
+                    .await
+                    .is_err()
+            );
+        }
+
+        #[test]
+        fn routes_map_to_scopes() {
+            assert_eq!(
+                required_scope(&Method::GET, "/api/proxy/reports/abc"),
+                Some("reports:read")
+            );
+            assert_eq!(
+                required_scope(&Method::PUT, "/api/proxy/reports/abc"),
+                Some("reports:write")
+            );
+            assert_eq!(
+                required_scope(&Method::POST, "/api/proxy/images/upload/abc"),
+                Some("reports:write")
+            );
+            assert_eq!(
+                required_scope(&Method::GET, "/api/proxy/shares/abc"),
+                Some("reports:read")
+            );
+            assert_eq!(
+                required_scope(&Method::DELETE, "/api/proxy/shares/abc/user/bob"),
+                Some("reports:write")
+            );
+            assert_eq!(
+                required_scope(&Method::POST, "/api/chat_stream"),
+                Some("chat")
+            );
+            assert_eq!(
+                required_scope(&Method::PUT, "/api/chat_context/abc"),
+                Some("chat")
+            );
+            assert_eq!(required_scope(&Method::GET, "/api/sessions"), None);
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) use ssr::bearer_token;
+#[cfg(feature = "ssr")]
+pub use ssr::{
+    ApiPrincipal, ApiTokenStore, agent_user_id, api_auth, create_api_token_handler,
+    list_api_tokens_handler, required_scope, revoke_api_token_handler,
+};
@@ -1,8 +1,11 @@
 use crate::auth::Auth;
+use crate::components::active_sessions::ActiveSessions;
+use crate::components::admin_console::AdminConsole;
+use crate::components::api_tokens_panel::ApiTokensPanel;
 use crate::components::chat::Chat;
 use crate::components::chat_context::ChatContext;
+use crate::components::deep_link::DeepLinkRestore;
 use crate::components::lang::{I18nProvider, LanguageSelector, LanguageSwitcher};
-use crate::components::media_proxy_script::MediaProxyScript;
 use crate::components::reports_panel::ReportsPage;
 use crate::components::side_body::SideBody;
 use crate::components::side_top::SideTop;
@@ -20,10 +23,6 @@ use leptos_router::*;
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
     provide_meta_context();
-    #[cfg(feature = "ssr")]
-    if let Some(client_config) = use_context::<crate::ClientConfig>() {
-        provide_context(client_config);
-    }
     view! {
         <!DOCTYPE html>
         <html lang="en">
@@ -38,7 +37,7 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
                 <LegacyStorageCleanupScript />
                 <HydrationScripts options />
                 <MetaTags />
-                <MediaProxyScript />
+                <CsrfTokenScript />
             </head>
             <body>
                 <App />
@@ -47,6 +46,20 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
     }
 }
 
+#[component]
+fn CsrfTokenScript() -> impl IntoView {
+    let nonce = use_context::<leptos::nonce::Nonce>();
+    let token = use_context::<crate::csrf::CsrfToken>()
+        .map(|token| token.0)
+        .unwrap_or_default();
+
+    view! {
+        <script nonce=nonce>
+            {format!("window.CSRF_TOKEN = {};", serde_json::to_string(&token).unwrap())}
+        </script>
+    }
+}
+
 #[component]
 fn LegacyStorageCleanupScript() -> impl IntoView {
     let nonce = use_context::<leptos::nonce::Nonce>();
@@ -73,6 +86,7 @@ pub fn App() -> impl IntoView {
                             <Route path=path!("profile") view=ProfilePage />
                             <Route path=path!("play") view=PlayPage />
                             <Route path=path!("reports") view=ReportsRoutePage />
+                            <Route path=path!("users") view=UsersPage />
                         </ParentRoute>
                     </Routes>
                 </main>
@@ -92,7 +106,11 @@ pub fn AuthWrapper() -> impl IntoView {
                     Ok(auth_state) => {
                         let auth_signal = RwSignal::new(auth_state.clone());
                         provide_context(auth_signal);
-                        view! { <Outlet /> }.into_any()
+                        view! {
+                            <DeepLinkRestore />
+                            <Outlet />
+                        }
+                            .into_any()
                     }
                     Err(e) => {
                         view! {
@@ -195,6 +213,35 @@ fn ReportsRoutePage() -> impl IntoView {
         }}
     }
 }
+#[component]
+fn UsersPage() -> impl IntoView {
+    let auth_signal = use_context::<RwSignal<Auth>>()
+        .expect("Auth context not found. Did you set up the provider?");
+
+    view! {
+        {move || {
+            let auth = auth_signal.get();
+            if !auth.is_authenticated() {
+                view! { <LoginPage /> }.into_any()
+            } else {
+                let is_admin = auth.is_authenticated_admin();
+                view! {
+                    <SideBar
+                        top=SideTop()
+                        side_body=view! { <SideBody is_admin=is_admin /> }
+                    >
+                        {if is_admin {
+                            view! { <AdminConsole /> }.into_any()
+                        } else {
+                            view! { <p class="error">{move_tr!("admin-forbidden")}</p> }.into_any()
+                        }}
+                    </SideBar>
+                }
+                    .into_any()
+            }
+        }}
+    }
+}
 // Dummy components
 #[component]
 fn PublicLandingPage() -> impl IntoView {
@@ -240,6 +287,8 @@ fn ProfilePage() -> impl IntoView {
                         side_body=view! { <SideBody is_admin=auth.is_authenticated_admin() /> }
                     >
                         <UserRolesDisplay user=auth.user() />
+                        <ActiveSessions />
+                        <ApiTokensPanel />
                     </SideBar>
                 }
                     .into_any()
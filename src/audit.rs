@@ -0,0 +1,432 @@
+use crate::api_tokens::ApiPrincipal;
+use crate::auth_ssr::session_user;
+use crate::sessions::error_response;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+const DEFAULT_QUERY_LIMIT: usize = 100;
+const MAX_QUERY_LIMIT: usize = 1000;
+/// The log is read from its end in blocks of this size.
+const QUERY_BLOCK_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    ReportUpdate,
+    ImageUpload,
+    ImageDelete,
+    ModelsUpdate,
+    BranchCreate,
+    BranchUpdate,
+    BranchDelete,
+    ShareGrant,
+    ShareRevoke,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// One mutating operation forwarded to the agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix seconds.
+    pub at: i64,
+    pub actor: String,
+    pub actor_name: Option<String>,
+    /// API token used instead of a browser session.
+    pub token_id: Option<String>,
+    pub action: AuditAction,
+    /// Node id, upload parent id or model settings user id.
+    pub target: String,
+    pub request_id: String,
+    pub outcome: AuditOutcome,
+    /// Status returned to the client.
+    pub status: u16,
+    /// Status of the agent response, absent when the agent was not reached.
+    pub agent_status: Option<u16>,
+}
+
+/// Admin query, times are RFC 3339 or unix seconds.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditQuery {
+    pub user: Option<String>,
+    pub node: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditFilter {
+    pub user: Option<String>,
+    pub target: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub limit: usize,
+}
+
+impl AuditFilter {
+    fn from_query(query: AuditQuery) -> Result<Self, String> {
+        let time = |value: Option<String>, name: &str| {
+            value
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| parse_time(&value).ok_or_else(|| format!("Invalid `{name}` time")))
+                .transpose()
+        };
+        Ok(Self {
+            from: time(query.from, "from")?,
+            to: time(query.to, "to")?,
+            user: query.user.filter(|value| !value.is_empty()),
+            target: query.node.filter(|value| !value.is_empty()),
+            limit: query
+                .limit
+                .unwrap_or(DEFAULT_QUERY_LIMIT)
+                .clamp(1, MAX_QUERY_LIMIT),
+        })
+    }
+
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        let user = self.user.as_deref().is_none_or(|user| {
+            entry.actor == user
+                || entry
+                    .actor_name
+                    .as_deref()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(user))
+        });
+        user && self.target.as_deref().is_none_or(|t| entry.target == t)
+            && self.from.is_none_or(|from| entry.at >= from)
+            && self.to.is_none_or(|to| entry.at <= to)
+    }
+}
+
+fn parse_time(value: &str) -> Option<i64> {
+    value.trim().parse::<i64>().ok().or_else(|| {
+        chrono::DateTime::parse_from_rfc3339(value.trim())
+            .ok()
+            .map(|at| at.timestamp())
+    })
+}
+
+/// Append-only audit storage.
+pub trait AuditSink: Send + Sync {
+    fn append<'a>(&'a self, entry: &'a AuditEntry) -> BoxFuture<'a, anyhow::Result<()>>;
+    /// Matching entries, newest first.
+    fn query<'a>(
+        &'a self,
+        filter: &'a AuditFilter,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<AuditEntry>>>;
+}
+
+/// One JSON object per line.
+pub struct JsonlAuditSink {
+    path: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl JsonlAuditSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            write_lock: Mutex::new(()),
+        }
+    }
+}
+
+impl AuditSink for JsonlAuditSink {
+    fn append<'a>(&'a self, entry: &'a AuditEntry) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let mut line = serde_json::to_vec(entry)?;
+            line.push(b'\n');
+            let _guard = self.write_lock.lock().await;
+            if let Some(parent) = self.path.parent()
+                && !parent.as_os_str().is_empty()
+            {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            file.write_all(&line).await?;
+            file.flush().await?;
+            Ok(())
+        })
+    }
+
+    fn query<'a>(
+        &'a self,
+        filter: &'a AuditFilter,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<AuditEntry>>> {
+        Box::pin(async move {
+            if filter.limit == 0 {
+                return Ok(Vec::new());
+            }
+            let file = match tokio::fs::File::open(&self.path).await {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(e) => return Err(e.into()),
+            };
+            let mut entries = Vec::new();
+            lines_backwards(file, QUERY_BLOCK_BYTES, |line| {
+                if let Ok(entry) = serde_json::from_slice::<AuditEntry>(line)
+                    && filter.matches(&entry)
+                {
+                    entries.push(entry);
+                }
+                entries.len() < filter.limit
+            })
+            .await?;
+            Ok(entries)
+        })
+    }
+}
+
+/// Passes the lines of `file` to `visit` last to first, reading `block_bytes` at a
+/// time from the end, until `visit` returns false.
+async fn lines_backwards(
+    mut file: tokio::fs::File,
+    block_bytes: usize,
+    mut visit: impl FnMut(&[u8]) -> bool,
+) -> std::io::Result<()> {
+    let mut end = file.seek(SeekFrom::End(0)).await?;
+    // The start of the line the previous block ended in
+    let mut partial = Vec::new();
+    while end > 0 {
+        let start = end.saturating_sub(block_bytes as u64);
+        let mut block = vec![0; (end - start) as usize];
+        file.seek(SeekFrom::Start(start)).await?;
+        file.read_exact(&mut block).await?;
+        block.extend_from_slice(&partial);
+        let mut rest = block.as_slice();
+        while let Some(newline) = rest.iter().rposition(|byte| *byte == b'\n') {
+            if !visit(&rest[newline + 1..]) {
+                return Ok(());
+            }
+            rest = &rest[..newline];
+        }
+        partial = rest.to_vec();
+        end = start;
+    }
+    visit(&partial);
+    Ok(())
+}
+
+/// Who performed a proxied request: the API token owner or the session user.
+#[derive(Debug, Clone, Default)]
+pub struct AuditActor {
+    pub subject: String,
+    pub name: Option<String>,
+    pub token_id: Option<String>,
+}
+
+impl AuditActor {
+    pub async fn resolve(
+        state: &AppState,
+        jar: &CookieJar,
+        principal: Option<&ApiPrincipal>,
+    ) -> Self {
+        if let Some(principal) = principal {
+            return Self {
+                subject: principal.user.subject.clone(),
+                name: Some(principal.user.name.clone()),
+                token_id: Some(principal.token_id.clone()),
+            };
+        }
+        match session_user(state, jar).await {
+            Some((_, user)) => Self {
+                subject: user.subject,
+                name: Some(user.name),
+                token_id: None,
+            },
+            None => Self {
+                subject: "anonymous".to_string(),
+                ..Self::default()
+            },
+        }
+    }
+}
+
+/// Appends the outcome of a proxied mutation; failures to write are only logged.
+pub async fn record(
+    state: &AppState,
+    actor: AuditActor,
+    action: AuditAction,
+    target: &str,
+    request_id: String,
+    status: StatusCode,
+    agent_status: Option<StatusCode>,
+) {
+    let entry = AuditEntry {
+        at: chrono::Utc::now().timestamp(),
+        actor: actor.subject,
+        actor_name: actor.name,
+        token_id: actor.token_id,
+        action,
+        target: target.to_string(),
+        request_id,
+        outcome: if status.is_success() {
+            AuditOutcome::Success
+        } else {
+            AuditOutcome::Failure
+        },
+        status: status.as_u16(),
+        agent_status: agent_status.map(|status| status.as_u16()),
+    };
+    tracing::info!(
+        actor = %entry.actor,
+        action = ?entry.action,
+        target = %entry.target,
+        request_id = %entry.request_id,
+        status = entry.status,
+        "audit"
+    );
+    if let Err(e) = state.audit.append(&entry).await {
+        tracing::error!(error = %e, request_id = %entry.request_id, "audit: failed to write entry");
+    }
+}
+
+pub async fn audit_log_handler(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(query): Query<AuditQuery>,
+) -> Response {
+    if let Err(response) = crate::admin::admin_user(&state, &jar).await {
+        return response;
+    }
+    let filter = match AuditFilter::from_query(query) {
+        Ok(filter) => filter,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e),
+    };
+    match state.audit.query(&filter).await {
+        Ok(entries) => Json(entries).into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "audit: query failed");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read audit log",
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(at: i64, actor: &str, target: &str) -> AuditEntry {
+        AuditEntry {
+            at,
+            actor: actor.to_string(),
+            actor_name: Some(format!("{actor} name")),
+            token_id: None,
+            action: AuditAction::ImageDelete,
+            target: target.to_string(),
+            request_id: format!("req-{at}"),
+            outcome: AuditOutcome::Success,
+            status: 204,
+            agent_status: Some(204),
+        }
+    }
+
+    #[test]
+    fn query_parses_times_and_clamps_limit() {
+        let filter = AuditFilter::from_query(AuditQuery {
+            from: Some("2026-01-01T00:00:00Z".to_string()),
+            to: Some("1767312000".to_string()),
+            limit: Some(10_000),
+            ..AuditQuery::default()
+        })
+        .unwrap();
+
+        assert_eq!(filter.from, Some(1_767_225_600));
+        assert_eq!(filter.to, Some(1_767_312_000));
+        assert_eq!(filter.limit, MAX_QUERY_LIMIT);
+        assert!(
+            AuditFilter::from_query(AuditQuery {
+                from: Some("yesterday".to_string()),
+                ..AuditQuery::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn log_is_read_backwards_across_blocks() {
+        let path = std::env::temp_dir().join(format!("gmr-audit-{}.jsonl", uuid::Uuid::now_v7()));
+        std::fs::write(&path, "first\nsecond line\n\nthird\n").unwrap();
+        let read = |limit: usize| {
+            let path = path.clone();
+            async move {
+                let file = tokio::fs::File::open(&path).await.unwrap();
+                let mut lines = Vec::new();
+                lines_backwards(file, 4, |line| {
+                    lines.push(String::from_utf8(line.to_vec()).unwrap());
+                    lines.len() < limit
+                })
+                .await
+                .unwrap();
+                lines
+            }
+        };
+
+        assert_eq!(read(10).await, ["", "third", "", "second line", "first"]);
+        assert_eq!(read(2).await, ["", "third"]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn jsonl_sink_appends_and_filters_newest_first() {
+        let path = std::env::temp_dir().join(format!("gmr-audit-{}.jsonl", uuid::Uuid::now_v7()));
+        let sink = JsonlAuditSink::new(&path);
+        for item in [
+            entry(100, "alice", "node-1"),
+            entry(200, "bob", "node-1"),
+            entry(300, "alice", "node-2"),
+        ] {
+            sink.append(&item).await.unwrap();
+        }
+
+        let by_user = sink
+            .query(&AuditFilter {
+                user: Some("ALICE NAME".to_string()),
+                limit: 10,
+                ..AuditFilter::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            by_user.iter().map(|e| e.at).collect::<Vec<_>>(),
+            vec![300, 100]
+        );
+
+        let by_node_and_time = sink
+            .query(&AuditFilter {
+                target: Some("node-1".to_string()),
+                from: Some(150),
+                limit: 10,
+                ..AuditFilter::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(by_node_and_time, vec![entry(200, "bob", "node-1")]);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
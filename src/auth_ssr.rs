@@ -7,6 +7,7 @@ use axum::{
     response::{IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
 use leptos::serde_json;
 use oauth2::{CsrfToken, PkceCodeVerifier, RefreshToken, TokenResponse};
 use openidconnect::{
@@ -35,6 +36,14 @@ pub struct SessionData {
     pub id_token_expires_at: Option<Instant>,
     pub is_refreshing: Arc<Mutex<bool>>,
     pub email: Option<String>,
+    /// OIDC session id (`sid` claim of the id_token), matched by back-channel logout
+    pub sid: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_activity: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    /// Synchronizer token required on state-changing API calls, see `crate::csrf`
+    pub csrf_api_token: String,
 }
 const REFRESH_THRESHOLD: Duration = Duration::from_secs(5 * 60);
 
@@ -92,12 +101,13 @@ fn id_token_expiry_instant(claims: &CoreIdTokenClaims) -> Instant {
 
 pub async fn get_and_refresh_session(state: &AppState, session_id: &str) -> Option<SessionData> {
     let session_data = {
-        let sessions = state.sessions.lock().await;
-        let Some(data) = sessions.get(session_id).cloned() else {
+        let mut sessions = state.sessions.lock().await;
+        let Some(data) = sessions.get_mut(session_id) else {
             debug!(session_id = %session_id, "session refresh: session not found");
             return None;
         };
-        data
+        data.last_activity = Utc::now();
+        data.clone()
     };
 
     let now = Instant::now();
@@ -303,6 +313,19 @@ where
     }
 }
 
+/// Resolves the cookie session into its id and authenticated user.
+pub async fn session_user(
+    state: &AppState,
+    jar: &CookieJar,
+) -> Option<(String, AuthenticatedUser)> {
+    let session_id = jar.get(SESSION_ID)?.value().to_string();
+    let session = get_and_refresh_session(state, &session_id).await?;
+    match Auth::try_from(&session) {
+        Ok(Auth::Authenticated(user)) => Some((session_id, user)),
+        _ => None,
+    }
+}
+
 impl TryFrom<&SessionData> for Auth {
     type Error = &'static str;
 
@@ -0,0 +1,480 @@
+use crate::ssr::terminate_session;
+use crate::state::AppState;
+use anyhow::{Result, anyhow, bail};
+use axum::{
+    Form, Json,
+    extract::State,
+    http::{HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use leptos::serde_json;
+use openidconnect::core::{CoreJsonWebKey, CoreJsonWebKeySet, CoreJwsSigningAlgorithm};
+use openidconnect::{JsonWebKey, JsonWebKeyId};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+const CLOCK_SKEW_SECS: i64 = 120;
+const MAX_TOKEN_AGE_SECS: i64 = 10 * 60;
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+/// The endpoint is unauthenticated, so unknown keys refetch the JWKS at most this often.
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+pub struct BackchannelLogoutForm {
+    logout_token: String,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: CoreJwsSigningAlgorithm,
+    kid: Option<JsonWebKeyId>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == client_id,
+            Audience::Many(auds) => auds.iter().any(|aud| aud == client_id),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LogoutTokenClaims {
+    iss: String,
+    aud: Audience,
+    iat: i64,
+    exp: Option<i64>,
+    sub: Option<String>,
+    sid: Option<String>,
+    events: Value,
+    nonce: Option<Value>,
+}
+
+/// Which sessions a verified logout token refers to.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LogoutTarget {
+    pub sub: Option<String>,
+    pub sid: Option<String>,
+}
+
+impl LogoutTarget {
+    /// A logout of the whole user rather than of one session, which also ends
+    /// API token access.
+    pub fn is_user_wide(&self) -> bool {
+        self.sub.is_some() && self.sid.is_none()
+    }
+
+    /// A session matches on `sub`, on `sid`, or both when both are given.
+    /// Sessions without a recorded `sid` fall back to matching by `sub`.
+    pub fn matches(&self, subject: Option<&str>, sid: Option<&str>) -> bool {
+        if let Some(sub) = &self.sub
+            && subject != Some(sub.as_str())
+        {
+            return false;
+        }
+        match (&self.sid, sid) {
+            (Some(expected), Some(actual)) => expected == actual,
+            (Some(_), None) => self.sub.is_some(),
+            (None, _) => true,
+        }
+    }
+}
+
+/// Provider signing keys, replaced when a token names a key the set lacks.
+pub struct JwksCache {
+    state: Mutex<JwksCacheState>,
+}
+
+struct JwksCacheState {
+    keys: Arc<CoreJsonWebKeySet>,
+    last_fetch: Option<Instant>,
+}
+
+impl JwksCacheState {
+    /// Whether a token with `kid` warrants a refetch. Claims the slot, so
+    /// concurrent tokens don't fetch twice.
+    fn claim_refresh(&mut self, kid: Option<&JsonWebKeyId>, now: Instant) -> bool {
+        // Without a kid every key was tried already
+        let Some(kid) = kid else {
+            return false;
+        };
+        if self.keys.keys().iter().any(|key| key.key_id() == Some(kid)) {
+            return false;
+        }
+        if self
+            .last_fetch
+            .is_some_and(|at| now.duration_since(at) < JWKS_REFRESH_INTERVAL)
+        {
+            return false;
+        }
+        self.last_fetch = Some(now);
+        true
+    }
+}
+
+impl JwksCache {
+    pub fn new(keys: CoreJsonWebKeySet) -> Self {
+        Self {
+            state: Mutex::new(JwksCacheState {
+                keys: Arc::new(keys),
+                last_fetch: None,
+            }),
+        }
+    }
+
+    async fn keys(&self) -> Arc<CoreJsonWebKeySet> {
+        self.state.lock().await.keys.clone()
+    }
+
+    async fn claim_refresh(&self, kid: Option<&JsonWebKeyId>) -> bool {
+        self.state.lock().await.claim_refresh(kid, Instant::now())
+    }
+
+    async fn replace(&self, keys: CoreJsonWebKeySet) -> Arc<CoreJsonWebKeySet> {
+        let keys = Arc::new(keys);
+        self.state.lock().await.keys = keys.clone();
+        keys
+    }
+}
+
+/// Key id from the header of a compact JWS, if it names one.
+fn token_kid(token: &str) -> Option<JsonWebKeyId> {
+    let header = BASE64_URL_SAFE_NO_PAD
+        .decode(token.split('.').next()?)
+        .ok()?;
+    serde_json::from_slice::<JwtHeader>(&header).ok()?.kid
+}
+
+/// Verifies signature and claims of a back-channel logout token.
+pub fn verify_logout_token(
+    token: &str,
+    keys: &[CoreJsonWebKey],
+    issuer: &str,
+    client_id: &str,
+    now: i64,
+) -> Result<LogoutTarget> {
+    let mut parts = token.split('.');
+    let (Some(header_b64), Some(payload_b64), Some(signature_b64), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        bail!("logout token is not a compact JWS");
+    };
+
+    let header: JwtHeader = serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(header_b64)?)?;
+    let signature = BASE64_URL_SAFE_NO_PAD.decode(signature_b64)?;
+    let signing_input = &token[..header_b64.len() + 1 + payload_b64.len()];
+
+    let verified = keys
+        .iter()
+        .filter(|key| header.kid.is_none() || key.key_id() == header.kid.as_ref())
+        .any(|key| {
+            key.verify_signature(&header.alg, signing_input.as_bytes(), &signature)
+                .is_ok()
+        });
+    if !verified {
+        bail!("no JWKS key verifies the logout token signature");
+    }
+
+    let claims: LogoutTokenClaims =
+        serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(payload_b64)?)?;
+    validate_claims(claims, issuer, client_id, now)
+}
+
+fn validate_claims(
+    claims: LogoutTokenClaims,
+    issuer: &str,
+    client_id: &str,
+    now: i64,
+) -> Result<LogoutTarget> {
+    if claims.iss.trim_end_matches('/') != issuer.trim_end_matches('/') {
+        bail!("unexpected issuer {}", claims.iss);
+    }
+    if !claims.aud.contains(client_id) {
+        bail!("audience does not contain client id");
+    }
+    if claims.iat > now + CLOCK_SKEW_SECS || claims.iat < now - MAX_TOKEN_AGE_SECS {
+        bail!("iat {} is outside the accepted window", claims.iat);
+    }
+    if claims.exp.is_some_and(|exp| exp < now - CLOCK_SKEW_SECS) {
+        bail!("logout token expired");
+    }
+    if claims.events.get(BACKCHANNEL_LOGOUT_EVENT).is_none() {
+        bail!("events claim has no back-channel logout member");
+    }
+    if claims.nonce.is_some() {
+        bail!("logout token must not contain a nonce");
+    }
+    if claims.sub.is_none() && claims.sid.is_none() {
+        bail!("logout token has neither sub nor sid");
+    }
+
+    Ok(LogoutTarget {
+        sub: claims.sub,
+        sid: claims.sid,
+    })
+}
+
+async fn fetch_jwks(state: &AppState) -> Result<CoreJsonWebKeySet> {
+    tokio::time::timeout(
+        JWKS_FETCH_TIMEOUT,
+        CoreJsonWebKeySet::fetch_async(&state.http_client.jwks_url, &state.sso_http_client),
+    )
+    .await
+    .map_err(|_| anyhow!("JWKS fetch timed out"))?
+    .map_err(|e| anyhow!("JWKS fetch failed: {e}"))
+}
+
+fn invalid_request() -> Response {
+    no_store(
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "invalid_request" })),
+        )
+            .into_response(),
+    )
+}
+
+fn no_store(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert("Cache-Control", HeaderValue::from_static("no-store"));
+    response
+}
+
+/// OIDC Back-Channel Logout: the IdP posts a signed `logout_token`,
+/// all matching sessions are dropped and their agent requests cancelled.
+pub async fn backchannel_logout_handler(
+    State(state): State<AppState>,
+    Form(form): Form<BackchannelLogoutForm>,
+) -> Response {
+    let config = &state.http_client.config;
+    let now = chrono::Utc::now().timestamp();
+    let verify = |keys: &CoreJsonWebKeySet| {
+        verify_logout_token(
+            &form.logout_token,
+            keys.keys(),
+            &config.oidc_issuer_url,
+            &config.oidc_client_id,
+            now,
+        )
+    };
+
+    let target = match verify(&*state.jwks.keys().await) {
+        Ok(target) => target,
+        // Keys may have been rotated since the last fetch
+        Err(first_error) => {
+            let refetched = if state
+                .jwks
+                .claim_refresh(token_kid(&form.logout_token).as_ref())
+                .await
+            {
+                match fetch_jwks(&state).await {
+                    Ok(jwks) => Some(state.jwks.replace(jwks).await),
+                    Err(fetch_error) => {
+                        warn!(error = %fetch_error, "backchannel-logout: JWKS refetch failed");
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            match refetched.map(|jwks| verify(&jwks)) {
+                Some(Ok(target)) => target,
+                Some(Err(error)) => {
+                    warn!(error = %error, "backchannel-logout: token rejected");
+                    return invalid_request();
+                }
+                None => {
+                    warn!(error = %first_error, "backchannel-logout: token rejected");
+                    return invalid_request();
+                }
+            }
+        }
+    };
+
+    let session_ids: Vec<String> = state
+        .sessions
+        .lock()
+        .await
+        .iter()
+        .filter(|(_, session)| target.matches(session.subject.as_deref(), session.sid.as_deref()))
+        .map(|(session_id, _)| session_id.clone())
+        .collect();
+
+    for session_id in &session_ids {
+        terminate_session(&state, session_id).await;
+    }
+    let revoked_tokens = match &target.sub {
+        Some(sub) if target.is_user_wide() => state.api_tokens.revoke_subject(sub).await,
+        _ => 0,
+    };
+    info!(
+        sub = ?target.sub,
+        sid = ?target.sid,
+        terminated = session_ids.len(),
+        revoked_tokens,
+        "backchannel-logout: sessions terminated"
+    );
+
+    no_store(StatusCode::OK.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openidconnect::PrivateSigningKey;
+    use openidconnect::core::CoreHmacKey;
+
+    const ISSUER: &str = "https://sso.example.test/auth/v1";
+    const CLIENT_ID: &str = "gmr";
+    const NOW: i64 = 1_700_000_000;
+
+    fn sign(claims: &Value, key: &CoreHmacKey) -> String {
+        let header = BASE64_URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"logout+jwt"}"#);
+        let payload = BASE64_URL_SAFE_NO_PAD.encode(claims.to_string());
+        let input = format!("{header}.{payload}");
+        let signature = key
+            .sign(&CoreJwsSigningAlgorithm::HmacSha256, input.as_bytes())
+            .unwrap();
+        format!("{input}.{}", BASE64_URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn claims() -> Value {
+        json!({
+            "iss": ISSUER,
+            "aud": [CLIENT_ID],
+            "iat": NOW,
+            "exp": NOW + 120,
+            "jti": "jti-1",
+            "sub": "user-1",
+            "sid": "sid-1",
+            "events": { BACKCHANNEL_LOGOUT_EVENT: {} }
+        })
+    }
+
+    #[test]
+    fn valid_token_yields_target() {
+        let key = CoreHmacKey::new(b"backchannel-test-secret-0123456789".to_vec());
+        let token = sign(&claims(), &key);
+
+        let target =
+            verify_logout_token(&token, &[key.as_verification_key()], ISSUER, CLIENT_ID, NOW)
+                .unwrap();
+
+        assert_eq!(target.sub.as_deref(), Some("user-1"));
+        assert_eq!(target.sid.as_deref(), Some("sid-1"));
+    }
+
+    #[test]
+    fn token_signed_with_other_key_is_rejected() {
+        let key = CoreHmacKey::new(b"backchannel-test-secret-0123456789".to_vec());
+        let other = CoreHmacKey::new(b"another-secret-entirely-9876543210".to_vec());
+        let token = sign(&claims(), &other);
+
+        assert!(
+            verify_logout_token(&token, &[key.as_verification_key()], ISSUER, CLIENT_ID, NOW)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn claim_violations_are_rejected() {
+        let key = CoreHmacKey::new(b"backchannel-test-secret-0123456789".to_vec());
+        let keys = [key.as_verification_key()];
+        let cases: [(&str, Value); 5] = [
+            ("iss", json!("https://evil.example.test")),
+            ("aud", json!("other-client")),
+            ("events", json!({})),
+            ("nonce", json!("n-1")),
+            ("iat", json!(NOW - MAX_TOKEN_AGE_SECS - 1)),
+        ];
+
+        for (claim, value) in cases {
+            let mut claims = claims();
+            claims[claim] = value;
+            let token = sign(&claims, &key);
+            assert!(
+                verify_logout_token(&token, &keys, ISSUER, CLIENT_ID, NOW).is_err(),
+                "{claim} should be rejected"
+            );
+        }
+
+        let mut claims = claims();
+        claims.as_object_mut().unwrap().remove("sub");
+        claims.as_object_mut().unwrap().remove("sid");
+        let token = sign(&claims, &key);
+        assert!(verify_logout_token(&token, &keys, ISSUER, CLIENT_ID, NOW).is_err());
+    }
+
+    #[test]
+    fn target_matches_by_sid_and_falls_back_to_sub() {
+        let target = LogoutTarget {
+            sub: Some("user-1".to_string()),
+            sid: Some("sid-1".to_string()),
+        };
+        assert!(target.matches(Some("user-1"), Some("sid-1")));
+        assert!(!target.matches(Some("user-1"), Some("sid-2")));
+        assert!(target.matches(Some("user-1"), None));
+        assert!(!target.matches(Some("user-2"), None));
+
+        let sid_only = LogoutTarget {
+            sub: None,
+            sid: Some("sid-1".to_string()),
+        };
+        assert!(sid_only.matches(None, Some("sid-1")));
+        assert!(!sid_only.matches(Some("user-1"), None));
+
+        assert!(!target.is_user_wide());
+        assert!(!sid_only.is_user_wide());
+        assert!(
+            LogoutTarget {
+                sub: Some("user-1".to_string()),
+                sid: None,
+            }
+            .is_user_wide()
+        );
+    }
+
+    #[test]
+    fn only_unknown_kids_refetch_and_at_most_once_a_minute() {
+        let key: CoreJsonWebKey =
+            serde_json::from_value(json!({ "kty": "oct", "kid": "k1", "k": "c2VjcmV0" })).unwrap();
+        let mut cache = JwksCacheState {
+            keys: Arc::new(CoreJsonWebKeySet::new(vec![key])),
+            last_fetch: None,
+        };
+        let known = JsonWebKeyId::new("k1".to_string());
+        let rotated = JsonWebKeyId::new("k2".to_string());
+        let start = Instant::now();
+
+        assert!(!cache.claim_refresh(None, start));
+        assert!(!cache.claim_refresh(Some(&known), start));
+        assert!(cache.claim_refresh(Some(&rotated), start));
+        assert!(!cache.claim_refresh(Some(&rotated), start + Duration::from_secs(30)));
+        assert!(cache.claim_refresh(Some(&rotated), start + JWKS_REFRESH_INTERVAL));
+    }
+
+    #[test]
+    fn kid_is_read_from_the_header() {
+        let header = BASE64_URL_SAFE_NO_PAD.encode(br#"{"alg":"RS256","kid":"k2"}"#);
+        assert_eq!(
+            token_kid(&format!("{header}.e30.sig")),
+            Some(JsonWebKeyId::new("k2".to_string()))
+        );
+        assert_eq!(token_kid("garbage"), None);
+    }
+}
@@ -0,0 +1,431 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const MAX_BRANCH_NAME_CHARS: usize = 120;
+
+/// New child branch of the node in the path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateBranch {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// Rename (`name`, `BranchData::title`) and/or move a branch under `parent_id`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UpdateBranch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<Uuid>,
+}
+
+/// Trimmed name if it is usable: not empty, no control characters and at most
+/// `MAX_BRANCH_NAME_CHARS` long.
+pub fn branch_name(value: &str) -> Option<String> {
+    let value = value.trim();
+    let valid = !value.is_empty()
+        && value.chars().count() <= MAX_BRANCH_NAME_CHARS
+        && !value.chars().any(char::is_control);
+    valid.then(|| value.to_string())
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::*;
+    use crate::api_tokens::{ApiPrincipal, agent_user_id};
+    use crate::audit::{self, AuditAction, AuditActor};
+    use crate::components::tree::{NodeType, TreeNode};
+    use crate::media::agent_tree;
+    use crate::proxy_reports::forward_signed;
+    use crate::proxy_tree::with_ancestors;
+    use crate::sessions::{CodedError, error_response};
+    use crate::ssr::correlation_id;
+    use crate::state::AppState;
+    use axum::{
+        Extension, Json,
+        extract::{Path, State},
+        http::{HeaderMap, StatusCode},
+        response::{IntoResponse, Response},
+    };
+    use axum_extra::extract::CookieJar;
+    use std::collections::HashSet;
+
+    /// Why a branch change is refused.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum BranchError {
+        NotFound,
+        NotOwned,
+        NotABranch,
+        NotEmpty,
+        InvalidName,
+        InvalidMove,
+        NoChange,
+    }
+
+    impl CodedError for BranchError {
+        fn code(self) -> &'static str {
+            match self {
+                Self::NotFound => "branch-not-found",
+                Self::NotOwned => "branch-not-owned",
+                Self::NotABranch => "branch-not-a-branch",
+                Self::NotEmpty => "branch-not-empty",
+                Self::InvalidName => "branch-invalid-name",
+                Self::InvalidMove => "branch-invalid-move",
+                Self::NoChange => "branch-no-change",
+            }
+        }
+
+        fn status(self) -> StatusCode {
+            match self {
+                Self::NotFound => StatusCode::NOT_FOUND,
+                Self::NotOwned => StatusCode::FORBIDDEN,
+                Self::NotEmpty => StatusCode::CONFLICT,
+                Self::NotABranch | Self::InvalidName | Self::InvalidMove | Self::NoChange => {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                }
+            }
+        }
+
+        fn message(self) -> &'static str {
+            match self {
+                Self::NotFound => "Node not found",
+                Self::NotOwned => "Node belongs to another user",
+                Self::NotABranch => "Node is not a branch",
+                Self::NotEmpty => "Branch is not empty",
+                Self::InvalidName => "Invalid branch name",
+                Self::InvalidMove => "Branch cannot be moved into itself",
+                Self::NoChange => "Nothing to change",
+            }
+        }
+    }
+
+    impl IntoResponse for BranchError {
+        fn into_response(self) -> Response {
+            self.response()
+        }
+    }
+
+    enum BranchChange {
+        Create {
+            parent_id: Uuid,
+            branch: CreateBranch,
+        },
+        Update {
+            node_id: Uuid,
+            update: UpdateBranch,
+        },
+        Delete {
+            node_id: Uuid,
+        },
+    }
+
+    impl BranchChange {
+        fn action(&self) -> AuditAction {
+            match self {
+                Self::Create { .. } => AuditAction::BranchCreate,
+                Self::Update { .. } => AuditAction::BranchUpdate,
+                Self::Delete { .. } => AuditAction::BranchDelete,
+            }
+        }
+
+        fn target(&self) -> Uuid {
+            match self {
+                Self::Create { parent_id, .. } => *parent_id,
+                Self::Update { node_id, .. } | Self::Delete { node_id } => *node_id,
+            }
+        }
+
+        /// Nodes whose trees show the change, a move also shows below the new parent.
+        fn touched(&self) -> Vec<Uuid> {
+            match self {
+                Self::Update {
+                    node_id,
+                    update:
+                        UpdateBranch {
+                            parent_id: Some(parent_id),
+                            ..
+                        },
+                } => vec![*node_id, *parent_id],
+                _ => vec![self.target()],
+            }
+        }
+    }
+
+    /// An own node that may hold branches.
+    fn own_container(nodes: &[TreeNode], id: Uuid) -> Result<&TreeNode, BranchError> {
+        let node = nodes
+            .iter()
+            .find(|node| node.id == id)
+            .ok_or(BranchError::NotFound)?;
+        if node.node_type == NodeType::ImageLeaf {
+            return Err(BranchError::NotABranch);
+        }
+        if !node.own {
+            return Err(BranchError::NotOwned);
+        }
+        Ok(node)
+    }
+
+    fn own_branch(nodes: &[TreeNode], id: Uuid) -> Result<&TreeNode, BranchError> {
+        let node = own_container(nodes, id)?;
+        if node.node_type != NodeType::Branch {
+            return Err(BranchError::NotABranch);
+        }
+        Ok(node)
+    }
+
+    /// Whether `node_id` is `ancestor_id` or lies below it.
+    fn is_within(nodes: &[TreeNode], node_id: Uuid, ancestor_id: Uuid) -> bool {
+        let mut current = Some(node_id);
+        // Bounded in case the agent ever returns a cycle
+        for _ in 0..=nodes.len() {
+            match current {
+                Some(id) if id == ancestor_id => return true,
+                Some(id) => {
+                    current = nodes
+                        .iter()
+                        .find(|node| node.id == id)
+                        .and_then(|node| node.parent_id);
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Validates a change against the user's tree (with leaves) and normalizes the
+    /// names it carries.
+    fn check_change(nodes: &[TreeNode], change: &mut BranchChange) -> Result<(), BranchError> {
+        let valid_name = |name: &mut String| {
+            *name = branch_name(name).ok_or(BranchError::InvalidName)?;
+            Ok::<_, BranchError>(())
+        };
+        match change {
+            BranchChange::Create { parent_id, branch } => {
+                own_container(nodes, *parent_id)?;
+                valid_name(&mut branch.name)?;
+                if let Some(title) = &mut branch.title {
+                    valid_name(title)?;
+                }
+            }
+            BranchChange::Update { node_id, update } => {
+                own_branch(nodes, *node_id)?;
+                if update.name.is_none() && update.title.is_none() && update.parent_id.is_none() {
+                    return Err(BranchError::NoChange);
+                }
+                if let Some(name) = &mut update.name {
+                    valid_name(name)?;
+                }
+                if let Some(title) = &mut update.title {
+                    valid_name(title)?;
+                }
+                if let Some(parent_id) = update.parent_id {
+                    own_container(nodes, parent_id)?;
+                    if is_within(nodes, parent_id, *node_id) {
+                        return Err(BranchError::InvalidMove);
+                    }
+                }
+            }
+            BranchChange::Delete { node_id } => {
+                own_branch(nodes, *node_id)?;
+                if nodes.iter().any(|node| node.parent_id == Some(*node_id)) {
+                    return Err(BranchError::NotEmpty);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Also returns the nodes whose trees the change reaches.
+    async fn change_branch(
+        state: &AppState,
+        user_id: &str,
+        request_id: &str,
+        mut change: BranchChange,
+    ) -> (Response, Option<StatusCode>, HashSet<Uuid>) {
+        let nodes = match agent_tree(state, user_id, true).await {
+            Ok(nodes) => nodes,
+            Err(response) => return (response, None, HashSet::new()),
+        };
+        if let Err(e) = check_change(&nodes, &mut change) {
+            return (e.into_response(), None, HashSet::new());
+        }
+        let touched = with_ancestors(&nodes, &change.touched());
+
+        let chat_config = &state.http_client.config.chat_config;
+        let agent_api_url = &chat_config.agent_api_url;
+        let (method, agent_url, body) = match &change {
+            BranchChange::Create { parent_id, branch } => (
+                reqwest::Method::POST,
+                format!("{agent_api_url}/agent/branches/{parent_id}"),
+                serde_json::to_vec(branch).ok(),
+            ),
+            BranchChange::Update { node_id, update } => (
+                reqwest::Method::PUT,
+                format!("{agent_api_url}/agent/branches/{node_id}"),
+                serde_json::to_vec(update).ok(),
+            ),
+            BranchChange::Delete { node_id } => (
+                reqwest::Method::DELETE,
+                format!("{agent_api_url}/agent/branches/{node_id}"),
+                None,
+            ),
+        };
+        let (response, agent_status) =
+            forward_signed(state, method, &agent_url, request_id, body).await;
+        (response, agent_status, touched)
+    }
+
+    async fn audited_change(
+        state: AppState,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+        change: BranchChange,
+    ) -> Response {
+        let Some(user_id) = agent_user_id(&state, &jar, principal.as_deref()).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+        let request_id = correlation_id(&headers);
+        let (action, target) = (change.action(), change.target().to_string());
+        let (response, agent_status, touched) =
+            change_branch(&state, &user_id, &request_id, change).await;
+        if response.status().is_success() {
+            let touched: Vec<Uuid> = touched.into_iter().collect();
+            state.trees.invalidate(&[&user_id], &touched).await;
+        }
+        audit::record(
+            &state,
+            actor,
+            action,
+            &target,
+            request_id,
+            response.status(),
+            agent_status,
+        )
+        .await;
+        response
+    }
+
+    /// `POST /api/proxy/branches/{node_id}`: a new child branch of the node.
+    pub async fn create_branch_handler(
+        State(state): State<AppState>,
+        Path(node_id): Path<Uuid>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+        Json(branch): Json<CreateBranch>,
+    ) -> Response {
+        let change = BranchChange::Create {
+            parent_id: node_id,
+            branch,
+        };
+        audited_change(state, jar, principal, headers, change).await
+    }
+
+    /// `PUT /api/proxy/branches/{node_id}`: rename and/or move the branch.
+    pub async fn update_branch_handler(
+        State(state): State<AppState>,
+        Path(node_id): Path<Uuid>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+        Json(update): Json<UpdateBranch>,
+    ) -> Response {
+        let change = BranchChange::Update { node_id, update };
+        audited_change(state, jar, principal, headers, change).await
+    }
+
+    /// `DELETE /api/proxy/branches/{node_id}`: only branches without children or
+    /// images.
+    pub async fn delete_branch_handler(
+        State(state): State<AppState>,
+        Path(node_id): Path<Uuid>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+    ) -> Response {
+        let change = BranchChange::Delete { node_id };
+        audited_change(state, jar, principal, headers, change).await
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::components::tree::test_node as node;
+
+        fn update(node_id: u128, parent_id: u128) -> BranchChange {
+            BranchChange::Update {
+                node_id: Uuid::from_u128(node_id),
+                update: UpdateBranch {
+                    parent_id: Some(Uuid::from_u128(parent_id)),
+                    ..UpdateBranch::default()
+                },
+            }
+        }
+
+        #[test]
+        fn changes_respect_ownership_emptiness_and_cycles() {
+            let nodes = [
+                node(1, None, NodeType::Root, true),
+                node(2, Some(1), NodeType::Branch, true),
+                node(3, Some(2), NodeType::Branch, true),
+                node(4, Some(3), NodeType::ImageLeaf, true),
+                node(5, Some(1), NodeType::Branch, false),
+                node(6, Some(1), NodeType::Branch, true),
+            ];
+            let check = |mut change| check_change(&nodes, &mut change);
+
+            let mut create = BranchChange::Create {
+                parent_id: Uuid::from_u128(1),
+                branch: CreateBranch {
+                    name: "  Haus A ".to_string(),
+                    title: None,
+                },
+            };
+            assert_eq!(check_change(&nodes, &mut create), Ok(()));
+            let BranchChange::Create { branch, .. } = create else {
+                unreachable!()
+            };
+            assert_eq!(branch.name, "Haus A");
+            assert_eq!(
+                check(BranchChange::Create {
+                    parent_id: Uuid::from_u128(5),
+                    branch: CreateBranch {
+                        name: "x".to_string(),
+                        title: None,
+                    },
+                }),
+                Err(BranchError::NotOwned)
+            );
+
+            assert_eq!(check(update(3, 6)), Ok(()));
+            assert_eq!(check(update(2, 3)), Err(BranchError::InvalidMove));
+            assert_eq!(check(update(2, 2)), Err(BranchError::InvalidMove));
+            assert_eq!(check(update(2, 4)), Err(BranchError::NotABranch));
+            assert_eq!(check(update(1, 2)), Err(BranchError::NotABranch));
+            assert_eq!(
+                check(BranchChange::Update {
+                    node_id: Uuid::from_u128(6),
+                    update: UpdateBranch::default(),
+                }),
+                Err(BranchError::NoChange)
+            );
+
+            let delete = |id| BranchChange::Delete {
+                node_id: Uuid::from_u128(id),
+            };
+            assert_eq!(check(delete(3)), Err(BranchError::NotEmpty));
+            assert_eq!(check(delete(5)), Err(BranchError::NotOwned));
+            assert_eq!(check(delete(6)), Ok(()));
+            assert_eq!(check(delete(9)), Err(BranchError::NotFound));
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub use ssr::{create_branch_handler, delete_branch_handler, update_branch_handler};
@@ -0,0 +1,558 @@
+use serde::{Deserialize, Serialize};
+
+/// Header carrying the hex SHA-256 of an upload part.
+pub const CHUNK_CHECKSUM_HEADER: &str = "X-Chunk-Sha256";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateChunkedUpload {
+    pub file_name: String,
+    pub size: u64,
+    #[serde(default)]
+    pub berlin_datetime: Option<String>,
+    #[serde(default)]
+    pub perceptual_hash: Option<String>,
+}
+
+/// Progress of a resumable upload, the next part starts at `received`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkedUploadStatus {
+    pub upload_id: String,
+    pub size: u64,
+    pub received: u64,
+    /// Largest part the server accepts.
+    pub chunk_size: usize,
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::*;
+    use crate::api_tokens::ApiPrincipal;
+    use crate::audit::{self, AuditAction, AuditActor};
+    use crate::config::UploadConfig;
+    use crate::duplicates::PERCEPTUAL_HASH_FIELD;
+    use crate::proxy_reports::upload_staged_image;
+    use crate::proxy_tree::invalidate_node;
+    use crate::sessions::error_response;
+    use crate::sharing::require_edit;
+    use crate::ssr::correlation_id;
+    use crate::state::AppState;
+    use crate::upload::{StagedImage, UploadError};
+    use axum::{
+        Extension, Json,
+        body::Bytes,
+        extract::{Path, Query, State},
+        http::{HeaderMap, StatusCode},
+        response::{IntoResponse, Response},
+    };
+    use axum_extra::extract::CookieJar;
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+    use tokio::sync::Mutex;
+
+    /// Uploads without a new part for this long are dropped.
+    const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+    const MAX_OPEN_UPLOADS_PER_USER: usize = 20;
+    /// How often stale uploads are looked for besides on `create`.
+    const SWEEP_EVERY: Duration = Duration::from_secs(60 * 60);
+
+    struct StagedUpload {
+        id: String,
+        parent_id: String,
+        file_name: String,
+        size: u64,
+        received: u64,
+        fields: Vec<(String, String)>,
+        path: PathBuf,
+        updated: Instant,
+    }
+
+    impl StagedUpload {
+        fn status(&self, config: &UploadConfig) -> ChunkedUploadStatus {
+            ChunkedUploadStatus {
+                upload_id: self.id.clone(),
+                size: self.size,
+                received: self.received,
+                chunk_size: config.chunk_bytes,
+            }
+        }
+
+        /// Where a part of `len` bytes at `offset` goes.
+        fn fit_part(&self, offset: u64, len: u64) -> PartFit {
+            match offset.checked_add(len) {
+                Some(end) if end <= self.size => {
+                    if end <= self.received {
+                        PartFit::AlreadyStored
+                    } else if offset != self.received {
+                        PartFit::OutOfOrder
+                    } else {
+                        PartFit::Append { end }
+                    }
+                }
+                _ => PartFit::OutOfBounds,
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum PartFit {
+        /// The next part, the upload then ends at `end`.
+        Append { end: u64 },
+        /// A replayed part, acknowledged without rewriting it.
+        AlreadyStored,
+        /// A gap or overlap; the client resumes from `received`.
+        OutOfOrder,
+        /// Past the declared size, or an offset that overflows.
+        OutOfBounds,
+    }
+
+    struct UploadEntry {
+        owner: String,
+        /// Declared size, counted against the store's budget.
+        size: u64,
+        upload: Arc<Mutex<StagedUpload>>,
+    }
+
+    /// Resumable uploads in progress, their bytes staged under `staging_dir`.
+    pub struct ChunkedUploadStore {
+        dir: PathBuf,
+        /// Declared sizes of all open uploads may not exceed this.
+        max_total_bytes: u64,
+        uploads: Mutex<HashMap<String, UploadEntry>>,
+    }
+
+    impl ChunkedUploadStore {
+        /// Staged files of a previous run cannot be resumed and are removed.
+        pub fn new(dir: impl Into<PathBuf>, max_total_bytes: u64) -> Self {
+            let dir = dir.into();
+            if let Ok(entries) = std::fs::read_dir(&dir) {
+                for path in entries.flatten().map(|entry| entry.path()) {
+                    if path.extension().is_some_and(|ext| ext == "part") {
+                        let _ = std::fs::remove_file(path);
+                    }
+                }
+            }
+            Self {
+                dir,
+                max_total_bytes,
+                uploads: Mutex::new(HashMap::new()),
+            }
+        }
+
+        /// Drops stale uploads every `SWEEP_EVERY` until the store is dropped.
+        pub fn spawn_sweeper(store: &Arc<Self>) {
+            let store = Arc::downgrade(store);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(SWEEP_EVERY);
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let Some(store) = store.upgrade() else {
+                        break;
+                    };
+                    store.sweep_at(Instant::now()).await;
+                }
+            });
+        }
+
+        async fn create(
+            &self,
+            owner: &str,
+            parent_id: &str,
+            request: CreateChunkedUpload,
+        ) -> Result<Arc<Mutex<StagedUpload>>, Response> {
+            self.sweep_at(Instant::now()).await;
+            let mut uploads = self.uploads.lock().await;
+            let open = uploads
+                .values()
+                .filter(|entry| entry.owner == owner)
+                .count();
+            if open >= MAX_OPEN_UPLOADS_PER_USER {
+                return Err(error_response(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "Too many unfinished uploads",
+                ));
+            }
+            let staged: u64 = uploads.values().map(|entry| entry.size).sum();
+            if staged.saturating_add(request.size) > self.max_total_bytes {
+                tracing::warn!(
+                    staged,
+                    size = request.size,
+                    "chunked upload: staging budget exhausted"
+                );
+                return Err(error_response(
+                    StatusCode::INSUFFICIENT_STORAGE,
+                    "Upload staging is full, try again later",
+                ));
+            }
+
+            let id = uuid::Uuid::now_v7().simple().to_string();
+            let path = self.dir.join(format!("{id}.part"));
+            let created = async {
+                tokio::fs::create_dir_all(&self.dir).await?;
+                tokio::fs::File::create(&path).await
+            };
+            if let Err(e) = created.await {
+                tracing::error!(error = %e, path = %path.display(), "chunked upload: staging failed");
+                return Err(error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to stage upload",
+                ));
+            }
+            let fields = [
+                ("berlin_datetime", request.berlin_datetime),
+                (PERCEPTUAL_HASH_FIELD, request.perceptual_hash),
+            ]
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value?)))
+            .filter(|(_, value)| !value.trim().is_empty())
+            .collect();
+            let size = request.size;
+            let upload = Arc::new(Mutex::new(StagedUpload {
+                id: id.clone(),
+                parent_id: parent_id.to_string(),
+                file_name: request.file_name,
+                size,
+                received: 0,
+                fields,
+                path,
+                updated: Instant::now(),
+            }));
+            uploads.insert(
+                id,
+                UploadEntry {
+                    owner: owner.to_string(),
+                    size,
+                    upload: upload.clone(),
+                },
+            );
+            Ok(upload)
+        }
+
+        /// The upload if it exists and belongs to `owner`.
+        async fn get(&self, id: &str, owner: &str) -> Option<Arc<Mutex<StagedUpload>>> {
+            let uploads = self.uploads.lock().await;
+            let entry = uploads.get(id).filter(|entry| entry.owner == owner)?;
+            Some(entry.upload.clone())
+        }
+
+        async fn remove(&self, upload: &StagedUpload) {
+            self.uploads.lock().await.remove(&upload.id);
+            if let Err(e) = tokio::fs::remove_file(&upload.path).await {
+                tracing::warn!(error = %e, upload_id = %upload.id, "chunked upload: failed to remove staged file");
+            }
+        }
+
+        async fn sweep_at(&self, now: Instant) {
+            let mut uploads = self.uploads.lock().await;
+            let mut stale = Vec::new();
+            for (id, entry) in uploads.iter() {
+                // Busy uploads are not stale
+                if let Ok(upload) = entry.upload.try_lock()
+                    && now.saturating_duration_since(upload.updated) > STALE_AFTER
+                {
+                    stale.push((id.clone(), upload.path.clone()));
+                }
+            }
+            for (id, path) in stale {
+                uploads.remove(&id);
+                let _ = tokio::fs::remove_file(path).await;
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct PartQuery {
+        pub offset: u64,
+    }
+
+    fn not_found() -> Response {
+        error_response(StatusCode::NOT_FOUND, "Unknown upload")
+    }
+
+    /// 409 with the current status, so the client can resume from `received`.
+    fn conflict(status: ChunkedUploadStatus) -> Response {
+        let mut body = serde_json::to_value(&status).unwrap_or_default();
+        body["error"] = "Upload offset mismatch".into();
+        (StatusCode::CONFLICT, Json(body)).into_response()
+    }
+
+    pub async fn create_chunked_upload_handler(
+        State(state): State<AppState>,
+        Path(parent_id): Path<String>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        Json(request): Json<CreateChunkedUpload>,
+    ) -> Response {
+        let config = &state.http_client.config.upload_config;
+        if request.size == 0 {
+            return UploadError::MissingFile.into_response();
+        }
+        if request.size > config.max_staged_bytes {
+            return UploadError::TooLarge {
+                max_bytes: config.max_staged_bytes as usize,
+            }
+            .into_response();
+        }
+        if let Err(response) = require_edit(&state, &jar, principal.as_deref(), &parent_id).await {
+            return response;
+        }
+        let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+        match state
+            .chunked_uploads
+            .create(&actor.subject, &parent_id, request)
+            .await
+        {
+            Ok(upload) => (
+                StatusCode::CREATED,
+                Json(upload.lock().await.status(config)),
+            )
+                .into_response(),
+            Err(response) => response,
+        }
+    }
+
+    pub async fn chunked_upload_status_handler(
+        State(state): State<AppState>,
+        Path(upload_id): Path<String>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+    ) -> Response {
+        let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+        match state.chunked_uploads.get(&upload_id, &actor.subject).await {
+            Some(upload) => Json(
+                upload
+                    .lock()
+                    .await
+                    .status(&state.http_client.config.upload_config),
+            )
+            .into_response(),
+            None => not_found(),
+        }
+    }
+
+    /// Stores one part at `offset`. Parts arrive in order; a repeated part that is
+    /// already stored is acknowledged without rewriting it.
+    pub async fn put_upload_part_handler(
+        State(state): State<AppState>,
+        Path(upload_id): Path<String>,
+        Query(query): Query<PartQuery>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response {
+        let config = &state.http_client.config.upload_config;
+        let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+        let Some(upload) = state.chunked_uploads.get(&upload_id, &actor.subject).await else {
+            return not_found();
+        };
+        let Some(checksum) = headers
+            .get(CHUNK_CHECKSUM_HEADER)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return error_response(StatusCode::BAD_REQUEST, "Missing part checksum");
+        };
+        if body.is_empty() || body.len() > config.chunk_bytes {
+            return error_response(StatusCode::BAD_REQUEST, "Invalid part size");
+        }
+        if !hex::encode(Sha256::digest(&body)).eq_ignore_ascii_case(checksum.trim()) {
+            return error_response(StatusCode::UNPROCESSABLE_ENTITY, "Part checksum mismatch");
+        }
+
+        let mut upload = upload.lock().await;
+        let end = match upload.fit_part(query.offset, body.len() as u64) {
+            PartFit::Append { end } => end,
+            PartFit::AlreadyStored => return Json(upload.status(config)).into_response(),
+            PartFit::OutOfOrder => return conflict(upload.status(config)),
+            PartFit::OutOfBounds => {
+                return error_response(StatusCode::BAD_REQUEST, "Part exceeds the declared size");
+            }
+        };
+        let written = async {
+            let mut file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .open(&upload.path)
+                .await?;
+            file.seek(std::io::SeekFrom::Start(query.offset)).await?;
+            file.write_all(&body).await?;
+            file.flush().await
+        };
+        if let Err(e) = written.await {
+            tracing::error!(error = %e, upload_id = %upload.id, "chunked upload: failed to write part");
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store part");
+        }
+        upload.received = end;
+        upload.updated = Instant::now();
+        Json(upload.status(config)).into_response()
+    }
+
+    pub async fn abort_chunked_upload_handler(
+        State(state): State<AppState>,
+        Path(upload_id): Path<String>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+    ) -> Response {
+        let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+        let Some(upload) = state.chunked_uploads.get(&upload_id, &actor.subject).await else {
+            return not_found();
+        };
+        state.chunked_uploads.remove(&*upload.lock().await).await;
+        StatusCode::NO_CONTENT.into_response()
+    }
+
+    /// Validates the staged file, applies the EXIF policy and hands it to the agent.
+    pub async fn complete_chunked_upload_handler(
+        State(state): State<AppState>,
+        Path(upload_id): Path<String>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+    ) -> Response {
+        let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+        let Some(upload) = state.chunked_uploads.get(&upload_id, &actor.subject).await else {
+            return not_found();
+        };
+        // Held until the hand-off is done, so no part can change the file meanwhile
+        let upload = upload.lock().await;
+        let config = &state.http_client.config.upload_config;
+        if upload.received != upload.size {
+            return conflict(upload.status(config));
+        }
+
+        let request_id = correlation_id(&headers);
+        // The share may have been revoked or narrowed since the upload started
+        let user_id =
+            match require_edit(&state, &jar, principal.as_deref(), &upload.parent_id).await {
+                Ok(user_id) => user_id,
+                Err(response) => {
+                    state.chunked_uploads.remove(&upload).await;
+                    return response;
+                }
+            };
+        let (response, agent_status, finished) = match StagedImage::prepare(
+            upload.path.clone(),
+            &upload.file_name,
+            upload.fields.clone(),
+            config,
+        )
+        .await
+        {
+            Ok(staged) => {
+                let (response, agent_status) =
+                    upload_staged_image(&state, &upload.parent_id, &request_id, &staged).await;
+                // Kept for another attempt when the agent is unreachable or failing
+                let retryable = agent_status.is_none_or(|status| status.is_server_error());
+                (response, agent_status, !retryable)
+            }
+            Err(e) => {
+                tracing::warn!(request_id = %request_id, code = e.code(), "upload rejected");
+                (e.into_response(), None, true)
+            }
+        };
+        if finished {
+            state.chunked_uploads.remove(&upload).await;
+        }
+        if response.status().is_success() {
+            invalidate_node(&state, &user_id, &upload.parent_id).await;
+        }
+        audit::record(
+            &state,
+            actor,
+            AuditAction::ImageUpload,
+            &upload.parent_id,
+            request_id,
+            response.status(),
+            agent_status,
+        )
+        .await;
+        response
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn request(size: u64) -> CreateChunkedUpload {
+            CreateChunkedUpload {
+                file_name: "pano.jpg".to_string(),
+                size,
+                berlin_datetime: Some(" ".to_string()),
+                perceptual_hash: None,
+            }
+        }
+
+        #[tokio::test]
+        async fn uploads_are_owned_and_cleaned_up() {
+            let dir = std::env::temp_dir().join(format!("gmr-chunks-{}", uuid::Uuid::now_v7()));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("leftover.part"), b"old").unwrap();
+            let store = ChunkedUploadStore::new(&dir, 100);
+            assert!(!dir.join("leftover.part").exists());
+
+            let upload = store.create("alice", "node-1", request(10)).await.unwrap();
+            let (id, path) = {
+                let upload = upload.lock().await;
+                assert!(upload.fields.is_empty());
+                (upload.id.clone(), upload.path.clone())
+            };
+            assert!(path.exists());
+            assert!(store.get(&id, "bob").await.is_none());
+            assert!(store.get(&id, "alice").await.is_some());
+
+            store.sweep_at(Instant::now() + STALE_AFTER * 2).await;
+            assert!(store.get(&id, "alice").await.is_none());
+            assert!(!path.exists());
+
+            let _ = std::fs::remove_dir_all(dir);
+        }
+
+        #[tokio::test]
+        async fn declared_sizes_share_one_budget() {
+            let dir = std::env::temp_dir().join(format!("gmr-chunks-{}", uuid::Uuid::now_v7()));
+            let store = ChunkedUploadStore::new(&dir, 100);
+            store.create("alice", "node-1", request(60)).await.unwrap();
+            let full = store.create("bob", "node-2", request(50)).await.err();
+            assert_eq!(
+                full.map(|response| response.status()),
+                Some(StatusCode::INSUFFICIENT_STORAGE)
+            );
+
+            let upload = store.create("bob", "node-2", request(40)).await.unwrap();
+            store.remove(&*upload.lock().await).await;
+            assert!(store.create("bob", "node-2", request(40)).await.is_ok());
+
+            let _ = std::fs::remove_dir_all(dir);
+        }
+
+        #[tokio::test]
+        async fn parts_are_placed_in_order_and_bounded() {
+            let dir = std::env::temp_dir().join(format!("gmr-chunks-{}", uuid::Uuid::now_v7()));
+            let store = ChunkedUploadStore::new(&dir, 100);
+            let upload = store.create("alice", "node-1", request(10)).await.unwrap();
+            let mut upload = upload.lock().await;
+
+            assert_eq!(upload.fit_part(0, 4), PartFit::Append { end: 4 });
+            upload.received = 4;
+            // Replayed part
+            assert_eq!(upload.fit_part(0, 4), PartFit::AlreadyStored);
+            // Gap and overlap
+            assert_eq!(upload.fit_part(6, 2), PartFit::OutOfOrder);
+            assert_eq!(upload.fit_part(2, 4), PartFit::OutOfOrder);
+            assert_eq!(upload.fit_part(4, 7), PartFit::OutOfBounds);
+            assert_eq!(upload.fit_part(u64::MAX - 1, 4), PartFit::OutOfBounds);
+            assert_eq!(upload.fit_part(4, 6), PartFit::Append { end: 10 });
+
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub use ssr::{
+    ChunkedUploadStore, abort_chunked_upload_handler, chunked_upload_status_handler,
+    complete_chunked_upload_handler, create_chunked_upload_handler, put_upload_part_handler,
+};
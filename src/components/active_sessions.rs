@@ -0,0 +1,202 @@
+use crate::components::args;
+use crate::csrf::{CSRF_HEADER, csrf_token};
+use crate::sessions::{ActiveSession, describe_user_agent};
+use js_sys::Date;
+use leptos::prelude::*;
+use leptos::{IntoView, component, view};
+use leptos_fluent::{I18n, move_tr};
+use wasm_bindgen_futures::{JsFuture, spawn_local};
+use web_sys::wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Request, RequestInit, Response};
+
+/// Sessions of the signed-in user, or of `subject` when an admin looks at another user.
+#[component]
+pub fn ActiveSessions(#[prop(optional, into)] subject: Option<String>) -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let (reload, set_reload) = signal(0_u32);
+    let (status, set_status) = signal(String::new());
+    let (revoking, set_revoking) = signal(None::<String>);
+
+    let sessions_resource = LocalResource::new(move || {
+        let _ = reload.get();
+        let url = sessions_url(subject.as_deref());
+        async move { fetch_sessions(&url).await }
+    });
+
+    let revoke = move |session: ActiveSession| {
+        set_revoking.set(Some(session.handle.clone()));
+        set_status.set(String::new());
+        spawn_local(async move {
+            let url = format!("/api/sessions/{}", session.handle);
+            match send("DELETE", &url).await {
+                Ok(_) if session.current => {
+                    if let Some(window) = web_sys::window() {
+                        let _ = window.location().set_href("/");
+                    }
+                }
+                Ok(_) => set_reload.update(|value| *value += 1),
+                Err(error) => set_status
+                    .set(i18n.tr_with_args("sessions-revoke-error", &args!["error" => error])),
+            }
+            set_revoking.set(None);
+        });
+    };
+
+    view! {
+        <div class="sessions-card">
+            <h3>{move_tr!("sessions-title")}</h3>
+            <Suspense fallback=move || view! { <p class="loading">{move_tr!("sessions-loading")}</p> }>
+                {move || {
+                    sessions_resource
+                        .get()
+                        .map(|result| match result {
+                            Ok(sessions) if sessions.is_empty() => {
+                                view! { <p>{move_tr!("sessions-empty")}</p> }.into_any()
+                            }
+                            Ok(sessions) => {
+                                let locale = i18n.language.get().id.to_string();
+                                view! {
+                                    <ul class="sessions-list">
+                                        {sessions
+                                            .into_iter()
+                                            .map(|session| {
+                                                let handle = session.handle.clone();
+                                                let device = session
+                                                    .user_agent
+                                                    .as_deref()
+                                                    .map(describe_user_agent)
+                                                    .unwrap_or_else(|| {
+                                                        move_tr!("sessions-unknown-device").get()
+                                                    });
+                                                let created = format_timestamp(session.created_at, &locale);
+                                                let last_activity =
+                                                    format_timestamp(session.last_activity, &locale);
+                                                let ip = session.ip.clone().unwrap_or_default();
+                                                let current = session.current;
+                                                view! {
+                                                    <li class="session-item" class:current=current>
+                                                        <div class="session-device">
+                                                            <i class="fas fa-display"></i>
+                                                            <span>{device}</span>
+                                                            <Show when=move || current>
+                                                                <span class="session-current">
+                                                                    {move_tr!("sessions-current")}
+                                                                </span>
+                                                            </Show>
+                                                        </div>
+                                                        <div class="session-meta">
+                                                            <span>{move_tr!("sessions-created")}" "{created}</span>
+                                                            <span>{move_tr!("sessions-last-active")}" "{last_activity}</span>
+                                                            <span class:none=ip.is_empty()>
+                                                                {move_tr!("sessions-ip")}" "{ip.clone()}
+                                                            </span>
+                                                        </div>
+                                                        <button
+                                                            type="button"
+                                                            class="session-revoke"
+                                                            disabled=move || revoking.get().as_deref() == Some(handle.as_str())
+                                                            on:click=move |_| revoke(session.clone())
+                                                        >
+                                                            <i class="fas fa-right-from-bracket"></i>
+                                                            <span>{move_tr!("sessions-revoke")}</span>
+                                                        </button>
+                                                    </li>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </ul>
+                                }
+                                    .into_any()
+                            }
+                            Err(error) => view! {
+                                <p class="error">
+                                    {i18n.tr_with_args("sessions-error", &args!["error" => error])}
+                                </p>
+                            }
+                                .into_any(),
+                        })
+                }}
+            </Suspense>
+            <p class="error" class:none=move || status.get().is_empty()>{move || status.get()}</p>
+        </div>
+    }
+}
+
+fn sessions_url(subject: Option<&str>) -> String {
+    match subject {
+        Some(subject) => format!(
+            "/api/sessions?subject={}",
+            js_sys::encode_uri_component(subject)
+        ),
+        None => "/api/sessions".to_string(),
+    }
+}
+
+pub(crate) fn format_timestamp(seconds: i64, locale: &str) -> String {
+    let date = Date::new(&JsValue::from_f64(seconds as f64 * 1000.0));
+    date.to_locale_string(locale, &JsValue::UNDEFINED).into()
+}
+
+async fn fetch_sessions(url: &str) -> Result<Vec<ActiveSession>, String> {
+    let text = send("GET", url).await?;
+    serde_json::from_str(&text).map_err(|e| format!("Failed to deserialize response: {e}"))
+}
+
+pub(crate) async fn send(method: &str, url: &str) -> Result<String, String> {
+    send_with_body(method, url, None).await
+}
+
+pub(crate) async fn send_with_body(
+    method: &str,
+    url: &str,
+    body: Option<String>,
+) -> Result<String, String> {
+    let window = web_sys::window().ok_or_else(|| "No window available".to_string())?;
+    let opts = RequestInit::new();
+    opts.set_method(method);
+    if let Some(body) = &body {
+        opts.set_body(&JsValue::from_str(body));
+    }
+
+    let request = Request::new_with_str_and_init(url, &opts)
+        .map_err(|e| format!("Failed to create request: {:?}", e))?;
+    request
+        .headers()
+        .set("Accept", "application/json")
+        .map_err(|e| format!("Failed to set accept header: {:?}", e))?;
+    request
+        .headers()
+        .set(CSRF_HEADER, &csrf_token())
+        .map_err(|e| format!("Failed to set CSRF header: {:?}", e))?;
+    if body.is_some() {
+        request
+            .headers()
+            .set("Content-Type", "application/json")
+            .map_err(|e| format!("Failed to set content type: {:?}", e))?;
+    }
+
+    let response: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(|e| format!("Fetch failed: {:?}", e))?
+        .dyn_into()
+        .map_err(|_| "Failed to convert fetch response".to_string())?;
+
+    let status = response.status();
+    let text = JsFuture::from(
+        response
+            .text()
+            .map_err(|e| format!("Failed to read response body: {:?}", e))?,
+    )
+    .await
+    .map_err(|e| format!("Failed to read response body: {:?}", e))?
+    .as_string()
+    .unwrap_or_default();
+
+    if !(200..300).contains(&status) {
+        let error = serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|value| value.get("error")?.as_str().map(str::to_string));
+        return Err(error.unwrap_or_else(|| format!("Request failed: {status}")));
+    }
+    Ok(text)
+}
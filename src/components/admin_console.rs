@@ -0,0 +1,250 @@
+use crate::admin::{AdminSession, RevokedAccess, group_by_user};
+use crate::components::active_sessions::{format_timestamp, send};
+use crate::components::args;
+use crate::sessions::describe_user_agent;
+use leptos::prelude::*;
+use leptos::{IntoView, component, view};
+use leptos_fluent::{I18n, move_tr};
+use wasm_bindgen_futures::spawn_local;
+
+#[component]
+pub fn AdminConsole() -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let (reload, set_reload) = signal(0_u32);
+    let (status, set_status) = signal(String::new());
+    let (notice, set_notice) = signal(String::new());
+    let (busy, set_busy) = signal(None::<String>);
+
+    let sessions_resource = LocalResource::new(move || {
+        let _ = reload.get();
+        async move {
+            let text = send("GET", "/api/admin/sessions").await?;
+            serde_json::from_str::<Vec<AdminSession>>(&text)
+                .map_err(|e| format!("Failed to deserialize response: {e}"))
+        }
+    });
+
+    let run_action = move |method: &'static str, url: String, handle: String| {
+        set_busy.set(Some(handle));
+        set_status.set(String::new());
+        set_notice.set(String::new());
+        spawn_local(async move {
+            if let Err(error) = send(method, &url).await {
+                set_status.set(i18n.tr_with_args("admin-error", &args!["error" => error]));
+            }
+            set_busy.set(None);
+            set_reload.update(|value| *value += 1);
+        });
+    };
+
+    let revoke_access = move |handle: String| {
+        set_busy.set(Some(handle.clone()));
+        set_status.set(String::new());
+        set_notice.set(String::new());
+        spawn_local(async move {
+            let url = format!("/api/admin/sessions/{handle}/revoke-access");
+            match send("POST", &url).await.and_then(|text| {
+                serde_json::from_str::<RevokedAccess>(&text).map_err(|e| e.to_string())
+            }) {
+                Ok(revoked) => set_notice.set(i18n.tr_with_args(
+                    "admin-access-revoked",
+                    &args![
+                        "sessions" => revoked.sessions,
+                        "tokens" => revoked.api_tokens
+                    ],
+                )),
+                Err(error) => {
+                    set_status.set(i18n.tr_with_args("admin-error", &args!["error" => error]))
+                }
+            }
+            set_busy.set(None);
+            set_reload.update(|value| *value += 1);
+        });
+    };
+
+    view! {
+        <div class="admin-console">
+            <div class="admin-header">
+                <h2>{move_tr!("admin-title")}</h2>
+                <button
+                    type="button"
+                    class="admin-refresh"
+                    title=move || move_tr!("admin-refresh").get()
+                    on:click=move |_| set_reload.update(|value| *value += 1)
+                >
+                    <i class="fas fa-rotate"></i>
+                </button>
+            </div>
+            <p class="error" class:none=move || status.get().is_empty()>{move || status.get()}</p>
+            <p class="admin-notice" class:none=move || notice.get().is_empty()>
+                {move || notice.get()}
+            </p>
+            <Suspense fallback=move || view! { <p class="loading">{move_tr!("admin-loading")}</p> }>
+                {move || {
+                    sessions_resource
+                        .get()
+                        .map(|result| match result {
+                            Ok(sessions) if sessions.is_empty() => {
+                                view! { <p>{move_tr!("admin-empty")}</p> }.into_any()
+                            }
+                            Ok(sessions) => {
+                                let locale = i18n.language.get().id.to_string();
+                                group_by_user(sessions)
+                                    .into_iter()
+                                    .map(|(subject, sessions)| {
+                                        let first = &sessions[0];
+                                        let name = first.session.name.clone().unwrap_or(subject.clone());
+                                        let email = first.email.clone().unwrap_or_default();
+                                        let roles = first.roles.clone();
+                                        let handle = first.session.handle.clone();
+                                        view! {
+                                            <section class="admin-user">
+                                                <header>
+                                                    <strong>{name}</strong>
+                                                    <span class="admin-email">{email}</span>
+                                                    <span class="roles-list">
+                                                        {roles
+                                                            .into_iter()
+                                                            .map(|role| {
+                                                                let class = format!("role-badge role-{role}");
+                                                                view! { <span class=class>{role}</span> }
+                                                            })
+                                                            .collect_view()}
+                                                    </span>
+                                                    <button
+                                                        type="button"
+                                                        class="session-revoke admin-revoke-access"
+                                                        title=move || move_tr!("admin-revoke-access-hint").get()
+                                                        on:click=move |_| revoke_access(handle.clone())
+                                                    >
+                                                        <i class="fas fa-user-lock"></i>
+                                                        <span>{move_tr!("admin-revoke-access")}</span>
+                                                    </button>
+                                                </header>
+                                                <ul class="sessions-list">
+                                                    {sessions
+                                                        .into_iter()
+                                                        .map(|row| {
+                                                            view! {
+                                                                <AdminSessionRow
+                                                                    row=row
+                                                                    locale=locale.clone()
+                                                                    busy=busy
+                                                                    on_action=run_action
+                                                                />
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                </ul>
+                                            </section>
+                                        }
+                                    })
+                                    .collect_view()
+                                    .into_any()
+                            }
+                            Err(error) => view! {
+                                <p class="error">
+                                    {i18n.tr_with_args("admin-error", &args!["error" => error])}
+                                </p>
+                            }
+                                .into_any(),
+                        })
+                }}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn AdminSessionRow<F>(
+    row: AdminSession,
+    locale: String,
+    busy: ReadSignal<Option<String>>,
+    on_action: F,
+) -> impl IntoView
+where
+    F: Fn(&'static str, String, String) + Copy + 'static,
+{
+    let i18n = expect_context::<I18n>();
+    let handle = row.session.handle.clone();
+    let device = row
+        .session
+        .user_agent
+        .as_deref()
+        .map(describe_user_agent)
+        .unwrap_or_else(|| move_tr!("sessions-unknown-device").get());
+    let last_activity = format_timestamp(row.session.last_activity, &locale);
+    let expiry = match row.expires_in_secs {
+        Some(secs) if secs > 0 => {
+            i18n.tr_with_args("admin-expires-in", &args!["minutes" => secs / 60])
+        }
+        Some(_) => i18n.tr("admin-expired"),
+        None => String::new(),
+    };
+    let refresh = if row.refreshing {
+        i18n.tr("admin-refreshing")
+    } else if row.has_refresh_token {
+        i18n.tr("admin-refresh-token")
+    } else {
+        i18n.tr("admin-no-refresh-token")
+    };
+    let current = row.session.current;
+    let stream = row.stream_request_id.clone();
+    let is_busy = {
+        let handle = handle.clone();
+        move || busy.get().as_deref() == Some(handle.as_str())
+    };
+    let stop_url = format!("/api/admin/sessions/{handle}/stop");
+    let terminate_url = format!("/api/admin/sessions/{handle}");
+    let stop_handle = handle.clone();
+
+    view! {
+        <li class="session-item" class:current=current>
+            <div class="session-device">
+                <i class="fas fa-display"></i>
+                <span>{device}</span>
+                <Show when=move || current>
+                    <span class="session-current">{move_tr!("sessions-current")}</span>
+                </Show>
+            </div>
+            <div class="session-meta">
+                <span>{move_tr!("sessions-last-active")}" "{last_activity}</span>
+                <span>{row.session.ip.clone().unwrap_or_default()}</span>
+                <span>{expiry}</span>
+                <span>{refresh}</span>
+                {stream
+                    .clone()
+                    .map(|request_id| {
+                        view! {
+                            <span class="admin-stream" title=request_id>
+                                <i class="fas fa-tower-broadcast"></i>
+                                {move_tr!("admin-stream-active")}
+                            </span>
+                        }
+                    })}
+            </div>
+            <div class="admin-actions">
+                <button
+                    type="button"
+                    class="session-revoke"
+                    class:none=stream.is_none()
+                    disabled=is_busy.clone()
+                    on:click=move |_| on_action("POST", stop_url.clone(), stop_handle.clone())
+                >
+                    <i class="fas fa-stop"></i>
+                    <span>{move_tr!("admin-stop-stream")}</span>
+                </button>
+                <button
+                    type="button"
+                    class="session-revoke"
+                    disabled=is_busy
+                    title=move || move_tr!("admin-terminate-hint").get()
+                    on:click=move |_| on_action("DELETE", terminate_url.clone(), handle.clone())
+                >
+                    <i class="fas fa-right-from-bracket"></i>
+                    <span>{move_tr!("admin-terminate")}</span>
+                </button>
+            </div>
+        </li>
+    }
+}
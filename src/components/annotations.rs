@@ -0,0 +1,146 @@
+use crate::components::chat_data::{Annotation, ElementCategory, annotation_counts};
+use leptos::prelude::*;
+use leptos_fluent::{I18n, move_tr};
+
+/// Which categories are drawn and which one is highlighted, shared by the
+/// images and the detail texts of one message.
+#[derive(Clone, Copy)]
+pub struct AnnotationState {
+    hidden: RwSignal<Vec<ElementCategory>>,
+    pub highlighted: RwSignal<Option<ElementCategory>>,
+}
+
+impl AnnotationState {
+    pub fn new() -> Self {
+        Self {
+            hidden: RwSignal::new(Vec::new()),
+            highlighted: RwSignal::new(None),
+        }
+    }
+
+    pub fn is_visible(&self, category: ElementCategory) -> bool {
+        self.hidden.with(|hidden| !hidden.contains(&category))
+    }
+
+    pub fn toggle(&self, category: ElementCategory) {
+        self.hidden.update(|hidden| {
+            if let Some(index) = hidden.iter().position(|c| *c == category) {
+                hidden.remove(index);
+            } else {
+                hidden.push(category);
+            }
+        });
+    }
+}
+
+fn category_class(category: ElementCategory) -> &'static str {
+    match category {
+        ElementCategory::Windows => "annotation-windows",
+        ElementCategory::Doors => "annotation-doors",
+        ElementCategory::Radiators => "annotation-radiators",
+        ElementCategory::Openings => "annotation-openings",
+    }
+}
+
+/// Detail text of one category, highlighted together with its outlines.
+#[component]
+pub fn AnnotatedDetail(
+    category: ElementCategory,
+    value: String,
+    state: AnnotationState,
+) -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let highlighted = move || state.highlighted.get() == Some(category);
+    view! {
+        <div
+            class=format!("detail-item {}", category_class(category))
+            class:annotation-highlighted=highlighted
+            on:mouseenter=move |_| state.highlighted.set(Some(category))
+            on:mouseleave=move |_| state.highlighted.set(None)
+        >
+            <strong>{i18n.tr(category.label_key())}" "</strong>
+            {value}
+        </div>
+    }
+}
+
+/// One switch per annotated category, with its count.
+#[component]
+pub fn AnnotationToggles(annotations: Vec<Annotation>, state: AnnotationState) -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    view! {
+        <div class="annotation-toggles" role="group">
+            {annotation_counts(&annotations)
+                .into_iter()
+                .map(|(category, count)| {
+                    let visible = move || state.is_visible(category);
+                    view! {
+                        <button
+                            type="button"
+                            class=format!("annotation-toggle {}", category_class(category))
+                            class:annotation-off=move || !visible()
+                            aria-pressed=move || visible().to_string()
+                            on:click=move |_| state.toggle(category)
+                            on:mouseenter=move |_| state.highlighted.set(Some(category))
+                            on:mouseleave=move |_| state.highlighted.set(None)
+                        >
+                            {format!("{} {count}", i18n.tr(category.label_key()).trim_end_matches(':'))}
+                        </button>
+                    }
+                })
+                .collect::<Vec<_>>()}
+        </div>
+    }
+}
+
+/// Report image with the outlines of `annotations` drawn over it.
+#[component]
+pub fn AnnotatedImage(
+    src: String,
+    alt: String,
+    annotations: Vec<Annotation>,
+    state: AnnotationState,
+) -> impl IntoView {
+    let shapes = annotations
+        .into_iter()
+        .map(|annotation| {
+            let category = annotation.category;
+            let class = move || {
+                let mut class = format!("annotation-shape {}", category_class(category));
+                if state.highlighted.get() == Some(category) {
+                    class.push_str(" annotation-highlighted");
+                }
+                class
+            };
+            view! {
+                <polygon
+                    class=class
+                    points=annotation.shape.svg_points()
+                    style:display=move || if state.is_visible(category) { "" } else { "none" }
+                    on:mouseenter=move |_| state.highlighted.set(Some(category))
+                    on:mouseleave=move |_| state.highlighted.set(None)
+                >
+                    <title>{annotation.title()}</title>
+                </polygon>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
+        <figure class="annotated-image">
+            <div class="annotated-image-frame">
+                <img crossorigin="anonymous" src=src alt=alt.clone() loading="lazy" />
+                <svg
+                    class="annotation-layer"
+                    viewBox="0 0 1 1"
+                    preserveAspectRatio="none"
+                    role="img"
+                    aria-label=move || move_tr!("annotations-layer").get()
+                >
+                    {shapes}
+                </svg>
+            </div>
+            <figcaption>{alt}</figcaption>
+        </figure>
+    }
+}
@@ -0,0 +1,220 @@
+use crate::api_tokens::{API_TOKEN_SCOPES, ApiTokenInfo, CreateApiTokenRequest, CreatedApiToken};
+use crate::components::active_sessions::{format_timestamp, send, send_with_body};
+use crate::components::args;
+use leptos::prelude::*;
+use leptos::{IntoView, component, view};
+use leptos_fluent::{I18n, move_tr};
+use wasm_bindgen_futures::spawn_local;
+
+const LIFETIME_DAYS: [u32; 3] = [30, 90, 365];
+
+#[component]
+pub fn ApiTokensPanel() -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let (reload, set_reload) = signal(0_u32);
+    let (name, set_name) = signal(String::new());
+    let (scopes, set_scopes) = signal(Vec::<String>::new());
+    let (lifetime, set_lifetime) = signal(LIFETIME_DAYS[0]);
+    let (created, set_created) = signal(None::<String>);
+    let (status, set_status) = signal(String::new());
+    let (busy, set_busy) = signal(false);
+
+    let tokens_resource = LocalResource::new(move || {
+        let _ = reload.get();
+        async move {
+            let text = send("GET", "/api/tokens").await?;
+            serde_json::from_str::<Vec<ApiTokenInfo>>(&text)
+                .map_err(|e| format!("Failed to deserialize response: {e}"))
+        }
+    });
+
+    let create = move |_| {
+        let request = CreateApiTokenRequest {
+            name: name.get_untracked(),
+            scopes: scopes.get_untracked(),
+            expires_in_days: lifetime.get_untracked(),
+        };
+        set_busy.set(true);
+        set_status.set(String::new());
+        spawn_local(async move {
+            let result = match serde_json::to_string(&request) {
+                Ok(body) => send_with_body("POST", "/api/tokens", Some(body))
+                    .await
+                    .and_then(|text| {
+                        serde_json::from_str::<CreatedApiToken>(&text)
+                            .map_err(|e| format!("Failed to deserialize response: {e}"))
+                    }),
+                Err(e) => Err(e.to_string()),
+            };
+            match result {
+                Ok(created) => {
+                    set_created.set(Some(created.token));
+                    set_name.set(String::new());
+                    set_reload.update(|value| *value += 1);
+                }
+                Err(error) => {
+                    set_status.set(i18n.tr_with_args("tokens-error", &args!["error" => error]))
+                }
+            }
+            set_busy.set(false);
+        });
+    };
+
+    let revoke = move |id: String| {
+        spawn_local(async move {
+            match send("DELETE", &format!("/api/tokens/{id}")).await {
+                Ok(_) => set_reload.update(|value| *value += 1),
+                Err(error) => {
+                    set_status.set(i18n.tr_with_args("tokens-error", &args!["error" => error]))
+                }
+            }
+        });
+    };
+
+    view! {
+        <div class="sessions-card tokens-card">
+            <h3>{move_tr!("tokens-title")}</h3>
+            <div class="tokens-form">
+                <input
+                    type="text"
+                    maxlength="64"
+                    placeholder=move || move_tr!("tokens-name").get()
+                    aria-label=move || move_tr!("tokens-name").get()
+                    prop:value=move || name.get()
+                    on:input=move |event| set_name.set(event_target_value(&event))
+                />
+                <div class="tokens-scopes">
+                    {API_TOKEN_SCOPES
+                        .into_iter()
+                        .map(|scope| {
+                            view! {
+                                <label>
+                                    <input
+                                        type="checkbox"
+                                        prop:checked=move || scopes.get().iter().any(|s| s == scope)
+                                        on:change=move |_| {
+                                            set_scopes.update(|list| {
+                                                if let Some(index) = list.iter().position(|s| s == scope) {
+                                                    list.remove(index);
+                                                } else {
+                                                    list.push(scope.to_string());
+                                                }
+                                            })
+                                        }
+                                    />
+                                    <code>{scope}</code>
+                                </label>
+                            }
+                        })
+                        .collect_view()}
+                </div>
+                <select
+                    aria-label=move || move_tr!("tokens-lifetime").get()
+                    on:change=move |event| {
+                        if let Ok(days) = event_target_value(&event).parse() {
+                            set_lifetime.set(days);
+                        }
+                    }
+                >
+                    {LIFETIME_DAYS
+                        .into_iter()
+                        .map(|days| {
+                            view! {
+                                <option value=days.to_string()>
+                                    {move || i18n.tr_with_args("tokens-days", &args!["days" => days])}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+                <button
+                    type="button"
+                    class="models-save"
+                    disabled=move || busy.get() || name.get().trim().is_empty() || scopes.get().is_empty()
+                    on:click=create
+                >
+                    <i class="fas fa-key"></i>
+                    <span>{move_tr!("tokens-create")}</span>
+                </button>
+            </div>
+            {move || {
+                created
+                    .get()
+                    .map(|token| {
+                        view! {
+                            <div class="tokens-created">
+                                <p>{move_tr!("tokens-copy-now")}</p>
+                                <input type="text" readonly value=token on:focus=|event| {
+                                    use web_sys::wasm_bindgen::JsCast;
+                                    if let Some(input) = event
+                                        .target()
+                                        .and_then(|target| target.dyn_into::<web_sys::HtmlInputElement>().ok())
+                                    {
+                                        input.select();
+                                    }
+                                } />
+                            </div>
+                        }
+                    })
+            }}
+            <p class="error" class:none=move || status.get().is_empty()>{move || status.get()}</p>
+            <Suspense fallback=move || view! { <p class="loading">{move_tr!("tokens-loading")}</p> }>
+                {move || {
+                    tokens_resource
+                        .get()
+                        .map(|result| match result {
+                            Ok(tokens) if tokens.is_empty() => {
+                                view! { <p>{move_tr!("tokens-empty")}</p> }.into_any()
+                            }
+                            Ok(tokens) => {
+                                let locale = i18n.language.get().id.to_string();
+                                view! {
+                                    <ul class="sessions-list">
+                                        {tokens
+                                            .into_iter()
+                                            .map(|token| {
+                                                let id = token.id.clone();
+                                                let expires = format_timestamp(token.expires_at, &locale);
+                                                let last_used = token
+                                                    .last_used_at
+                                                    .map(|at| format_timestamp(at, &locale))
+                                                    .unwrap_or_else(|| "—".to_string());
+                                                view! {
+                                                    <li class="session-item">
+                                                        <div class="session-device">
+                                                            <i class="fas fa-key"></i>
+                                                            <span>{token.name}</span>
+                                                        </div>
+                                                        <div class="session-meta">
+                                                            <span>{token.scopes.join(", ")}</span>
+                                                            <span>{move_tr!("tokens-expires")}" "{expires}</span>
+                                                            <span>{move_tr!("tokens-last-used")}" "{last_used}</span>
+                                                        </div>
+                                                        <button
+                                                            type="button"
+                                                            class="session-revoke"
+                                                            on:click=move |_| revoke(id.clone())
+                                                        >
+                                                            <i class="fas fa-trash"></i>
+                                                            <span>{move_tr!("tokens-revoke")}</span>
+                                                        </button>
+                                                    </li>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </ul>
+                                }
+                                    .into_any()
+                            }
+                            Err(error) => view! {
+                                <p class="error">
+                                    {i18n.tr_with_args("tokens-error", &args!["error" => error])}
+                                </p>
+                            }
+                                .into_any(),
+                        })
+                }}
+            </Suspense>
+        </div>
+    }
+}
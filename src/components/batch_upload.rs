@@ -0,0 +1,830 @@
+use crate::chunked_upload::{CHUNK_CHECKSUM_HEADER, ChunkedUploadStatus, CreateChunkedUpload};
+use crate::components::args;
+use crate::components::reports_panel::{
+    current_datetime_agent, datetime_for_agent, exif_datetime, localized_upload_error,
+    response_error_message, validate_agent_datetime,
+};
+use crate::csrf::{CSRF_HEADER, csrf_token};
+use crate::duplicates::{
+    DuplicateMatch, DuplicateQuery, PERCEPTUAL_HASH_FIELD, PERCEPTUAL_HASH_HEIGHT,
+    PERCEPTUAL_HASH_WIDTH, format_perceptual_hash, perceptual_hash,
+};
+use leptos::prelude::*;
+use leptos::wasm_bindgen::{JsCast, closure::Closure};
+use leptos::{IntoView, component, view};
+use leptos_fluent::{I18n, move_tr};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    CanvasRenderingContext2d, DragEvent, File, FileList, FormData, HtmlCanvasElement,
+    HtmlImageElement, HtmlInputElement, ProgressEvent, Request, RequestInit, Response,
+    XmlHttpRequest,
+};
+
+/// Files uploaded at the same time.
+const UPLOAD_CONCURRENCY: usize = 3;
+/// Attempts per file for network errors, 5xx and rate limiting.
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY_MS: u32 = 1_000;
+/// Larger files go through the resumable upload protocol.
+const CHUNKED_THRESHOLD: f64 = 16.0 * 1024.0 * 1024.0;
+const UPLOADS_URL: &str = "/api/proxy/images/uploads";
+/// Slice of the file hashed at a time.
+const HASH_SLICE_BYTES: f64 = 4.0 * 1024.0 * 1024.0;
+
+#[derive(Clone, Debug, PartialEq)]
+enum UploadStatus {
+    /// The capture date is still being read from the file.
+    Reading,
+    Pending,
+    Checking,
+    /// Matches in the branch, waiting for the user to choose.
+    Duplicate(Vec<DuplicateMatch>),
+    Uploading,
+    Done,
+    Failed(String),
+}
+
+/// What to do with a file the branch already has.
+#[derive(Clone, Debug, PartialEq)]
+enum DuplicateDecision {
+    KeepBoth,
+    /// Upload, then delete the matching leaves.
+    Replace(Vec<Uuid>),
+}
+
+#[derive(Clone)]
+struct UploadItem {
+    id: usize,
+    file: File,
+    /// Object URL of the file, revoked when the item is removed.
+    preview: String,
+    datetime: RwSignal<String>,
+    /// Changed by the user, so the EXIF date no longer applies.
+    datetime_edited: StoredValue<bool>,
+    exif_missing: RwSignal<bool>,
+    progress: RwSignal<f64>,
+    status: RwSignal<UploadStatus>,
+    /// Resumable upload to continue on retry.
+    upload_id: StoredValue<Option<String>>,
+    /// Hashes of the file, taken once.
+    hashes: StoredValue<Option<DuplicateQuery>>,
+    duplicate_decision: StoredValue<Option<DuplicateDecision>>,
+    replace_failed: RwSignal<bool>,
+}
+
+impl UploadItem {
+    /// Queues the item once its date is known.
+    fn mark_ready(&self) {
+        if self.status.get_untracked() == UploadStatus::Reading {
+            self.status.set(UploadStatus::Pending);
+        }
+    }
+}
+
+/// Why an attempt failed and whether it is worth repeating.
+struct UploadFailure {
+    message: String,
+    retry_after_ms: Option<u32>,
+}
+
+/// Drop zone queueing many photos for one object, each with its own timestamp.
+#[component]
+pub fn BatchUpload(parent_id: Uuid, #[prop(into)] on_uploaded: Callback<()>) -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let items = RwSignal::new_local(Vec::<UploadItem>::new());
+    let next_id = StoredValue::new(0_usize);
+    let dragging = RwSignal::new(false);
+    let running = RwSignal::new(false);
+    let summary = RwSignal::new(None::<(usize, usize, usize)>);
+
+    let add_files = move |files: FileList| {
+        let mut added = Vec::new();
+        for index in 0..files.length() {
+            let Some(file) = files.item(index) else {
+                continue;
+            };
+            // HEIC often arrives without a MIME type
+            let kind = file.type_();
+            if !kind.is_empty() && !kind.starts_with("image/") {
+                continue;
+            }
+            let id = next_id.get_value();
+            next_id.set_value(id + 1);
+            added.push(UploadItem {
+                id,
+                preview: web_sys::Url::create_object_url_with_blob(&file).unwrap_or_default(),
+                file,
+                datetime: RwSignal::new(current_datetime_agent()),
+                datetime_edited: StoredValue::new(false),
+                exif_missing: RwSignal::new(false),
+                progress: RwSignal::new(0.0),
+                status: RwSignal::new(UploadStatus::Reading),
+                upload_id: StoredValue::new(None),
+                hashes: StoredValue::new(None),
+                duplicate_decision: StoredValue::new(None),
+                replace_failed: RwSignal::new(false),
+            });
+        }
+        if added.is_empty() {
+            return;
+        }
+        summary.set(None);
+        items.update(|items| items.extend(added.iter().cloned()));
+        // One file at a time keeps memory flat on large batches; items wait
+        // for their date so none is sent with the time of selection
+        leptos::task::spawn_local(async move {
+            for item in added {
+                let datetime = exif_datetime(&item.file).await;
+                if !item.datetime_edited.get_value() {
+                    match datetime {
+                        Some(datetime) => item.datetime.set(datetime),
+                        None => item.exif_missing.set(true),
+                    }
+                }
+                item.mark_ready();
+            }
+        });
+    };
+
+    let remove = move |id: usize| {
+        items.update(|items| {
+            items.retain(|item| {
+                if item.id == id {
+                    let _ = web_sys::Url::revoke_object_url(&item.preview);
+                }
+                item.id != id
+            })
+        });
+    };
+
+    let start = move || {
+        if running.get_untracked() {
+            return;
+        }
+        running.set(true);
+        summary.set(None);
+        let remaining = StoredValue::new(UPLOAD_CONCURRENCY);
+        for _ in 0..UPLOAD_CONCURRENCY {
+            leptos::task::spawn_local(async move {
+                run_worker(items, parent_id, i18n).await;
+                remaining.update_value(|count| *count -= 1);
+                if remaining.get_value() == 0 {
+                    let counts = items.with_untracked(|items| {
+                        let done = items
+                            .iter()
+                            .filter(|item| item.status.get_untracked() == UploadStatus::Done)
+                            .count();
+                        let failed = items
+                            .iter()
+                            .filter(|item| {
+                                matches!(item.status.get_untracked(), UploadStatus::Failed(_))
+                            })
+                            .count();
+                        (done, failed, items.len())
+                    });
+                    summary.set(Some(counts));
+                    running.set(false);
+                    if counts.0 > 0 {
+                        on_uploaded.run(());
+                    }
+                }
+            });
+        }
+    };
+
+    let retry_failed = move || {
+        items.with_untracked(|items| {
+            for item in items {
+                if matches!(item.status.get_untracked(), UploadStatus::Failed(_)) {
+                    item.progress.set(0.0);
+                    item.status.set(UploadStatus::Pending);
+                }
+            }
+        });
+        start();
+    };
+
+    let clear_finished = move || {
+        summary.set(None);
+        items.update(|items| {
+            items.retain(|item| {
+                let done = item.status.get_untracked() == UploadStatus::Done;
+                if done {
+                    let _ = web_sys::Url::revoke_object_url(&item.preview);
+                }
+                !done
+            })
+        });
+    };
+
+    let decide = move |item: &UploadItem, decision: DuplicateDecision| {
+        item.duplicate_decision.set_value(Some(decision));
+        item.status.set(UploadStatus::Pending);
+        start();
+    };
+
+    on_cleanup(move || {
+        items.with_untracked(|items| {
+            for item in items {
+                let _ = web_sys::Url::revoke_object_url(&item.preview);
+            }
+        });
+    });
+
+    let count_status = move |wanted: fn(&UploadStatus) -> bool| {
+        items.with(|items| {
+            items
+                .iter()
+                .filter(|item| wanted(&item.status.get()))
+                .count()
+        })
+    };
+
+    view! {
+        <div class="reports-batch">
+            <div
+                class="reports-dropzone"
+                class:dragging=move || dragging.get()
+                on:dragover=move |ev: DragEvent| {
+                    ev.prevent_default();
+                    dragging.set(true);
+                }
+                on:dragleave=move |_| dragging.set(false)
+                on:drop=move |ev: DragEvent| {
+                    ev.prevent_default();
+                    dragging.set(false);
+                    if let Some(files) = ev.data_transfer().and_then(|data| data.files()) {
+                        add_files(files);
+                    }
+                }
+            >
+                <i class="fas fa-images"></i>
+                <span>{move_tr!("reports-drop-hint")}</span>
+                <label class="reports-choose-files">
+                    <input
+                        id="reports-upload-file"
+                        name="image"
+                        type="file"
+                        accept="image/*"
+                        multiple
+                        aria-label=move || move_tr!("reports-file").get()
+                        on:change=move |ev| {
+                            let input: HtmlInputElement = event_target(&ev);
+                            if let Some(files) = input.files() {
+                                add_files(files);
+                            }
+                            input.set_value("");
+                        }
+                    />
+                    <span>{move_tr!("reports-choose-files")}</span>
+                </label>
+            </div>
+
+            <Show when=move || !items.with(Vec::is_empty)>
+                <ul class="reports-batch-list">
+                    <For
+                        each=move || items.get()
+                        key=|item| item.id
+                        children=move |item| {
+                            let id = item.id;
+                            let status = item.status;
+                            let editable = move || {
+                                matches!(
+                                    status.get(),
+                                    UploadStatus::Reading
+                                        | UploadStatus::Pending
+                                        | UploadStatus::Duplicate(_)
+                                        | UploadStatus::Failed(_)
+                                )
+                            };
+                            let busy = move || {
+                                matches!(status.get(), UploadStatus::Checking | UploadStatus::Uploading)
+                            };
+                            let keep_item = item.clone();
+                            let replace_item = item.clone();
+                            let edited_item = item.clone();
+                            view! {
+                                <li class="reports-batch-item">
+                                    <img src=item.preview.clone() alt=item.file.name() loading="lazy" />
+                                    <div class="reports-batch-meta">
+                                        <span class="reports-batch-name" title=item.file.name()>
+                                            {item.file.name()}
+                                        </span>
+                                        <input
+                                            type="text"
+                                            name="berlin_datetime"
+                                            aria-label=move || move_tr!("reports-datetime").get()
+                                            prop:value=move || item.datetime.get()
+                                            disabled=move || !editable()
+                                            on:change=move |ev| {
+                                                edited_item.datetime_edited.set_value(true);
+                                                edited_item.datetime.set(event_target_value(&ev));
+                                                edited_item.mark_ready();
+                                            }
+                                        />
+                                        <Show when=move || item.exif_missing.get()>
+                                            <span class="reports-upload-warning">
+                                                {move_tr!("reports-exif-missing")}
+                                            </span>
+                                        </Show>
+                                        <progress max="100" prop:value=move || item.progress.get()></progress>
+                                        <span class="reports-batch-status">
+                                            {move || match status.get() {
+                                                UploadStatus::Reading => move_tr!("reports-file-reading").get(),
+                                                UploadStatus::Pending => move_tr!("reports-file-pending").get(),
+                                                UploadStatus::Checking => move_tr!("reports-file-checking").get(),
+                                                UploadStatus::Duplicate(found) => duplicate_message(&found, i18n),
+                                                UploadStatus::Uploading => {
+                                                    format!("{:.0} %", item.progress.get())
+                                                }
+                                                UploadStatus::Done => move_tr!("reports-file-done").get(),
+                                                UploadStatus::Failed(error) => error,
+                                            }}
+                                        </span>
+                                        <Show when=move || item.replace_failed.get()>
+                                            <span class="reports-upload-warning">
+                                                {move_tr!("reports-duplicate-replace-failed")}
+                                            </span>
+                                        </Show>
+                                        <Show when=move || matches!(status.get(), UploadStatus::Duplicate(_))>
+                                            <div class="reports-duplicate-actions">
+                                                <button type="button" on:click=move |_| remove(id)>
+                                                    {move_tr!("reports-duplicate-skip")}
+                                                </button>
+                                                <button
+                                                    type="button"
+                                                    on:click={
+                                                        let item = replace_item.clone();
+                                                        move |_| {
+                                                            let UploadStatus::Duplicate(found) = status.get_untracked() else {
+                                                                return;
+                                                            };
+                                                            let ids = found.iter().map(|found| found.node_id).collect();
+                                                            decide(&item, DuplicateDecision::Replace(ids));
+                                                        }
+                                                    }
+                                                >
+                                                    {move_tr!("reports-duplicate-replace")}
+                                                </button>
+                                                <button
+                                                    type="button"
+                                                    on:click={
+                                                        let item = keep_item.clone();
+                                                        move |_| decide(&item, DuplicateDecision::KeepBoth)
+                                                    }
+                                                >
+                                                    {move_tr!("reports-duplicate-keep")}
+                                                </button>
+                                            </div>
+                                        </Show>
+                                    </div>
+                                    <button
+                                        type="button"
+                                        class="reports-delete"
+                                        title=move || move_tr!("reports-remove-file").get()
+                                        disabled=busy
+                                        on:click=move |_| remove(id)
+                                    >
+                                        <i class="fas fa-xmark"></i>
+                                    </button>
+                                </li>
+                            }
+                        }
+                    />
+                </ul>
+                <div class="reports-batch-actions">
+                    <button
+                        type="button"
+                        class="reports-batch-start"
+                        disabled=move || {
+                            running.get() || count_status(|status| *status == UploadStatus::Pending) == 0
+                        }
+                        on:click=move |_| start()
+                    >
+                        <i class="fas fa-upload"></i>
+                        <span>{move_tr!("reports-upload-all")}</span>
+                    </button>
+                    <Show when=move || {
+                        !running.get() && count_status(|status| matches!(status, UploadStatus::Failed(_))) > 0
+                    }>
+                        <button type="button" on:click=move |_| retry_failed()>
+                            <i class="fas fa-rotate-right"></i>
+                            <span>{move_tr!("reports-retry-failed")}</span>
+                        </button>
+                    </Show>
+                    <Show when=move || {
+                        !running.get() && count_status(|status| *status == UploadStatus::Done) > 0
+                    }>
+                        <button type="button" on:click=move |_| clear_finished()>
+                            <i class="fas fa-broom"></i>
+                            <span>{move_tr!("reports-clear-finished")}</span>
+                        </button>
+                    </Show>
+                </div>
+                {move || {
+                    summary
+                        .get()
+                        .map(|(uploaded, failed, total)| {
+                            view! {
+                                <div class="reports-status" class:reports-error={failed > 0}>
+                                    {i18n
+                                        .tr_with_args(
+                                            "reports-batch-summary",
+                                            &args![
+                                                "uploaded" => uploaded, "failed" => failed, "total" => total
+                                            ],
+                                        )}
+                                </div>
+                            }
+                        })
+                }}
+            </Show>
+        </div>
+    }
+}
+
+/// Takes pending items one by one until none are left.
+async fn run_worker(items: RwSignal<Vec<UploadItem>, LocalStorage>, parent_id: Uuid, i18n: I18n) {
+    loop {
+        let next = items.with_untracked(|items| {
+            items
+                .iter()
+                .find(|item| item.status.get_untracked() == UploadStatus::Pending)
+                .cloned()
+        });
+        let Some(item) = next else {
+            return;
+        };
+        item.status.set(UploadStatus::Checking);
+        let found = check_duplicates(&item, parent_id, i18n).await;
+        if !found.is_empty() {
+            item.status.set(UploadStatus::Duplicate(found));
+            continue;
+        }
+        item.status.set(UploadStatus::Uploading);
+        let status = match upload_item(&item, parent_id, i18n).await {
+            Ok(()) => {
+                if let Some(DuplicateDecision::Replace(ids)) = item.duplicate_decision.get_value() {
+                    item.replace_failed.set(!delete_replaced(&ids, i18n).await);
+                }
+                item.duplicate_decision.set_value(None);
+                UploadStatus::Done
+            }
+            Err(error) => UploadStatus::Failed(error),
+        };
+        item.status.set(status);
+    }
+}
+
+fn duplicate_message(found: &[DuplicateMatch], i18n: I18n) -> String {
+    let Some(first) = found.first() else {
+        return String::new();
+    };
+    let name = first
+        .name
+        .clone()
+        .unwrap_or_else(|| i18n.tr("reports-image"));
+    let key = if first.exact {
+        "reports-duplicate-exact"
+    } else {
+        "reports-duplicate-similar"
+    };
+    i18n.tr_with_args(key, &args!["name" => name])
+}
+
+/// Leaves of the branch holding the same photo, unless the user already decided.
+/// A failed check does not hold the upload back.
+async fn check_duplicates(item: &UploadItem, parent_id: Uuid, i18n: I18n) -> Vec<DuplicateMatch> {
+    if item.duplicate_decision.get_value().is_some() {
+        return Vec::new();
+    }
+    let query = match item.hashes.get_value() {
+        Some(query) => query,
+        None => {
+            let Ok(hash) = content_hash(&item.file).await else {
+                return Vec::new();
+            };
+            let query = DuplicateQuery {
+                hash,
+                perceptual_hash: preview_perceptual_hash(&item.preview)
+                    .await
+                    .map(format_perceptual_hash),
+            };
+            item.hashes.set_value(Some(query.clone()));
+            query
+        }
+    };
+    let Ok(body) = serde_json::to_string(&query) else {
+        return Vec::new();
+    };
+    let url = format!("/api/proxy/images/duplicates/{parent_id}");
+    match fetch("POST", &url, Some(body.into()), None, i18n).await {
+        Ok((200, body, _)) => serde_json::from_str(&body).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Hex SHA-256 of the file as selected, read a slice at a time.
+async fn content_hash(file: &File) -> Result<String, wasm_bindgen::JsValue> {
+    let mut hasher = Sha256::new();
+    let mut start = 0.0;
+    while start < file.size() {
+        let end = (start + HASH_SLICE_BYTES).min(file.size());
+        let buffer =
+            JsFuture::from(file.slice_with_f64_and_f64(start, end)?.array_buffer()).await?;
+        hasher.update(js_sys::Uint8Array::new(&buffer).to_vec());
+        start = end;
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Perceptual hash of the preview drawn onto a tiny canvas; `None` where the
+/// browser cannot decode the format (HEIC outside Safari).
+async fn preview_perceptual_hash(preview: &str) -> Option<u64> {
+    let document = web_sys::window()?.document()?;
+    let image: HtmlImageElement = document.create_element("img").ok()?.dyn_into().ok()?;
+    image.set_src(preview);
+    JsFuture::from(image.decode()).await.ok()?;
+    let canvas: HtmlCanvasElement = document.create_element("canvas").ok()?.dyn_into().ok()?;
+    canvas.set_width(PERCEPTUAL_HASH_WIDTH);
+    canvas.set_height(PERCEPTUAL_HASH_HEIGHT);
+    let context: CanvasRenderingContext2d = canvas.get_context("2d").ok()??.dyn_into().ok()?;
+    let (width, height) = (
+        f64::from(PERCEPTUAL_HASH_WIDTH),
+        f64::from(PERCEPTUAL_HASH_HEIGHT),
+    );
+    context
+        .draw_image_with_html_image_element_and_dw_and_dh(&image, 0.0, 0.0, width, height)
+        .ok()?;
+    let pixels = context.get_image_data(0.0, 0.0, width, height).ok()?;
+    perceptual_hash(&pixels.data())
+}
+
+/// Deletes the leaves a new upload replaces; false if any is left.
+async fn delete_replaced(ids: &[Uuid], i18n: I18n) -> bool {
+    let mut deleted = true;
+    for id in ids {
+        let url = format!("/api/proxy/images/{id}");
+        deleted &= matches!(
+            fetch("DELETE", &url, None, None, i18n).await,
+            Ok((200..=299, _, _))
+        );
+    }
+    deleted
+}
+
+async fn upload_item(item: &UploadItem, parent_id: Uuid, i18n: I18n) -> Result<(), String> {
+    let datetime = datetime_for_agent(&item.datetime.get_untracked());
+    validate_agent_datetime(&datetime)?;
+    let url = format!("/api/proxy/images/upload/{parent_id}");
+    let mut attempt = 1;
+    loop {
+        item.progress.set(0.0);
+        let perceptual_hash = item
+            .hashes
+            .get_value()
+            .and_then(|hashes| hashes.perceptual_hash);
+        let sent = if item.file.size() > CHUNKED_THRESHOLD {
+            send_chunked(item, parent_id, &datetime, perceptual_hash, i18n).await
+        } else {
+            send_upload(
+                &url,
+                &item.file,
+                &datetime,
+                perceptual_hash.as_deref(),
+                item.progress,
+                i18n,
+            )
+            .await
+        };
+        let failure = match sent {
+            Ok(()) => {
+                item.progress.set(100.0);
+                return Ok(());
+            }
+            Err(failure) => failure,
+        };
+        match failure.retry_after_ms {
+            Some(delay) if attempt < MAX_ATTEMPTS => {
+                gloo_timers::future::TimeoutFuture::new(delay.max(RETRY_DELAY_MS * attempt)).await;
+                attempt += 1;
+            }
+            _ => return Err(failure.message),
+        }
+    }
+}
+
+/// Posts the form through `XMLHttpRequest`, which unlike `fetch` reports upload progress.
+async fn send_upload(
+    url: &str,
+    file: &File,
+    datetime: &str,
+    perceptual_hash: Option<&str>,
+    progress: RwSignal<f64>,
+    i18n: I18n,
+) -> Result<(), UploadFailure> {
+    let form = FormData::new().map_err(fatal)?;
+    form.append_with_blob("image", file).map_err(fatal)?;
+    form.append_with_str("berlin_datetime", datetime)
+        .map_err(fatal)?;
+    if let Some(perceptual_hash) = perceptual_hash {
+        form.append_with_str(PERCEPTUAL_HASH_FIELD, perceptual_hash)
+            .map_err(fatal)?;
+    }
+
+    let xhr = XmlHttpRequest::new().map_err(fatal)?;
+    xhr.open_with_async("POST", url, true).map_err(fatal)?;
+    xhr.set_request_header(CSRF_HEADER, &csrf_token())
+        .map_err(fatal)?;
+    let on_progress = Closure::<dyn FnMut(ProgressEvent)>::new(move |event: ProgressEvent| {
+        if event.length_computable() && event.total() > 0.0 {
+            // The last percent is the agent processing the image
+            progress.set((event.loaded() / event.total() * 99.0).min(99.0));
+        }
+    });
+    if let Ok(upload) = xhr.upload() {
+        upload.set_onprogress(Some(on_progress.as_ref().unchecked_ref()));
+    }
+    let finished = js_sys::Promise::new(&mut |resolve, reject| {
+        xhr.set_onload(Some(&resolve));
+        xhr.set_onerror(Some(&reject));
+        xhr.set_onabort(Some(&reject));
+    });
+    xhr.send_with_opt_form_data(Some(&form)).map_err(fatal)?;
+    let sent = JsFuture::from(finished).await;
+    drop(on_progress);
+    if sent.is_err() {
+        return Err(UploadFailure {
+            message: i18n.tr("reports-upload-network-error"),
+            retry_after_ms: Some(RETRY_DELAY_MS),
+        });
+    }
+
+    let status = xhr.status().unwrap_or(0);
+    if (200..300).contains(&status) {
+        return Ok(());
+    }
+    let body = xhr.response_text().ok().flatten().unwrap_or_default();
+    let retry_after = xhr.get_response_header("Retry-After").ok().flatten();
+    Err(failure(status, &body, retry_after, i18n))
+}
+
+fn failure(status: u16, body: &str, retry_after: Option<String>, i18n: I18n) -> UploadFailure {
+    if status == 429 {
+        let seconds = retry_after
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .unwrap_or(1);
+        return UploadFailure {
+            message: i18n.tr_with_args("rate-limited", &args!["seconds" => seconds]),
+            retry_after_ms: Some(seconds * 1_000),
+        };
+    }
+    UploadFailure {
+        message: localized_upload_error(i18n, body)
+            .unwrap_or_else(|| response_error_message(status, body)),
+        retry_after_ms: (status >= 500).then_some(RETRY_DELAY_MS),
+    }
+}
+
+fn fatal(e: wasm_bindgen::JsValue) -> UploadFailure {
+    UploadFailure {
+        message: format!("{e:?}"),
+        retry_after_ms: None,
+    }
+}
+
+/// Uploads in parts of the server's chunk size; a retry asks the server how much
+/// it already has and continues from there.
+async fn send_chunked(
+    item: &UploadItem,
+    parent_id: Uuid,
+    datetime: &str,
+    perceptual_hash: Option<String>,
+    i18n: I18n,
+) -> Result<(), UploadFailure> {
+    let resumed = match item.upload_id.get_value() {
+        Some(id) => match fetch("GET", &format!("{UPLOADS_URL}/{id}"), None, None, i18n).await? {
+            (200, body, _) => Some(parse_status(&body)?),
+            // Expired or already finished
+            _ => None,
+        },
+        None => None,
+    };
+    let mut status = match resumed {
+        Some(status) => status,
+        None => {
+            let request = CreateChunkedUpload {
+                file_name: item.file.name(),
+                size: item.file.size() as u64,
+                berlin_datetime: Some(datetime.to_string()),
+                perceptual_hash,
+            };
+            let body = serde_json::to_string(&request).map_err(|e| UploadFailure {
+                message: e.to_string(),
+                retry_after_ms: None,
+            })?;
+            let url = format!("/api/proxy/images/upload/{parent_id}/chunked");
+            match fetch("POST", &url, Some(body.into()), None, i18n).await? {
+                (201, body, _) => parse_status(&body)?,
+                (code, body, retry_after) => return Err(failure(code, &body, retry_after, i18n)),
+            }
+        }
+    };
+    item.upload_id.set_value(Some(status.upload_id.clone()));
+
+    while status.received < status.size {
+        let start = status.received as f64;
+        let end = (start + status.chunk_size as f64).min(status.size as f64);
+        let part = item
+            .file
+            .slice_with_f64_and_f64(start, end)
+            .map_err(fatal)?;
+        let buffer = JsFuture::from(part.array_buffer()).await.map_err(fatal)?;
+        let bytes = js_sys::Uint8Array::new(&buffer);
+        let checksum = hex::encode(Sha256::digest(bytes.to_vec()));
+        let url = format!(
+            "{UPLOADS_URL}/{}?offset={}",
+            status.upload_id, status.received
+        );
+        status = match fetch("PUT", &url, Some(bytes.into()), Some(&checksum), i18n).await? {
+            // A conflict carries the offset the server expects next
+            (200 | 409, body, _) => parse_status(&body)?,
+            (code, body, retry_after) => return Err(failure(code, &body, retry_after, i18n)),
+        };
+        item.progress
+            .set(status.received as f64 / status.size as f64 * 99.0);
+    }
+
+    let url = format!("{UPLOADS_URL}/{}/complete", status.upload_id);
+    let (code, body, retry_after) = fetch("POST", &url, None, None, i18n).await?;
+    // Only agent failures keep the staged file for another attempt
+    if code < 500 {
+        item.upload_id.set_value(None);
+    }
+    if (200..300).contains(&code) {
+        Ok(())
+    } else {
+        Err(failure(code, &body, retry_after, i18n))
+    }
+}
+
+fn parse_status(body: &str) -> Result<ChunkedUploadStatus, UploadFailure> {
+    serde_json::from_str(body).map_err(|e| UploadFailure {
+        message: format!("Failed to deserialize response: {e}"),
+        retry_after_ms: None,
+    })
+}
+
+/// Status, body and `Retry-After` of a request; network errors are retryable.
+async fn fetch(
+    method: &str,
+    url: &str,
+    body: Option<wasm_bindgen::JsValue>,
+    checksum: Option<&str>,
+    i18n: I18n,
+) -> Result<(u16, String, Option<String>), UploadFailure> {
+    let window = web_sys::window().ok_or_else(|| fatal("No window available".into()))?;
+    let opts = RequestInit::new();
+    opts.set_method(method);
+    if let Some(body) = &body {
+        opts.set_body(body);
+    }
+    let request = Request::new_with_str_and_init(url, &opts).map_err(fatal)?;
+    let headers = request.headers();
+    headers.set(CSRF_HEADER, &csrf_token()).map_err(fatal)?;
+    match checksum {
+        Some(checksum) => {
+            headers
+                .set(CHUNK_CHECKSUM_HEADER, checksum)
+                .map_err(fatal)?;
+            headers
+                .set("Content-Type", "application/octet-stream")
+                .map_err(fatal)?;
+        }
+        None if body.is_some() => headers
+            .set("Content-Type", "application/json")
+            .map_err(fatal)?,
+        None => {}
+    }
+    let response: Response = match JsFuture::from(window.fetch_with_request(&request)).await {
+        Ok(response) => response.unchecked_into(),
+        Err(_) => {
+            return Err(UploadFailure {
+                message: i18n.tr("reports-upload-network-error"),
+                retry_after_ms: Some(RETRY_DELAY_MS),
+            });
+        }
+    };
+    let text = match response.text() {
+        Ok(promise) => JsFuture::from(promise)
+            .await
+            .ok()
+            .and_then(|text| text.as_string())
+            .unwrap_or_default(),
+        Err(_) => String::new(),
+    };
+    let retry_after = response.headers().get("Retry-After").ok().flatten();
+    Ok((response.status(), text, retry_after))
+}
@@ -1,8 +1,8 @@
 use crate::components::{
-    chat_context::ChatContext,
+    chat_context::{ChatContext, StaleContext},
     chat_types::{Message, MessageRole},
     message_renderer::MessageRenderer,
-    node_info_display::NodeInfoDisplay,
+    node_info_display::{NodeInfoDisplay, PinnedReportsDisplay},
     tree::NodeInfo,
 };
 use leptos::prelude::*;
@@ -15,8 +15,11 @@ use {
     crate::components::{
         args,
         chat_client::{handle_stream, send_stop_beacon},
+        context_store::{current_chat_id, new_chat_id, persist_chat_context},
+        deep_link::DeepLink,
     },
     leptos::reactive::spawn_local,
+    leptos_router::hooks::use_location,
     wasm_bindgen::JsCast,
     web_sys::HtmlDivElement,
 };
@@ -34,7 +37,7 @@ pub fn Chat() -> impl IntoView {
 
     #[cfg(not(feature = "ssr"))]
     let (chat_id, user_id) = {
-        let chat_id = uuid::Uuid::now_v7().to_string();
+        let chat_id = StoredValue::new(current_chat_id());
         let auth_signal = use_context::<RwSignal<Auth>>().expect("Auth must be provided");
         let user_id = auth_signal
             .get_untracked()
@@ -43,6 +46,14 @@ pub fn Chat() -> impl IntoView {
         (chat_id, user_id)
     };
     let ctx = use_context::<ChatContext>().expect("ChatContext not provided");
+    // A deep link sets the context itself
+    #[cfg(not(feature = "ssr"))]
+    {
+        let linked = use_location()
+            .query
+            .with_untracked(|query| !DeepLink::parse(|key| query.get(key)).is_empty());
+        persist_chat_context(ctx, chat_id, user_id.clone(), !linked);
+    }
     let delete_node_info =
         Callback::new(move |node_info: NodeInfo| ctx.delete_node_info(node_info));
 
@@ -51,6 +62,8 @@ pub fn Chat() -> impl IntoView {
         if ctx.clear_history.get() {
             set_history.set(Vec::new());
             set_chat_state.set(String::new());
+            #[cfg(not(feature = "ssr"))]
+            chat_id.set_value(new_chat_id());
             ctx.clear();
             ctx.clear_history.set(false);
         }
@@ -105,8 +118,6 @@ pub fn Chat() -> impl IntoView {
     let owner = Owner::current();
     // Submit handler
     let on_submit = {
-        #[cfg(not(feature = "ssr"))]
-        let chat_id = chat_id.clone();
         move |ev: ev::SubmitEvent| {
             ev.prevent_default();
             let prompt = input.get();
@@ -121,7 +132,7 @@ pub fn Chat() -> impl IntoView {
             #[cfg(not(feature = "ssr"))]
             {
                 if let Some(owner_ref) = owner.as_ref() {
-                    let chat_id = chat_id.clone();
+                    let chat_id = chat_id.get_value();
                     let language = i18n.language.get().id.to_string();
                     let user_id = user_id.clone();
                     let owner_clone = owner_ref.clone();
@@ -213,6 +224,7 @@ pub fn Chat() -> impl IntoView {
                         prop:disabled=is_loading
                     />
                     <div class="node-info-section">
+                        {move || ctx.stale.get().map(|stale| view! { <StaleContextNotice stale=stale /> })}
                         <NodeInfoDisplay node_signal=ctx.parent on_node_click=delete_node_info />
                         <div class="node-info-leafs">
                             <NodeInfoDisplay
@@ -224,6 +236,7 @@ pub fn Chat() -> impl IntoView {
                                 on_node_click=delete_node_info
                             />
                         </div>
+                        <PinnedReportsDisplay />
                     </div>
 
                     <button
@@ -255,6 +268,36 @@ pub fn Chat() -> impl IntoView {
     }
 }
 
+/// Tells that a restored context may no longer match the objects.
+#[component]
+fn StaleContextNotice(stale: StaleContext) -> impl IntoView {
+    let ctx = use_context::<ChatContext>().expect("ChatContext not provided");
+    let i18n = expect_context::<I18n>();
+    let text = move || {
+        if stale.dropped > 0 {
+            i18n.tr_with_args(
+                "context-restored-dropped",
+                &crate::components::args!["count" => stale.dropped],
+            )
+        } else {
+            i18n.tr("context-restored-stale")
+        }
+    };
+    view! {
+        <div class="context-stale" role="status">
+            <i class="fas fa-clock-rotate-left"></i>
+            <span>{text}</span>
+            <button
+                type="button"
+                title=move || move_tr!("context-restored-dismiss").get()
+                on:click=move |_| ctx.stale.set(None)
+            >
+                "×"
+            </button>
+        </div>
+    }
+}
+
 #[component]
 fn Fake() -> impl IntoView {
     view! {
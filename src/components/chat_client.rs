@@ -1,9 +1,13 @@
 #![cfg(not(feature = "ssr"))]
 use crate::components::args;
 use crate::components::chat_context::ChatContext;
-use crate::components::chat_data::{ComparisonData, ContextRequest, DescriptionData};
+use crate::components::chat_data::{ComparisonData, ContextRequest, DescriptionData, TimelineData};
 use crate::components::chat_types::{Message, MessageContent, MessageRole};
 use crate::components::tree::{NodeInfo, NodeType, NodeWithLeaf};
+use crate::csrf::{CSRF_HEADER, CSRF_QUERY_PARAM, csrf_token};
+use crate::media::{MediaVariant, media_url};
+use crate::rate_limit::retry_after;
+use crate::sharing::NodeAccess;
 use leptos::leptos_dom::log;
 use leptos::logging;
 use leptos::prelude::{GetUntracked, Set, Update, WriteSignal};
@@ -33,6 +37,9 @@ pub async fn handle_stream(
     headers
         .append("Content-Type", "application/json")
         .map_err(|e| format!("Header append error: {:?}", e))?;
+    headers
+        .append(CSRF_HEADER, &csrf_token())
+        .map_err(|e| format!("Header append error: {:?}", e))?;
 
     let opts = RequestInit::new();
     opts.set_method("POST");
@@ -63,6 +70,11 @@ pub async fn handle_stream(
         body_map.insert("next_leaf".to_string(), json!(nl.id));
     }
 
+    let pinned = context.pinned_ids();
+    if !pinned.is_empty() {
+        body_map.insert("reports".to_string(), json!(pinned));
+    }
+
     let body = serde_json::Value::Object(body_map).to_string();
 
     opts.set_body(&wasm_bindgen::JsValue::from_str(&body));
@@ -79,7 +91,9 @@ pub async fn handle_stream(
 
     if !response.ok() {
         let status = response.status();
-        let error_text = if let Ok(text_promise) = response.text() {
+        let error_text = if let Some(seconds) = retry_after(&response) {
+            i18n.tr_with_args("rate-limited", &args!["seconds" => seconds])
+        } else if let Ok(text_promise) = response.text() {
             JsFuture::from(text_promise)
                 .await
                 .ok()
@@ -242,7 +256,6 @@ fn process_sse_event(
 
         Some("description") => match serde_json::from_str::<Vec<DescriptionData>>(data) {
             Ok(json_data) => {
-                let media_proxy = media_proxy_rule();
                 for description in &json_data {
                     let Ok(id) = uuid::Uuid::parse_str(&description.date_id) else {
                         continue;
@@ -253,12 +266,18 @@ fn process_sse_event(
                         name: Some(description.date.clone()),
                         node_type: NodeType::ImageLeaf,
                         date_time: 0,
-                        thumbnail_url: description.thumbnail_url.clone(),
-                        full_url: description.full_url.clone(),
+                        thumbnail_url: description
+                            .thumbnail_url
+                            .as_ref()
+                            .map(|_| media_url(id, MediaVariant::Thumbnail)),
+                        full_url: description
+                            .full_url
+                            .as_ref()
+                            .map(|_| media_url(id, MediaVariant::Full)),
                         mime_type: None,
                         size: None,
-                    }
-                    .with_media_proxy(&media_proxy);
+                        access: NodeAccess::default(),
+                    };
                     context.remember_report_media(node);
                 }
                 set_history.update(|h| {
@@ -279,7 +298,7 @@ fn process_sse_event(
                 set_history.update(|h| {
                     h.push(Message::new(
                         MessageRole::Llm,
-                        MessageContent::Comparison(json_data),
+                        MessageContent::Comparison(Box::new(json_data)),
                     ));
                 });
             }
@@ -288,6 +307,20 @@ fn process_sse_event(
                 log!("{}", data);
             }
         },
+        Some("timeline") => match serde_json::from_str::<TimelineData>(data) {
+            Ok(json_data) => {
+                set_history.update(|h| {
+                    h.push(Message::new(
+                        MessageRole::Llm,
+                        MessageContent::Timeline(json_data),
+                    ));
+                });
+            }
+            Err(e) => {
+                logging::error!("Failed to parse timeline: {}", e);
+                log!("{}", data);
+            }
+        },
         Some("context_request") => {
             match serde_json::from_str::<ContextRequest>(data) {
                 Ok(json_data) => {
@@ -359,23 +392,16 @@ fn process_sse_event(
     }
 }
 
-fn media_proxy_rule() -> String {
-    web_sys::window()
-        .and_then(|window| {
-            js_sys::Reflect::get(&window, &wasm_bindgen::JsValue::from_str("MEDIA_PROXY")).ok()
-        })
-        .and_then(|value| value.as_string())
-        .unwrap_or_default()
-}
-
-// Stop request - session_id extracted from cookie on server
+// Stop request - session_id extracted from cookie on server.
+// sendBeacon cannot set headers, so the CSRF token travels in the query.
 pub fn send_stop_beacon() -> Result<bool, String> {
     tracing::info!("Stop beacon called!");
 
+    let url = format!("/api/stop?{CSRF_QUERY_PARAM}={}", csrf_token());
     web_sys::window()
         .ok_or("No window")?
         .navigator()
-        .send_beacon_with_opt_str("/api/stop", None)
+        .send_beacon_with_opt_str(&url, None)
         .map_err(|e| format!("Beacon error: {:?}", e))
 }
 
@@ -1,9 +1,14 @@
 use crate::components::tree::NodeInfo;
 use crate::components::tree::NodeWithLeaf;
+#[cfg(not(feature = "ssr"))]
+use crate::saved_context::SavedContext;
 use leptos::prelude::*;
 use std::collections::HashMap;
 use uuid::Uuid;
 
+/// Reports that can be pinned for a timeline at once.
+pub const MAX_PINNED_REPORTS: usize = 12;
+
 #[derive(Clone, Copy)]
 pub struct ChatContext {
     pub clear_history: RwSignal<bool>,
@@ -12,8 +17,21 @@ pub struct ChatContext {
     pub parent: RwSignal<Option<NodeInfo>>,
     pub prev_leaf: RwSignal<Option<NodeInfo>>,
     pub next_leaf: RwSignal<Option<NodeInfo>>,
+    /// Reports of the object pinned for a timeline, oldest first.
+    pub pinned: RwSignal<Vec<NodeInfo>>,
     report_media: RwSignal<HashMap<Uuid, NodeInfo>>,
     report_context_submitted: RwSignal<bool>,
+    /// Restored from an earlier visit and possibly outdated.
+    pub stale: RwSignal<Option<StaleContext>>,
+}
+
+/// Why a restored context may no longer match the objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaleContext {
+    /// Unix milliseconds.
+    pub saved_at: i64,
+    /// Deleted nodes left out of the context.
+    pub dropped: usize,
 }
 
 impl ChatContext {
@@ -25,14 +43,18 @@ impl ChatContext {
             parent: RwSignal::new(None),
             prev_leaf: RwSignal::new(None),
             next_leaf: RwSignal::new(None),
+            pinned: RwSignal::new(Vec::new()),
             report_media: RwSignal::new(HashMap::new()),
             report_context_submitted: RwSignal::new(false),
+            stale: RwSignal::new(None),
         }
     }
     pub fn clear(&self) {
+        self.stale.set(None);
         self.parent.set(None);
         self.prev_leaf.set(None);
         self.next_leaf.set(None);
+        self.pinned.set(Vec::new());
         self.report_media.set(HashMap::new());
         self.report_context_submitted.set(false);
     }
@@ -44,8 +66,10 @@ impl ChatContext {
             self.parent.set(None);
             self.prev_leaf.set(None);
             self.next_leaf.set(None);
+            self.pinned.set(Vec::new());
             self.report_context_submitted.set(false);
         }
+        self.unpin(id);
         if let Some(next) = self.next_leaf.get()
             && next.id == id
         {
@@ -67,30 +91,70 @@ impl ChatContext {
         {
             return;
         }
+        self.stale.set(None);
         self.parent.set(Some(node_info));
         self.prev_leaf.set(None);
         self.next_leaf.set(None);
+        self.pinned.set(Vec::new());
         self.report_context_submitted.set(false);
     }
 
-    pub fn set_leaf(
-        &self,
-        node_info: &NodeWithLeaf,
-        parent_node: &NodeWithLeaf,
-        media_proxy: &str,
-    ) {
-        if let Some(parent) = &self.parent.get()
-            && parent.id != parent_node.id
-        {
-            self.parent.set(Some(parent_node.clone().into()));
+    pub fn is_pinned(&self, id: Uuid) -> bool {
+        self.pinned
+            .with(|pinned| pinned.iter().any(|node| node.id == id))
+    }
+
+    /// Pins a report of the current object, or unpins it when already pinned.
+    /// Returns `false` when `MAX_PINNED_REPORTS` are pinned already.
+    pub fn toggle_pin(&self, node: NodeInfo) -> bool {
+        if self.is_pinned(node.id) {
+            self.unpin(node.id);
+            return true;
+        }
+        if self.pinned.with_untracked(Vec::len) >= MAX_PINNED_REPORTS {
+            return false;
+        }
+        self.stale.set(None);
+        self.remember_report_media(node.clone());
+        self.pinned.update(|pinned| {
+            let index = pinned.partition_point(|pin| pin.date_time <= node.date_time);
+            pinned.insert(index, node);
+        });
+        true
+    }
+
+    pub fn unpin(&self, id: Uuid) {
+        if self.is_pinned(id) {
+            self.pinned
+                .update(|pinned| pinned.retain(|node| node.id != id));
         }
-        if self.parent.get().is_none() {
-            self.parent.set(Some(parent_node.clone().into()));
+    }
+
+    /// Pinned reports in the order the agent gets them.
+    #[cfg(any(not(feature = "ssr"), test))]
+    pub fn pinned_ids(&self) -> Vec<Uuid> {
+        self.pinned
+            .with_untracked(|pinned| pinned.iter().map(|node| node.id).collect())
+    }
+
+    /// Picks a report of `parent`, whose access comes from the user's tree.
+    pub fn set_leaf(&self, node_info: &NodeWithLeaf, parent: NodeInfo) {
+        let access = parent.access;
+        if self
+            .parent
+            .get()
+            .is_none_or(|current| current.id != parent.id)
+        {
+            self.parent.set(Some(parent));
         }
-        self.set_one_leaf(NodeInfo::from(node_info.clone()).with_media_proxy(media_proxy))
+        self.set_one_leaf(NodeInfo {
+            access,
+            ..NodeInfo::from(node_info.clone())
+        })
     }
 
     pub fn set_one_leaf(&self, new_node: NodeInfo) {
+        self.stale.set(None);
         self.remember_report_media(new_node.clone());
 
         if self.report_context_submitted.get_untracked() {
@@ -132,6 +196,39 @@ impl ChatContext {
             .with_untracked(|reports| reports.get(&id).cloned())
     }
 
+    /// Current context; tracks every part when called in an effect.
+    #[cfg(not(feature = "ssr"))]
+    pub fn snapshot(&self, saved_at: i64) -> SavedContext {
+        SavedContext {
+            parent: self.parent.get(),
+            prev_leaf: self.prev_leaf.get(),
+            next_leaf: self.next_leaf.get(),
+            pinned: self.pinned.get(),
+            report_media: self
+                .report_media
+                .with(|reports| reports.values().cloned().collect()),
+            saved_at,
+        }
+    }
+
+    /// Replaces the context with a saved one.
+    #[cfg(not(feature = "ssr"))]
+    pub fn restore(&self, saved: SavedContext, stale: Option<StaleContext>) {
+        self.parent.set(saved.parent);
+        self.prev_leaf.set(saved.prev_leaf);
+        self.next_leaf.set(saved.next_leaf);
+        self.pinned.set(saved.pinned);
+        self.report_media.set(
+            saved
+                .report_media
+                .into_iter()
+                .map(|node| (node.id, node))
+                .collect(),
+        );
+        self.report_context_submitted.set(false);
+        self.stale.set(stale);
+    }
+
     #[cfg(any(not(feature = "ssr"), test))]
     pub fn mark_report_context_submitted(&self) {
         if self.prev_leaf.get_untracked().is_some() || self.next_leaf.get_untracked().is_some() {
@@ -144,6 +241,7 @@ impl ChatContext {
 mod tests {
     use super::*;
     use crate::components::tree::NodeType;
+    use crate::sharing::NodeAccess;
     use uuid::Uuid;
 
     fn report(name: &str, date_time: i64) -> NodeInfo {
@@ -157,6 +255,7 @@ mod tests {
             full_url: Some(format!("https://example.test/{name}.jpg")),
             mime_type: Some("image/jpeg".to_string()),
             size: Some(1024),
+            access: NodeAccess::default(),
         }
     }
 
@@ -209,6 +308,67 @@ mod tests {
         });
     }
 
+    #[test]
+    fn pinned_reports_stay_in_date_order() {
+        Owner::new().with(|| {
+            let context = ChatContext::new();
+            let object = NodeInfo {
+                node_type: NodeType::Branch,
+                ..report("Building", 0)
+            };
+            let (first, second, third) = (report("a", 1), report("b", 2), report("c", 3));
+            context.set_parent(object.clone());
+
+            assert!(context.toggle_pin(third.clone()));
+            assert!(context.toggle_pin(first.clone()));
+            assert!(context.toggle_pin(second.clone()));
+            assert_eq!(context.pinned_ids(), [first.id, second.id, third.id]);
+
+            assert!(context.toggle_pin(second.clone()));
+            context.delete_node_info(first);
+            assert_eq!(context.pinned_ids(), [third.id]);
+
+            for index in 0..MAX_PINNED_REPORTS as i64 {
+                context.toggle_pin(report("more", 10 + index));
+            }
+            assert!(!context.toggle_pin(second));
+            assert_eq!(context.pinned_ids().len(), MAX_PINNED_REPORTS);
+
+            context.set_parent(NodeInfo {
+                id: Uuid::now_v7(),
+                ..object
+            });
+            assert!(context.pinned_ids().is_empty());
+        });
+    }
+
+    #[cfg(not(feature = "ssr"))]
+    #[test]
+    fn restored_context_is_stale_until_changed() {
+        Owner::new().with(|| {
+            let context = ChatContext::new();
+            let saved_report = report("22.05.2026 19:30:00", 1);
+            let saved_id = saved_report.id.to_string();
+            let saved = SavedContext {
+                prev_leaf: Some(saved_report.clone()),
+                report_media: vec![saved_report],
+                ..SavedContext::default()
+            };
+            let stale = StaleContext {
+                saved_at: 0,
+                dropped: 1,
+            };
+
+            context.restore(saved, Some(stale));
+            assert_eq!(context.stale.get_untracked(), Some(stale));
+            assert!(context.report_by_id(&saved_id).is_some());
+            assert_eq!(context.snapshot(5).report_media.len(), 1);
+
+            context.set_one_leaf(report("30.08.2026 17:00:00", 2));
+            assert!(context.stale.get_untracked().is_none());
+        });
+    }
+
     #[test]
     fn backend_resolved_report_media_can_be_remembered_without_selecting_it() {
         Owner::new().with(|| {
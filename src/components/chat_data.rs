@@ -6,6 +6,114 @@ pub struct ContextRequest {
     pub suggestions: Vec<String>,
 }
 
+/// Kind of building element the model reports on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElementCategory {
+    Windows,
+    Doors,
+    Radiators,
+    Openings,
+}
+
+impl ElementCategory {
+    pub const ALL: [Self; 4] = [Self::Windows, Self::Doors, Self::Radiators, Self::Openings];
+
+    pub fn label_key(self) -> &'static str {
+        match self {
+            Self::Windows => "detail-label-windows",
+            Self::Doors => "detail-label-doors",
+            Self::Radiators => "detail-label-radiators",
+            Self::Openings => "detail-label-openings",
+        }
+    }
+
+    fn markdown_label(self) -> &'static str {
+        match self {
+            Self::Windows => "Windows",
+            Self::Doors => "Doors",
+            Self::Radiators => "Radiators",
+            Self::Openings => "Openings",
+        }
+    }
+}
+
+/// Outline of a detected element, in fractions of the image width and height.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnnotationShape {
+    Box {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    Polygon {
+        points: Vec<[f32; 2]>,
+    },
+}
+
+impl AnnotationShape {
+    /// Corners for an SVG `points` attribute, clamped to the image.
+    pub fn svg_points(&self) -> String {
+        let corners = match self {
+            Self::Box {
+                x,
+                y,
+                width,
+                height,
+            } => vec![
+                [*x, *y],
+                [x + width, *y],
+                [x + width, y + height],
+                [*x, y + height],
+            ],
+            Self::Polygon { points } => points.clone(),
+        };
+        corners
+            .iter()
+            .map(|[x, y]| format!("{},{}", x.clamp(0.0, 1.0), y.clamp(0.0, 1.0)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Element the model counted, drawn over the report image.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub category: ElementCategory,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    pub shape: AnnotationShape,
+}
+
+impl Annotation {
+    /// Label with the confidence, e.g. `Window 2 (87%)`.
+    pub fn title(&self) -> String {
+        match self.confidence {
+            Some(confidence) => format!("{} ({:.0}%)", self.label, confidence * 100.0),
+            None => self.label.clone(),
+        }
+    }
+}
+
+/// How many elements of each category were annotated, in category order.
+pub fn annotation_counts(annotations: &[Annotation]) -> Vec<(ElementCategory, usize)> {
+    ElementCategory::ALL
+        .into_iter()
+        .map(|category| {
+            let count = annotations
+                .iter()
+                .filter(|annotation| annotation.category == category)
+                .count();
+            (category, count)
+        })
+        .filter(|(_, count)| *count > 0)
+        .collect()
+}
+
 /// Client-side structure matching server's DescriptionData
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DescriptionData {
@@ -31,9 +139,21 @@ pub struct DescriptionData {
     pub model_name: String,
     pub confidence: Option<f32>,
     pub created_at: String,
+    /// Detected elements on the report image.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
 }
 
 impl DescriptionData {
+    pub fn detail(&self, category: ElementCategory) -> Option<&str> {
+        match category {
+            ElementCategory::Windows => self.windows.as_deref(),
+            ElementCategory::Doors => self.doors.as_deref(),
+            ElementCategory::Radiators => self.radiators.as_deref(),
+            ElementCategory::Openings => self.openings.as_deref(),
+        }
+    }
+
     /// Renders the description data as a Markdown string
     pub fn to_markdown(&self) -> String {
         let (object_name, report_name) = extract_name_pair(&self.object);
@@ -85,9 +205,24 @@ pub struct ComparisonData {
     pub radiators: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub openings: Option<String>,
+    /// Detected elements on the older report image.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prev_annotations: Vec<Annotation>,
+    /// Detected elements on the newer report image.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub next_annotations: Vec<Annotation>,
 }
 
 impl ComparisonData {
+    pub fn detail(&self, category: ElementCategory) -> Option<&str> {
+        match category {
+            ElementCategory::Windows => self.windows.as_deref(),
+            ElementCategory::Doors => self.doors.as_deref(),
+            ElementCategory::Radiators => self.radiators.as_deref(),
+            ElementCategory::Openings => self.openings.as_deref(),
+        }
+    }
+
     /// Renders the comparison data as a Markdown string
     pub fn to_markdown(&self) -> String {
         let mut md = format!(
@@ -121,6 +256,90 @@ impl ComparisonData {
     }
 }
 
+/// Changes of one report against the one before it on the timeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineStep {
+    #[serde(default)]
+    pub report_id: String,
+    pub date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub windows: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doors: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub radiators: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub openings: Option<String>,
+}
+
+impl TimelineStep {
+    pub fn change(&self, category: ElementCategory) -> Option<&str> {
+        match category {
+            ElementCategory::Windows => self.windows.as_deref(),
+            ElementCategory::Doors => self.doors.as_deref(),
+            ElementCategory::Radiators => self.radiators.as_deref(),
+            ElementCategory::Openings => self.openings.as_deref(),
+        }
+    }
+}
+
+/// Client-side structure matching server's TimelineData
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineData {
+    pub object_name: String,
+    pub description: String,
+    /// Oldest first; the first report is the baseline of the others.
+    pub steps: Vec<TimelineStep>,
+}
+
+impl TimelineData {
+    /// Categories with at least one change.
+    pub fn categories(&self) -> Vec<ElementCategory> {
+        ElementCategory::ALL
+            .into_iter()
+            .filter(|category| {
+                self.steps
+                    .iter()
+                    .any(|step| step.change(*category).is_some())
+            })
+            .collect()
+    }
+
+    fn date_range(&self) -> (&str, &str) {
+        fn date(step: Option<&TimelineStep>) -> &str {
+            step.map_or("", |step| step.date.as_str())
+        }
+        (date(self.steps.first()), date(self.steps.last()))
+    }
+
+    /// Renders the timeline as a Markdown string, one section per category
+    pub fn to_markdown(&self) -> String {
+        let (first, last) = self.date_range();
+        let mut md = format!(
+            "# {}\n\n**Changes from** {} **to** {}\n\n{}\n",
+            self.object_name, first, last, self.description
+        );
+        for category in self.categories() {
+            md.push_str(&format!("\n## {}\n\n", category.markdown_label()));
+            for step in &self.steps {
+                if let Some(change) = step.change(category) {
+                    md.push_str(&format!("- **{}:** {}\n", step.date, change));
+                }
+            }
+        }
+        md
+    }
+
+    /// Builds the filename from the header text (spaces replaced with underscores)
+    pub fn filename(&self) -> String {
+        let (first, last) = self.date_range();
+        let object_name = self.object_name.replace(" - ", "_");
+        let header = format!("Timeline_{}_from_{}_to_{}", &object_name, first, last);
+        let sanitized = header.replace(' ', "_");
+        format!("{}.md", sanitized)
+    }
+}
+
 pub fn extract_name_pair(full_name: &str) -> (String, String) {
     let full_name = full_name.replace("Root/", "");
     let parts: Vec<&str> = full_name.split('/').collect();
@@ -133,7 +352,10 @@ pub fn extract_name_pair(full_name: &str) -> (String, String) {
 
 #[cfg(test)]
 mod tests {
-    use super::{ComparisonData, DescriptionData};
+    use super::{
+        AnnotationShape, ComparisonData, DescriptionData, ElementCategory, TimelineData,
+        annotation_counts,
+    };
 
     #[test]
     fn description_deserialization_preserves_report_id() {
@@ -181,5 +403,71 @@ mod tests {
 
         assert_eq!(data.prev_id, "older-id");
         assert_eq!(data.next_id, "newer-id");
+        assert!(data.prev_annotations.is_empty());
+    }
+
+    #[test]
+    fn annotations_are_optional_and_typed() {
+        let data: ComparisonData = serde_json::from_str(
+            r#"{
+                "object_name":"Building",
+                "prev_date":"22.05.2026 19:30:00",
+                "next_date":"22.05.2026 20:00:00",
+                "description":"Changed",
+                "next_annotations":[
+                    {"category":"windows","label":"Window 1","confidence":0.87,
+                     "shape":{"type":"box","x":0.5,"y":0.25,"width":0.75,"height":0.5}},
+                    {"category":"doors","shape":{"type":"polygon","points":[[0,0],[0.5,0],[0.25,0.5]]}},
+                    {"category":"windows","label":"Window 2",
+                     "shape":{"type":"box","x":0,"y":0,"width":0.1,"height":0.1}}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let annotations = &data.next_annotations;
+        assert_eq!(annotations[0].title(), "Window 1 (87%)");
+        assert_eq!(
+            annotations[0].shape.svg_points(),
+            "0.5,0.25 1,0.25 1,0.75 0.5,0.75"
+        );
+        assert!(matches!(
+            annotations[1].shape,
+            AnnotationShape::Polygon { .. }
+        ));
+        assert_eq!(
+            annotation_counts(annotations),
+            [(ElementCategory::Windows, 2), (ElementCategory::Doors, 1)]
+        );
+    }
+
+    #[test]
+    fn timeline_lists_changes_per_category() {
+        let data: TimelineData = serde_json::from_str(
+            r#"{
+                "object_name":"Building",
+                "description":"Windows were fitted, then doors",
+                "steps":[
+                    {"report_id":"first-id","date":"01.03.2026 10:00:00"},
+                    {"report_id":"second-id","date":"01.04.2026 10:00:00","windows":"3 fitted"},
+                    {"date":"01.05.2026 10:00:00","windows":"1 more","doors":"front door"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(data.steps[1].report_id, "second-id");
+        assert_eq!(
+            data.categories(),
+            [ElementCategory::Windows, ElementCategory::Doors]
+        );
+        let md = data.to_markdown();
+        assert!(md.contains("**Changes from** 01.03.2026 10:00:00 **to** 01.05.2026 10:00:00"));
+        assert!(md.contains("## Windows\n\n- **01.04.2026 10:00:00:** 3 fitted\n- **01.05.2026"));
+        assert!(!md.contains("Radiators"));
+        assert_eq!(
+            data.filename(),
+            "Timeline_Building_from_01.03.2026_10:00:00_to_01.05.2026_10:00:00.md"
+        );
     }
 }
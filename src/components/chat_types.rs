@@ -2,7 +2,7 @@
 // Types shared between SSR and client targets.
 // No browser APIs, no WASM dependencies.
 
-use crate::components::chat_data::{ComparisonData, ContextRequest, DescriptionData};
+use crate::components::chat_data::{ComparisonData, ContextRequest, DescriptionData, TimelineData};
 use crate::components::tree::{NodeWithLeaf, Tree};
 use serde::{Deserialize, Serialize};
 
@@ -31,7 +31,8 @@ pub enum MessageContent {
     ObjectTree(Vec<Tree>),
     DocumentTree(Vec<NodeWithLeaf>),
     Description(Box<Vec<DescriptionData>>),
-    Comparison(ComparisonData),
+    Comparison(Box<ComparisonData>),
+    Timeline(TimelineData),
     ContextRequest(ContextRequest),
 }
 
@@ -0,0 +1,251 @@
+use crate::components::zoom_pan::{ZOOM_STEP, ZoomPan};
+use crate::media::{MediaVariant, POPUP_SIZES, RESPONSIVE_VARIANTS, media_srcset, media_url};
+use leptos::ev::KeyboardEvent;
+use leptos::html::Div;
+use leptos::prelude::*;
+use leptos_fluent::{I18n, move_tr};
+use uuid::Uuid;
+
+/// How the two reports are laid over each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareMode {
+    SideBySide,
+    /// The newer report covers the older one up to a slider.
+    Swipe,
+    /// The newer report is faded over the older one.
+    Overlay,
+}
+
+impl CompareMode {
+    const ALL: [Self; 3] = [Self::SideBySide, Self::Swipe, Self::Overlay];
+
+    fn label_key(self) -> &'static str {
+        match self {
+            Self::SideBySide => "compare-mode-side-by-side",
+            Self::Swipe => "compare-mode-swipe",
+            Self::Overlay => "compare-mode-overlay",
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            Self::SideBySide => "fas fa-table-columns",
+            Self::Swipe => "fas fa-arrows-left-right",
+            Self::Overlay => "fas fa-layer-group",
+        }
+    }
+}
+
+/// One side of the comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompareImage {
+    pub id: Uuid,
+    pub label: String,
+}
+
+/// Full-screen viewer of two reports with synchronized zoom and pan.
+#[component]
+pub fn CompareViewer(
+    prev: CompareImage,
+    next: CompareImage,
+    #[prop(into)] on_close: Callback<()>,
+) -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let mode = RwSignal::new(CompareMode::SideBySide);
+    let zoom_pan = ZoomPan::new();
+    let transform = zoom_pan.transform;
+    // Swipe position and overlay opacity, in percent
+    let split = RwSignal::new(50.0_f64);
+    let opacity = RwSignal::new(50.0_f64);
+    let dialog_ref = NodeRef::<Div>::new();
+    let zoomed = move || transform.get().is_zoomed();
+
+    Effect::new(move |_| {
+        if let Some(dialog) = dialog_ref.get() {
+            let _ = dialog.focus();
+        }
+    });
+
+    // Buttons and keys zoom around the top-left corner of the visible part
+    let zoom = move |factor: f64| zoom_pan.zoom(factor);
+    let on_keydown = move |ev: KeyboardEvent| match ev.key().as_str() {
+        "Escape" => on_close.run(()),
+        "+" | "=" => zoom(ZOOM_STEP),
+        "-" => zoom(1.0 / ZOOM_STEP),
+        "0" => zoom_pan.reset(),
+        _ => {}
+    };
+
+    let image = move |image: &CompareImage| {
+        view! {
+            <img
+                crossorigin="anonymous"
+                src=media_url(image.id, MediaVariant::Full)
+                srcset=media_srcset(image.id, &RESPONSIVE_VARIANTS)
+                sizes=POPUP_SIZES
+                alt=image.label.clone()
+                draggable="false"
+                style:transform=move || transform.get().css()
+            />
+        }
+    };
+    let stage = move |content: AnyView, labels: AnyView| {
+        view! {
+            <div
+                class="compare-stage"
+                on:wheel=move |ev| zoom_pan.on_wheel(ev)
+                on:pointerdown=move |ev| zoom_pan.on_pointer_down(ev)
+                on:pointermove=move |ev| zoom_pan.on_pointer_move(ev)
+                on:pointerup=move |ev| zoom_pan.on_pointer_up(ev)
+                on:pointercancel=move |ev| zoom_pan.on_pointer_up(ev)
+            >
+                {content}
+                {labels}
+            </div>
+        }
+    };
+    let label = |text: String, class: &'static str| {
+        view! { <span class=format!("compare-label {class}")>{text}</span> }
+    };
+
+    let body = move || {
+        match mode.get() {
+        CompareMode::SideBySide => view! {
+            <div class="compare-side-by-side">
+                {stage(image(&prev).into_any(), label(prev.label.clone(), "compare-label-left").into_any())}
+                {stage(image(&next).into_any(), label(next.label.clone(), "compare-label-left").into_any())}
+            </div>
+        }
+        .into_any(),
+        CompareMode::Swipe => {
+            let layers = view! {
+                <div class="compare-layer">{image(&prev)}</div>
+                <div
+                    class="compare-layer"
+                    style:clip-path=move || format!("inset(0 0 0 {}%)", split.get())
+                >
+                    {image(&next)}
+                </div>
+                <div class="compare-divider" style:left=move || format!("{}%", split.get())></div>
+            };
+            let labels = view! {
+                {label(prev.label.clone(), "compare-label-left")}
+                {label(next.label.clone(), "compare-label-right")}
+            };
+            view! {
+                {stage(layers.into_any(), labels.into_any())}
+                <input
+                    type="range"
+                    class="compare-range"
+                    min="0"
+                    max="100"
+                    aria-label=move || move_tr!("compare-swipe-position").get()
+                    prop:value=move || split.get()
+                    on:input=move |ev| {
+                        split.set(event_target_value(&ev).parse().unwrap_or(50.0))
+                    }
+                />
+            }
+            .into_any()
+        }
+        CompareMode::Overlay => {
+            let layers = view! {
+                <div class="compare-layer">{image(&prev)}</div>
+                <div class="compare-layer" style:opacity=move || format!("{}", opacity.get() / 100.0)>
+                    {image(&next)}
+                </div>
+            };
+            let labels = view! {
+                {label(prev.label.clone(), "compare-label-left")}
+                {label(next.label.clone(), "compare-label-right")}
+            };
+            view! {
+                {stage(layers.into_any(), labels.into_any())}
+                <input
+                    type="range"
+                    class="compare-range"
+                    min="0"
+                    max="100"
+                    aria-label=move || move_tr!("compare-overlay-opacity").get()
+                    prop:value=move || opacity.get()
+                    on:input=move |ev| {
+                        opacity.set(event_target_value(&ev).parse().unwrap_or(50.0))
+                    }
+                />
+            }
+            .into_any()
+        }
+    }
+    };
+
+    view! {
+        <div
+            class="compare-viewer"
+            role="dialog"
+            aria-modal="true"
+            aria-label=move || move_tr!("compare-viewer").get()
+            tabindex="-1"
+            node_ref=dialog_ref
+            on:keydown=on_keydown
+        >
+            <div class="compare-toolbar">
+                <div class="compare-modes" role="group">
+                    {CompareMode::ALL
+                        .into_iter()
+                        .map(|value| {
+                            view! {
+                                <button
+                                    type="button"
+                                    class:active=move || mode.get() == value
+                                    aria-pressed=move || (mode.get() == value).to_string()
+                                    on:click=move |_| mode.set(value)
+                                >
+                                    <i class=value.icon()></i>
+                                    " "
+                                    {move || i18n.tr(value.label_key())}
+                                </button>
+                            }
+                        })
+                        .collect::<Vec<_>>()}
+                </div>
+                <div class="compare-zoom" role="group">
+                    <button
+                        type="button"
+                        title=move || move_tr!("compare-zoom-out").get()
+                        on:click=move |_| zoom(1.0 / ZOOM_STEP)
+                    >
+                        <i class="fas fa-magnifying-glass-minus"></i>
+                    </button>
+                    <span class="compare-scale">
+                        {move || format!("{:.0}%", transform.get().scale * 100.0)}
+                    </span>
+                    <button
+                        type="button"
+                        title=move || move_tr!("compare-zoom-in").get()
+                        on:click=move |_| zoom(ZOOM_STEP)
+                    >
+                        <i class="fas fa-magnifying-glass-plus"></i>
+                    </button>
+                    <button
+                        type="button"
+                        title=move || move_tr!("compare-zoom-reset").get()
+                        on:click=move |_| zoom_pan.reset()
+                    >
+                        <i class="fas fa-expand"></i>
+                    </button>
+                </div>
+                <button
+                    type="button"
+                    class="compare-close"
+                    title=move || move_tr!("compare-close").get()
+                    on:click=move |_| on_close.run(())
+                >
+                    "×"
+                </button>
+            </div>
+            <div class="compare-body" class:compare-zoomed=zoomed>
+                {body}
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,188 @@
+#![cfg(not(feature = "ssr"))]
+use crate::components::chat_context::{ChatContext, StaleContext};
+use crate::components::reports_panel::{send_json_request, send_request};
+use crate::components::tree::{fetch_tree_data, find_node};
+use crate::saved_context::SavedContext;
+use leptos::prelude::*;
+use uuid::Uuid;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::Storage;
+
+/// Chat of the tab, kept in session storage so a reload continues it.
+const CHAT_ID_KEY: &str = "cx58-chat-id";
+const CONTEXT_KEY_PREFIX: &str = "cx58-chat-context:";
+/// Chats whose context the browser keeps.
+const MAX_STORED_CONTEXTS: usize = 20;
+/// Quiet time before a change is saved with the conversation.
+const REMOTE_SAVE_DELAY_MS: u32 = 1_000;
+
+fn session_storage() -> Option<Storage> {
+    web_sys::window()?.session_storage().ok().flatten()
+}
+
+fn local_storage() -> Option<Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+fn now_ms() -> i64 {
+    js_sys::Date::now() as i64
+}
+
+/// Chat id of this tab, the same after a reload.
+pub fn current_chat_id() -> String {
+    session_storage()
+        .and_then(|storage| storage.get_item(CHAT_ID_KEY).ok().flatten())
+        .filter(|id| Uuid::parse_str(id).is_ok())
+        .unwrap_or_else(new_chat_id)
+}
+
+/// Starts a new chat in this tab.
+pub fn new_chat_id() -> String {
+    let chat_id = Uuid::now_v7().to_string();
+    if let Some(storage) = session_storage() {
+        let _ = storage.set_item(CHAT_ID_KEY, &chat_id);
+    }
+    chat_id
+}
+
+fn load_local(chat_id: &str) -> Option<SavedContext> {
+    let stored = local_storage()?
+        .get_item(&format!("{CONTEXT_KEY_PREFIX}{chat_id}"))
+        .ok()??;
+    serde_json::from_str(&stored).ok()
+}
+
+/// Saves the context of one chat and forgets the oldest beyond `MAX_STORED_CONTEXTS`.
+fn store_local(chat_id: &str, saved: &SavedContext) {
+    let Some(storage) = local_storage() else {
+        return;
+    };
+    let key = format!("{CONTEXT_KEY_PREFIX}{chat_id}");
+    if saved.is_empty() {
+        let _ = storage.remove_item(&key);
+        return;
+    }
+    if let Ok(json) = serde_json::to_string(saved) {
+        let _ = storage.set_item(&key, &json);
+    }
+
+    let mut stored: Vec<(i64, String)> = (0..storage.length().unwrap_or(0))
+        .filter_map(|index| storage.key(index).ok().flatten())
+        .filter(|key| key.starts_with(CONTEXT_KEY_PREFIX))
+        .map(|key| {
+            let saved_at = storage
+                .get_item(&key)
+                .ok()
+                .flatten()
+                .and_then(|json| serde_json::from_str::<SavedContext>(&json).ok())
+                .map_or(0, |saved| saved.saved_at);
+            (saved_at, key)
+        })
+        .collect();
+    if stored.len() > MAX_STORED_CONTEXTS {
+        stored.sort();
+        for (_, key) in &stored[..stored.len() - MAX_STORED_CONTEXTS] {
+            let _ = storage.remove_item(key);
+        }
+    }
+}
+
+fn context_url(chat_id: &str) -> String {
+    format!("/api/chat_context/{chat_id}")
+}
+
+/// Context saved with the conversation, `None` if the agent keeps none.
+async fn load_remote(chat_id: &str) -> Option<SavedContext> {
+    let response = send_request("GET", &context_url(chat_id), None)
+        .await
+        .ok()?;
+    let json = JsFuture::from(response.json().ok()?).await.ok()?;
+    serde_wasm_bindgen::from_value(json).ok()
+}
+
+async fn store_remote(chat_id: &str, saved: &SavedContext) {
+    let Ok(body) = serde_json::to_string(saved) else {
+        return;
+    };
+    match send_json_request("PUT", &context_url(chat_id), &body).await {
+        Ok(response) if !response.ok() => {
+            tracing::debug!("Chat context not saved: HTTP {}", response.status());
+        }
+        Ok(_) => {}
+        Err(e) => tracing::debug!("Chat context not saved: {e}"),
+    }
+}
+
+/// Puts back the newest saved context of the chat without deleted nodes.
+async fn restore_saved(ctx: ChatContext, chat_id: &str, user_id: &str) {
+    let local = load_local(chat_id);
+    let remote = load_remote(chat_id).await;
+    let Some(mut saved) = [local, remote]
+        .into_iter()
+        .flatten()
+        .filter(|saved| !saved.is_empty())
+        .max_by_key(|saved| saved.saved_at)
+    else {
+        return;
+    };
+    let tree = match fetch_tree_data(user_id, true).await {
+        Ok(tree) => tree,
+        Err(e) => {
+            tracing::warn!("Chat context not restored: {e}");
+            return;
+        }
+    };
+    // Something picked meanwhile wins over the saved context
+    if ctx.parent.get_untracked().is_some() {
+        return;
+    }
+    let dropped = saved.retain_accessible(|id| find_node(&tree, id).and_then(|node| node.access()));
+    let stale = (dropped > 0 || saved.is_stale(now_ms())).then_some(StaleContext {
+        saved_at: saved.saved_at,
+        dropped,
+    });
+    ctx.restore(saved, stale);
+}
+
+/// Restores the context of the tab's chat unless `restore` is false, then
+/// saves every change in the browser and, shortly after, with the conversation.
+pub fn persist_chat_context(
+    ctx: ChatContext,
+    chat_id: StoredValue<String>,
+    user_id: String,
+    restore: bool,
+) {
+    let ready = StoredValue::new(!restore);
+    if restore {
+        leptos::task::spawn_local(async move {
+            restore_saved(ctx, &chat_id.get_value(), &user_id).await;
+            ready.set_value(true);
+        });
+    }
+
+    let version = StoredValue::new(0_u64);
+    Effect::new(move |_| {
+        // An untouched restored context keeps its age
+        let saved_at = ctx
+            .stale
+            .with(|stale| stale.map(|stale| stale.saved_at))
+            .unwrap_or_else(now_ms);
+        let saved = ctx.snapshot(saved_at);
+        if !ready.get_value() {
+            return;
+        }
+        let chat_id = chat_id.get_value();
+        store_local(&chat_id, &saved);
+        if saved.is_empty() {
+            return;
+        }
+        version.update_value(|version| *version += 1);
+        let current = version.get_value();
+        leptos::task::spawn_local(async move {
+            gloo_timers::future::TimeoutFuture::new(REMOTE_SAVE_DELAY_MS).await;
+            if version.try_get_value() == Some(current) {
+                store_remote(&chat_id, &saved).await;
+            }
+        });
+    });
+}
@@ -0,0 +1,279 @@
+use crate::auth::Auth;
+use crate::components::chat_context::ChatContext;
+use crate::components::reports_panel::fetch_reports;
+use crate::components::tree::{NodeInfo, fetch_tree_data, find_node};
+use leptos::ev::MouseEvent;
+use leptos::prelude::*;
+use leptos::*;
+use leptos_fluent::{I18n, move_tr};
+use leptos_router::NavigateOptions;
+use leptos_router::hooks::{use_location, use_navigate};
+use uuid::Uuid;
+
+/// Context restored from `?object=…&report=…&compare=…,…`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeepLink {
+    pub object: Option<Uuid>,
+    /// Report shown in the reports panel and put into the chat context.
+    pub report: Option<Uuid>,
+    /// Two reports of the object, pre-filled as a comparison.
+    pub compare: Option<(Uuid, Uuid)>,
+}
+
+impl DeepLink {
+    pub fn object(id: Uuid) -> Self {
+        Self {
+            object: Some(id),
+            ..Self::default()
+        }
+    }
+
+    pub fn report(object: Option<Uuid>, report: Uuid) -> Self {
+        Self {
+            object,
+            report: Some(report),
+            ..Self::default()
+        }
+    }
+
+    pub fn compare(object: Option<Uuid>, prev: Uuid, next: Uuid) -> Self {
+        Self {
+            object,
+            compare: Some((prev, next)),
+            ..Self::default()
+        }
+    }
+
+    /// Reads the link parameters; unparsable ones are ignored.
+    pub fn parse(get: impl Fn(&str) -> Option<String>) -> Self {
+        let id = |key: &str| get(key).and_then(|value| value.trim().parse().ok());
+        let compare = get("compare").and_then(|value| {
+            let (prev, next) = value.split_once(',')?;
+            Some((prev.trim().parse().ok()?, next.trim().parse().ok()?))
+        });
+        Self {
+            object: id("object"),
+            report: id("report"),
+            compare,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Reports the link puts into the chat context.
+    pub fn reports(&self) -> Vec<Uuid> {
+        match (self.compare, self.report) {
+            (Some((prev, next)), _) => vec![prev, next],
+            (None, Some(report)) => vec![report],
+            (None, None) => Vec::new(),
+        }
+    }
+
+    /// Single reports open in the reports panel, everything else in the chat.
+    pub fn path(&self) -> &'static str {
+        if self.report.is_some() && self.compare.is_none() {
+            "/reports"
+        } else {
+            "/"
+        }
+    }
+
+    pub fn href(&self) -> String {
+        let mut query = Vec::new();
+        if let Some(object) = self.object {
+            query.push(format!("object={object}"));
+        }
+        if let Some(report) = self.report {
+            query.push(format!("report={report}"));
+        }
+        if let Some((prev, next)) = self.compare {
+            query.push(format!("compare={prev},{next}"));
+        }
+        format!("{}?{}", self.path(), query.join("&"))
+    }
+}
+
+/// Absolute form of an in-app `href`.
+fn absolute_url(href: &str) -> Option<String> {
+    let base = web_sys::window()?.document()?.url().ok()?;
+    Some(web_sys::Url::new_with_base(href, &base).ok()?.href())
+}
+
+/// Puts `text` on the clipboard, falling back to a prompt to copy it from.
+async fn copy_text(text: &str) -> bool {
+    let Some(window) = web_sys::window() else {
+        return false;
+    };
+    let clipboard = window.navigator().clipboard();
+    if wasm_bindgen_futures::JsFuture::from(clipboard.write_text(text))
+        .await
+        .is_ok()
+    {
+        return true;
+    }
+    let _ = window.prompt_with_message_and_default("", text);
+    false
+}
+
+/// Copies an absolute link to `link` and briefly confirms it.
+#[component]
+pub fn CopyLinkButton(link: DeepLink, #[prop(optional)] class: &'static str) -> impl IntoView {
+    let copied = RwSignal::new(false);
+    let on_click = move |ev: MouseEvent| {
+        // Inside a tree summary a click would also fold the branch
+        ev.prevent_default();
+        ev.stop_propagation();
+        let Some(url) = absolute_url(&link.href()) else {
+            return;
+        };
+        leptos::task::spawn_local(async move {
+            if copy_text(&url).await {
+                copied.set(true);
+                gloo_timers::future::TimeoutFuture::new(1_500).await;
+                copied.try_set(false);
+            }
+        });
+    };
+    let title = move || {
+        if copied.get() {
+            move_tr!("copy-link-done").get()
+        } else {
+            move_tr!("copy-link").get()
+        }
+    };
+
+    view! {
+        <button
+            type="button"
+            class=format!("copy-link {class}")
+            title=title
+            aria-label=title
+            on:click=on_click
+        >
+            <i class=move || if copied.get() { "fas fa-check" } else { "fas fa-link" }></i>
+        </button>
+    }
+}
+
+/// Restores the chat context of a deep link once the user is known.
+#[component]
+pub fn DeepLinkRestore() -> impl IntoView {
+    let auth_signal = use_context::<RwSignal<Auth>>().expect("Auth must be provided");
+    let ctx = use_context::<ChatContext>().expect("ChatContext must be provided");
+    let i18n = expect_context::<I18n>();
+    let location = use_location();
+    let navigate = use_navigate();
+
+    // Effects only run in the browser, after hydration
+    Effect::new(move |_| {
+        let auth = auth_signal.get_untracked();
+        let Some(user_id) = auth.email().filter(|_| !auth.is_authenticated_guest()) else {
+            return;
+        };
+        let link = location
+            .query
+            .with_untracked(|query| DeepLink::parse(|key| query.get(key)));
+        if link.is_empty() {
+            return;
+        }
+        let pathname = location.pathname.get_untracked();
+        let navigate = navigate.clone();
+        leptos::task::spawn_local(async move {
+            match restore(ctx, &user_id, link).await {
+                Ok(()) => {
+                    if link.compare.is_some() {
+                        ctx.insert_text
+                            .set(Some(i18n.tr("deep-link-compare-prompt")));
+                    }
+                    if pathname != link.path() {
+                        navigate(
+                            &link.href(),
+                            NavigateOptions {
+                                replace: true,
+                                ..NavigateOptions::default()
+                            },
+                        );
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to open link: {e}"),
+            }
+        });
+    });
+}
+
+/// Sets the object and reports of `link` as the chat context.
+async fn restore(ctx: ChatContext, user_id: &str, link: DeepLink) -> Result<(), String> {
+    let reports = link.reports();
+    // Links to reports may leave out their object
+    let tree = fetch_tree_data(user_id, !reports.is_empty()).await?;
+    let object_id = link
+        .object
+        .or_else(|| {
+            reports
+                .iter()
+                .find_map(|id| find_node(&tree, *id)?.parent_id)
+        })
+        .ok_or("Link names no object")?;
+    let object = find_node(&tree, object_id)
+        .filter(|node| node.access().is_some())
+        .ok_or("Object not found")?;
+    let object = object.node_info();
+    let access = object.access;
+    ctx.set_parent(object);
+    if reports.is_empty() {
+        return Ok(());
+    }
+
+    let leaves = fetch_reports(object_id).await?;
+    for id in reports {
+        let report = leaves
+            .iter()
+            .find(|leaf| leaf.id == id)
+            .ok_or("Report not found")?;
+        ctx.set_one_leaf(NodeInfo {
+            access,
+            ..NodeInfo::from(report.clone())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn links_round_trip_through_the_query() {
+        let (object, prev, next) = (Uuid::now_v7(), Uuid::now_v7(), Uuid::now_v7());
+        let parse = |href: &str| {
+            let query: HashMap<String, String> = href
+                .split_once('?')
+                .map(|(_, query)| query)
+                .unwrap_or_default()
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            DeepLink::parse(|key| query.get(key).cloned())
+        };
+
+        let report = DeepLink::report(Some(object), prev);
+        assert!(report.href().starts_with("/reports?"));
+        assert_eq!(parse(&report.href()), report);
+        assert_eq!(report.reports(), [prev]);
+
+        let compare = DeepLink::compare(None, prev, next);
+        assert_eq!(compare.href(), format!("/?compare={prev},{next}"));
+        assert_eq!(parse(&compare.href()), compare);
+        assert_eq!(compare.reports(), [prev, next]);
+
+        assert_eq!(
+            parse(&format!("/?object={object}&compare={prev},oops")),
+            DeepLink::object(object)
+        );
+        assert!(parse("/?panel=faq").is_empty());
+    }
+}
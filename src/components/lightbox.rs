@@ -0,0 +1,235 @@
+use crate::components::active_sessions::format_timestamp;
+use crate::components::tree::NodeInfo;
+use crate::components::zoom_pan::{ZOOM_STEP, ZoomPan};
+use crate::media::{MediaVariant, POPUP_SIZES, RESPONSIVE_VARIANTS, media_srcset, media_url};
+use leptos::ev::KeyboardEvent;
+use leptos::html::Div;
+use leptos::prelude::*;
+use leptos_fluent::{I18n, move_tr};
+
+/// Index of the neighbour `delta` steps away, wrapping around the set.
+fn step(index: usize, len: usize, delta: isize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    (index as isize + delta).rem_euclid(len as isize) as usize
+}
+
+/// File size for people, in the largest unit below 1024.
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Full-screen image viewer with zoom, pan and navigation through `items`.
+#[component]
+pub fn Lightbox(
+    items: Vec<NodeInfo>,
+    start: usize,
+    #[prop(into)] on_close: Callback<()>,
+) -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let count = items.len();
+    let items = StoredValue::new(items);
+    let index = RwSignal::new(start.min(count.saturating_sub(1)));
+    let zoom_pan = ZoomPan::new();
+    let transform = zoom_pan.transform;
+    let zoomed = move || transform.get().is_zoomed();
+    let dialog_ref = NodeRef::<Div>::new();
+
+    Effect::new(move |_| {
+        if let Some(dialog) = dialog_ref.get() {
+            let _ = dialog.focus();
+        }
+    });
+
+    let current = move || items.with_value(|items| items.get(index.get()).cloned());
+    let go = move |delta: isize| {
+        index.update(|index| *index = step(*index, count, delta));
+        zoom_pan.reset();
+    };
+    let on_keydown = move |ev: KeyboardEvent| match ev.key().as_str() {
+        "Escape" => on_close.run(()),
+        "ArrowLeft" => go(-1),
+        "ArrowRight" => go(1),
+        "Home" => {
+            index.set(0);
+            zoom_pan.reset();
+        }
+        "End" => {
+            index.set(count.saturating_sub(1));
+            zoom_pan.reset();
+        }
+        "+" | "=" => zoom_pan.zoom(ZOOM_STEP),
+        "-" => zoom_pan.zoom(1.0 / ZOOM_STEP),
+        "0" => zoom_pan.reset(),
+        _ => {}
+    };
+
+    let image = move || {
+        current().map(|node| {
+            let name = node
+                .name
+                .clone()
+                .unwrap_or_else(|| i18n.tr("selected-context-unnamed"));
+            let src = node
+                .full_url
+                .clone()
+                .unwrap_or_else(|| media_url(node.id, MediaVariant::Full));
+            view! {
+                <img
+                    crossorigin="anonymous"
+                    src=src
+                    srcset=media_srcset(node.id, &RESPONSIVE_VARIANTS)
+                    sizes=POPUP_SIZES
+                    alt=name
+                    draggable="false"
+                    style:transform=move || transform.get().css()
+                />
+            }
+        })
+    };
+    let details = move || {
+        current().map(|node| {
+            let name = node
+                .name
+                .clone()
+                .unwrap_or_else(|| i18n.tr("selected-context-unnamed"));
+            let locale = i18n.language.get().id.to_string();
+            let date =
+                (node.date_time > 0).then(|| format_timestamp(node.date_time / 1000, &locale));
+            let download = node
+                .full_url
+                .clone()
+                .unwrap_or_else(|| media_url(node.id, MediaVariant::Full));
+            view! {
+                <h3>{name.clone()}</h3>
+                <dl>
+                    {date.map(|date| view! {
+                        <dt>{move_tr!("lightbox-date")}</dt>
+                        <dd>{date}</dd>
+                    })}
+                    {node.size.map(|size| view! {
+                        <dt>{move_tr!("lightbox-size")}</dt>
+                        <dd>{format_size(size)}</dd>
+                    })}
+                    {node.mime_type.map(|mime_type| view! {
+                        <dt>{move_tr!("lightbox-mime-type")}</dt>
+                        <dd>{mime_type}</dd>
+                    })}
+                </dl>
+                <a class="lightbox-download" href=download download=name>
+                    <i class="fas fa-arrow-down"></i>
+                    " "
+                    {move_tr!("lightbox-download")}
+                </a>
+            }
+        })
+    };
+
+    view! {
+        <div
+            class="lightbox"
+            role="dialog"
+            aria-modal="true"
+            aria-label=move || move_tr!("lightbox").get()
+            tabindex="-1"
+            node_ref=dialog_ref
+            on:keydown=on_keydown
+        >
+            <div class="lightbox-toolbar">
+                <span class="lightbox-position">{move || format!("{} / {}", index.get() + 1, count)}</span>
+                <button
+                    type="button"
+                    title=move || move_tr!("compare-zoom-out").get()
+                    on:click=move |_| zoom_pan.zoom(1.0 / ZOOM_STEP)
+                >
+                    <i class="fas fa-magnifying-glass-minus"></i>
+                </button>
+                <span class="lightbox-scale">
+                    {move || format!("{:.0}%", transform.get().scale * 100.0)}
+                </span>
+                <button
+                    type="button"
+                    title=move || move_tr!("compare-zoom-in").get()
+                    on:click=move |_| zoom_pan.zoom(ZOOM_STEP)
+                >
+                    <i class="fas fa-magnifying-glass-plus"></i>
+                </button>
+                <button
+                    type="button"
+                    title=move || move_tr!("compare-zoom-reset").get()
+                    on:click=move |_| zoom_pan.reset()
+                >
+                    <i class="fas fa-expand"></i>
+                </button>
+                <button
+                    type="button"
+                    class="lightbox-close"
+                    title=move || move_tr!("compare-close").get()
+                    on:click=move |_| on_close.run(())
+                >
+                    "×"
+                </button>
+            </div>
+            <div class="lightbox-main" class:lightbox-zoomed=zoomed>
+                <button
+                    type="button"
+                    class="lightbox-nav lightbox-prev"
+                    title=move || move_tr!("lightbox-previous").get()
+                    disabled=count < 2
+                    on:click=move |_| go(-1)
+                >
+                    <i class="fas fa-chevron-left"></i>
+                </button>
+                <div
+                    class="lightbox-stage"
+                    on:wheel=move |ev| zoom_pan.on_wheel(ev)
+                    on:pointerdown=move |ev| zoom_pan.on_pointer_down(ev)
+                    on:pointermove=move |ev| zoom_pan.on_pointer_move(ev)
+                    on:pointerup=move |ev| zoom_pan.on_pointer_up(ev)
+                    on:pointercancel=move |ev| zoom_pan.on_pointer_up(ev)
+                >
+                    {image}
+                </div>
+                <button
+                    type="button"
+                    class="lightbox-nav lightbox-next"
+                    title=move || move_tr!("lightbox-next").get()
+                    disabled=count < 2
+                    on:click=move |_| go(1)
+                >
+                    <i class="fas fa-chevron-right"></i>
+                </button>
+                <aside class="lightbox-details">{details}</aside>
+            </div>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigation_wraps_and_sizes_read_naturally() {
+        assert_eq!(step(0, 3, -1), 2);
+        assert_eq!(step(2, 3, 1), 0);
+        assert_eq!(step(1, 3, 1), 2);
+        assert_eq!(step(0, 0, 1), 0);
+
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+    }
+}
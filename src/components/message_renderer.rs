@@ -4,6 +4,7 @@ use crate::components::show_carusel::CarouselRenderer;
 use crate::components::show_comparison::ComparisonRenderer;
 use crate::components::show_context_request::ContextRequestRenderer;
 use crate::components::show_description::DescriptionListRenderer;
+use crate::components::show_timeline::TimelineRenderer;
 use crate::components::show_tree::DetailsTreeRendererWithContext;
 use leptos::IntoView;
 use leptos::context::use_context;
@@ -44,7 +45,13 @@ pub fn MessageRenderer(message: Message) -> impl IntoView {
         .into_any(),
         MessageContent::Comparison(data) => view! {
             <div class=css_class>
-                <ComparisonRenderer data=data />
+                <ComparisonRenderer data=*data />
+            </div>
+        }
+        .into_any(),
+        MessageContent::Timeline(data) => view! {
+            <div class=css_class>
+                <TimelineRenderer data=data />
             </div>
         }
         .into_any(),
@@ -1,11 +1,20 @@
+pub(crate) mod active_sessions;
+pub(crate) mod admin_console;
+pub(crate) mod annotations;
+pub(crate) mod api_tokens_panel;
+pub(crate) mod batch_upload;
 pub(crate) mod chat_context;
+pub(crate) mod compare_viewer;
+pub(crate) mod context_store;
+pub(crate) mod deep_link;
 pub(crate) mod home_page;
 pub(crate) mod lang;
-pub(crate) mod media_proxy_script;
+pub(crate) mod lightbox;
 pub(crate) mod model_settings_panel;
 pub(crate) mod node_info_display;
 pub(crate) mod report_preview;
 pub(crate) mod reports_panel;
+pub(crate) mod share_dialog;
 pub(crate) mod show_carusel;
 pub(crate) mod show_description;
 pub(crate) mod show_tree;
@@ -13,7 +22,9 @@ pub(crate) mod side_body;
 pub(crate) mod side_top;
 pub(crate) mod sidebar;
 pub(crate) mod tree;
+pub(crate) mod tree_filter;
 pub(crate) mod user_info;
+pub(crate) mod zoom_pan;
 
 pub(crate) mod chat;
 pub(crate) mod chat_client;
@@ -22,6 +33,7 @@ pub(crate) mod chat_types;
 pub(crate) mod message_renderer;
 pub(crate) mod show_comparison;
 pub(crate) mod show_context_request;
+pub(crate) mod show_timeline;
 
 // Builds a FluentValue args map from key => value pairs.
 // Usage: args!["error" => some_string, "status" => code]
@@ -1,3 +1,4 @@
+use crate::csrf::{CSRF_HEADER, csrf_token};
 use crate::model_settings::{
     ModelChange, ModelSettings, ModelsResponse, OllamaModelInfo, UpdateModelsRequest,
     UpdateModelsResponse,
@@ -410,6 +411,10 @@ where
         .headers()
         .set("Content-Type", "application/json")
         .map_err(|e| format!("Failed to set content type: {:?}", e))?;
+    request
+        .headers()
+        .set(CSRF_HEADER, &csrf_token())
+        .map_err(|e| format!("Failed to set CSRF header: {:?}", e))?;
 
     let response_value = JsFuture::from(window.fetch_with_request(&request))
         .await
@@ -1,6 +1,9 @@
+use crate::components::args;
+use crate::components::chat_context::{ChatContext, MAX_PINNED_REPORTS};
+use crate::components::lightbox::Lightbox;
 use crate::components::tree::{NodeInfo, NodeType};
 use leptos::prelude::*;
-use leptos_fluent::move_tr;
+use leptos_fluent::{I18n, move_tr};
 
 #[component]
 pub fn NodeInfoDisplay(
@@ -25,24 +28,23 @@ pub fn NodeInfoDisplay(
                         .unwrap_or_else(|| move_tr!("selected-context-unnamed").get());
 
                     if node.node_type == NodeType::ImageLeaf {
-                        let popup_id = format!("selected-report-popup-{}", node.id);
-                        let popup_target = popup_id.clone();
-                        let popup_close = popup_id.clone();
                         let thumbnail_url = node.thumbnail_url.clone().or_else(|| node.full_url.clone());
-                        let full_url = node.full_url.clone().or_else(|| thumbnail_url.clone());
+                        let can_open = thumbnail_url.is_some();
                         let open_label = move_tr!("selected-report-open").get();
                         let remove_label = move_tr!("selected-report-remove").get();
                         let open_accessible_label = format!("{}: {}", open_label, display_name);
+                        let lightbox = RwSignal::new(false);
+                        let lightbox_node = node.clone();
 
                         view! {
                             <div class="node-info-item selected-report-item">
                                 <button
                                     type="button"
                                     class="selected-report-open"
-                                    popovertarget=popup_target
                                     title=open_accessible_label.clone()
                                     aria-label=open_accessible_label
-                                    disabled=full_url.is_none()
+                                    disabled=!can_open
+                                    on:click=move |_| lightbox.set(true)
                                 >
                                     {thumbnail_url
                                         .map(|url| view! {
@@ -65,26 +67,17 @@ pub fn NodeInfoDisplay(
                                     <i class="fas fa-times"></i>
                                 </button>
                             </div>
-                            {full_url.map(|url| view! {
-                                <div id=popup_id popover class="popup">
-                                    <div class="popup-content">
-                                        <button
-                                            type="button"
-                                            popovertarget=popup_close
-                                            class="popup-close"
-                                            aria-label=move_tr!("selected-report-close")
-                                        >
-                                            "×"
-                                        </button>
-                                        <img
-                                            crossorigin="anonymous"
-                                            src=url
-                                            alt=display_name
-                                            class="popup-image"
+                            {move || {
+                                lightbox
+                                    .get()
+                                    .then(|| view! {
+                                        <Lightbox
+                                            items=vec![lightbox_node.clone()]
+                                            start=0
+                                            on_close=move |_| lightbox.set(false)
                                         />
-                                    </div>
-                                </div>
-                            })}
+                                    })
+                            }}
                         }.into_any()
                     } else {
                         view! {
@@ -109,3 +102,108 @@ pub fn NodeInfoDisplay(
         }}
     }
 }
+
+/// Reports pinned for a timeline, each removable, with a prompt to ask for it.
+#[component]
+pub fn PinnedReportsDisplay() -> impl IntoView {
+    let ctx = expect_context::<ChatContext>();
+    let i18n = expect_context::<I18n>();
+
+    view! {
+        {move || {
+            let pinned = ctx.pinned.get();
+            (!pinned.is_empty())
+                .then(|| {
+                    let count = pinned.len();
+                    view! {
+                        <div class="node-info-pinned">
+                            <span class="node-info-pinned-label">
+                                <i class="fas fa-thumbtack"></i>
+                                {i18n.tr_with_args("pinned-reports", &args!["count" => count])}
+                            </span>
+                            {pinned
+                                .into_iter()
+                                .map(|node| {
+                                    let id = node.id;
+                                    let name = node
+                                        .name
+                                        .unwrap_or_else(|| i18n.tr("selected-context-unnamed"));
+                                    let remove_label = format!(
+                                        "{}: {}",
+                                        i18n.tr("selected-report-remove"),
+                                        name,
+                                    );
+                                    view! {
+                                        <span class="node-info-pin">
+                                            {name}
+                                            <button
+                                                type="button"
+                                                class="delete-btn"
+                                                title=remove_label.clone()
+                                                aria-label=remove_label
+                                                on:click=move |_| ctx.unpin(id)
+                                            >
+                                                <i class="fas fa-times"></i>
+                                            </button>
+                                        </span>
+                                    }
+                                })
+                                .collect::<Vec<_>>()}
+                            <button
+                                type="button"
+                                class="node-info-timeline"
+                                disabled=count < 2
+                                on:click=move |_| {
+                                    ctx.insert_text.set(Some(i18n.tr("pinned-timeline-prompt")))
+                                }
+                            >
+                                {move_tr!("pinned-timeline")}
+                            </button>
+                        </div>
+                    }
+                })
+        }}
+    }
+}
+
+/// Pins `node` for a timeline or unpins it; `parent` becomes the chat object first.
+#[component]
+pub fn PinButton(
+    node: NodeInfo,
+    #[prop(optional)] parent: Option<NodeInfo>,
+    #[prop(optional)] class: &'static str,
+) -> impl IntoView {
+    let ctx = expect_context::<ChatContext>();
+    let id = node.id;
+    let pinned = move || ctx.is_pinned(id);
+    let full = move || !pinned() && ctx.pinned.with(Vec::len) >= MAX_PINNED_REPORTS;
+    let title = move || {
+        if pinned() {
+            move_tr!("unpin-report").get()
+        } else if full() {
+            move_tr!("pin-limit").get()
+        } else {
+            move_tr!("pin-report").get()
+        }
+    };
+
+    view! {
+        <button
+            type="button"
+            class=format!("pin-report {class}")
+            class:pinned=pinned
+            title=title
+            aria-label=title
+            aria-pressed=move || pinned().to_string()
+            disabled=full
+            on:click=move |_| {
+                if let Some(parent) = parent.clone() {
+                    ctx.set_parent(parent);
+                }
+                ctx.toggle_pin(node.clone());
+            }
+        >
+            <i class="fas fa-thumbtack"></i>
+        </button>
+    }
+}
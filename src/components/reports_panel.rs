@@ -1,15 +1,25 @@
 use crate::auth::Auth;
+use crate::components::args;
+use crate::components::batch_upload::BatchUpload;
 use crate::components::chat_context::ChatContext;
+use crate::components::compare_viewer::{CompareImage, CompareViewer};
+use crate::components::deep_link::{CopyLinkButton, DeepLink};
+use crate::components::lightbox::Lightbox;
+use crate::components::node_info_display::PinButton;
 use crate::components::show_tree::DetailsTreeRendererWithContext;
-use crate::components::tree::{NodeData, NodeInfo, NodeType, NodeWithLeaf, TreeViewerResource};
-use js_sys::{Date, Reflect};
+use crate::components::tree::{NodeInfo, NodeType, NodeWithLeaf, TreeViewerResource};
+use crate::csrf::{CSRF_HEADER, csrf_token};
+use crate::exif::{berlin_utc_offset_hours, capture_time, is_leap_year};
+use crate::media::{MediaVariant, THUMBNAIL_SIZES, media_srcset, media_url};
+use js_sys::Date;
 use leptos::prelude::*;
 use leptos::wasm_bindgen::{JsCast, JsValue};
 use leptos::{IntoView, component, view};
-use leptos_fluent::move_tr;
+use leptos_fluent::{I18n, move_tr};
+use leptos_router::hooks::use_location;
 use uuid::Uuid;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{FormData, HtmlInputElement, Request, RequestInit, RequestMode, Response, window};
+use web_sys::{Request, RequestInit, RequestMode, Response, window};
 
 const REPORTS_PAGE_SIZE: usize = 10;
 
@@ -73,13 +83,16 @@ fn SelectedReports(node: NodeInfo) -> impl IntoView {
     let reports = RwSignal::new(Vec::<NodeWithLeaf>::new());
     let loading = RwSignal::new(false);
     let error = RwSignal::new(None::<String>);
-    let file_input_ref = NodeRef::<leptos::html::Input>::new();
-    let datetime_input_ref = NodeRef::<leptos::html::Input>::new();
     let parent_id = node.id;
-    let has_selected_file = RwSignal::new(false);
-    let uploading = RwSignal::new(false);
+    // Shared read-only objects can be browsed but not changed
+    let can_edit = node.access.can_edit();
     let current_page = RwSignal::new(0usize);
-    let media_proxy = media_proxy_rule();
+    // A deep link opens the page holding its report
+    let linked = use_location()
+        .query
+        .with_untracked(|query| DeepLink::parse(|key| query.get(key)).report);
+    let jump_to_linked = StoredValue::new(linked.is_some());
+    let lightbox = RwSignal::new(None::<usize>);
 
     let reload = Action::new_unsync(move |_: &()| async move {
         loading.set(true);
@@ -89,8 +102,15 @@ fn SelectedReports(node: NodeInfo) -> impl IntoView {
                 data.retain(|item| item.node_type == NodeType::ImageLeaf);
                 data.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
                 let max_page = max_page_index(data.len());
+                let linked_page = linked
+                    .filter(|_| jump_to_linked.get_value())
+                    .and_then(|id| data.iter().position(|item| item.id == id))
+                    .map(|index| index / REPORTS_PAGE_SIZE);
+                jump_to_linked.set_value(false);
                 current_page.update(|page| {
-                    if *page > max_page {
+                    if let Some(linked_page) = linked_page {
+                        *page = linked_page;
+                    } else if *page > max_page {
                         *page = max_page;
                     }
                 });
@@ -105,79 +125,20 @@ fn SelectedReports(node: NodeInfo) -> impl IntoView {
         reload.dispatch(());
     });
 
-    let on_upload = move |_| {
-        if uploading.get_untracked() {
-            return;
-        }
-        let Some(input) = file_input_ref.get() else {
-            return;
-        };
-        let input: HtmlInputElement = input.unchecked_into();
-        let Some(file_list) = input.files() else {
-            return;
-        };
-        let Some(file) = file_list.item(0) else {
-            return;
-        };
-        let datetime = datetime_input_ref
-            .get()
-            .map(|input| {
-                let input: HtmlInputElement = input.unchecked_into();
-                input.value()
-            })
-            .filter(|value| !value.is_empty())
-            .unwrap_or_else(current_datetime_berlin);
-
-        uploading.set(true);
-        leptos::task::spawn_local(async move {
-            match upload_report(parent_id, &file, &datetime).await {
-                Ok(()) => {
-                    if let Some(input) = file_input_ref.get() {
-                        let input: HtmlInputElement = input.unchecked_into();
-                        input.set_value("");
-                    }
-                    has_selected_file.set(false);
-                    current_page.set(0);
-                    reload.dispatch(());
-                }
-                Err(e) => error.set(Some(e)),
-            }
-            uploading.set(false);
-        });
-    };
-
     view! {
         <div class="reports-selected">
-            <div class="reports-upload">
-                <input
-                    id="reports-upload-datetime"
-                    name="berlin_datetime"
-                    type="text"
-                    node_ref=datetime_input_ref
-                    prop:value=current_datetime_agent()
-                    aria-label=move || move_tr!("reports-datetime").get()
-                />
-                <input
-                    id="reports-upload-file"
-                    name="image"
-                    type="file"
-                    accept="image/*"
-                    node_ref=file_input_ref
-                    aria-label=move || move_tr!("reports-file").get()
-                    on:change=move |ev| {
-                        let input: HtmlInputElement = event_target(&ev);
-                        has_selected_file.set(input.files().is_some_and(|files| files.length() > 0));
-                    }
-                />
-                <button
-                    type="button"
-                    on:click=on_upload
-                    disabled=move || !has_selected_file.get() || uploading.get()
-                    title=move || move_tr!("reports-upload").get()
-                >
-                    <i class="fas fa-upload"></i>
-                </button>
-            </div>
+            {if can_edit {
+                view! {
+                    <BatchUpload parent_id=parent_id on_uploaded=move |_| {
+                        current_page.set(0);
+                        reload.dispatch(());
+                    } />
+                }
+                    .into_any()
+            } else {
+                view! { <div class="reports-status">{move_tr!("reports-read-only")}</div> }
+                    .into_any()
+            }}
 
             {move || if loading.get() {
                 view! { <div class="reports-status">{move_tr!("reports-loading")}</div> }.into_any()
@@ -186,34 +147,96 @@ fn SelectedReports(node: NodeInfo) -> impl IntoView {
             } else if reports.get().is_empty() {
                 view! { <div class="reports-status">{move_tr!("reports-empty")}</div> }.into_any()
             } else {
-                let media_proxy = media_proxy.clone();
                 view! {
                     <ReportsPaginator reports=reports current_page=current_page />
+                    <ComparePinnedButton />
                     <div class="reports-list">
                         <For
                             each=move || paged_reports(reports.get(), current_page.get())
                             key=|report| report.id
-                            children={
-                                move |report| {
-                                    let media_proxy = media_proxy.clone();
-                                    view! {
-                                        <ReportItem
-                                            report=report
-                                            reload=reload
-                                            current_page=current_page
-                                            media_proxy=media_proxy
-                                        />
-                                    }
+                            children=move |report| {
+                                let is_linked = linked == Some(report.id);
+                                view! {
+                                    <ReportItem
+                                        report=report
+                                        reload=reload
+                                        current_page=current_page
+                                        can_edit=can_edit
+                                        linked=is_linked
+                                        on_open=move |id| {
+                                            let index = reports
+                                                .with_untracked(|reports| {
+                                                    reports.iter().position(|report| report.id == id)
+                                                });
+                                            lightbox.set(index);
+                                        }
+                                    />
                                 }
                             }
                         />
                     </div>
                 }.into_any()
             }}
+            {move || {
+                lightbox
+                    .get()
+                    .map(|start| {
+                        let items = reports
+                            .get_untracked()
+                            .into_iter()
+                            .map(NodeInfo::from)
+                            .collect::<Vec<_>>();
+                        view! { <Lightbox items=items start=start on_close=move |_| lightbox.set(None) /> }
+                    })
+            }}
         </div>
     }
 }
 
+/// Opens the comparison viewer once exactly two reports are pinned.
+#[component]
+fn ComparePinnedButton() -> impl IntoView {
+    let ctx = use_context::<ChatContext>().expect("ChatContext must be provided");
+    let i18n = expect_context::<I18n>();
+    let viewer_open = RwSignal::new(false);
+    let pair = move || {
+        ctx.pinned.with(|pinned| match pinned.as_slice() {
+            [prev, next] => {
+                let image = |node: &NodeInfo| CompareImage {
+                    id: node.id,
+                    label: node
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| i18n.tr("reports-image")),
+                };
+                Some((image(prev), image(next)))
+            }
+            _ => None,
+        })
+    };
+
+    view! {
+        <button
+            type="button"
+            class="reports-compare"
+            disabled=move || pair().is_none()
+            title=move || move_tr!("compare-pin-two").get()
+            on:click=move |_| viewer_open.set(true)
+        >
+            <i class="fas fa-images"></i>
+            " "
+            {move_tr!("compare-open")}
+        </button>
+        {move || {
+            pair()
+                .filter(|_| viewer_open.get())
+                .map(|(prev, next)| view! {
+                    <CompareViewer prev=prev next=next on_close=move |_| viewer_open.set(false) />
+                })
+        }}
+    }
+}
+
 #[component]
 fn ReportsPaginator(
     reports: RwSignal<Vec<NodeWithLeaf>>,
@@ -290,39 +313,46 @@ fn ReportItem(
     report: NodeWithLeaf,
     reload: Action<(), ()>,
     current_page: RwSignal<usize>,
-    media_proxy: String,
+    /// Upload, delete and date changes are offered.
+    can_edit: bool,
+    /// The report a deep link points at.
+    linked: bool,
+    /// Shows the report in the lightbox.
+    #[prop(into)]
+    on_open: Callback<Uuid>,
 ) -> impl IntoView {
     let report_id = report.id;
+    let link = DeepLink::report(report.parent_id, report_id);
+    let pin = NodeInfo::from(report.clone());
     let name = report
         .name
         .clone()
         .unwrap_or_else(|| move_tr!("reports-image").get());
-    let thumbnail = image_url(&report, true, &media_proxy);
-    let full_url = image_url(&report, false, &media_proxy);
-    let popup_id = format!("report-popup-{}", Uuid::now_v7());
-    let popup_target = popup_id.clone();
-    let popup_close = popup_id.clone();
+    let thumbnail = media_url(report_id, MediaVariant::Thumbnail);
+    let thumbnail_srcset = media_srcset(report_id, &[MediaVariant::Thumbnail, MediaVariant::Small]);
     let date_input_id = format!("report-date-{report_id}");
     let edit_value = RwSignal::new(datetime_edit_value(&report));
     let update_error = RwSignal::new(None::<String>);
     let mutating = RwSignal::new(false);
 
     view! {
-        <div class="reports-item">
-            <button type="button" popovertarget=popup_target class="reports-thumb">
-                <img crossorigin="anonymous" src=thumbnail alt=name.clone() loading="lazy" />
+        <div class="reports-item" class:reports-item-linked=linked>
+            <button type="button" class="reports-thumb" on:click=move |_| on_open.run(report_id)>
+                <img
+                    crossorigin="anonymous"
+                    src=thumbnail
+                    srcset=thumbnail_srcset
+                    sizes=THUMBNAIL_SIZES
+                    alt=name.clone()
+                    loading="lazy"
+                />
             </button>
-            <div id=popup_id popover class="popup">
-                <div class="popup-content">
-                    <button popovertarget=popup_close class="popup-close">"×"</button>
-                    <img crossorigin="anonymous" src=full_url alt=name.clone() class="popup-image" />
-                </div>
-            </div>
             <input
                 id=date_input_id
                 name="berlin_datetime"
                 class="reports-date-edit"
                 type="text"
+                readonly=!can_edit
                 prop:value=move || edit_value.get()
                 on:input=move |ev| edit_value.set(event_target_value(&ev))
             />
@@ -330,9 +360,9 @@ fn ReportItem(
                 type="button"
                 class="reports-save-date"
                 title=move || move_tr!("reports-save-date").get()
-                disabled=move || mutating.get()
+                disabled=move || !can_edit || mutating.get()
                 on:click=move |_| {
-                    if mutating.get_untracked() {
+                    if !can_edit || mutating.get_untracked() {
                         return;
                     }
                     let datetime = edit_value.get();
@@ -355,9 +385,9 @@ fn ReportItem(
                 type="button"
                 class="reports-delete"
                 title=move || move_tr!("reports-delete").get()
-                disabled=move || mutating.get()
+                disabled=move || !can_edit || mutating.get()
                 on:click=move |_| {
-                    if mutating.get_untracked() {
+                    if !can_edit || mutating.get_untracked() {
                         return;
                     }
                     mutating.set(true);
@@ -376,49 +406,15 @@ fn ReportItem(
             >
                 <i class="fas fa-trash"></i>
             </button>
+            <PinButton node=pin />
+            <CopyLinkButton link=link />
             <div class="reports-item-error" class:none=move || update_error.get().is_none()>
                 {move || update_error.get().unwrap_or_default()}
             </div>
         </div>
     }
 }
-fn image_url(report: &NodeWithLeaf, thumbnail: bool, media_proxy: &str) -> String {
-    match &report.data {
-        NodeData::Image(data) if thumbnail => proxy_media(
-            media_proxy,
-            &data
-                .thumbnail_url
-                .clone()
-                .or_else(|| data.url.clone())
-                .unwrap_or_default(),
-        ),
-        NodeData::Image(data) => proxy_media(media_proxy, &data.url.clone().unwrap_or_default()),
-        _ => String::new(),
-    }
-}
-
-fn media_proxy_rule() -> String {
-    window()
-        .and_then(|w| {
-            Reflect::get(&w, &JsValue::from_str("MEDIA_PROXY"))
-                .ok()
-                .and_then(|v| v.as_string())
-        })
-        .unwrap_or_default()
-}
-
-fn proxy_media(rule: &str, value: &str) -> String {
-    if rule.is_empty() {
-        return value.to_string();
-    }
-    let parts: Vec<&str> = rule.split(',').collect();
-    if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
-        return value.to_string();
-    }
-    value.replace(parts[0], parts[1])
-}
-
-async fn fetch_reports(node_id: Uuid) -> Result<Vec<NodeWithLeaf>, String> {
+pub(super) async fn fetch_reports(node_id: Uuid) -> Result<Vec<NodeWithLeaf>, String> {
     let url = format!("/api/proxy/reports/{}", node_id);
     let resp = send_request("GET", &url, None).await?;
     let json = JsFuture::from(
@@ -431,28 +427,26 @@ async fn fetch_reports(node_id: Uuid) -> Result<Vec<NodeWithLeaf>, String> {
     serde_wasm_bindgen::from_value(json).map_err(|e| format!("Failed to deserialize: {:?}", e))
 }
 
-async fn upload_report(
-    node_id: Uuid,
-    file: &web_sys::File,
-    datetime_local: &str,
-) -> Result<(), String> {
-    let form = FormData::new().map_err(|e| format!("{e:?}"))?;
-    let datetime = datetime_for_agent(datetime_local);
-    validate_agent_datetime(&datetime)?;
-
-    form.append_with_blob("image", file)
-        .map_err(|e| format!("{e:?}"))?;
-    form.append_with_str("berlin_datetime", &datetime)
-        .map_err(|e| format!("{e:?}"))?;
-
-    let url = format!("/api/proxy/images/upload/{}", node_id);
-    let body = JsValue::from(form);
-    let resp = send_request("POST", &url, Some(body)).await?;
-    if resp.ok() {
-        Ok(())
-    } else {
-        Err(response_error(&resp).await)
-    }
+/// Capture time of the selected photo in the agent's Berlin format.
+pub(super) async fn exif_datetime(file: &web_sys::File) -> Option<String> {
+    let buffer = JsFuture::from(file.array_buffer()).await.ok()?;
+    let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+    let datetime = datetime_for_agent(&capture_time(&bytes)?.agent_datetime());
+    validate_agent_datetime(&datetime).ok().map(|_| datetime)
+}
+
+/// Upload rejections carry an ftl key in `code` plus the limit that was hit.
+pub(super) fn localized_upload_error(i18n: I18n, body: &str) -> Option<String> {
+    let json = serde_json::from_str::<serde_json::Value>(body).ok()?;
+    let code = json
+        .get("code")
+        .and_then(|code| code.as_str())
+        .filter(|code| code.starts_with("upload-"))?;
+    let limit = |name: &str| json.get(name).and_then(|value| value.as_u64()).unwrap_or(0);
+    Some(i18n.tr_with_args(
+        code,
+        &args!["max_mb" => limit("max_mb"), "max_megapixels" => limit("max_megapixels")],
+    ))
 }
 
 async fn update_report_date(node_id: Uuid, datetime_local: &str) -> Result<(), String> {
@@ -470,7 +464,11 @@ async fn update_report_date(node_id: Uuid, datetime_local: &str) -> Result<(), S
     }
 }
 
-async fn send_json_request(method: &str, url: &str, body: &str) -> Result<Response, String> {
+pub(super) async fn send_json_request(
+    method: &str,
+    url: &str,
+    body: &str,
+) -> Result<Response, String> {
     let window = window().ok_or_else(|| "No window available".to_string())?;
     let opts = RequestInit::new();
     opts.set_method(method);
@@ -486,6 +484,10 @@ async fn send_json_request(method: &str, url: &str, body: &str) -> Result<Respon
         .headers()
         .set("Accept", "application/json")
         .map_err(|e| format!("Failed to set header: {:?}", e))?;
+    request
+        .headers()
+        .set(CSRF_HEADER, &csrf_token())
+        .map_err(|e| format!("Failed to set header: {:?}", e))?;
     let resp_value = JsFuture::from(window.fetch_with_request(&request))
         .await
         .map_err(|e| format!("Fetch failed: {:?}", e))?;
@@ -583,19 +585,21 @@ async fn delete_report(node_id: Uuid) -> Result<(), String> {
 }
 
 async fn response_error(response: &Response) -> String {
-    let status = response.status();
-    let body = match response.text() {
+    response_error_message(response.status(), &response_text(response).await)
+}
+
+pub(super) async fn response_text(response: &Response) -> String {
+    match response.text() {
         Ok(promise) => JsFuture::from(promise)
             .await
             .ok()
             .and_then(|value| value.as_string())
             .unwrap_or_default(),
         Err(_) => String::new(),
-    };
-    response_error_message(status, &body)
+    }
 }
 
-fn response_error_message(status: u16, body: &str) -> String {
+pub(super) fn response_error_message(status: u16, body: &str) -> String {
     let detail = serde_json::from_str::<serde_json::Value>(body)
         .ok()
         .and_then(|json| {
@@ -617,7 +621,25 @@ fn response_error_message(status: u16, body: &str) -> String {
     }
 }
 
-async fn send_request(method: &str, url: &str, body: Option<JsValue>) -> Result<Response, String> {
+/// Localized message for error codes starting with `prefix`, the plain
+/// error otherwise.
+pub(super) async fn coded_response_error(i18n: I18n, response: &Response, prefix: &str) -> String {
+    let body = response_text(response).await;
+    let code = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|json| json.get("code")?.as_str().map(str::to_string))
+        .filter(|code| code.starts_with(prefix));
+    match code {
+        Some(code) => i18n.tr(&code),
+        None => response_error_message(response.status(), &body),
+    }
+}
+
+pub(super) async fn send_request(
+    method: &str,
+    url: &str,
+    body: Option<JsValue>,
+) -> Result<Response, String> {
     let window = window().ok_or_else(|| "No window available".to_string())?;
     let opts = RequestInit::new();
     opts.set_method(method);
@@ -627,6 +649,10 @@ async fn send_request(method: &str, url: &str, body: Option<JsValue>) -> Result<
     }
     let request = Request::new_with_str_and_init(url, &opts)
         .map_err(|e| format!("Failed to create request: {:?}", e))?;
+    request
+        .headers()
+        .set(CSRF_HEADER, &csrf_token())
+        .map_err(|e| format!("Failed to set header: {:?}", e))?;
     let resp_value = JsFuture::from(window.fetch_with_request(&request))
         .await
         .map_err(|e| format!("Fetch failed: {:?}", e))?;
@@ -660,58 +686,11 @@ fn current_datetime_berlin() -> String {
     )
 }
 
-fn current_datetime_agent() -> String {
+pub(super) fn current_datetime_agent() -> String {
     datetime_for_agent(&current_datetime_berlin())
 }
 
-fn berlin_utc_offset_hours(year: u32, month: u32, day: u32, hour_utc: u32) -> i32 {
-    let in_dst = match month {
-        4..=9 => true,
-        1 | 2 | 11 | 12 => false,
-        3 => {
-            let start_day = last_sunday_day(year as i32, 3);
-            day > start_day || (day == start_day && hour_utc >= 1)
-        }
-        10 => {
-            let end_day = last_sunday_day(year as i32, 10);
-            day < end_day || (day == end_day && hour_utc < 1)
-        }
-        _ => false,
-    };
-    if in_dst { 2 } else { 1 }
-}
-
-fn last_sunday_day(year: i32, month: u32) -> u32 {
-    let mut day = match month {
-        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
-        4 | 6 | 9 | 11 => 30,
-        2 if is_leap_year(year) => 29,
-        2 => 28,
-        _ => 30,
-    };
-    while day_of_week(year, month, day) != 0 {
-        day -= 1;
-    }
-    day
-}
-
-fn is_leap_year(year: i32) -> bool {
-    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
-}
-
-fn day_of_week(year: i32, month: u32, day: u32) -> u32 {
-    let (month, year) = if month < 3 {
-        (month as i32 + 12, year - 1)
-    } else {
-        (month as i32, year)
-    };
-    let k = year % 100;
-    let j = year / 100;
-    let h = (day as i32 + (13 * (month + 1)) / 5 + k + k / 4 + j / 4 + 5 * j) % 7;
-    ((h + 6) % 7) as u32
-}
-
-fn validate_agent_datetime(datetime: &str) -> Result<(), String> {
+pub(super) fn validate_agent_datetime(datetime: &str) -> Result<(), String> {
     let mut parts = datetime.split(['.', ' ', ':']);
     let parse_part = |part: Option<&str>| {
         part.ok_or_else(|| "Invalid datetime".to_string())?
@@ -746,7 +725,7 @@ fn validate_agent_datetime(datetime: &str) -> Result<(), String> {
 
     Ok(())
 }
-fn datetime_for_agent(datetime_value: &str) -> String {
+pub(super) fn datetime_for_agent(datetime_value: &str) -> String {
     if let Some(datetime) = normalize_agent_datetime(datetime_value) {
         return datetime;
     }
@@ -0,0 +1,246 @@
+use crate::components::args;
+use crate::components::reports_panel::{
+    coded_response_error, response_text, send_json_request, send_request,
+};
+use crate::sharing::{GranteeKind, Share, SharePermission, ShareUpdate, grantee_name, share_path};
+use leptos::ev::SubmitEvent;
+use leptos::prelude::*;
+use leptos::*;
+use leptos_fluent::{I18n, move_tr};
+use uuid::Uuid;
+
+/// Changes the users and groups an own branch is shared with.
+#[component]
+pub fn ShareDialog(
+    node_id: Uuid,
+    name: String,
+    #[prop(into)] on_close: Callback<()>,
+) -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let shares = RwSignal::new(Vec::<Share>::new());
+    let loading = RwSignal::new(true);
+    let busy = RwSignal::new(false);
+    let error = RwSignal::new(None::<String>);
+    let kind = RwSignal::new(GranteeKind::User);
+    let grantee = RwSignal::new(String::new());
+    let permission = RwSignal::new(SharePermission::Read);
+
+    let reload = move || {
+        loading.set(true);
+        leptos::task::spawn_local(async move {
+            match fetch_shares(node_id).await {
+                Ok(list) => shares.set(list),
+                Err(e) => error.set(Some(e)),
+            }
+            loading.set(false);
+        });
+    };
+    reload();
+
+    // One change at a time, the list is fetched again once it is done
+    let change = move |kind: GranteeKind, grantee: String, update: Option<ShareUpdate>| {
+        if busy.get_untracked() {
+            return;
+        }
+        busy.set(true);
+        error.set(None);
+        leptos::task::spawn_local(async move {
+            match send_share(i18n, node_id, kind, &grantee, update).await {
+                Ok(()) => reload(),
+                Err(e) => error.set(Some(e)),
+            }
+            busy.set(false);
+        });
+    };
+    let submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let kind = kind.get_untracked();
+        let Some(name) = grantee_name(kind, &grantee.get_untracked()) else {
+            error.set(Some(i18n.tr("share-invalid-grantee")));
+            return;
+        };
+        grantee.set(String::new());
+        change(
+            kind,
+            name,
+            Some(ShareUpdate {
+                permission: permission.get_untracked(),
+            }),
+        );
+    };
+    let title = i18n.tr_with_args("share-title", &args!["name" => name]);
+    let label = title.clone();
+
+    view! {
+        <section class="share-dialog" aria-label=label>
+            <header>
+                <h3>{title}</h3>
+                <button
+                    type="button"
+                    class="share-close"
+                    title=move || move_tr!("share-close").get()
+                    on:click=move |_| on_close.run(())
+                >
+                    <i class="fas fa-xmark"></i>
+                </button>
+            </header>
+            {move || {
+                if loading.get() {
+                    view! { <p class="share-status">{move_tr!("share-loading")}</p> }.into_any()
+                } else if shares.with(Vec::is_empty) {
+                    view! { <p class="share-status">{move_tr!("share-none")}</p> }.into_any()
+                } else {
+                    view! {
+                        <ul class="share-list">
+                            {shares
+                                .get()
+                                .into_iter()
+                                .map(|share| {
+                                    let icon = match share.kind {
+                                        GranteeKind::User => "fas fa-user",
+                                        GranteeKind::Group => "fas fa-users",
+                                    };
+                                    let (kind, grantee) = (share.kind, share.grantee.clone());
+                                    let revoked = grantee.clone();
+                                    view! {
+                                        <li>
+                                            <i class=icon></i>
+                                            <span class="share-grantee">{share.grantee}</span>
+                                            <PermissionSelect
+                                                value=share.permission
+                                                disabled=busy
+                                                on_change=move |permission| {
+                                                    change(
+                                                        kind,
+                                                        grantee.clone(),
+                                                        Some(ShareUpdate { permission }),
+                                                    )
+                                                }
+                                            />
+                                            <button
+                                                type="button"
+                                                title=move || move_tr!("share-remove").get()
+                                                disabled=move || busy.get()
+                                                on:click=move |_| change(kind, revoked.clone(), None)
+                                            >
+                                                <i class="fas fa-trash"></i>
+                                            </button>
+                                        </li>
+                                    }
+                                })
+                                .collect::<Vec<_>>()}
+                        </ul>
+                    }
+                        .into_any()
+                }
+            }}
+            <form class="share-add" on:submit=submit>
+                <select
+                    aria-label=move || move_tr!("share-kind").get()
+                    on:change=move |ev| {
+                        kind.set(
+                            match event_target_value(&ev).as_str() {
+                                "group" => GranteeKind::Group,
+                                _ => GranteeKind::User,
+                            },
+                        )
+                    }
+                >
+                    <option value="user" selected=move || kind.get() == GranteeKind::User>
+                        {move_tr!("share-kind-user")}
+                    </option>
+                    <option value="group" selected=move || kind.get() == GranteeKind::Group>
+                        {move_tr!("share-kind-group")}
+                    </option>
+                </select>
+                <input
+                    type="text"
+                    name="grantee"
+                    maxlength=crate::sharing::MAX_GRANTEE_CHARS
+                    aria-label=move || move_tr!("share-grantee").get()
+                    placeholder=move || move_tr!("share-grantee").get()
+                    prop:value=move || grantee.get()
+                    on:input=move |ev| grantee.set(event_target_value(&ev))
+                />
+                <PermissionSelect
+                    value=SharePermission::Read
+                    disabled=busy
+                    on_change=move |value| permission.set(value)
+                />
+                <button type="submit" disabled=move || busy.get()>
+                    {move_tr!("share-add")}
+                </button>
+            </form>
+            {move || error.get().map(|error| view! { <p class="share-error">{error}</p> })}
+        </section>
+    }
+}
+
+#[component]
+fn PermissionSelect(
+    value: SharePermission,
+    disabled: RwSignal<bool>,
+    #[prop(into)] on_change: Callback<SharePermission>,
+) -> impl IntoView {
+    let option = move |permission: SharePermission| {
+        view! {
+            <option value=permission.as_str() selected=value == permission>
+                {match permission {
+                    SharePermission::Read => move_tr!("share-permission-read"),
+                    SharePermission::Edit => move_tr!("share-permission-edit"),
+                }}
+            </option>
+        }
+    };
+    view! {
+        <select
+            aria-label=move || move_tr!("share-permission").get()
+            disabled=move || disabled.get()
+            on:change=move |ev| {
+                on_change
+                    .run(
+                        match event_target_value(&ev).as_str() {
+                            "edit" => SharePermission::Edit,
+                            _ => SharePermission::Read,
+                        },
+                    )
+            }
+        >
+            {option(SharePermission::Read)}
+            {option(SharePermission::Edit)}
+        </select>
+    }
+}
+
+fn shares_url(path: &str) -> String {
+    format!("/api/proxy/shares/{path}")
+}
+
+async fn fetch_shares(node_id: Uuid) -> Result<Vec<Share>, String> {
+    let response = send_request("GET", &shares_url(&node_id.to_string()), None).await?;
+    let body = response_text(&response).await;
+    serde_json::from_str(&body).map_err(|e| format!("Failed to read shares: {e}"))
+}
+
+/// Grants or changes access with `update`, revokes it without.
+async fn send_share(
+    i18n: I18n,
+    node_id: Uuid,
+    kind: GranteeKind,
+    grantee: &str,
+    update: Option<ShareUpdate>,
+) -> Result<(), String> {
+    let url = shares_url(&share_path(node_id, kind, grantee));
+    let response = match update {
+        Some(update) => {
+            let body = serde_json::to_string(&update).map_err(|e| e.to_string())?;
+            send_json_request("PUT", &url, &body).await?
+        }
+        None => send_request("DELETE", &url, None).await?,
+    };
+    if response.ok() {
+        Ok(())
+    } else {
+        Err(coded_response_error(i18n, &response, "share-").await)
+    }
+}
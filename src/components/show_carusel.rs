@@ -1,32 +1,26 @@
+use crate::auth::Auth;
 use crate::components::args;
 use crate::components::chat_context::ChatContext;
-use crate::components::tree::{NodeData, NodeType, NodeWithLeaf, proxy_media};
+use crate::components::lightbox::Lightbox;
+use crate::components::node_info_display::PinButton;
+use crate::components::tree::{NodeInfo, NodeType, NodeWithLeaf, node_access};
+use crate::media::{MediaVariant, THUMBNAIL_SIZES, media_srcset, media_url};
 use leptos::context::use_context;
 use leptos::prelude::ElementChild;
-use leptos::prelude::GlobalAttributes;
 use leptos::prelude::IntoAny;
 use leptos::prelude::OnAttribute;
-use leptos::prelude::{ClassAttribute, expect_context};
+use leptos::prelude::{ClassAttribute, Get, GetUntracked, RwSignal, Set, expect_context};
 use leptos::*;
 use leptos_fluent::{I18n, move_tr};
-use uuid::Uuid;
 
 /// Carousel renderer for a single Branch node with ImageLeaf children
 /// Displays node info and a 2-image-wide carousel with CSS popup
 #[component]
 pub fn CarouselRenderer(data: Vec<NodeWithLeaf>) -> impl IntoView {
-    use web_sys::window;
     let i18n = expect_context::<I18n>();
 
-    let media_proxy = window()
-        .and_then(|w| {
-            js_sys::Reflect::get(&w, &wasm_bindgen::JsValue::from_str("MEDIA_PROXY"))
-                .ok()
-                .and_then(|v| v.as_string())
-        })
-        .unwrap_or_default();
-
     let ctx = use_context::<ChatContext>().expect("Context lost");
+    let auth = use_context::<RwSignal<Auth>>().expect("Auth must be provided");
     let branches = data.iter().find(|n| n.node_type == NodeType::Branch);
     match branches {
         Some(branch) => {
@@ -36,6 +30,12 @@ pub fn CarouselRenderer(data: Vec<NodeWithLeaf>) -> impl IntoView {
                 .filter(|n| n.node_type == NodeType::ImageLeaf)
                 .collect();
             images.sort_by_key(|p| std::cmp::Reverse(p.updated_at.clone()));
+            let lightbox = RwSignal::new(None::<usize>);
+            let lightbox_items = images
+                .iter()
+                .cloned()
+                .map(NodeInfo::from)
+                .collect::<Vec<_>>();
             view! {
                 <div class="carousel-container">
                     <div class="node-info">
@@ -66,44 +66,31 @@ pub fn CarouselRenderer(data: Vec<NodeWithLeaf>) -> impl IntoView {
                                         .into_iter()
                                         .enumerate()
                                         .map(|(idx, img)| {
-                                            let thumbnail = match &img.data {
-                                                NodeData::Image(img_data) => {
-                                                    let img = img_data
-                                                        .thumbnail_url
-                                                        .clone()
-                                                        .unwrap_or_else(|| {
-                                                            img_data.url.clone().unwrap_or_default()
-                                                        });
-                                                    proxy_media(&media_proxy, &img)
-                                                }
-                                                _ => String::new(),
-                                            };
-                                            let full_url = match &img.data {
-                                                NodeData::Image(img_data) => {
-                                                    img_data.url.clone().unwrap_or_default()
-                                                }
-                                                _ => String::new(),
-                                            };
-                                            let full_url = proxy_media(&media_proxy, &full_url);
+                                            let thumbnail = media_url(img.id, MediaVariant::Thumbnail);
+                                            let thumbnail_srcset = media_srcset(
+                                                img.id,
+                                                &[MediaVariant::Thumbnail, MediaVariant::Small],
+                                            );
                                             let img_name = img.name.clone()
                                                 .unwrap_or_else(|| i18n.tr_with_args(
                                                     "carousel-image-fallback",
                                                     &args!["index" => (idx + 1).to_string()],
                                                 ));
-                                            let popup_id = format!("popup-{}", Uuid::now_v7());
-                                            //let popup_id_for_click = popup_id.clone();
+                                            let pin = NodeInfo::from(img.clone());
+                                            let pin_parent = NodeInfo::from(branch.clone());
                                             let img_clone_for_label = img.clone();
                                             let branch_clone_for_label = branch.clone();
-                                            let media_proxy_for_label = media_proxy.clone();
                                             view! {
                                                 <div class="carousel-item">
                                                     <button
-                                                        popovertarget=popup_id.clone()
                                                         class="thumbnail-link"
+                                                        on:click=move |_| lightbox.set(Some(idx))
                                                     >
                                                         <img
                                                             crossorigin="anonymous"
                                                             src=thumbnail
+                                                            srcset=thumbnail_srcset
+                                                            sizes=THUMBNAIL_SIZES
                                                             alt=img_name.clone()
                                                             class="thumbnail"
                                                             loading="lazy"
@@ -112,29 +99,24 @@ pub fn CarouselRenderer(data: Vec<NodeWithLeaf>) -> impl IntoView {
                                                     <div
                                                         class="image-label"
                                                         on:click=move |_ev| {
-                                                            ctx.set_leaf(
-                                                                &img_clone_for_label,
-                                                                &branch_clone_for_label,
-                                                                &media_proxy_for_label,
-                                                            );
+                                                            let report = img_clone_for_label.clone();
+                                                            let object = NodeInfo::from(branch_clone_for_label.clone());
+                                                            let user_id = auth.get_untracked().email().unwrap_or_default();
+                                                            // Shared objects may be read-only
+                                                            leptos::task::spawn_local(async move {
+                                                                match node_access(&user_id, object.id).await {
+                                                                    Ok(Some(access)) => {
+                                                                        ctx.set_leaf(&report, NodeInfo { access, ..object })
+                                                                    }
+                                                                    Ok(None) => tracing::warn!("Object {} is not in the tree", object.id),
+                                                                    Err(e) => tracing::warn!("Report not picked: {e}"),
+                                                                }
+                                                            });
                                                         }
                                                     >
                                                         {img_name.clone()}
                                                     </div>
-                                                </div>
-
-                                                <div id=popup_id popover class="popup">
-                                                    <div class="popup-content">
-                                                        <button popovertarget=popup_id.clone() class="popup-close">
-                                                            "×"
-                                                        </button>
-                                                        <img
-                                                            crossorigin="anonymous"
-                                                            src=full_url
-                                                            alt=img_name
-                                                            class="popup-image"
-                                                        />
-                                                    </div>
+                                                    <PinButton node=pin parent=pin_parent class="carousel-pin" />
                                                 </div>
                                             }
                                         })
@@ -144,6 +126,19 @@ pub fn CarouselRenderer(data: Vec<NodeWithLeaf>) -> impl IntoView {
                         }
                             .into_any()
                     }}
+                    {move || {
+                        lightbox
+                            .get()
+                            .map(|start| {
+                                view! {
+                                    <Lightbox
+                                        items=lightbox_items.clone()
+                                        start=start
+                                        on_close=move |_| lightbox.set(None)
+                                    />
+                                }
+                            })
+                    }}
                 </div>
             }.into_any()
         }
@@ -1,29 +1,58 @@
+use crate::components::annotations::{
+    AnnotatedDetail, AnnotatedImage, AnnotationState, AnnotationToggles,
+};
 use crate::components::chat_context::ChatContext;
-use crate::components::chat_data::ComparisonData;
+use crate::components::chat_data::{ComparisonData, ElementCategory};
+use crate::components::compare_viewer::{CompareImage, CompareViewer};
+use crate::components::deep_link::{CopyLinkButton, DeepLink};
 use crate::components::report_preview::ReportPreview;
 use crate::components::show_description::download_text_file;
-use leptos::prelude::{ClassAttribute, expect_context};
-use leptos::prelude::{ElementChild, GlobalAttributes, OnAttribute};
+use crate::media::{MediaVariant, media_url};
+use leptos::prelude::{AriaAttributes, ElementChild, GlobalAttributes, OnAttribute};
+use leptos::prelude::{ClassAttribute, Get, GetUntracked, RwSignal, Set, Update, expect_context};
 use leptos::*;
-use leptos_fluent::{I18n, move_tr};
-
-/// Compact version without optional sections
-#[component]
-fn ComparisonDetailItem(label: String, value: String) -> impl IntoView {
-    view! {
-        <div class="detail-item">
-            <strong>{label}" "</strong>
-            {value}
-        </div>
-    }
-}
+use leptos_fluent::move_tr;
 
 #[component]
 pub fn ComparisonRenderer(data: ComparisonData) -> impl IntoView {
-    let i18n = expect_context::<I18n>();
     let ctx = expect_context::<ChatContext>();
     let previous_report = ctx.report_by_id(&data.prev_id);
     let current_report = ctx.report_by_id(&data.next_id);
+    let object_id = previous_report
+        .as_ref()
+        .and_then(|report| report.parent_id)
+        .or_else(|| ctx.parent.get_untracked().map(|parent| parent.id));
+    let ids = data.prev_id.parse().ok().zip(data.next_id.parse().ok());
+    let link = ids.map(|(prev, next)| DeepLink::compare(object_id, prev, next));
+    let images = ids.map(|(prev, next)| {
+        (
+            CompareImage {
+                id: prev,
+                label: data.prev_date.clone(),
+            },
+            CompareImage {
+                id: next,
+                label: data.next_date.clone(),
+            },
+        )
+    });
+    let viewer_open = RwSignal::new(false);
+    let details = ElementCategory::ALL
+        .into_iter()
+        .filter_map(|category| {
+            data.detail(category)
+                .map(|value| (category, value.to_string()))
+        })
+        .collect::<Vec<_>>();
+    // Both images share one state, so a toggle or highlight applies to both
+    let annotation_state = AnnotationState::new();
+    let annotations_open = RwSignal::new(false);
+    let annotated = images
+        .clone()
+        .filter(|_| !data.prev_annotations.is_empty() || !data.next_annotations.is_empty());
+    let has_annotations = annotated.is_some();
+    let prev_annotations = data.prev_annotations.clone();
+    let next_annotations = data.next_annotations.clone();
     // Clone what we need for the download closure
     let markdown = data.to_markdown();
     let filename = data.filename();
@@ -51,18 +80,64 @@ pub fn ComparisonRenderer(data: ComparisonData) -> impl IntoView {
             <p class="compact-description">{data.description}</p>
 
             <div class="compact-details">
-                {data.windows.map(|v| view! {
-                    <ComparisonDetailItem label=i18n.tr("detail-label-windows") value=v />})}
-                {data.doors.map(|v| view! {
-                    <ComparisonDetailItem label=i18n.tr("detail-label-doors") value=v />})}
-                {data.radiators.map(|v| view! {
-                    <ComparisonDetailItem label=i18n.tr("detail-label-radiators") value=v />})}
-                {data.openings.map(|v| view! {
-                    <ComparisonDetailItem label=i18n.tr("detail-label-openings") value=v />})}
+                {details
+                    .into_iter()
+                    .map(|(category, value)| view! {
+                        <AnnotatedDetail category=category value=value state=annotation_state />
+                    })
+                    .collect::<Vec<_>>()}
             </div>
+            {move || {
+                annotated
+                    .clone()
+                    .filter(|_| annotations_open.get())
+                    .map(|(prev, next)| {
+                        let all = [prev_annotations.clone(), next_annotations.clone()].concat();
+                        view! {
+                            <div class="annotation-panel">
+                                <AnnotationToggles annotations=all state=annotation_state />
+                                <div class="annotation-pair">
+                                    <AnnotatedImage
+                                        src=media_url(prev.id, MediaVariant::Large)
+                                        alt=prev.label
+                                        annotations=prev_annotations.clone()
+                                        state=annotation_state
+                                    />
+                                    <AnnotatedImage
+                                        src=media_url(next.id, MediaVariant::Large)
+                                        alt=next.label
+                                        annotations=next_annotations.clone()
+                                        state=annotation_state
+                                    />
+                                </div>
+                            </div>
+                        }
+                    })
+            }}
 
             // Download button anchored to the bottom-right of the card
             <div class="compact-download-row">
+                {has_annotations.then(|| view! {
+                    <button
+                        class="compact-download-btn"
+                        class:active=move || annotations_open.get()
+                        title=move_tr!("annotations-show")
+                        aria-pressed=move || annotations_open.get().to_string()
+                        on:click=move |_| annotations_open.update(|open| *open = !*open)
+                    >
+                        <i class="fas fa-vector-square"></i>
+                    </button>
+                })}
+                {images.is_some().then(|| view! {
+                    <button
+                        class="compact-download-btn"
+                        title=move_tr!("compare-open")
+                        on:click=move |_| viewer_open.set(true)
+                    >
+                        <i class="fas fa-images"></i>
+                    </button>
+                })}
+                {link.map(|link| view! { <CopyLinkButton link=link class="compact-download-btn" /> })}
                 <button
                     class="compact-download-btn"
                     title=move_tr!("download-as-markdown")
@@ -71,6 +146,14 @@ pub fn ComparisonRenderer(data: ComparisonData) -> impl IntoView {
                     <i class="fas fa-arrow-down"></i>
                 </button>
             </div>
+            {move || {
+                images
+                    .clone()
+                    .filter(|_| viewer_open.get())
+                    .map(|(prev, next)| view! {
+                        <CompareViewer prev=prev next=next on_close=move |_| viewer_open.set(false) />
+                    })
+            }}
         </div>
     }
 }
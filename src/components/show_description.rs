@@ -1,9 +1,14 @@
+use crate::components::annotations::{
+    AnnotatedDetail, AnnotatedImage, AnnotationState, AnnotationToggles,
+};
 use crate::components::chat_context::ChatContext;
-use crate::components::chat_data::{DescriptionData, extract_name_pair};
+use crate::components::chat_data::{DescriptionData, ElementCategory, extract_name_pair};
+use crate::components::deep_link::{CopyLinkButton, DeepLink};
 use crate::components::report_preview::ReportPreview;
+use crate::media::{MediaVariant, media_url};
 use leptos::prelude::*;
 use leptos::*;
-use leptos_fluent::{I18n, move_tr};
+use leptos_fluent::move_tr;
 
 /// Triggers a browser download of `content` as a text file with the given `filename`
 pub fn download_text_file(filename: &str, content: &str) {
@@ -56,23 +61,39 @@ pub fn DescriptionListRenderer(
     }
 }
 
-/// Compact version without optional sections
-#[component]
-fn DetailItem(label: String, value: String) -> impl IntoView {
-    view! {
-        <div class="detail-item">
-            <strong>{label}" "</strong>
-            {value}
-        </div>
-    }
-}
-
 #[component]
 pub fn DescriptionRendererCompact(data: DescriptionData) -> impl IntoView {
-    let i18n = expect_context::<I18n>();
     let ctx = expect_context::<ChatContext>();
     let (object_name, report_name) = extract_name_pair(data.object.as_str());
     let report = ctx.report_by_id(&data.date_id);
+    let link = data
+        .date_id
+        .parse()
+        .ok()
+        .map(|report_id| DeepLink::report(data.object_id.parse().ok(), report_id));
+
+    let details = ElementCategory::ALL
+        .into_iter()
+        .filter_map(|category| {
+            data.detail(category)
+                .map(|value| (category, value.to_string()))
+        })
+        .collect::<Vec<_>>();
+    let annotation_state = AnnotationState::new();
+    let annotations_open = RwSignal::new(false);
+    let annotated = data
+        .date_id
+        .parse()
+        .ok()
+        .filter(|_| !data.annotations.is_empty())
+        .map(|report_id| {
+            (
+                media_url(report_id, MediaVariant::Large),
+                data.annotations.clone(),
+            )
+        });
+    let has_annotations = annotated.is_some();
+    let image_alt = report_name.clone();
 
     // Prepare download payload before the view consumes `data`
     let markdown = data.to_markdown();
@@ -98,14 +119,44 @@ pub fn DescriptionRendererCompact(data: DescriptionData) -> impl IntoView {
             <p class="compact-description">{data.description}</p>
 
             <div class="compact-details">
-                {data.windows.map(|v| view! { <DetailItem label=i18n.tr("detail-label-windows") value=v /> })}
-                {data.doors.map(|v| view! { <DetailItem label=i18n.tr("detail-label-doors") value=v /> })}
-                {data.radiators.map(|v| view! { <DetailItem label=i18n.tr("detail-label-radiators") value=v /> })}
-                {data.openings.map(|v| view! { <DetailItem label=i18n.tr("detail-label-openings") value=v /> })}
+                {details
+                    .into_iter()
+                    .map(|(category, value)| view! {
+                        <AnnotatedDetail category=category value=value state=annotation_state />
+                    })
+                    .collect::<Vec<_>>()}
             </div>
+            {move || {
+                annotated
+                    .clone()
+                    .filter(|_| annotations_open.get())
+                    .map(|(src, annotations)| view! {
+                        <div class="annotation-panel">
+                            <AnnotationToggles annotations=annotations.clone() state=annotation_state />
+                            <AnnotatedImage
+                                src=src
+                                alt=image_alt.clone()
+                                annotations=annotations
+                                state=annotation_state
+                            />
+                        </div>
+                    })
+            }}
 
             // Download button anchored to the bottom-right of the card
             <div class="compact-download-row">
+                {has_annotations.then(|| view! {
+                    <button
+                        class="compact-download-btn"
+                        class:active=move || annotations_open.get()
+                        title=move_tr!("annotations-show")
+                        aria-pressed=move || annotations_open.get().to_string()
+                        on:click=move |_| annotations_open.update(|open| *open = !*open)
+                    >
+                        <i class="fas fa-vector-square"></i>
+                    </button>
+                })}
+                {link.map(|link| view! { <CopyLinkButton link=link class="compact-download-btn" /> })}
                 <button
                     class="compact-download-btn"
                     title=move_tr!("download-as-markdown")
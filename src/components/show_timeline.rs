@@ -0,0 +1,98 @@
+use crate::components::args;
+use crate::components::chat_context::ChatContext;
+use crate::components::chat_data::TimelineData;
+use crate::components::report_preview::ReportPreview;
+use crate::components::show_description::download_text_file;
+use leptos::prelude::{ClassAttribute, expect_context};
+use leptos::prelude::{ElementChild, GlobalAttributes, OnAttribute};
+use leptos::*;
+use leptos_fluent::{I18n, move_tr};
+
+/// Changes across several reports: one column per date, one row per category.
+#[component]
+pub fn TimelineRenderer(data: TimelineData) -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let ctx = expect_context::<ChatContext>();
+    let markdown = data.to_markdown();
+    let filename = data.filename();
+    let on_download = move |_| {
+        download_text_file(&filename, &markdown);
+    };
+
+    let dates = data
+        .steps
+        .iter()
+        .map(|step| {
+            let report = ctx.report_by_id(&step.report_id);
+            view! {
+                <th scope="col">
+                    <i class="fas fa-image right5"></i>
+                    <ReportPreview label=step.date.clone() report=report />
+                </th>
+            }
+        })
+        .collect::<Vec<_>>();
+    let rows = data
+        .categories()
+        .into_iter()
+        .map(|category| {
+            let cells = data
+                .steps
+                .iter()
+                .map(|step| {
+                    let change = step.change(category).map(str::to_string);
+                    let unchanged = change.is_none();
+                    view! {
+                        <td class:timeline-unchanged=unchanged>
+                            {change.unwrap_or_else(|| "–".to_string())}
+                        </td>
+                    }
+                })
+                .collect::<Vec<_>>();
+            view! {
+                <tr>
+                    <th scope="row">{i18n.tr(category.label_key())}</th>
+                    {cells}
+                </tr>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
+        <div class="description-compact border-left-cx58">
+            <div class="compact-header">
+                <span>
+                    <i class="fas fa-building right5"></i>
+                    <strong>{data.object_name}</strong>
+                </span>
+                <span class="compact-date">
+                    <i class="fas fa-timeline right5"></i>
+                    {i18n.tr_with_args("timeline-reports", &args!["count" => data.steps.len()])}
+                </span>
+            </div>
+            <p class="compact-description">{data.description}</p>
+
+            <div class="timeline-scroll">
+                <table class="timeline-table">
+                    <thead>
+                        <tr>
+                            <td></td>
+                            {dates}
+                        </tr>
+                    </thead>
+                    <tbody>{rows}</tbody>
+                </table>
+            </div>
+
+            <div class="compact-download-row">
+                <button
+                    class="compact-download-btn"
+                    title=move_tr!("download-as-markdown")
+                    on:click=on_download
+                >
+                    <i class="fas fa-arrow-down"></i>
+                </button>
+            </div>
+        </div>
+    }
+}
@@ -1,26 +1,747 @@
-use crate::components::tree::{NodeInfo, NodeType, Tree};
-use leptos::ev::MouseEvent;
-use leptos::prelude::{ClassAttribute, IntoAny, OnAttribute};
-use leptos::prelude::{ElementChild, expect_context};
+use crate::branches::{CreateBranch, UpdateBranch, branch_name};
+use crate::components::args;
+use crate::components::deep_link::{CopyLinkButton, DeepLink};
+use crate::components::reports_panel::{coded_response_error, send_json_request, send_request};
+use crate::components::share_dialog::ShareDialog;
+use crate::components::tree::{
+    NodeInfo, NodeType, Tree, TreeRefresh, TreeSource, fetch_tree_children, fetch_tree_data,
+    find_node,
+};
+use crate::components::tree_filter::{
+    TreeFilter, TreeMove, VisibleNode, match_range, tree_move, visible_order,
+};
+use leptos::ev::{DragEvent, FocusEvent, KeyboardEvent, MouseEvent, SubmitEvent};
+use leptos::prelude::*;
 use leptos::*;
-use leptos_fluent::I18n;
+use leptos_fluent::{I18n, move_tr};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+use wasm_bindgen::JsCast;
+
+/// Pending change edited in the form above the tree.
+#[derive(Clone, Debug, PartialEq)]
+enum BranchForm {
+    Create { parent_id: Uuid },
+    Rename { node_id: Uuid },
+}
+
+/// Branch being dragged, with its parent and every node below it.
+#[derive(Clone, Debug, PartialEq)]
+struct DraggedBranch {
+    id: Uuid,
+    parent_id: Option<Uuid>,
+    subtree: Vec<Uuid>,
+}
+
+/// Branch management shared by the nodes of an editable tree.
+#[derive(Clone, Copy)]
+struct TreeEditor {
+    form: RwSignal<Option<BranchForm>>,
+    name: RwSignal<String>,
+    dragged: RwSignal<Option<DraggedBranch>>,
+    busy: RwSignal<bool>,
+    error: RwSignal<Option<String>>,
+    /// Branch whose share dialog is open, with its name.
+    sharing: RwSignal<Option<(Uuid, String)>>,
+    refresh: Option<TreeRefresh>,
+    i18n: I18n,
+}
+
+impl TreeEditor {
+    fn open(self, form: BranchForm, name: String) {
+        self.error.set(None);
+        self.name.set(name);
+        self.form.set(Some(form));
+    }
+
+    /// Sends one change at a time and reloads the tree once it is done.
+    fn run(self, change: BranchRequest) {
+        if self.busy.get_untracked() {
+            return;
+        }
+        self.busy.set(true);
+        self.error.set(None);
+        leptos::task::spawn_local(async move {
+            match change.send(self.i18n).await {
+                Ok(()) => {
+                    self.form.set(None);
+                    if let Some(refresh) = self.refresh {
+                        refresh.refresh();
+                    }
+                }
+                Err(error) => self.error.set(Some(error)),
+            }
+            self.busy.set(false);
+        });
+    }
+
+    /// Whether the dragged branch may be dropped under `target`.
+    fn accepts(self, target: Option<Uuid>) -> bool {
+        self.dragged.with_untracked(|dragged| {
+            dragged.as_ref().is_some_and(|dragged| {
+                dragged.parent_id != target
+                    && target.is_none_or(|target| !dragged.subtree.contains(&target))
+            })
+        })
+    }
+
+    fn drop_on(self, parent_id: Uuid) {
+        let Some(dragged) = self.dragged.get_untracked() else {
+            return;
+        };
+        self.dragged.set(None);
+        self.run(BranchRequest::Update(
+            dragged.id,
+            UpdateBranch {
+                parent_id: Some(parent_id),
+                ..UpdateBranch::default()
+            },
+        ));
+    }
+}
+
+enum BranchRequest {
+    Create(Uuid, CreateBranch),
+    Update(Uuid, UpdateBranch),
+    Delete(Uuid),
+}
+
+impl BranchRequest {
+    async fn send(self, i18n: I18n) -> Result<(), String> {
+        let response = match self {
+            Self::Create(parent_id, branch) => {
+                let body = serde_json::to_string(&branch).map_err(|e| e.to_string())?;
+                send_json_request("POST", &branch_url(parent_id), &body).await?
+            }
+            Self::Update(node_id, update) => {
+                let body = serde_json::to_string(&update).map_err(|e| e.to_string())?;
+                send_json_request("PUT", &branch_url(node_id), &body).await?
+            }
+            Self::Delete(node_id) => send_request("DELETE", &branch_url(node_id), None).await?,
+        };
+        if response.ok() {
+            return Ok(());
+        }
+        Err(coded_response_error(i18n, &response, "branch-").await)
+    }
+}
+
+fn branch_url(node_id: Uuid) -> String {
+    format!("/api/proxy/branches/{node_id}")
+}
+
+fn subtree_ids(node: &Tree) -> Vec<Uuid> {
+    let mut ids = vec![node.id];
+    for child in &node.children {
+        ids.extend(subtree_ids(child));
+    }
+    ids
+}
+
+fn find_node_mut(nodes: &mut [Tree], id: Uuid) -> Option<&mut Tree> {
+    for node in nodes {
+        if node.id == id {
+            return Some(node);
+        }
+        if let Some(found) = find_node_mut(&mut node.children, id) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+/// Search, folding, keyboard focus and loading of one rendered tree.
+#[derive(Clone, Copy)]
+struct TreeView {
+    /// Nodes loaded so far.
+    tree: RwSignal<Vec<Tree>>,
+    /// Children of the rendered branches, set when they are loaded.
+    children: StoredValue<HashMap<Uuid, RwSignal<Vec<Tree>>>>,
+    loading: RwSignal<HashSet<Uuid>>,
+    /// Where unloaded nodes come from, `None` for a tree given in full.
+    source: StoredValue<Option<TreeSource>>,
+    /// Every node is loaded or being loaded, as a search needs them all.
+    complete: StoredValue<bool>,
+    /// Unfolded branches, remembered per user.
+    open: RwSignal<HashSet<Uuid>>,
+    /// Unfolded branches while a search is shown.
+    found_open: RwSignal<HashSet<Uuid>>,
+    query: RwSignal<String>,
+    filter: Memo<Option<TreeFilter>>,
+    focused: RwSignal<Option<Uuid>>,
+    storage_key: StoredValue<Option<String>>,
+}
+
+impl TreeView {
+    fn new(tree: Vec<Tree>, storage_key: Option<String>, source: Option<TreeSource>) -> Self {
+        let open = storage_key.as_deref().map(stored_open).unwrap_or_default();
+        let tree = RwSignal::new(tree);
+        let query = RwSignal::new(String::new());
+        Self {
+            tree,
+            children: StoredValue::new(HashMap::new()),
+            loading: RwSignal::new(HashSet::new()),
+            complete: StoredValue::new(source.is_none()),
+            source: StoredValue::new(source),
+            open: RwSignal::new(open),
+            found_open: RwSignal::new(HashSet::new()),
+            query,
+            filter: Memo::new(move |_| {
+                query.with(|query| tree.with(|tree| TreeFilter::new(tree, query)))
+            }),
+            focused: RwSignal::new(None),
+            storage_key: StoredValue::new(storage_key),
+        }
+    }
+
+    fn folding(self, searching: bool) -> RwSignal<HashSet<Uuid>> {
+        if searching {
+            self.found_open
+        } else {
+            self.open
+        }
+    }
+
+    fn is_open(self, id: Uuid) -> bool {
+        self.folding(self.filter.with(Option::is_some))
+            .with(|open| open.contains(&id))
+    }
+
+    fn is_open_untracked(self, id: Uuid) -> bool {
+        self.folding(self.filter.with_untracked(Option::is_some))
+            .with_untracked(|open| open.contains(&id))
+    }
+
+    fn is_shown(self, id: Uuid) -> bool {
+        self.filter.with(|filter| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| filter.visible.contains(&id))
+        })
+    }
+
+    fn is_shown_untracked(self, id: Uuid) -> bool {
+        self.filter.with_untracked(|filter| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| filter.visible.contains(&id))
+        })
+    }
+
+    /// Folds or unfolds a branch; only folding outside a search is remembered.
+    fn set_open(self, id: Uuid, open: bool) {
+        let searching = self.filter.with_untracked(Option::is_some);
+        self.folding(searching).update(|folding| {
+            if open {
+                folding.insert(id);
+            } else {
+                folding.remove(&id);
+            }
+        });
+        if !searching && let Some(key) = self.storage_key.get_value() {
+            self.open.with_untracked(|open| store_open(&key, open));
+        }
+    }
+
+    /// Applies a search and unfolds the ancestors of its hits.
+    fn search(self, query: String) {
+        let searching = !query.trim().is_empty();
+        self.query.set(query);
+        self.focused.set(None);
+        self.reveal_hits();
+        if searching {
+            self.load_all();
+        }
+    }
+
+    fn reveal_hits(self) {
+        let found_open = self.filter.with_untracked(|filter| {
+            filter
+                .as_ref()
+                .map(|filter| filter.expanded.clone())
+                .unwrap_or_default()
+        });
+        self.found_open.set(found_open);
+    }
+
+    /// Children signal of a rendered branch, replacing the one of an earlier render.
+    fn children_of(self, node: &Tree) -> RwSignal<Vec<Tree>> {
+        let children = RwSignal::new(node.children.clone());
+        self.children.update_value(|signals| {
+            signals.insert(node.id, children);
+        });
+        children
+    }
+
+    fn attach(self, parent_id: Uuid, children: Vec<Tree>) {
+        self.tree.update(|tree| {
+            if let Some(parent) = find_node_mut(tree, parent_id) {
+                parent.has_children = !children.is_empty();
+                parent.children = children.clone();
+            }
+        });
+        if let Some(signal) = self
+            .children
+            .with_value(|signals| signals.get(&parent_id).copied())
+        {
+            signal.try_set(children);
+        }
+    }
+
+    /// Fetches the children of an unfolded branch.
+    fn load_children(self, parent_id: Uuid) {
+        let Some(source) = self.source.get_value() else {
+            return;
+        };
+        if self
+            .loading
+            .with_untracked(|loading| loading.contains(&parent_id))
+        {
+            return;
+        }
+        self.loading.update(|loading| {
+            loading.insert(parent_id);
+        });
+        leptos::task::spawn_local(async move {
+            match fetch_tree_children(&source.user_id, source.with_leafs, parent_id).await {
+                Ok(children) => self.attach(parent_id, children),
+                Err(e) => tracing::warn!("Failed to load tree children: {e}"),
+            }
+            self.loading.update(|loading| {
+                loading.remove(&parent_id);
+            });
+        });
+    }
+
+    /// Fetches the whole tree once, so a search also finds unloaded nodes.
+    fn load_all(self) {
+        let Some(source) = self.source.get_value() else {
+            return;
+        };
+        if self.complete.get_value() {
+            return;
+        }
+        self.complete.set_value(true);
+        leptos::task::spawn_local(async move {
+            match fetch_tree_data(&source.user_id, source.with_leafs).await {
+                Ok(tree) => {
+                    for (id, signal) in self.children.get_value() {
+                        if let Some(node) = find_node(&tree, id) {
+                            signal.try_set(node.children.clone());
+                        }
+                    }
+                    self.tree.set(tree);
+                    self.reveal_hits();
+                }
+                Err(e) => {
+                    self.complete.set_value(false);
+                    tracing::warn!("Failed to load tree: {e}");
+                }
+            }
+        });
+    }
+
+    fn order(self) -> Vec<VisibleNode> {
+        self.tree.with_untracked(|tree| {
+            visible_order(tree, &|id| self.is_open_untracked(id), &|id| {
+                self.is_shown_untracked(id)
+            })
+        })
+    }
+
+    fn focus(self, items: NodeRef<html::Div>, id: Uuid) {
+        self.focused.set(Some(id));
+        let item = items.get_untracked().and_then(|items| {
+            items
+                .query_selector(&format!("[data-node-id=\"{id}\"]"))
+                .ok()
+                .flatten()
+        });
+        if let Some(item) = item.and_then(|item| item.dyn_into::<web_sys::HtmlElement>().ok()) {
+            let _ = item.focus();
+        }
+    }
+
+    fn node_info(self, id: Uuid) -> Option<NodeInfo> {
+        self.tree.with_untracked(|tree| {
+            find_node(tree, id)
+                .filter(|node| node.access().is_some())
+                .map(Tree::node_info)
+        })
+    }
+}
+
+/// Node id of the tree item an event started in.
+fn event_node_id(target: Option<web_sys::EventTarget>, closest: bool) -> Option<Uuid> {
+    let element = target?.dyn_into::<web_sys::Element>().ok()?;
+    let item = if closest {
+        element.closest("[data-node-id]").ok().flatten()?
+    } else {
+        element
+    };
+    item.get_attribute("data-node-id")?.parse().ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn stored_open(key: &str) -> HashSet<Uuid> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(key).ok().flatten())
+        .and_then(|stored| serde_json::from_str(&stored).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn stored_open(_key: &str) -> HashSet<Uuid> {
+    HashSet::new()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn store_open(key: &str, open: &HashSet<Uuid>) {
+    if let Some(storage) =
+        web_sys::window().and_then(|window| window.local_storage().ok().flatten())
+        && let Ok(open) = serde_json::to_string(open)
+    {
+        let _ = storage.set_item(key, &open);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn store_open(_key: &str, _open: &HashSet<Uuid>) {}
 
 #[component]
 pub fn DetailsTreeRendererWithContext(
     tree: Vec<Tree>,
     on_node_click: impl Fn(NodeInfo) + 'static + Clone,
+    /// Offers creating, renaming, moving and deleting own branches.
+    #[prop(optional)]
+    editable: bool,
+    /// User whose unfolded branches are remembered across visits.
+    #[prop(optional, into)]
+    remember_for: Option<String>,
 ) -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let editor = editable.then(|| TreeEditor {
+        form: RwSignal::new(None),
+        name: RwSignal::new(String::new()),
+        dragged: RwSignal::new(None),
+        busy: RwSignal::new(false),
+        error: RwSignal::new(None),
+        sharing: RwSignal::new(None),
+        refresh: use_context::<TreeRefresh>(),
+        i18n,
+    });
+    // Own top-level nodes hang below the user's root, shared ones come last
+    let root_id = tree
+        .iter()
+        .find(|node| node.own)
+        .and_then(|node| node.parent_id);
+    let (own, shared): (Vec<Tree>, Vec<Tree>) = tree.iter().cloned().partition(|node| node.own);
+    let tree_view = TreeView::new(
+        tree,
+        remember_for.map(|user| format!("cx58-tree-open:{user}")),
+        use_context::<TreeSource>(),
+    );
+    let items = NodeRef::<html::Div>::new();
+
+    let focus_first = move || {
+        if let Some(first) = tree_view.order().first() {
+            tree_view.focus(items, first.id);
+        }
+    };
+    let on_filter_key = move |ev: KeyboardEvent| match ev.key().as_str() {
+        "Escape" => tree_view.search(String::new()),
+        "ArrowDown" => {
+            ev.prevent_default();
+            focus_first();
+        }
+        _ => {}
+    };
+    let on_activate = on_node_click.clone();
+    let on_key = move |ev: KeyboardEvent| {
+        // Keys pressed on the branch buttons keep their own meaning
+        if event_node_id(ev.target(), false).is_none() {
+            return;
+        }
+        let order = tree_view.order();
+        let Some(step) = tree_move(&order, tree_view.focused.get_untracked(), &ev.key()) else {
+            return;
+        };
+        ev.prevent_default();
+        match step {
+            TreeMove::Focus(id) => tree_view.focus(items, id),
+            TreeMove::Expand(id) => tree_view.set_open(id, true),
+            TreeMove::Collapse(id) => tree_view.set_open(id, false),
+            TreeMove::Activate(id) => {
+                if let Some(info) = tree_view.node_info(id) {
+                    on_activate(info);
+                }
+            }
+        }
+    };
+    let no_hits = move || {
+        tree_view
+            .filter
+            .with(|filter| filter.as_ref().is_some_and(|filter| filter.hits.is_empty()))
+    };
+
+    view! {
+        <div
+            class="obj-area tree"
+            on:dragover=move |ev: DragEvent| {
+                if let (Some(editor), Some(_)) = (editor, root_id)
+                    && editor.accepts(root_id)
+                {
+                    ev.prevent_default();
+                }
+            }
+            on:drop=move |ev: DragEvent| {
+                if let (Some(editor), Some(root_id)) = (editor, root_id)
+                    && editor.accepts(Some(root_id))
+                {
+                    ev.prevent_default();
+                    editor.drop_on(root_id);
+                }
+            }
+        >
+            {editor.map(|editor| view! { <BranchEditorForm editor=editor root_id=root_id /> })}
+            <input
+                type="search"
+                class="tree-filter"
+                placeholder=move || move_tr!("tree-filter").get()
+                aria-label=move || move_tr!("tree-filter").get()
+                prop:value=move || tree_view.query.get()
+                on:input=move |ev| tree_view.search(event_target_value(&ev))
+                on:keydown=on_filter_key
+            />
+            <div
+                class="tree-items"
+                role="tree"
+                aria-label=move || move_tr!("tree-label").get()
+                node_ref=items
+                // Until a node has focus the tree itself is the tab stop
+                tabindex=move || if tree_view.focused.get().is_none() { "0" } else { "-1" }
+                on:focus=move |_| focus_first()
+                on:focusin=move |ev: FocusEvent| {
+                    if let Some(id) = event_node_id(ev.target(), true) {
+                        tree_view.focused.set(Some(id));
+                    }
+                }
+                on:keydown=on_key
+            >
+                {own
+                    .into_iter()
+                    .map(|node| {
+                        view! {
+                            <DetailsTreeNodeWithContext
+                                node=node
+                                on_node_click=on_node_click.clone()
+                                editor=editor
+                                tree_view=tree_view
+                                level=1
+                            />
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+                {(!shared.is_empty())
+                    .then(|| {
+                        view! {
+                            <p class="tree-shared-heading">{move_tr!("tree-shared-with-me")}</p>
+                        }
+                    })}
+                {shared
+                    .into_iter()
+                    .map(|node| {
+                        view! {
+                            <DetailsTreeNodeWithContext
+                                node=node
+                                on_node_click=on_node_click.clone()
+                                editor=editor
+                                tree_view=tree_view
+                                level=1
+                            />
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </div>
+            <Show when=no_hits>
+                <p class="tree-filter-empty">{move_tr!("tree-filter-empty")}</p>
+            </Show>
+        </div>
+    }
+}
+
+/// New-object button and the form for naming a new or renamed branch.
+#[component]
+fn BranchEditorForm(editor: TreeEditor, root_id: Option<Uuid>) -> impl IntoView {
+    let i18n = editor.i18n;
+    let submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let Some(form) = editor.form.get_untracked() else {
+            return;
+        };
+        let Some(name) = branch_name(&editor.name.get_untracked()) else {
+            editor.error.set(Some(i18n.tr("branch-invalid-name")));
+            return;
+        };
+        editor.run(match form {
+            BranchForm::Create { parent_id } => BranchRequest::Create(
+                parent_id,
+                CreateBranch {
+                    name: name.clone(),
+                    title: Some(name),
+                },
+            ),
+            BranchForm::Rename { node_id } => BranchRequest::Update(
+                node_id,
+                UpdateBranch {
+                    name: Some(name.clone()),
+                    title: Some(name),
+                    parent_id: None,
+                },
+            ),
+        });
+    };
+
     view! {
-        <div class="obj-area tree">
-            {tree
-                .into_iter()
-                .map(|node| {
+        {root_id
+            .map(|parent_id| {
+                view! {
+                    <button
+                        type="button"
+                        class="tree-branch-new"
+                        on:click=move |_| {
+                            editor.open(BranchForm::Create { parent_id }, String::new())
+                        }
+                    >
+                        <i class="fas fa-plus"></i>
+                        <span>{move_tr!("tree-branch-new")}</span>
+                    </button>
+                }
+            })}
+        <Show when=move || editor.form.with(Option::is_some)>
+            <form class="tree-branch-form" on:submit=submit>
+                <input
+                    type="text"
+                    name="branch_name"
+                    maxlength=crate::branches::MAX_BRANCH_NAME_CHARS
+                    aria-label=move || move_tr!("tree-branch-name").get()
+                    placeholder=move || move_tr!("tree-branch-name").get()
+                    prop:value=move || editor.name.get()
+                    on:input=move |ev| editor.name.set(event_target_value(&ev))
+                    autofocus
+                />
+                <button type="submit" disabled=move || editor.busy.get()>
+                    {move_tr!("tree-branch-save")}
+                </button>
+                <button type="button" on:click=move |_| editor.form.set(None)>
+                    {move_tr!("tree-branch-cancel")}
+                </button>
+            </form>
+        </Show>
+        {move || {
+            editor.error.get().map(|error| view! { <p class="tree-branch-error">{error}</p> })
+        }}
+        {move || {
+            editor
+                .sharing
+                .get()
+                .map(|(node_id, name)| {
                     view! {
-                        <DetailsTreeNodeWithContext node=node on_node_click=on_node_click.clone() />
+                        <ShareDialog
+                            node_id=node_id
+                            name=name
+                            on_close=move |_| editor.sharing.set(None)
+                        />
                     }
                 })
-                .collect::<Vec<_>>()}
-        </div>
+        }}
+    }
+}
+
+/// Add, rename, share and delete buttons of an own branch.
+#[component]
+fn BranchActions(editor: TreeEditor, node_id: Uuid, name: String, empty: bool) -> impl IntoView {
+    let i18n = editor.i18n;
+    let stop = |ev: &MouseEvent| {
+        // Inside a summary a click would also fold the branch
+        ev.prevent_default();
+        ev.stop_propagation();
+    };
+    let rename_to = name.clone();
+    let share_name = name.clone();
+    view! {
+        <span class="tree-branch-actions">
+            <button
+                type="button"
+                title=move || move_tr!("tree-branch-add").get()
+                on:click=move |ev| {
+                    stop(&ev);
+                    editor.open(BranchForm::Create { parent_id: node_id }, String::new());
+                }
+            >
+                <i class="fas fa-plus"></i>
+            </button>
+            <button
+                type="button"
+                title=move || move_tr!("tree-branch-rename").get()
+                on:click=move |ev| {
+                    stop(&ev);
+                    editor.open(BranchForm::Rename { node_id }, rename_to.clone());
+                }
+            >
+                <i class="fas fa-pen"></i>
+            </button>
+            <button
+                type="button"
+                title=move || move_tr!("share-open").get()
+                on:click=move |ev| {
+                    stop(&ev);
+                    editor.sharing.set(Some((node_id, share_name.clone())));
+                }
+            >
+                <i class="fas fa-share-nodes"></i>
+            </button>
+            <button
+                type="button"
+                title=move || move_tr!("tree-branch-delete").get()
+                disabled=move || !empty || editor.busy.get()
+                on:click=move |ev| {
+                    stop(&ev);
+                    let question = i18n
+                        .tr_with_args("tree-branch-delete-confirm", &args!["name" => name.clone()]);
+                    let confirmed = web_sys::window()
+                        .and_then(|window| window.confirm_with_message(&question).ok())
+                        .unwrap_or(false);
+                    if confirmed {
+                        editor.run(BranchRequest::Delete(node_id));
+                    }
+                }
+            >
+                <i class="fas fa-trash"></i>
+            </button>
+        </span>
+    }
+}
+
+/// Node name with the part matching the search marked.
+#[component]
+fn NodeLabel(name: String, tree_view: TreeView) -> impl IntoView {
+    move || {
+        let range = tree_view.filter.with(|filter| {
+            filter
+                .as_ref()
+                .and_then(|filter| match_range(&name, &filter.query))
+        });
+        match range {
+            Some(range) => view! {
+                {name[..range.start].to_string()}
+                <mark>{name[range.clone()].to_string()}</mark>
+                {name[range.end..].to_string()}
+            }
+            .into_any(),
+            None => name.clone().into_any(),
+        }
     }
 }
 
@@ -28,16 +749,21 @@ pub fn DetailsTreeRendererWithContext(
 fn DetailsTreeNodeWithContext(
     node: Tree,
     on_node_click: impl Fn(NodeInfo) + 'static + Clone,
+    editor: Option<TreeEditor>,
+    tree_view: TreeView,
+    /// Depth in the rendered tree, 1 at the top.
+    level: usize,
 ) -> impl IntoView {
     let i18n = expect_context::<I18n>();
-    let has_children = !node.children.is_empty();
-    let is_leaf = node.node_type == NodeType::ImageLeaf;
+    let has_children = node.has_children;
     let is_own = node.own;
+    // Own nodes and shared ones can be picked, not the way to a share
+    let can_pick = node.access().is_some();
     let node_name = node
         .name
         .clone()
         .unwrap_or_else(|| i18n.tr("tree-node-unnamed"));
-    let children = node.children.clone();
+    let children = tree_view.children_of(&node);
 
     let icon_class = match node.node_type {
         NodeType::Root => "fas fa-building",
@@ -45,79 +771,175 @@ fn DetailsTreeNodeWithContext(
         NodeType::ImageLeaf => "fas fa-image",
     };
 
-    if is_leaf {
+    // Own branches of an editable tree can be changed and dragged onto each other
+    let editor = editor.filter(|_| is_own && node.node_type == NodeType::Branch);
+    let node_id = node.id;
+    let drop_over = RwSignal::new(false);
+    let dragged = DraggedBranch {
+        id: node.id,
+        parent_id: node.parent_id,
+        subtree: subtree_ids(&node),
+    };
+    let copy_link = (can_pick && node.node_type == NodeType::Branch).then(|| {
         view! {
-            <div class="leaf">
-                <span
-                    class="node-content"
-                    class:clickable=is_own
-                    on:click=move |e: MouseEvent| {
-                        if is_own {
-                            on_node_click(node.node_info());
-                            e.stop_propagation();
-                            e.prevent_default();
-                        }
+            <span class="tree-branch-actions">
+                <CopyLinkButton link=DeepLink::object(node_id) />
+            </span>
+        }
+    });
+    let actions = editor.map(|editor| {
+        view! {
+            <BranchActions
+                editor=editor
+                node_id=node_id
+                name=node_name.clone()
+                empty=!has_children
+            />
+        }
+    });
+    let drag_start = move |ev: DragEvent| {
+        if let Some(editor) = editor {
+            ev.stop_propagation();
+            if let Some(transfer) = ev.data_transfer() {
+                let _ = transfer.set_data("text/plain", &dragged.id.to_string());
+            }
+            editor.dragged.set(Some(dragged.clone()));
+        }
+    };
+    let drag_end = move |_: DragEvent| {
+        if let Some(editor) = editor {
+            editor.dragged.set(None);
+        }
+    };
+    let drag_over = move |ev: DragEvent| {
+        if let Some(editor) = editor
+            && editor.accepts(Some(node_id))
+        {
+            ev.prevent_default();
+            ev.stop_propagation();
+            drop_over.set(true);
+        }
+    };
+    let drag_leave = move |_: DragEvent| drop_over.set(false);
+    let drop = move |ev: DragEvent| {
+        drop_over.set(false);
+        if let Some(editor) = editor
+            && editor.accepts(Some(node_id))
+        {
+            ev.prevent_default();
+            ev.stop_propagation();
+            editor.drop_on(node_id);
+        }
+    };
+
+    let content = view! {
+        <span
+            class="node-content"
+            class:clickable=can_pick
+            class:drop-target=move || drop_over.get()
+            class:tree-hit=move || {
+                tree_view
+                    .filter
+                    .with(|filter| filter.as_ref().is_some_and(|filter| filter.hits.contains(&node_id)))
+            }
+            draggable=editor.map(|_| "true")
+            on:dragstart=drag_start
+            on:dragend=drag_end
+            on:dragover=drag_over
+            on:dragleave=drag_leave
+            on:drop=drop
+            on:click={
+                let on_node_click = on_node_click.clone();
+                move |e: MouseEvent| {
+                    if can_pick {
+                        on_node_click(node.node_info());
+                        e.stop_propagation();
+                        e.prevent_default();
                     }
-                >
-                    <i class=icon_class></i>
-                    {node_name.clone()}
-                </span>
-            </div>
+                }
+            }
+        >
+            <i class=icon_class></i>
+            <NodeLabel name=node_name tree_view=tree_view />
+        </span>
+    };
+    let tab_index = move || {
+        if tree_view.focused.get() == Some(node_id) {
+            "0"
+        } else {
+            "-1"
         }
-        .into_any()
-    } else if has_children {
+    };
+    let hidden = move || !tree_view.is_shown(node_id);
+
+    if has_children {
+        let is_open = move || tree_view.is_open(node_id);
+        Effect::new(move |_| {
+            if is_open() && children.with(Vec::is_empty) {
+                tree_view.load_children(node_id);
+            }
+        });
+        let loading = move || tree_view.loading.with(|loading| loading.contains(&node_id));
+        // Children re-render once loaded, which needs a thread-safe handle
+        let on_child_click = StoredValue::new_local(on_node_click);
         view! {
-            <details>
-                <summary>
-                    <span
-                        class="node-content"
-                        class:clickable=is_own
-                        on:click={
-                            let value = on_node_click.clone();
-                            move |e: MouseEvent| {
-                                if is_own {
-                                    value(node.node_info());
-                                    e.stop_propagation();
-                                    e.prevent_default();
-                                }
-                            }
-                        }
-                    >
-                        <i class=icon_class></i>
-                        {node_name.clone()}
-                    </span>
+            <details
+                role="treeitem"
+                data-node-id=node_id.to_string()
+                attr:aria-level=level.to_string()
+                aria-expanded=move || is_open().to_string()
+                tabindex=tab_index
+                class:tree-hidden=hidden
+                prop:open=is_open
+            >
+                <summary
+                    tabindex="-1"
+                    on:click=move |e: MouseEvent| {
+                        // Folding is tracked here rather than by the browser
+                        e.prevent_default();
+                        tree_view.set_open(node_id, !tree_view.is_open_untracked(node_id));
+                    }
+                >
+                    {content}
+                    {copy_link}
+                    {actions}
                 </summary>
-                {children
-                    .into_iter()
-                    .map(|child| {
-                        view! {
-                            <DetailsTreeNodeWithContext
-                                node=child
-                                on_node_click=on_node_click.clone()
-                            />
-                        }
-                    })
-                    .collect::<Vec<_>>()}
+                <div role="group" aria-busy=move || loading().to_string()>
+                    {move || {
+                        children
+                            .get()
+                            .into_iter()
+                            .map(|child| {
+                                view! {
+                                    <DetailsTreeNodeWithContext
+                                        node=child
+                                        on_node_click=on_child_click.get_value()
+                                        editor=editor
+                                        tree_view=tree_view
+                                        level=level + 1
+                                    />
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    }}
+                    {move || loading().then(|| view! { <i class="tree-loader"></i> })}
+                </div>
             </details>
         }
         .into_any()
     } else {
         view! {
-            <div class="leaf">
-                <span
-                    class="node-content"
-                    class:clickable=is_own
-                    on:click=move |e: MouseEvent| {
-                        if is_own {
-                            on_node_click(node.node_info());
-                            e.stop_propagation();
-                            e.prevent_default();
-                        }
-                    }
-                >
-                    <i class=icon_class></i>
-                    {node_name.clone()}
-                </span>
+            <div
+                class="leaf"
+                role="treeitem"
+                data-node-id=node_id.to_string()
+                attr:aria-level=level.to_string()
+                tabindex=tab_index
+                class:tree-hidden=hidden
+            >
+                {content}
+                {copy_link}
+                {actions}
             </div>
         }
         .into_any()
@@ -114,13 +114,13 @@ pub fn SideBody(is_admin: bool) -> impl IntoView {
         </a>
         <hr />
 
+        <a href="/profile">
+            <i class="fas fa-user"></i>
+            <span>{move || move_tr!("profile")}</span>
+        </a>
         {if is_admin {
             view! {
-                <a href="/profile">
-                    <i class="fas fa-user"></i>
-                    <span>{move || move_tr!("profile")}</span>
-                </a>
-                <a href="#">
+                <a href="/users">
                     <i class="fas fa-users"></i>
                     <span>{move || move_tr!("users")}</span>
                 </a>
@@ -166,6 +166,7 @@ fn Objects() -> impl IntoView {
         .get_untracked()
         .email()
         .unwrap_or("mock".to_string());
+    let remember_for = email.clone();
 
     view! {
         <ErrorBoundary fallback=|errors| {
@@ -184,6 +185,8 @@ fn Objects() -> impl IntoView {
                     view! {
                         <DetailsTreeRendererWithContext
                             tree=tree
+                            editable=true
+                            remember_for=remember_for.clone()
                             on_node_click=move |node_info| {
                                 tracing::info!("Node clicked: {:?}", node_info.name);
                                 ctx.set_parent(node_info.clone())
@@ -1,5 +1,9 @@
+use crate::media::{MediaVariant, media_url};
+use crate::sharing::{NodeAccess, SharePermission};
 use js_sys::Date;
-use leptos::prelude::{ClassAttribute, Get, IntoAny, Suspense};
+use leptos::prelude::{
+    ClassAttribute, Get, IntoAny, RwSignal, Suspense, Track, Update, provide_context,
+};
 use leptos::prelude::{ElementChild, LocalResource};
 use leptos::*;
 use serde::{Deserialize, Serialize};
@@ -53,6 +57,35 @@ pub struct TreeNode {
     pub updated_at: String,
     pub depth: i32,
     pub own: bool,
+    /// Set by the tree proxy; children of a node may be loaded later.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub has_children: bool,
+    /// Granted by another user's share, on every node of the shared branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permission: Option<SharePermission>,
+}
+
+/// Bare tree node for tests of the tree, branch and sharing proxies.
+#[cfg(all(test, feature = "ssr"))]
+pub(crate) fn test_node(
+    id: u128,
+    parent: Option<u128>,
+    node_type: NodeType,
+    own: bool,
+) -> TreeNode {
+    TreeNode {
+        id: Uuid::from_u128(id),
+        parent_id: parent.map(Uuid::from_u128),
+        node_type,
+        name: None,
+        data: serde_json::Value::Null,
+        path: String::new(),
+        updated_at: String::new(),
+        depth: 0,
+        own,
+        has_children: false,
+        permission: None,
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -67,6 +100,9 @@ pub struct Tree {
     pub updated_at: String,
     pub depth: i32,
     pub own: bool,
+    /// Children exist, though they may not be loaded yet.
+    pub has_children: bool,
+    pub permission: Option<SharePermission>,
     pub children: Vec<Tree>,
 }
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -83,10 +119,13 @@ pub struct NodeWithLeaf {
 
 impl From<NodeWithLeaf> for NodeInfo {
     fn from(node: NodeWithLeaf) -> Self {
+        let media = |variant: MediaVariant, image: &ImageData| {
+            variant.source(image).map(|_| media_url(node.id, variant))
+        };
         let (thumbnail_url, full_url, mime_type, size) = match &node.data {
             NodeData::Image(image) => (
-                image.thumbnail_url.clone().or_else(|| image.url.clone()),
-                image.url.clone(),
+                media(MediaVariant::Thumbnail, image),
+                media(MediaVariant::Full, image),
                 image.mime_type.clone(),
                 image.size,
             ),
@@ -102,6 +141,7 @@ impl From<NodeWithLeaf> for NodeInfo {
             full_url,
             mime_type,
             size,
+            access: NodeAccess::default(),
         }
     }
 }
@@ -121,17 +161,16 @@ pub struct NodeInfo {
     pub mime_type: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub size: Option<u64>,
+    #[serde(default)]
+    pub access: NodeAccess,
 }
 
-impl NodeInfo {
-    pub fn with_media_proxy(mut self, media_proxy: &str) -> Self {
-        self.thumbnail_url = self.thumbnail_url.map(|url| proxy_media(media_proxy, &url));
-        self.full_url = self.full_url.map(|url| proxy_media(media_proxy, &url));
-        self
+impl Tree {
+    /// `None` for nodes of other users that are only the way to a share.
+    pub fn access(&self) -> Option<NodeAccess> {
+        NodeAccess::new(self.own, self.permission)
     }
-}
 
-impl Tree {
     pub fn node_info(&self) -> NodeInfo {
         NodeInfo {
             id: self.id,
@@ -143,59 +182,11 @@ impl Tree {
             full_url: None,
             mime_type: None,
             size: None,
+            access: self.access().unwrap_or(NodeAccess::Read),
         }
     }
 }
 
-pub(crate) fn proxy_media(rule: &str, value: &str) -> String {
-    let mut parts = rule.split(',');
-    let (Some(old_value), Some(new_value), None) = (parts.next(), parts.next(), parts.next())
-    else {
-        return value.to_string();
-    };
-    let old_value = old_value.trim();
-    let new_value = new_value.trim();
-    if old_value.is_empty() || new_value.is_empty() {
-        return value.to_string();
-    }
-    value
-        .strip_prefix(old_value)
-        .map(|suffix| format!("{new_value}{suffix}"))
-        .unwrap_or_else(|| value.to_string())
-}
-
-#[cfg(test)]
-mod media_proxy_tests {
-    use super::proxy_media;
-
-    #[test]
-    fn proxy_media_rewrites_valid_two_part_rule() {
-        assert_eq!(
-            proxy_media(
-                "http://agent:3000,https://app.example.test/media",
-                "http://agent:3000/report.jpg",
-            ),
-            "https://app.example.test/media/report.jpg"
-        );
-    }
-
-    #[test]
-    fn proxy_media_preserves_value_for_malformed_rule() {
-        let value = "http://agent:3000/report.jpg";
-        assert_eq!(proxy_media("", value), value);
-        assert_eq!(proxy_media("from,to,extra", value), value);
-    }
-
-    #[test]
-    fn proxy_media_only_rewrites_the_url_prefix() {
-        let rule = "http://agent:3000,https://app.example.test/media";
-        let already_proxied = "https://app.example.test/media/report.jpg";
-        let embedded_origin = "https://example.test/?next=http://agent:3000/report.jpg";
-
-        assert_eq!(proxy_media(rule, already_proxied), already_proxied);
-        assert_eq!(proxy_media(rule, embedded_origin), embedded_origin);
-    }
-}
 pub fn parse_dt_or_default_ms(s: &str) -> i64 {
     // default: Unix epoch
     let default = 0.0;
@@ -237,21 +228,30 @@ fn parse_node_data(node_type: NodeType, raw_data: &serde_json::Value) -> NodeDat
 }
 
 /// Converts a flat list of TreeNodes into a hierarchical tree structure
-/// Root nodes are discarded, and their children become top-level nodes
+/// Root nodes are discarded, and their children become top-level nodes, as do
+/// branches shared by other users
 pub fn build_tree(nodes: Vec<TreeNode>) -> Vec<Tree> {
-    // Create a HashMap for quick lookup by id
-    let mut node_map: HashMap<Uuid, TreeNode> =
-        nodes.into_iter().map(|node| (node.id, node)).collect();
-
-    // Find and remove the root node(s)
-    let root_ids: Vec<Uuid> = node_map
-        .values()
+    let root_ids: Vec<Uuid> = nodes
+        .iter()
         .filter(|node| node.node_type == NodeType::Root)
         .map(|node| node.id)
         .collect();
+    build_forest(nodes, &root_ids)
+}
+
+/// Subtrees below `parent_id`, as loaded when a branch is unfolded
+pub fn build_children(nodes: Vec<TreeNode>, parent_id: Uuid) -> Vec<Tree> {
+    build_forest(nodes, &[parent_id])
+}
+
+/// Trees hanging below `root_ids`, which are left out themselves
+fn build_forest(nodes: Vec<TreeNode>, root_ids: &[Uuid]) -> Vec<Tree> {
+    // Create a HashMap for quick lookup by id
+    let mut node_map: HashMap<Uuid, TreeNode> =
+        nodes.into_iter().map(|node| (node.id, node)).collect();
 
     // Remove root nodes from the map
-    for root_id in &root_ids {
+    for root_id in root_ids {
         node_map.remove(root_id);
     }
 
@@ -297,45 +297,104 @@ pub fn build_tree(nodes: Vec<TreeNode>) -> Vec<Tree> {
             updated_at: node.updated_at.clone(),
             depth: node.depth,
             own: node.own,
+            has_children: node.has_children || !children.is_empty(),
+            permission: node.permission,
             children,
         }
     }
 
-    // Find all nodes that were children of root nodes
-    let top_level_ids: Vec<Uuid> = root_ids
-        .iter()
-        .filter_map(|root_id| children_map.get(&Some(*root_id)))
-        .flatten()
-        .copied()
+    // Children of the roots, and shared branches whose parents belong to
+    // other users
+    let top_level_ids: Vec<Uuid> = node_map
+        .values()
+        .filter(|node| {
+            node.parent_id
+                .is_none_or(|parent_id| !node_map.contains_key(&parent_id))
+        })
+        .map(|node| node.id)
         .collect();
 
-    // Build trees for each top-level node
+    // Build trees for each top-level node, own ones before shared ones
     let mut trees: Vec<Tree> = top_level_ids
         .into_iter()
         .map(|node_id| build_subtree(node_id, &node_map, &children_map))
         .collect();
-    trees.sort_by(compare_tree_name);
+    trees.sort_by(|a, b| b.own.cmp(&a.own).then_with(|| compare_tree_name(a, b)));
     trees
 }
 
+/// Node with `id` anywhere in `nodes`.
+pub fn find_node(nodes: &[Tree], id: Uuid) -> Option<&Tree> {
+    nodes.iter().find_map(|node| {
+        (node.id == id)
+            .then_some(node)
+            .or_else(|| find_node(&node.children, id))
+    })
+}
+
 fn compare_tree_name(a: &Tree, b: &Tree) -> std::cmp::Ordering {
     let a_name = a.name.as_deref().unwrap_or("").to_lowercase();
     let b_name = b.name.as_deref().unwrap_or("").to_lowercase();
     a_name.cmp(&b_name).then_with(|| a.id.cmp(&b.id))
 }
 
+/// Part of a tree requested from the tree proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TreeLevel {
+    Whole,
+    Top,
+    Children(Uuid),
+}
+
 /// Fetch tree data from API and convert to hierarchical structure
 pub async fn fetch_tree_data(user_id: &str, with_leafs: bool) -> Result<Vec<Tree>, String> {
+    Ok(build_tree(
+        fetch_tree_nodes(user_id, with_leafs, TreeLevel::Whole).await?,
+    ))
+}
+
+/// Access of `id` in the user's tree, `None` if the user cannot pick it.
+pub async fn node_access(user_id: &str, id: Uuid) -> Result<Option<NodeAccess>, String> {
+    let tree = fetch_tree_data(user_id, false).await?;
+    Ok(find_node(&tree, id).and_then(Tree::access))
+}
+
+/// Top-level nodes only; deeper branches are fetched when they are unfolded
+pub async fn fetch_tree_top(user_id: &str, with_leafs: bool) -> Result<Vec<Tree>, String> {
+    Ok(build_tree(
+        fetch_tree_nodes(user_id, with_leafs, TreeLevel::Top).await?,
+    ))
+}
+
+/// Direct children of a branch, each with its `has_children` flag
+pub async fn fetch_tree_children(
+    user_id: &str,
+    with_leafs: bool,
+    parent_id: Uuid,
+) -> Result<Vec<Tree>, String> {
+    Ok(build_children(
+        fetch_tree_nodes(user_id, with_leafs, TreeLevel::Children(parent_id)).await?,
+        parent_id,
+    ))
+}
+
+/// The proxy answers with an `ETag`, so the browser revalidates instead of
+/// downloading an unchanged tree again
+async fn fetch_tree_nodes(
+    user_id: &str,
+    with_leafs: bool,
+    level: TreeLevel,
+) -> Result<Vec<TreeNode>, String> {
     // Get window object
     let window = web_sys::window().ok_or_else(|| "No window available".to_string())?;
 
     // Build URL
-    let url = format!("/api/proxy/tree/{}", user_id);
-    let url = if with_leafs {
-        format!("{}?with_leafs=true", url)
-    } else {
-        url
-    };
+    let mut url = format!("/api/proxy/tree/{}?with_leafs={}", user_id, with_leafs);
+    match level {
+        TreeLevel::Whole => {}
+        TreeLevel::Top => url.push_str("&lazy=true"),
+        TreeLevel::Children(parent_id) => url.push_str(&format!("&parent={parent_id}")),
+    }
 
     // Create request options
     let opts = RequestInit::new();
@@ -376,15 +435,27 @@ pub async fn fetch_tree_data(user_id: &str, with_leafs: bool) -> Result<Vec<Tree
     .map_err(|e| format!("Failed to parse JSON: {:?}", e))?;
 
     // Deserialize to Vec<TreeNode>
-    let nodes: Vec<TreeNode> = serde_wasm_bindgen::from_value(json).map_err(|e| {
+    serde_wasm_bindgen::from_value(json).map_err(|e| {
         logging::error!("Deserialize error: {:?}", e);
         format!("Failed to deserialize: {:?}", e)
-    })?;
+    })
+}
 
-    // Convert flat nodes to hierarchical tree
-    let tree = build_tree(nodes);
+/// Reloads the tree of the enclosing `TreeViewerResource`.
+#[derive(Clone, Copy)]
+pub struct TreeRefresh(RwSignal<usize>);
 
-    Ok(tree)
+impl TreeRefresh {
+    pub fn refresh(self) {
+        self.0.update(|version| *version += 1);
+    }
+}
+
+/// Where the enclosing `TreeViewerResource` loads further nodes from.
+#[derive(Debug, Clone)]
+pub struct TreeSource {
+    pub user_id: String,
+    pub with_leafs: bool,
 }
 
 /// TreeViewer component with customizable renderer
@@ -400,13 +471,20 @@ where
     IV: IntoView,
 {
     tracing::info!("TreeViewerResource created for user: {}", user_id);
+    let refresh = TreeRefresh(RwSignal::new(0));
+    provide_context(refresh);
+    provide_context(TreeSource {
+        user_id: user_id.clone(),
+        with_leafs,
+    });
     // Use LocalResource instead of Resource for non-Send futures
     let tree_resource = LocalResource::new(move || {
         tracing::info!("LocalResource fetcher called");
+        refresh.0.track();
         let user_id = user_id.clone();
         async move {
-            tracing::info!("Starting fetch_tree_data for: {}", user_id);
-            fetch_tree_data(&user_id, with_leafs).await
+            tracing::info!("Starting fetch_tree_top for: {}", user_id);
+            fetch_tree_top(&user_id, with_leafs).await
         }
     });
     view! {
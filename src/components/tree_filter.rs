@@ -0,0 +1,299 @@
+use crate::components::tree::Tree;
+use std::collections::HashSet;
+use std::ops::Range;
+use unicode_normalization::char::{decompose_canonical, is_combining_mark};
+use uuid::Uuid;
+
+/// Case- and diacritic-insensitive form of `text` used for matching.
+pub fn fold(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        fold_char(c, &mut folded);
+    }
+    folded
+}
+
+fn fold_char(c: char, folded: &mut String) {
+    decompose_canonical(c, |part| {
+        if is_combining_mark(part) {
+            return;
+        }
+        for lower in part.to_lowercase() {
+            if lower == 'ß' {
+                folded.push_str("ss");
+            } else {
+                folded.push(lower);
+            }
+        }
+    });
+}
+
+/// Byte range of the first match of the folded `query` in `text`.
+pub fn match_range(text: &str, query: &str) -> Option<Range<usize>> {
+    if query.is_empty() {
+        return None;
+    }
+    let mut folded = String::with_capacity(text.len());
+    // Start in `text` of the character each folded byte comes from
+    let mut origin = Vec::with_capacity(text.len());
+    for (start, c) in text.char_indices() {
+        let before = folded.len();
+        fold_char(c, &mut folded);
+        origin.resize(origin.len() + folded.len() - before, start);
+    }
+    let at = folded.find(query)?;
+    let last = origin[at + query.len() - 1];
+    let end = last + text[last..].chars().next().map_or(0, char::len_utf8);
+    Some(origin[at]..end)
+}
+
+/// Nodes left by a search: hits, their ancestors and everything below a hit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeFilter {
+    /// Folded query the names are highlighted with.
+    pub query: String,
+    pub hits: HashSet<Uuid>,
+    pub visible: HashSet<Uuid>,
+    /// Ancestors of hits, opened so the hits can be seen.
+    pub expanded: HashSet<Uuid>,
+}
+
+impl TreeFilter {
+    /// Matches node names and paths, `None` for a blank query.
+    pub fn new(tree: &[Tree], query: &str) -> Option<Self> {
+        let query = fold(query.trim());
+        if query.is_empty() {
+            return None;
+        }
+        let mut filter = Self {
+            query,
+            hits: HashSet::new(),
+            visible: HashSet::new(),
+            expanded: HashSet::new(),
+        };
+        for node in tree {
+            filter.walk(node);
+        }
+        Some(filter)
+    }
+
+    /// Whether the subtree of `node` holds a hit.
+    fn walk(&mut self, node: &Tree) -> bool {
+        let hit = [node.name.as_deref().unwrap_or_default(), &node.path]
+            .iter()
+            .any(|text| fold(text).contains(&self.query));
+        if hit {
+            self.hits.insert(node.id);
+            self.show_all(node);
+        }
+        let mut below = false;
+        for child in &node.children {
+            below |= self.walk(child);
+        }
+        if below {
+            self.expanded.insert(node.id);
+            self.visible.insert(node.id);
+        }
+        hit || below
+    }
+
+    fn show_all(&mut self, node: &Tree) {
+        self.visible.insert(node.id);
+        for child in &node.children {
+            self.show_all(child);
+        }
+    }
+}
+
+/// A node reachable with the keyboard, in display order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VisibleNode {
+    pub id: Uuid,
+    /// Parent inside the rendered tree, `None` at the top level.
+    pub parent: Option<Uuid>,
+    pub expandable: bool,
+    pub expanded: bool,
+}
+
+/// Shown nodes top to bottom, skipping folded and filtered out subtrees.
+pub fn visible_order(
+    tree: &[Tree],
+    expanded: &impl Fn(Uuid) -> bool,
+    visible: &impl Fn(Uuid) -> bool,
+) -> Vec<VisibleNode> {
+    fn walk(
+        nodes: &[Tree],
+        parent: Option<Uuid>,
+        expanded: &impl Fn(Uuid) -> bool,
+        visible: &impl Fn(Uuid) -> bool,
+        order: &mut Vec<VisibleNode>,
+    ) {
+        for node in nodes.iter().filter(|node| visible(node.id)) {
+            let expandable = node.has_children;
+            let is_expanded = expandable && expanded(node.id);
+            order.push(VisibleNode {
+                id: node.id,
+                parent,
+                expandable,
+                expanded: is_expanded,
+            });
+            if is_expanded {
+                walk(&node.children, Some(node.id), expanded, visible, order);
+            }
+        }
+    }
+    let mut order = Vec::new();
+    walk(tree, None, expanded, visible, &mut order);
+    order
+}
+
+/// What a key pressed in the tree does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeMove {
+    Focus(Uuid),
+    Expand(Uuid),
+    Collapse(Uuid),
+    /// Makes the node the chat context.
+    Activate(Uuid),
+}
+
+/// Follows the WAI-ARIA tree pattern for `key` (a `KeyboardEvent.key`).
+pub fn tree_move(order: &[VisibleNode], current: Option<Uuid>, key: &str) -> Option<TreeMove> {
+    const KEYS: [&str; 7] = [
+        "ArrowDown",
+        "ArrowUp",
+        "ArrowRight",
+        "ArrowLeft",
+        "Home",
+        "End",
+        "Enter",
+    ];
+    if !KEYS.contains(&key) {
+        return None;
+    }
+    let first = order.first()?;
+    let Some(index) = current.and_then(|id| order.iter().position(|node| node.id == id)) else {
+        return Some(TreeMove::Focus(first.id));
+    };
+    let node = order[index];
+    match key {
+        "ArrowDown" => order.get(index + 1).map(|next| TreeMove::Focus(next.id)),
+        "ArrowUp" => index
+            .checked_sub(1)
+            .map(|previous| TreeMove::Focus(order[previous].id)),
+        "Home" => Some(TreeMove::Focus(first.id)),
+        "End" => order.last().map(|last| TreeMove::Focus(last.id)),
+        "ArrowRight" if node.expanded => order
+            .get(index + 1)
+            .filter(|child| child.parent == Some(node.id))
+            .map(|child| TreeMove::Focus(child.id)),
+        "ArrowRight" if node.expandable => Some(TreeMove::Expand(node.id)),
+        "ArrowLeft" if node.expanded => Some(TreeMove::Collapse(node.id)),
+        "ArrowLeft" => node.parent.map(TreeMove::Focus),
+        "Enter" => Some(TreeMove::Activate(node.id)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::tree::{NodeData, NodeType};
+
+    fn node(name: &str, path: &str, children: Vec<Tree>) -> Tree {
+        Tree {
+            id: Uuid::now_v7(),
+            parent_id: None,
+            node_type: NodeType::Branch,
+            name: Some(name.to_string()),
+            data: NodeData::Empty,
+            raw_data: serde_json::Value::Null,
+            path: path.to_string(),
+            updated_at: String::new(),
+            depth: 0,
+            own: true,
+            has_children: !children.is_empty(),
+            permission: None,
+            children,
+        }
+    }
+
+    #[test]
+    fn search_ignores_case_and_diacritics_and_opens_ancestors() {
+        assert_eq!(fold("Straße Ümlaut"), "strasse umlaut");
+        assert_eq!(match_range("Große Brücke", "brucke"), Some(7..14));
+        assert_eq!(match_range("Große Brücke", "gross"), Some(0..5));
+
+        let bridge = node("Brücke", "site.bridge", vec![node("Pier 1", "p1", vec![])]);
+        let (bridge_id, pier_id) = (bridge.id, bridge.children[0].id);
+        let site = node(
+            "Site",
+            "site",
+            vec![bridge, node("Tower", "site.tower", vec![])],
+        );
+        let site_id = site.id;
+        let tree = vec![site];
+
+        let filter = TreeFilter::new(&tree, " BRUCKE ").unwrap();
+        assert_eq!(filter.hits, HashSet::from([bridge_id]));
+        assert_eq!(filter.visible, HashSet::from([site_id, bridge_id, pier_id]));
+        assert_eq!(filter.expanded, HashSet::from([site_id]));
+        assert_eq!(TreeFilter::new(&tree, "tower").unwrap().hits.len(), 1);
+        assert!(TreeFilter::new(&tree, "  ").is_none());
+    }
+
+    #[test]
+    fn keys_walk_the_shown_nodes() {
+        let bridge = node("Bridge", "", vec![node("Pier", "", vec![])]);
+        let (bridge_id, pier_id) = (bridge.id, bridge.children[0].id);
+        let tower = node("Tower", "", vec![]);
+        let tower_id = tower.id;
+        let tree = vec![bridge, tower];
+        let shown = |_| true;
+
+        let folded = visible_order(&tree, &|_| false, &shown);
+        assert_eq!(folded.len(), 2);
+        assert_eq!(
+            tree_move(&folded, None, "ArrowDown"),
+            Some(TreeMove::Focus(bridge_id))
+        );
+        assert_eq!(tree_move(&folded, None, "Tab"), None);
+        assert_eq!(
+            tree_move(&folded, Some(bridge_id), "ArrowRight"),
+            Some(TreeMove::Expand(bridge_id))
+        );
+        assert_eq!(
+            tree_move(&folded, Some(bridge_id), "ArrowDown"),
+            Some(TreeMove::Focus(tower_id))
+        );
+        assert_eq!(tree_move(&folded, Some(bridge_id), "ArrowUp"), None);
+
+        let open = visible_order(&tree, &|id| id == bridge_id, &shown);
+        assert_eq!(
+            tree_move(&open, Some(bridge_id), "ArrowRight"),
+            Some(TreeMove::Focus(pier_id))
+        );
+        assert_eq!(
+            tree_move(&open, Some(pier_id), "ArrowLeft"),
+            Some(TreeMove::Focus(bridge_id))
+        );
+        assert_eq!(
+            tree_move(&open, Some(bridge_id), "ArrowLeft"),
+            Some(TreeMove::Collapse(bridge_id))
+        );
+        assert_eq!(
+            tree_move(&open, Some(pier_id), "End"),
+            Some(TreeMove::Focus(tower_id))
+        );
+        assert_eq!(
+            tree_move(&open, Some(pier_id), "Enter"),
+            Some(TreeMove::Activate(pier_id))
+        );
+
+        let filtered = visible_order(&tree, &|_| true, &|id| id != bridge_id);
+        assert_eq!(
+            filtered.iter().map(|node| node.id).collect::<Vec<_>>(),
+            [tower_id]
+        );
+    }
+}
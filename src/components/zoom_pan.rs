@@ -0,0 +1,201 @@
+use leptos::ev::{PointerEvent, WheelEvent};
+use leptos::prelude::*;
+use leptos::wasm_bindgen::JsCast;
+
+const MIN_SCALE: f64 = 1.0;
+const MAX_SCALE: f64 = 8.0;
+pub const ZOOM_STEP: f64 = 1.25;
+
+/// Zoom and pan of an image; the origin is the top-left corner of its stage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewTransform {
+    pub scale: f64,
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Default for ViewTransform {
+    fn default() -> Self {
+        Self {
+            scale: MIN_SCALE,
+            x: 0.0,
+            y: 0.0,
+        }
+    }
+}
+
+impl ViewTransform {
+    /// Zooms by `factor` keeping the point at `(cx, cy)` of the stage in place.
+    pub fn zoom_at(self, factor: f64, cx: f64, cy: f64) -> Self {
+        let scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        if scale == MIN_SCALE {
+            return Self::default();
+        }
+        let ratio = scale / self.scale;
+        Self {
+            scale,
+            x: cx - (cx - self.x) * ratio,
+            y: cy - (cy - self.y) * ratio,
+        }
+    }
+
+    /// Moves the image, only once it is zoomed in.
+    pub fn pan(self, dx: f64, dy: f64) -> Self {
+        if self.scale == MIN_SCALE {
+            return self;
+        }
+        Self {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..self
+        }
+    }
+
+    pub fn is_zoomed(self) -> bool {
+        self.scale > MIN_SCALE
+    }
+
+    pub fn css(self) -> String {
+        format!(
+            "translate({}px, {}px) scale({})",
+            self.x, self.y, self.scale
+        )
+    }
+}
+
+/// Two fingers on the stage, as `(x, y)` relative to it.
+fn pinch(a: (f64, f64), b: (f64, f64)) -> (f64, (f64, f64)) {
+    let distance = (a.0 - b.0).hypot(a.1 - b.1);
+    (distance, ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0))
+}
+
+/// Wheel, drag and pinch handling for one transform, shared by every stage
+/// that shows it.
+#[derive(Clone, Copy)]
+pub struct ZoomPan {
+    pub transform: RwSignal<ViewTransform>,
+    /// Pointers down on a stage with their last position.
+    pointers: StoredValue<Vec<(i32, (f64, f64))>>,
+}
+
+impl ZoomPan {
+    pub fn new() -> Self {
+        Self {
+            transform: RwSignal::new(ViewTransform::default()),
+            pointers: StoredValue::new(Vec::new()),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.transform.set(ViewTransform::default());
+    }
+
+    /// Zooms around the top-left corner, for buttons and keys.
+    pub fn zoom(&self, factor: f64) {
+        self.transform
+            .update(|view| *view = view.zoom_at(factor, 0.0, 0.0));
+    }
+
+    fn stage_point(ev: &web_sys::MouseEvent) -> (f64, f64) {
+        ev.current_target()
+            .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+            .map(|stage| {
+                let rect = stage.get_bounding_client_rect();
+                (
+                    ev.client_x() as f64 - rect.left(),
+                    ev.client_y() as f64 - rect.top(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn on_wheel(&self, ev: WheelEvent) {
+        ev.prevent_default();
+        let factor = if ev.delta_y() < 0.0 {
+            ZOOM_STEP
+        } else {
+            1.0 / ZOOM_STEP
+        };
+        let (cx, cy) = Self::stage_point(&ev);
+        self.transform
+            .update(|view| *view = view.zoom_at(factor, cx, cy));
+    }
+
+    pub fn on_pointer_down(&self, ev: PointerEvent) {
+        if let Some(stage) = ev
+            .current_target()
+            .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+        {
+            let _ = stage.set_pointer_capture(ev.pointer_id());
+        }
+        let point = Self::stage_point(&ev);
+        self.pointers.update_value(|pointers| {
+            pointers.retain(|(id, _)| *id != ev.pointer_id());
+            pointers.push((ev.pointer_id(), point));
+        });
+    }
+
+    /// One pointer pans, two pinch-zoom around their middle.
+    pub fn on_pointer_move(&self, ev: PointerEvent) {
+        let point = Self::stage_point(&ev);
+        let before = self.pointers.get_value();
+        if !before.iter().any(|(id, _)| *id == ev.pointer_id()) {
+            return;
+        }
+        self.pointers.update_value(|pointers| {
+            for (id, position) in pointers.iter_mut() {
+                if *id == ev.pointer_id() {
+                    *position = point;
+                }
+            }
+        });
+        let after = self.pointers.get_value();
+        match (before.as_slice(), after.as_slice()) {
+            ([(_, old)], [_]) => {
+                self.transform
+                    .update(|view| *view = view.pan(point.0 - old.0, point.1 - old.1));
+            }
+            ([(_, a0), (_, b0)], [(_, a1), (_, b1)]) => {
+                let (old_distance, old_middle) = pinch(*a0, *b0);
+                let (distance, middle) = pinch(*a1, *b1);
+                if old_distance > 0.0 {
+                    self.transform.update(|view| {
+                        *view = view
+                            .zoom_at(distance / old_distance, old_middle.0, old_middle.1)
+                            .pan(middle.0 - old_middle.0, middle.1 - old_middle.1);
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn on_pointer_up(&self, ev: PointerEvent) {
+        self.pointers
+            .update_value(|pointers| pointers.retain(|(id, _)| *id != ev.pointer_id()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_keeps_the_point_under_the_cursor() {
+        let view = ViewTransform::default().zoom_at(2.0, 100.0, 50.0);
+        assert_eq!((view.scale, view.x, view.y), (2.0, -100.0, -50.0));
+        // The stage point (100, 50) still shows image point (100, 50)
+        assert_eq!((100.0 - view.x) / view.scale, 100.0);
+
+        let panned = view.pan(10.0, -5.0);
+        assert_eq!((panned.x, panned.y), (-90.0, -55.0));
+        assert_eq!(
+            ViewTransform::default().pan(10.0, 10.0),
+            ViewTransform::default()
+        );
+
+        assert_eq!(view.zoom_at(100.0, 0.0, 0.0).scale, MAX_SCALE);
+        assert_eq!(panned.zoom_at(0.1, 0.0, 0.0), ViewTransform::default());
+        assert_eq!(pinch((0.0, 0.0), (30.0, 40.0)), (50.0, (15.0, 20.0)));
+    }
+}
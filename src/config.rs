@@ -14,8 +14,15 @@ pub struct AppConfig {
     pub cookie_config: CookieConfig,
     pub trust_data_list: String,
     pub trust_connect_list: String,
-    pub media_proxy: String,
     pub chat_config: ChatConfig,
+    pub upload_config: UploadConfig,
+    pub api_tokens_path: String,
+    pub audit_log_path: String,
+    /// Objects served under `/media`
+    pub media_cache_dir: String,
+    pub media_cache_max_bytes: u64,
+    /// Proxies allowed to set `X-Forwarded-For`, comma separated addresses or CIDR ranges
+    pub trusted_proxies: String,
     pub is_prod: bool,
 }
 
@@ -38,6 +45,48 @@ impl Default for ChatConfig {
         }
     }
 }
+/// EXIF handling for uploaded images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExifPolicy {
+    /// Forward metadata unchanged.
+    Keep,
+    /// Remove GPS location, keep the rest.
+    StripGps,
+    /// Remove everything except the orientation.
+    Strip,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UploadConfig {
+    pub max_bytes: usize,
+    pub max_pixels: u64,
+    pub exif_policy: ExifPolicy,
+    /// Size limit of resumable uploads, which are staged on disk.
+    pub max_staged_bytes: u64,
+    /// Declared sizes of all unfinished resumable uploads together.
+    pub max_staging_total_bytes: u64,
+    /// Largest part accepted by a resumable upload.
+    pub chunk_bytes: usize,
+    pub staging_dir: String,
+    /// Stream uploads to the agent with a trailing signature instead of buffering
+    /// them to sign up front. Needs an agent that verifies `TRAILER_SIGNATURE`.
+    pub trailer_signature: bool,
+}
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 20 * 1024 * 1024,
+            max_pixels: 50_000_000,
+            exif_policy: ExifPolicy::StripGps,
+            max_staged_bytes: 512 * 1024 * 1024,
+            max_staging_total_bytes: 4 * 1024 * 1024 * 1024,
+            chunk_bytes: 8 * 1024 * 1024,
+            staging_dir: "data/uploads".to_string(),
+            trailer_signature: false,
+        }
+    }
+}
 impl AppConfig {
     pub fn from_env() -> Result<Self, env::VarError> {
         // Determine environment (DEV/PROD)
@@ -110,6 +159,47 @@ impl AppConfig {
             chat_config.max_chat_tokens = parsed;
         }
 
+        let mut upload_config = UploadConfig::default();
+        if let Ok(v) = env::var("UPLOAD_MAX_BYTES")
+            && let Ok(parsed) = v.parse::<usize>()
+        {
+            upload_config.max_bytes = parsed;
+        }
+        if let Ok(v) = env::var("UPLOAD_MAX_PIXELS")
+            && let Ok(parsed) = v.parse::<u64>()
+        {
+            upload_config.max_pixels = parsed;
+        }
+        if let Ok(v) = env::var("UPLOAD_EXIF") {
+            upload_config.exif_policy = match v.to_ascii_lowercase().as_str() {
+                "keep" => ExifPolicy::Keep,
+                "strip" => ExifPolicy::Strip,
+                _ => ExifPolicy::StripGps,
+            }
+        }
+        if let Ok(v) = env::var("UPLOAD_MAX_STAGED_BYTES")
+            && let Ok(parsed) = v.parse::<u64>()
+        {
+            upload_config.max_staged_bytes = parsed;
+        }
+        if let Ok(v) = env::var("UPLOAD_MAX_STAGING_TOTAL_BYTES")
+            && let Ok(parsed) = v.parse::<u64>()
+        {
+            upload_config.max_staging_total_bytes = parsed;
+        }
+        if let Ok(v) = env::var("UPLOAD_CHUNK_BYTES")
+            && let Ok(parsed) = v.parse::<usize>()
+        {
+            upload_config.chunk_bytes = parsed.max(64 * 1024);
+        }
+        if let Ok(v) = env::var("UPLOAD_STAGING_DIR") {
+            upload_config.staging_dir = v;
+        }
+        if let Ok(v) = env::var("UPLOAD_TRAILER_SIGNATURE") {
+            upload_config.trailer_signature =
+                matches!(v.to_ascii_lowercase().as_str(), "1" | "true");
+        }
+
         Ok(Self {
             oidc_issuer_url: env::var("OIDC_ISSUER_URL").expect("OIDC_ISSUER_URL must be set"),
             oidc_client_id: env::var("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID must be set"),
@@ -129,19 +219,26 @@ impl AppConfig {
             trust_connect_list: env::var("TRUST_CONNECT_LIST")
                 .unwrap_or_else(|_| "".to_string())
                 .replace('"', ""),
-            media_proxy: env::var("MEDIA_PROXY")
+            chat_config,
+            upload_config,
+            api_tokens_path: env::var("API_TOKENS_PATH")
+                .unwrap_or_else(|_| "data/api_tokens.json".to_string()),
+            audit_log_path: env::var("AUDIT_LOG_PATH")
+                .unwrap_or_else(|_| "data/audit.jsonl".to_string()),
+            media_cache_dir: env::var("MEDIA_CACHE_DIR")
+                .unwrap_or_else(|_| "data/media".to_string()),
+            media_cache_max_bytes: env::var("MEDIA_CACHE_MAX_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1024 * 1024 * 1024),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
                 .unwrap_or_else(|_| "".to_string())
                 .replace('"', ""),
-            chat_config,
             is_prod,
         })
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct ClientConfig {
-    pub media_proxy: String,
-}
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SameSiteConfig {
@@ -0,0 +1,213 @@
+/// Header carrying the per-session CSRF token on state-changing API calls.
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+/// Query parameter fallback for requests that cannot set headers (`sendBeacon`).
+pub const CSRF_QUERY_PARAM: &str = "csrf_token";
+
+/// Synchronizer token of the current session, rendered into the page by `CsrfTokenScript`.
+#[derive(Clone, Debug, Default)]
+pub struct CsrfToken(pub String);
+
+/// Token injected as `window.CSRF_TOKEN`, empty outside the browser or before login.
+pub fn csrf_token() -> String {
+    web_sys::window()
+        .and_then(|window| {
+            js_sys::Reflect::get(&window, &wasm_bindgen::JsValue::from_str("CSRF_TOKEN")).ok()
+        })
+        .and_then(|value| value.as_string())
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::{CSRF_HEADER, CSRF_QUERY_PARAM};
+    use crate::api_tokens::bearer_token;
+    use crate::auth::SESSION_ID;
+    use crate::sessions::error_response;
+    use crate::state::AppState;
+    use axum::{
+        extract::State,
+        http::{HeaderMap, Method, StatusCode, header},
+        middleware::Next,
+        response::Response,
+    };
+    use axum_extra::extract::CookieJar;
+
+    pub fn new_csrf_token() -> String {
+        let mut bytes = [0_u8; 32];
+        getrandom::fill(&mut bytes).expect("system RNG unavailable");
+        hex::encode(bytes)
+    }
+
+    pub async fn session_csrf_token(state: &AppState, headers: &HeaderMap) -> Option<String> {
+        let session_id = CookieJar::from_headers(headers)
+            .get(SESSION_ID)?
+            .value()
+            .to_string();
+        state
+            .sessions
+            .lock()
+            .await
+            .get(&session_id)
+            .map(|session| session.csrf_api_token.clone())
+    }
+
+    fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).fold(0_u8, |acc, (x, y)| acc | (x ^ y)) == 0
+    }
+
+    fn submitted_token(headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+        headers
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .or_else(|| {
+                url::form_urlencoded::parse(query?.as_bytes())
+                    .find(|(key, _)| key == CSRF_QUERY_PARAM)
+                    .map(|(_, value)| value.into_owned())
+            })
+    }
+
+    /// `scheme://host[:port]` of the Origin header, or of the Referer when Origin is
+    /// missing or opaque (`null`, e.g. under `Referrer-Policy: no-referrer`).
+    fn request_origin(headers: &HeaderMap) -> Option<url::Url> {
+        let origin = headers
+            .get(header::ORIGIN)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| *value != "null");
+        let source = origin.or_else(|| {
+            headers
+                .get(header::REFERER)
+                .and_then(|value| value.to_str().ok())
+        })?;
+        url::Url::parse(source).ok()
+    }
+
+    /// Origin/Referer must point at the host the request was sent to.
+    fn same_origin(headers: &HeaderMap) -> bool {
+        let Some(origin) = request_origin(headers) else {
+            return false;
+        };
+        let host = headers
+            .get("x-forwarded-host")
+            .or_else(|| headers.get(header::HOST))
+            .and_then(|value| value.to_str().ok());
+        let origin_host = match (origin.host_str(), origin.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return false,
+        };
+        host.is_some_and(|host| host.eq_ignore_ascii_case(&origin_host))
+    }
+
+    /// Safe methods and API token calls, which carry no ambient credentials.
+    /// Only what `api_auth` accepts as a token counts: any other
+    /// `Authorization` scheme is authenticated by the session cookie.
+    fn exempt(method: &Method, headers: &HeaderMap) -> bool {
+        matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+            || bearer_token(headers).is_some()
+    }
+
+    /// Verifies unsafe cookie-authenticated requests: the session's synchronizer
+    /// token when submitted, otherwise a same-origin Origin/Referer.
+    pub async fn csrf_protect(
+        State(state): State<AppState>,
+        req: axum::extract::Request,
+        next: Next,
+    ) -> Response {
+        let headers = req.headers();
+        if exempt(req.method(), headers) {
+            return next.run(req).await;
+        }
+
+        let submitted = submitted_token(headers, req.uri().query());
+        let allowed = match submitted {
+            Some(submitted) => session_csrf_token(&state, headers)
+                .await
+                .is_some_and(|expected| {
+                    constant_time_eq(expected.as_bytes(), submitted.as_bytes())
+                }),
+            None => same_origin(headers),
+        };
+        if !allowed {
+            tracing::warn!(path = %req.uri().path(), "csrf: request rejected");
+            return error_response(StatusCode::FORBIDDEN, "CSRF validation failed");
+        }
+        next.run(req).await
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use axum::http::HeaderValue;
+
+        #[test]
+        fn token_is_read_from_header_or_query() {
+            let mut headers = HeaderMap::new();
+            assert_eq!(
+                submitted_token(&headers, Some("a=1&csrf_token=abc")).as_deref(),
+                Some("abc")
+            );
+            headers.insert(CSRF_HEADER, HeaderValue::from_static("def"));
+            assert_eq!(submitted_token(&headers, None).as_deref(), Some("def"));
+        }
+
+        #[test]
+        fn origin_fallback_requires_matching_host() {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::HOST, HeaderValue::from_static("gmr.example.test"));
+            assert!(!same_origin(&headers));
+
+            headers.insert(header::ORIGIN, HeaderValue::from_static("null"));
+            headers.insert(
+                header::REFERER,
+                HeaderValue::from_static("https://gmr.example.test/reports"),
+            );
+            assert!(same_origin(&headers));
+
+            headers.insert(
+                header::ORIGIN,
+                HeaderValue::from_static("https://evil.example.test"),
+            );
+            assert!(!same_origin(&headers));
+
+            headers.insert(header::HOST, HeaderValue::from_static("127.0.0.1:3000"));
+            headers.insert(
+                header::ORIGIN,
+                HeaderValue::from_static("http://127.0.0.1:3000"),
+            );
+            assert!(same_origin(&headers));
+        }
+
+        #[test]
+        fn only_bearer_tokens_skip_the_check() {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::COOKIE, HeaderValue::from_static("session_id=abc"));
+            assert!(exempt(&Method::GET, &headers));
+            assert!(!exempt(&Method::POST, &headers));
+
+            // Another scheme leaves the session cookie in charge
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_static("Basic eDp5"),
+            );
+            assert!(!exempt(&Method::POST, &headers));
+            assert!(!exempt(&Method::DELETE, &headers));
+
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_static("Bearer gmr_abc.def"),
+            );
+            assert!(exempt(&Method::POST, &headers));
+        }
+
+        #[test]
+        fn tokens_compare_exactly() {
+            assert!(constant_time_eq(b"abc", b"abc"));
+            assert!(!constant_time_eq(b"abc", b"abd"));
+            assert!(!constant_time_eq(b"abc", b"ab"));
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub use ssr::{csrf_protect, new_csrf_token, session_csrf_token};
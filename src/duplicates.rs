@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Samples per row of the grid a perceptual hash is taken on.
+pub const PERCEPTUAL_HASH_WIDTH: u32 = 9;
+/// Rows of the grid a perceptual hash is taken on.
+pub const PERCEPTUAL_HASH_HEIGHT: u32 = 8;
+/// Differing bits up to which two images count as the same photo.
+pub const NEAR_DUPLICATE_BITS: u32 = 6;
+/// Upload field with the hex SHA-256 of the image as selected.
+pub const HASH_FIELD: &str = "hash";
+/// Upload field with the hex perceptual hash the browser took.
+pub const PERCEPTUAL_HASH_FIELD: &str = "perceptual_hash";
+
+/// Hashes of an image about to be uploaded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateQuery {
+    pub hash: String,
+    #[serde(default)]
+    pub perceptual_hash: Option<String>,
+}
+
+/// A leaf of the target branch the image matches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateMatch {
+    pub node_id: Uuid,
+    pub name: Option<String>,
+    /// Same bytes, not only a similar picture.
+    pub exact: bool,
+}
+
+/// Difference hash of a `PERCEPTUAL_HASH_WIDTH` × `PERCEPTUAL_HASH_HEIGHT` RGBA
+/// grid: one bit per pair of neighbours in a row, set where the left is brighter.
+/// Survives scaling, re-encoding and stripped metadata.
+pub fn perceptual_hash(rgba: &[u8]) -> Option<u64> {
+    let width = PERCEPTUAL_HASH_WIDTH as usize;
+    if rgba.len() != width * PERCEPTUAL_HASH_HEIGHT as usize * 4 {
+        return None;
+    }
+    let luma: Vec<u32> = rgba
+        .chunks_exact(4)
+        .map(|px| 299 * px[0] as u32 + 587 * px[1] as u32 + 114 * px[2] as u32)
+        .collect();
+    let mut hash = 0_u64;
+    for row in luma.chunks_exact(width) {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | u64::from(pair[0] > pair[1]);
+        }
+    }
+    Some(hash)
+}
+
+pub fn format_perceptual_hash(hash: u64) -> String {
+    format!("{hash:016x}")
+}
+
+pub fn parse_perceptual_hash(value: &str) -> Option<u64> {
+    let value = value.trim();
+    (value.len() == 16)
+        .then(|| u64::from_str_radix(value, 16).ok())
+        .flatten()
+}
+
+/// Hex SHA-256 as sent in `HASH_FIELD`.
+pub fn is_content_hash(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(pixel: impl Fn(usize, usize) -> u8) -> Vec<u8> {
+        let mut rgba = Vec::new();
+        for y in 0..PERCEPTUAL_HASH_HEIGHT as usize {
+            for x in 0..PERCEPTUAL_HASH_WIDTH as usize {
+                let value = pixel(x, y);
+                rgba.extend_from_slice(&[value, value, value, 255]);
+            }
+        }
+        rgba
+    }
+
+    #[test]
+    fn perceptual_hash_ignores_brightness_but_not_content() {
+        let gradient = perceptual_hash(&grid(|x, y| (x * 20 + y * 3) as u8)).unwrap();
+        let brighter = perceptual_hash(&grid(|x, y| (x * 20 + y * 3 + 40) as u8)).unwrap();
+        let mirrored = perceptual_hash(&grid(|x, y| ((8 - x) * 20 + y * 3) as u8)).unwrap();
+
+        assert_eq!(gradient, brighter);
+        assert!((gradient ^ mirrored).count_ones() > NEAR_DUPLICATE_BITS);
+        assert_eq!(
+            parse_perceptual_hash(&format_perceptual_hash(mirrored)),
+            Some(mirrored)
+        );
+        assert_eq!(perceptual_hash(&[0; 16]), None);
+        assert!(!is_content_hash("../etc/passwd"));
+    }
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::*;
+    use crate::api_tokens::{ApiPrincipal, agent_user_id};
+    use crate::components::tree::{ImageData, NodeType, TreeNode};
+    use crate::media::leaf_perceptual_hash;
+    use crate::proxy_tree::cached_tree;
+    use crate::sessions::error_response;
+    use crate::state::AppState;
+    use axum::{
+        Extension, Json,
+        extract::{Path, State},
+        http::StatusCode,
+        response::{IntoResponse, Response},
+    };
+    use axum_extra::extract::CookieJar;
+    use futures::StreamExt;
+
+    /// Leaves whose thumbnails are hashed at the same time.
+    const HASH_CONCURRENCY: usize = 4;
+
+    /// Whether `leaf` matches the query, `None` if it does not.
+    async fn matches(
+        state: &AppState,
+        query: &DuplicateQuery,
+        perceptual: Option<u64>,
+        leaf: TreeNode,
+    ) -> Option<DuplicateMatch> {
+        let stored_perceptual = leaf
+            .data
+            .get(PERCEPTUAL_HASH_FIELD)
+            .and_then(|value| value.as_str())
+            .and_then(parse_perceptual_hash);
+        let image: ImageData = serde_json::from_value(leaf.data).ok()?;
+        let exact = image
+            .hash
+            .as_deref()
+            .is_some_and(|hash| hash.eq_ignore_ascii_case(&query.hash));
+        if !exact {
+            let perceptual = perceptual?;
+            let stored = match stored_perceptual {
+                Some(stored) => stored,
+                None => leaf_perceptual_hash(state, &image).await?,
+            };
+            if (perceptual ^ stored).count_ones() > NEAR_DUPLICATE_BITS {
+                return None;
+            }
+        }
+        Some(DuplicateMatch {
+            node_id: leaf.id,
+            name: leaf.name,
+            exact,
+        })
+    }
+
+    /// `/api/proxy/images/duplicates/{parent_id}`: leaves of the branch that hold
+    /// the same or a near-identical photo, exact matches first.
+    pub async fn duplicates_handler(
+        State(state): State<AppState>,
+        Path(parent_id): Path<Uuid>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        Json(query): Json<DuplicateQuery>,
+    ) -> Response {
+        let Some(user_id) = agent_user_id(&state, &jar, principal.as_deref()).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        if !is_content_hash(&query.hash) {
+            return error_response(StatusCode::BAD_REQUEST, "Invalid content hash");
+        }
+        let perceptual = match query.perceptual_hash.as_deref() {
+            Some(value) => match parse_perceptual_hash(value) {
+                Some(hash) => Some(hash),
+                None => {
+                    return error_response(StatusCode::BAD_REQUEST, "Invalid perceptual hash");
+                }
+            },
+            None => None,
+        };
+
+        let nodes = match cached_tree(&state, &user_id, true).await {
+            Ok(nodes) => nodes,
+            Err(response) => return response,
+        };
+        let leaves: Vec<TreeNode> = nodes
+            .iter()
+            .filter(|node| {
+                node.parent_id == Some(parent_id) && node.node_type == NodeType::ImageLeaf
+            })
+            .cloned()
+            .collect();
+        let mut found: Vec<DuplicateMatch> = futures::stream::iter(leaves)
+            .map(|leaf| matches(&state, &query, perceptual, leaf))
+            .buffered(HASH_CONCURRENCY)
+            .filter_map(|found| async move { found })
+            .collect()
+            .await;
+        found.sort_by_key(|found| !found.exact);
+        Json(found).into_response()
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub use ssr::duplicates_handler;
@@ -43,6 +43,12 @@ pub enum StreamEvent {
         data: serde_json::Value,
     },
 
+    /// Changes across all reports of `PromptRequest::reports`.
+    Timeline {
+        request_id: String,
+        data: serde_json::Value,
+    },
+
     // Completion events
     Completed {
         request_id: String,
@@ -86,6 +92,7 @@ impl StreamEvent {
             | StreamEvent::ReportList { request_id, .. }
             | StreamEvent::Description { request_id, .. }
             | StreamEvent::Comparison { request_id, .. }
+            | StreamEvent::Timeline { request_id, .. }
             | StreamEvent::ContextRequest { request_id, .. }
             | StreamEvent::Completed { request_id, .. }
             | StreamEvent::Error { request_id, .. }
@@ -0,0 +1,336 @@
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+
+/// `DateTimeOriginal` of a photo, with `OffsetTimeOriginal` when the camera wrote it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaptureTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// Minutes east of UTC.
+    pub offset_minutes: Option<i32>,
+}
+
+impl CaptureTime {
+    /// Berlin wall clock in the agent's `DD.MM.YYYY HH:MM:SS` format. Without an
+    /// offset the camera clock is assumed to be on Berlin time already.
+    pub fn agent_datetime(&self) -> String {
+        let Some(offset) = self.offset_minutes else {
+            return format!(
+                "{:02}.{:02}.{:04} {:02}:{:02}:{:02}",
+                self.day, self.month, self.year, self.hour, self.minute, self.second
+            );
+        };
+        let local = days_from_civil(self.year, self.month, self.day) * 86_400
+            + (self.hour * 3600 + self.minute * 60 + self.second) as i64;
+        let utc = local - offset as i64 * 60;
+        let (year, month, day) = civil_from_days(utc.div_euclid(86_400));
+        let hour_utc = (utc.rem_euclid(86_400) / 3600) as u32;
+        let berlin = utc + berlin_utc_offset_hours(year as u32, month, day, hour_utc) as i64 * 3600;
+        let (year, month, day) = civil_from_days(berlin.div_euclid(86_400));
+        let seconds = berlin.rem_euclid(86_400);
+        format!(
+            "{:02}.{:02}.{:04} {:02}:{:02}:{:02}",
+            day,
+            month,
+            year,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    }
+}
+
+/// Capture time from the EXIF block of a JPEG, PNG, WebP or HEIC file.
+pub fn capture_time(bytes: &[u8]) -> Option<CaptureTime> {
+    let tiff = find_tiff(bytes)?;
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let reader = TiffReader {
+        bytes: tiff,
+        little_endian,
+    };
+    if reader.u16(2)? != 42 {
+        return None;
+    }
+    let exif_ifd = reader.tag(reader.u32(4)? as usize, TAG_EXIF_IFD)?;
+    let exif_ifd = reader.u32(exif_ifd + 8)? as usize;
+    let datetime = reader.ascii(reader.tag(exif_ifd, TAG_DATE_TIME_ORIGINAL)?)?;
+    let offset = reader
+        .tag(exif_ifd, TAG_OFFSET_TIME_ORIGINAL)
+        .and_then(|entry| reader.ascii(entry))
+        .and_then(parse_offset);
+    parse_datetime(datetime, offset)
+}
+
+/// TIFF header following `Exif\0\0` (JPEG APP1, HEIC item) or inside a PNG `eXIf`
+/// or WebP `EXIF` chunk.
+fn find_tiff(bytes: &[u8]) -> Option<&[u8]> {
+    let is_tiff = |at: usize| matches!(bytes.get(at..at + 4), Some(b"II*\0" | b"MM\0*"));
+    if let Some(at) = bytes
+        .windows(EXIF_HEADER.len())
+        .position(|window| window == EXIF_HEADER)
+        .map(|at| at + EXIF_HEADER.len())
+        .filter(|at| is_tiff(*at))
+    {
+        return bytes.get(at..);
+    }
+    bytes
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == b"eXIf" || *window == b"EXIF")
+        .flat_map(|(at, _)| [at + 4, at + 8])
+        .find(|at| is_tiff(*at))
+        .and_then(|at| bytes.get(at..))
+}
+
+struct TiffReader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl TiffReader<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let raw: [u8; 2] = self.bytes.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(raw)
+        } else {
+            u16::from_be_bytes(raw)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let raw: [u8; 4] = self.bytes.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(raw)
+        } else {
+            u32::from_be_bytes(raw)
+        })
+    }
+
+    /// Offset of the IFD entry for `tag`.
+    fn tag(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = self.u16(ifd)? as usize;
+        (0..count)
+            .map(|index| ifd + 2 + index * 12)
+            .find(|at| self.u16(*at) == Some(tag))
+    }
+
+    fn ascii(&self, entry: usize) -> Option<&str> {
+        if self.u16(entry + 2)? != 2 {
+            return None;
+        }
+        let len = self.u32(entry + 4)? as usize;
+        let start = if len <= 4 {
+            entry + 8
+        } else {
+            self.u32(entry + 8)? as usize
+        };
+        let value = self.bytes.get(start..start.checked_add(len)?)?;
+        std::str::from_utf8(value)
+            .ok()
+            .map(|value| value.trim_end_matches('\0'))
+    }
+}
+
+/// `YYYY:MM:DD HH:MM:SS`; cameras without a clock write blanks or zeros.
+fn parse_datetime(value: &str, offset_minutes: Option<i32>) -> Option<CaptureTime> {
+    let mut parts = value.trim().split([':', ' ']);
+    let mut next = || parts.next()?.trim().parse::<u32>().ok();
+    let (year, month, day) = (next()?, next()?, next()?);
+    let (hour, minute, second) = (next()?, next()?, next()?);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year as i32) => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if year < 1900 || day == 0 || day > days_in_month || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    Some(CaptureTime {
+        year: year as i32,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        offset_minutes,
+    })
+}
+
+/// `+HH:MM` or `-HH:MM`.
+fn parse_offset(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let sign = match value.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = value.get(1..)?.split_once(':')?;
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    (hours <= 14 && minutes < 60).then_some(sign * (hours * 60 + minutes))
+}
+
+/// Central European Time, with summer time from the last Sunday of March to
+/// the last Sunday of October at 01:00 UTC.
+pub fn berlin_utc_offset_hours(year: u32, month: u32, day: u32, hour_utc: u32) -> i32 {
+    let in_dst = match month {
+        4..=9 => true,
+        1 | 2 | 11 | 12 => false,
+        3 => {
+            let start_day = last_sunday_day(year as i32, 3);
+            day > start_day || (day == start_day && hour_utc >= 1)
+        }
+        10 => {
+            let end_day = last_sunday_day(year as i32, 10);
+            day < end_day || (day == end_day && hour_utc < 1)
+        }
+        _ => false,
+    };
+    if in_dst { 2 } else { 1 }
+}
+
+fn last_sunday_day(year: i32, month: u32) -> u32 {
+    let mut day = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 30,
+    };
+    while day_of_week(year, month, day) != 0 {
+        day -= 1;
+    }
+    day
+}
+
+pub fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn day_of_week(year: i32, month: u32, day: u32) -> u32 {
+    let (month, year) = if month < 3 {
+        (month as i32 + 12, year - 1)
+    } else {
+        (month as i32, year)
+    };
+    let k = year % 100;
+    let j = year / 100;
+    let h = (day as i32 + (13 * (month + 1)) / 5 + k + k / 4 + j / 4 + 5 * j) % 7;
+    ((h + 6) % 7) as u32
+}
+
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year } as i64;
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i32, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian TIFF: IFD0 -> Exif IFD with DateTimeOriginal and an optional offset.
+    fn tiff(datetime: &str, offset: Option<&str>) -> Vec<u8> {
+        let entries = 1 + offset.is_some() as u16;
+        let exif_ifd = 8 + 2 + 12 + 4;
+        let values = exif_ifd + 2 + entries as u32 * 12 + 4;
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(8_u32.to_le_bytes());
+        tiff.extend(1_u16.to_le_bytes());
+        tiff.extend(TAG_EXIF_IFD.to_le_bytes());
+        tiff.extend(4_u16.to_le_bytes());
+        tiff.extend(1_u32.to_le_bytes());
+        tiff.extend(exif_ifd.to_le_bytes());
+        tiff.extend(0_u32.to_le_bytes());
+        tiff.extend(entries.to_le_bytes());
+        tiff.extend(TAG_DATE_TIME_ORIGINAL.to_le_bytes());
+        tiff.extend(2_u16.to_le_bytes());
+        tiff.extend(20_u32.to_le_bytes());
+        tiff.extend(values.to_le_bytes());
+        if offset.is_some() {
+            tiff.extend(TAG_OFFSET_TIME_ORIGINAL.to_le_bytes());
+            tiff.extend(2_u16.to_le_bytes());
+            tiff.extend(7_u32.to_le_bytes());
+            tiff.extend((values + 20).to_le_bytes());
+        }
+        tiff.extend(0_u32.to_le_bytes());
+        tiff.extend(datetime.as_bytes());
+        tiff.push(0);
+        if let Some(offset) = offset {
+            tiff.extend(offset.as_bytes());
+            tiff.push(0);
+        }
+        tiff
+    }
+
+    fn jpeg(tiff: &[u8]) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend(((2 + EXIF_HEADER.len() + tiff.len()) as u16).to_be_bytes());
+        jpeg.extend(EXIF_HEADER);
+        jpeg.extend(tiff);
+        jpeg.extend([0xFF, 0xD9]);
+        jpeg
+    }
+
+    #[test]
+    fn reads_capture_time_and_converts_to_berlin() {
+        let tokyo = capture_time(&jpeg(&tiff("2026:07:01 12:30:15", Some("+09:00")))).unwrap();
+        assert_eq!(tokyo.offset_minutes, Some(540));
+        assert_eq!(tokyo.agent_datetime(), "01.07.2026 05:30:15");
+
+        let new_york = capture_time(&jpeg(&tiff("2026:01:01 20:00:00", Some("-05:00")))).unwrap();
+        assert_eq!(new_york.agent_datetime(), "02.01.2026 02:00:00");
+
+        let local = capture_time(&jpeg(&tiff("2026:03:29 08:15:00", None))).unwrap();
+        assert_eq!(local.agent_datetime(), "29.03.2026 08:15:00");
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(b"\0\0\0\0eXIf");
+        png.extend(tiff("2025:12:24 18:00:00", Some("+01:00")));
+        assert_eq!(
+            capture_time(&png).unwrap().agent_datetime(),
+            "24.12.2025 18:00:00"
+        );
+    }
+
+    #[test]
+    fn missing_or_blank_capture_time_is_none() {
+        assert_eq!(capture_time(b"\xFF\xD8\xFF\xD9"), None);
+        assert_eq!(
+            capture_time(&jpeg(&tiff("    :  :     :  :  ", None))),
+            None
+        );
+        assert_eq!(
+            capture_time(&jpeg(&tiff("0000:00:00 00:00:00", None))),
+            None
+        );
+    }
+}
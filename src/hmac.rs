@@ -4,6 +4,14 @@ use sha2::Sha256;
 
 type HmacSha256 = Hmac<Sha256>;
 
+/// `X-Signature` value of a body streamed before it could be signed. The last two
+/// form fields, `TIMESTAMP_FIELD` and `SIGNATURE_FIELD`, then carry the signature
+/// over every byte before them followed by the timestamp, see
+/// `docs/agent-upload-signature.md`.
+pub const TRAILER_SIGNATURE: &str = "trailer";
+pub const TIMESTAMP_FIELD: &str = "x-timestamp";
+pub const SIGNATURE_FIELD: &str = "x-signature";
+
 pub fn build_hmac(secret: &str, payload: &[u8]) -> Result<(i64, String)> {
     let timestamp = chrono::Utc::now().timestamp();
     let signature = build_hmac_at(secret, payload, timestamp)?;
@@ -11,14 +19,77 @@ pub fn build_hmac(secret: &str, payload: &[u8]) -> Result<(i64, String)> {
 }
 
 fn build_hmac_at(secret: &str, payload: &[u8], timestamp: i64) -> Result<String> {
-    if secret.trim().is_empty() {
-        bail!("HMAC secret must not be empty");
+    let mut signer = HmacSigner::at(secret, timestamp)?;
+    signer.update(payload);
+    Ok(signer.finish().1)
+}
+
+/// Signs a payload fed in pieces, for bodies streamed from disk.
+pub struct HmacSigner {
+    mac: HmacSha256,
+    timestamp: i64,
+}
+
+impl HmacSigner {
+    pub fn new(secret: &str) -> Result<Self> {
+        Self::at(secret, chrono::Utc::now().timestamp())
+    }
+
+    pub(crate) fn at(secret: &str, timestamp: i64) -> Result<Self> {
+        if secret.trim().is_empty() {
+            bail!("HMAC secret must not be empty");
+        }
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
+        mac.update(timestamp.to_string().as_bytes());
+        Ok(Self { mac, timestamp })
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
     }
 
-    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
-    mac.update(timestamp.to_string().as_bytes());
-    mac.update(payload);
-    Ok(hex::encode(mac.finalize().into_bytes()))
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.mac.update(bytes);
+    }
+
+    /// Timestamp and hex signature, as returned by `build_hmac`.
+    pub fn finish(self) -> (i64, String) {
+        (
+            self.timestamp,
+            hex::encode(self.mac.finalize().into_bytes()),
+        )
+    }
+}
+
+/// Signs a body whose end is not known in advance. The timestamp is taken and
+/// signed when the body is complete, so a long upload is not stale on arrival.
+pub struct TrailerSigner {
+    mac: HmacSha256,
+}
+
+impl TrailerSigner {
+    pub fn new(secret: &str) -> Result<Self> {
+        if secret.trim().is_empty() {
+            bail!("HMAC secret must not be empty");
+        }
+        Ok(Self {
+            mac: HmacSha256::new_from_slice(secret.as_bytes())?,
+        })
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.mac.update(bytes);
+    }
+
+    /// Timestamp and hex signature over the body followed by the timestamp.
+    pub fn finish(self) -> (i64, String) {
+        self.finish_at(chrono::Utc::now().timestamp())
+    }
+
+    pub(crate) fn finish_at(mut self, timestamp: i64) -> (i64, String) {
+        self.mac.update(timestamp.to_string().as_bytes());
+        (timestamp, hex::encode(self.mac.finalize().into_bytes()))
+    }
 }
 
 #[cfg(test)]
@@ -34,8 +105,41 @@ mod tests {
         assert_ne!(first, second);
     }
 
+    #[test]
+    fn incremental_signature_matches_one_shot() {
+        let timestamp = 1_700_000_000;
+        let mut signer = HmacSigner::at("demo-secret", timestamp).unwrap();
+        signer.update(b"--boundary\r\n");
+        signer.update(b"image bytes");
+
+        assert_eq!(
+            signer.finish(),
+            (
+                timestamp,
+                build_hmac_at("demo-secret", b"--boundary\r\nimage bytes", timestamp).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn trailer_signature_covers_the_timestamp_last() {
+        let timestamp = 1_700_000_000;
+        let mut signer = TrailerSigner::new("demo-secret").unwrap();
+        signer.update(b"--boundary\r\n");
+        let (_, signature) = signer.finish_at(timestamp);
+
+        let mut mac = HmacSha256::new_from_slice(b"demo-secret").unwrap();
+        mac.update(b"--boundary\r\n1700000000");
+        assert_eq!(signature, hex::encode(mac.finalize().into_bytes()));
+        assert_ne!(
+            signature,
+            build_hmac_at("demo-secret", b"--boundary\r\n", timestamp).unwrap()
+        );
+    }
+
     #[test]
     fn empty_secret_is_rejected() {
         assert!(build_hmac("  ", b"payload").is_err());
+        assert!(TrailerSigner::new(" ").is_err());
     }
 }
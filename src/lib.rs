@@ -1,25 +1,43 @@
 #![recursion_limit = "256"]
+pub mod admin;
+pub mod api_tokens;
 pub mod app;
+#[cfg(feature = "ssr")]
+pub mod audit;
 pub mod auth;
 #[cfg(feature = "ssr")]
 pub mod auth_ssr;
 #[cfg(feature = "ssr")]
+pub mod backchannel;
+pub mod branches;
+#[cfg(feature = "ssr")]
 pub mod chunk_assembler;
+pub mod chunked_upload;
 pub mod components;
 pub mod config;
+pub mod csrf;
+pub mod duplicates;
+pub mod exif;
 #[cfg(feature = "ssr")]
 pub mod llm_stream;
+pub mod media;
 #[cfg(feature = "ssr")]
 pub mod proxy_reports;
 #[cfg(feature = "ssr")]
 pub mod proxy_tree;
+pub mod rate_limit;
+pub mod saved_context;
 pub mod server_fn;
+pub mod sessions;
+pub mod sharing;
 #[cfg(feature = "ssr")]
 pub mod ssr;
 #[cfg(feature = "ssr")]
 pub mod state;
 #[cfg(feature = "ssr")]
 pub mod stop;
+#[cfg(feature = "ssr")]
+pub mod upload;
 
 pub mod events;
 #[cfg(feature = "ssr")]
@@ -28,8 +46,6 @@ pub mod model_settings;
 #[cfg(feature = "ssr")]
 pub(crate) mod stats;
 
-use config::ClientConfig;
-
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
 pub fn hydrate() {
@@ -1,3 +1,4 @@
+use crate::api_tokens::ApiPrincipal;
 use crate::auth::SESSION_ID;
 use crate::chunk_assembler::*;
 use crate::components::chat_data::ContextRequest;
@@ -8,6 +9,7 @@ use crate::state::ChatSession;
 use crate::stats::format_stats_table;
 use async_stream::stream;
 use axum::{
+    Extension,
     extract::State,
     response::{IntoResponse, Response, Sse, sse::Event},
 };
@@ -49,17 +51,26 @@ pub struct PromptRequest {
     pub object_id: Option<String>,
     pub prev_leaf: Option<String>,
     pub next_leaf: Option<String>,
+    /// Pinned reports of the object, oldest first, for a timeline of changes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reports: Vec<String>,
 }
 
 pub async fn chat_stream_handler(
     State(state): State<AppState>,
     jar: CookieJar,
-    axum::Json(req): axum::Json<PromptRequest>,
+    principal: Option<Extension<ApiPrincipal>>,
+    axum::Json(mut req): axum::Json<PromptRequest>,
 ) -> Result<impl IntoResponse, Response> {
     debug!("Received streaming request for prompt: {}", &req.message);
-    let session_id = match jar.get(SESSION_ID) {
-        Some(cookie) => cookie.value().to_string(),
-        None => {
+    let session_id = match (&principal, jar.get(SESSION_ID)) {
+        // API tokens act as their owner, whatever the body claims
+        (Some(Extension(principal)), _) => {
+            req.user_id = principal.user_id();
+            principal.session_key()
+        }
+        (None, Some(cookie)) => cookie.value().to_string(),
+        (None, None) => {
             tracing::error!("No session_id in cookie");
             return Err((StatusCode::UNAUTHORIZED, "No session to stop").into_response());
         }
@@ -237,6 +248,10 @@ pub async fn chat_stream_handler(
                                                 let data_str = serde_json::to_string(&comp_data).unwrap_or_default();
                                                 yield Ok(Event::default().event("comparison").data(data_str));
                                             }
+                                            StreamEvent::Timeline { data: timeline_data, .. } => {
+                                                let data_str = serde_json::to_string(&timeline_data).unwrap_or_default();
+                                                yield Ok(Event::default().event("timeline").data(data_str));
+                                            }
                                             StreamEvent::ContextRequest { prompt, suggestions, .. } => {
                                                  let data = serde_json::to_string(&ContextRequest {
                                                     prompt,
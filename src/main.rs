@@ -7,12 +7,35 @@ async fn main() {
         Router,
         routing::{get, post},
     }; //post
+    use gmr::admin::{
+        admin_revoke_access_handler, admin_sessions_handler, admin_stop_stream_handler,
+        admin_terminate_session_handler,
+    };
+    use gmr::api_tokens::{
+        api_auth, create_api_token_handler, list_api_tokens_handler, revoke_api_token_handler,
+    };
+    use gmr::audit::audit_log_handler;
+    use gmr::backchannel::backchannel_logout_handler;
+    use gmr::branches::{create_branch_handler, delete_branch_handler, update_branch_handler};
+    use gmr::chunked_upload::{
+        abort_chunked_upload_handler, chunked_upload_status_handler,
+        complete_chunked_upload_handler, create_chunked_upload_handler, put_upload_part_handler,
+    };
+    use gmr::csrf::csrf_protect;
+    use gmr::duplicates::duplicates_handler;
+    use gmr::media::media_handler;
     use gmr::model_settings::{get_models_handler, update_models_handler};
     use gmr::proxy_reports::{
         proxy_delete_image_handler, proxy_reports_handler, proxy_update_report_handler,
         proxy_upload_image_handler,
     };
     use gmr::proxy_tree::proxy_tree_handler;
+    use gmr::rate_limit::{CHAT_POLICY, UPLOAD_PART_POLICY, UPLOAD_POLICY, rate_limit};
+    use gmr::saved_context::{
+        MAX_SAVED_CONTEXT_BYTES, get_chat_context_handler, put_chat_context_handler,
+    };
+    use gmr::sessions::{list_sessions_handler, revoke_session_handler};
+    use gmr::sharing::{grant_share_handler, list_shares_handler, revoke_share_handler};
     use gmr::stop::stop_handler;
     use gmr::{app::*, llm_stream::*, ssr::*, state::AppState};
     use leptos_axum::file_and_error_handler;
@@ -25,18 +48,10 @@ async fn main() {
     tracing_subscriber::fmt::init();
     let leptos_routes = generate_route_list(App);
     let state = AppState::init().await.unwrap();
+    let chunk_limit = state.http_client.config.upload_config.chunk_bytes + 64 * 1024;
 
-    let app = Router::new()
-        .route("/login", get(login_handler))
-        .route("/callback", get(callback_handler))
-        .route("/logout", get(logout_handler))
-        .route("/api/health", get(|| async { "OK" }))
-        .route("/api/ready", get(readiness_handler))
-        .route(
-            "/api/get_auth{_}",
-            post(leptos_server_fn_handler).get(leptos_server_fn_handler),
-        )
-        //.route("/api/get_media_proxy{_}", post(leptos_server_fn_handler))
+    // Routes usable with a browser session or a scoped API token
+    let api_routes = Router::new()
         .route("/api/stop", post(stop_handler))
         .route("/api/proxy/tree/{user_id}", get(proxy_tree_handler))
         .route(
@@ -45,7 +60,52 @@ async fn main() {
         )
         .route(
             "/api/proxy/images/upload/{parent_id}",
-            post(proxy_upload_image_handler).layer(DefaultBodyLimit::max(25 * 1024 * 1024)),
+            post(proxy_upload_image_handler)
+                .layer(DefaultBodyLimit::max(25 * 1024 * 1024))
+                .layer(middleware::from_fn_with_state(
+                    (state.clone(), UPLOAD_POLICY),
+                    rate_limit,
+                )),
+        )
+        .route(
+            "/api/proxy/images/upload/{parent_id}/chunked",
+            post(create_chunked_upload_handler).layer(middleware::from_fn_with_state(
+                (state.clone(), UPLOAD_POLICY),
+                rate_limit,
+            )),
+        )
+        .route(
+            "/api/proxy/images/uploads/{upload_id}",
+            get(chunked_upload_status_handler)
+                .put(put_upload_part_handler)
+                .delete(abort_chunked_upload_handler)
+                .layer(DefaultBodyLimit::max(chunk_limit))
+                .layer(middleware::from_fn_with_state(
+                    (state.clone(), UPLOAD_PART_POLICY),
+                    rate_limit,
+                )),
+        )
+        .route(
+            "/api/proxy/images/uploads/{upload_id}/complete",
+            post(complete_chunked_upload_handler).layer(middleware::from_fn_with_state(
+                (state.clone(), UPLOAD_PART_POLICY),
+                rate_limit,
+            )),
+        )
+        .route(
+            "/api/proxy/branches/{node_id}",
+            post(create_branch_handler)
+                .put(update_branch_handler)
+                .delete(delete_branch_handler),
+        )
+        .route("/api/proxy/shares/{node_id}", get(list_shares_handler))
+        .route(
+            "/api/proxy/shares/{node_id}/{kind}/{grantee}",
+            axum::routing::put(grant_share_handler).delete(revoke_share_handler),
+        )
+        .route(
+            "/api/proxy/images/duplicates/{parent_id}",
+            post(duplicates_handler),
         )
         .route(
             "/api/proxy/images/{node_id}",
@@ -55,7 +115,68 @@ async fn main() {
             "/api/models/{user_id}",
             get(get_models_handler).put(update_models_handler),
         )
-        .route("/api/chat_stream", axum::routing::post(chat_stream_handler))
+        .route(
+            "/api/chat_context/{chat_id}",
+            get(get_chat_context_handler)
+                .put(put_chat_context_handler)
+                .layer(DefaultBodyLimit::max(MAX_SAVED_CONTEXT_BYTES)),
+        )
+        .route(
+            "/api/chat_stream",
+            post(chat_stream_handler).layer(middleware::from_fn_with_state(
+                (state.clone(), CHAT_POLICY),
+                rate_limit,
+            )),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), csrf_protect))
+        .route_layer(middleware::from_fn_with_state(state.clone(), api_auth));
+
+    // Browser-session account and admin routes
+    let account_routes = Router::new()
+        .route("/api/sessions", get(list_sessions_handler))
+        .route(
+            "/api/sessions/{handle}",
+            axum::routing::delete(revoke_session_handler),
+        )
+        .route("/api/admin/sessions", get(admin_sessions_handler))
+        .route(
+            "/api/admin/sessions/{handle}",
+            axum::routing::delete(admin_terminate_session_handler),
+        )
+        .route(
+            "/api/admin/sessions/{handle}/stop",
+            post(admin_stop_stream_handler),
+        )
+        .route(
+            "/api/admin/sessions/{handle}/revoke-access",
+            post(admin_revoke_access_handler),
+        )
+        .route("/api/admin/audit", get(audit_log_handler))
+        .route(
+            "/api/tokens",
+            get(list_api_tokens_handler).post(create_api_token_handler),
+        )
+        .route(
+            "/api/tokens/{id}",
+            axum::routing::delete(revoke_api_token_handler),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), csrf_protect));
+
+    let app = Router::new()
+        .route("/login", get(login_handler))
+        .route("/callback", get(callback_handler))
+        .route("/logout", get(logout_handler))
+        .route("/backchannel-logout", post(backchannel_logout_handler))
+        .route("/api/health", get(|| async { "OK" }))
+        .route("/api/ready", get(readiness_handler))
+        .route("/media/{node_id}/{variant}", get(media_handler))
+        .route(
+            "/api/get_auth{_}",
+            post(leptos_server_fn_handler).get(leptos_server_fn_handler),
+        )
+        //.route("/api/get_media_proxy{_}", post(leptos_server_fn_handler))
+        .merge(account_routes)
+        .merge(api_routes)
         .leptos_routes_with_handler(leptos_routes.clone(), leptos_main_handler)
         .layer(middleware::from_fn_with_state(
             state.clone(),
@@ -76,9 +197,12 @@ async fn main() {
         listener.local_addr().unwrap()
     );
     //info!("{:#?}", &leptos_routes);
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 #[cfg(not(feature = "ssr"))]
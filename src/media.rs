@@ -0,0 +1,968 @@
+use crate::components::tree::ImageData;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Image served under `/media/{node_id}/{variant}`; all but `Full` are scaled
+/// down by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaVariant {
+    Thumbnail,
+    Small,
+    Medium,
+    Large,
+    Full,
+}
+
+/// Widths offered for images shown at screen size.
+pub const RESPONSIVE_VARIANTS: [MediaVariant; 3] = [
+    MediaVariant::Small,
+    MediaVariant::Medium,
+    MediaVariant::Large,
+];
+
+/// `sizes` of thumbnails in the reports grid and carousel.
+pub const THUMBNAIL_SIZES: &str = "(max-width: 640px) 50vw, 320px";
+/// `sizes` of the popup image.
+pub const POPUP_SIZES: &str = "90vw";
+
+impl MediaVariant {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Thumbnail => "thumbnail",
+            Self::Small => "small",
+            Self::Medium => "medium",
+            Self::Large => "large",
+            Self::Full => "full",
+        }
+    }
+
+    /// Width the image is scaled down to, `None` for the original.
+    pub fn width(self) -> Option<u32> {
+        match self {
+            Self::Thumbnail => Some(320),
+            Self::Small => Some(640),
+            Self::Medium => Some(1280),
+            Self::Large => Some(1920),
+            Self::Full => None,
+        }
+    }
+
+    /// Object URL the variant is made from; without an original the stored
+    /// thumbnail is scaled instead.
+    pub fn source(self, image: &ImageData) -> Option<&str> {
+        match self {
+            Self::Full => image.url.as_deref(),
+            _ => image.url.as_deref().or(image.thumbnail_url.as_deref()),
+        }
+    }
+}
+
+/// Same-origin URL of an image leaf, object URLs never reach the browser.
+pub fn media_url(node_id: Uuid, variant: MediaVariant) -> String {
+    format!("/media/{node_id}/{}", variant.as_str())
+}
+
+/// `srcset` listing the given scaled variants by width.
+pub fn media_srcset(node_id: Uuid, variants: &[MediaVariant]) -> String {
+    variants
+        .iter()
+        .filter_map(|variant| {
+            let width = variant.width()?;
+            Some(format!("{} {width}w", media_url(node_id, *variant)))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaled_variants_are_made_from_the_original() {
+        let image = ImageData {
+            hash: None,
+            mime_type: None,
+            size: None,
+            src: None,
+            storage_path: None,
+            thumbnail_url: Some("http://storage/a_thumb.jpg".to_string()),
+            url: Some("http://storage/a.jpg".to_string()),
+        };
+        let id = Uuid::nil();
+
+        assert_eq!(
+            MediaVariant::Thumbnail.source(&image),
+            Some("http://storage/a.jpg")
+        );
+        let thumbnail_only = ImageData { url: None, ..image };
+        assert_eq!(
+            MediaVariant::Small.source(&thumbnail_only),
+            Some("http://storage/a_thumb.jpg")
+        );
+        assert_eq!(MediaVariant::Full.source(&thumbnail_only), None);
+        assert_eq!(
+            media_srcset(
+                id,
+                &[
+                    MediaVariant::Thumbnail,
+                    MediaVariant::Full,
+                    MediaVariant::Small
+                ]
+            ),
+            format!("/media/{id}/thumbnail 320w, /media/{id}/small 640w")
+        );
+        assert_eq!(
+            media_url(id, MediaVariant::Thumbnail),
+            format!("/media/{id}/thumbnail")
+        );
+        assert_eq!(
+            serde_json::from_str::<MediaVariant>("\"full\"").unwrap(),
+            MediaVariant::Full
+        );
+    }
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::*;
+    use crate::api_tokens::agent_user_id;
+    use crate::components::tree::{NodeType, TreeNode};
+    use crate::duplicates::{PERCEPTUAL_HASH_HEIGHT, PERCEPTUAL_HASH_WIDTH, perceptual_hash};
+    use crate::hmac::build_hmac;
+    use crate::proxy_tree::cached_tree;
+    use crate::sessions::error_response;
+    use crate::state::AppState;
+    use axum::{
+        body::{Body, Bytes},
+        extract::{Path, State},
+        http::{HeaderMap, HeaderValue, StatusCode, header},
+        response::{IntoResponse, Response},
+    };
+    use axum_extra::extract::CookieJar;
+    use futures::{Stream, StreamExt};
+    use image::codecs::jpeg::JpegEncoder;
+    use image::codecs::webp::WebPEncoder;
+    use image::{DynamicImage, ExtendedColorType, ImageDecoder, ImageReader, Limits};
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::{Mutex, OwnedMutexGuard, Semaphore};
+    use tokio_util::io::ReaderStream;
+
+    const CACHE_CONTROL: &str = "private, max-age=86400";
+    /// A share may be revoked, so browsers ask again soon for shared images.
+    const SHARED_CACHE_CONTROL: &str = "private, max-age=60, must-revalidate";
+    /// Larger originals are served as they are instead of scaled.
+    const MAX_RENDER_SOURCE_BYTES: u64 = 64 * 1024 * 1024;
+    /// Decoded pixels of one original, 8K RGBA fits.
+    const MAX_RENDER_ALLOC: u64 = 256 * 1024 * 1024;
+    const JPEG_QUALITY: u8 = 82;
+    /// Remembered perceptual hashes, forgotten all at once beyond this.
+    const MAX_HASHES: usize = 100_000;
+    /// Anything else could be rendered as a document on this origin.
+    const IMAGE_TYPES: [&str; 6] = [
+        "image/jpeg",
+        "image/png",
+        "image/webp",
+        "image/heic",
+        "image/gif",
+        "image/avif",
+    ];
+
+    /// Image leaves of one cached tree, rebuilt when the tree cache lets it go.
+    struct MediaIndex {
+        tree: Arc<Vec<TreeNode>>,
+        images: HashMap<Uuid, MediaLeaf>,
+    }
+
+    #[derive(Clone)]
+    struct MediaLeaf {
+        image: ImageData,
+        own: bool,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct CachedMedia {
+        content_type: String,
+        etag: String,
+        len: u64,
+        /// Recency of the last response, higher is newer.
+        #[serde(skip)]
+        used: u64,
+    }
+
+    #[derive(Default)]
+    struct CacheState {
+        entries: HashMap<String, CachedMedia>,
+        total: u64,
+        tick: u64,
+    }
+
+    /// Objects served under `/media`, kept in `dir` up to `max_bytes`; the least
+    /// recently served are evicted first. Each object is stored as `{key}` with its
+    /// headers in `{key}.json`, see [`cache_key`].
+    pub struct MediaStore {
+        dir: PathBuf,
+        max_bytes: u64,
+        cache: Mutex<CacheState>,
+        indexes: Mutex<HashMap<String, Arc<MediaIndex>>>,
+        /// Scaling is CPU and memory heavy, one per core at a time.
+        renders: Semaphore,
+        /// Perceptual hashes of thumbnails by cache key.
+        hashes: Mutex<HashMap<String, u64>>,
+        /// Keys being fetched or rendered, see [`MediaStore::fill_lock`].
+        filling: std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    }
+
+    /// Held while a key is filled; forgets the key's lock when nobody waits for it.
+    struct FillGuard<'a> {
+        filling: &'a std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>,
+        key: String,
+        guard: Option<OwnedMutexGuard<()>>,
+    }
+
+    impl Drop for FillGuard<'_> {
+        fn drop(&mut self) {
+            self.guard.take();
+            let mut filling = self.filling.lock().unwrap_or_else(|e| e.into_inner());
+            if filling
+                .get(&self.key)
+                .is_some_and(|lock| Arc::strong_count(lock) == 1)
+            {
+                filling.remove(&self.key);
+            }
+        }
+    }
+
+    impl MediaStore {
+        /// Loads the objects cached by a previous run, oldest first.
+        pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+            let dir = dir.into();
+            let mut found = Vec::new();
+            for path in std::fs::read_dir(&dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+            {
+                if path.extension().is_some_and(|ext| ext == "tmp") {
+                    let _ = std::fs::remove_file(&path);
+                    continue;
+                }
+                if path.extension().is_none_or(|ext| ext != "json") {
+                    continue;
+                }
+                let data = path.with_extension("");
+                let meta = std::fs::read(&path)
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice::<CachedMedia>(&bytes).ok());
+                let modified = std::fs::metadata(&data)
+                    .ok()
+                    .filter(|metadata| meta.as_ref().is_some_and(|m| m.len == metadata.len()))
+                    .and_then(|metadata| metadata.modified().ok());
+                match (meta, modified, data.file_name().and_then(|n| n.to_str())) {
+                    (Some(meta), Some(modified), Some(key)) => {
+                        found.push((modified, key.to_string(), meta));
+                    }
+                    _ => {
+                        let _ = std::fs::remove_file(&path);
+                        let _ = std::fs::remove_file(&data);
+                    }
+                }
+            }
+            found.sort_by_key(|(modified, _, _)| *modified);
+
+            let mut cache = CacheState::default();
+            for (_, key, mut meta) in found {
+                cache.tick += 1;
+                meta.used = cache.tick;
+                cache.total += meta.len;
+                cache.entries.insert(key, meta);
+            }
+            let evicted = evict(&mut cache, max_bytes);
+            let store = Self {
+                dir,
+                max_bytes,
+                cache: Mutex::new(cache),
+                indexes: Mutex::new(HashMap::new()),
+                renders: Semaphore::new(
+                    std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+                ),
+                hashes: Mutex::new(HashMap::new()),
+                filling: std::sync::Mutex::new(HashMap::new()),
+            };
+            for key in evicted {
+                store.remove_files(&key);
+            }
+            store
+        }
+
+        fn data_path(&self, key: &str) -> PathBuf {
+            self.dir.join(key)
+        }
+
+        fn remove_files(&self, key: &str) {
+            let _ = std::fs::remove_file(self.data_path(key));
+            let _ = std::fs::remove_file(self.data_path(key).with_extension("json"));
+        }
+
+        async fn cached(&self, key: &str) -> Option<(CachedMedia, tokio::fs::File)> {
+            let meta = {
+                let mut cache = self.cache.lock().await;
+                cache.tick += 1;
+                let tick = cache.tick;
+                let meta = cache.entries.get_mut(key)?;
+                meta.used = tick;
+                meta.clone()
+            };
+            match tokio::fs::File::open(self.data_path(key)).await {
+                Ok(file) => Some((meta, file)),
+                Err(_) => {
+                    // Removed behind our back, fetched again
+                    let mut cache = self.cache.lock().await;
+                    if let Some(meta) = cache.entries.remove(key) {
+                        cache.total -= meta.len;
+                    }
+                    None
+                }
+            }
+        }
+
+        /// Writes an object to disk and caches it; one larger than the whole cache
+        /// is served from an unlinked file.
+        async fn store<S, E>(
+            &self,
+            key: &str,
+            content_type: String,
+            mut body: S,
+        ) -> anyhow::Result<(CachedMedia, tokio::fs::File)>
+        where
+            S: Stream<Item = Result<Bytes, E>> + Unpin,
+            E: std::error::Error + Send + Sync + 'static,
+        {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            let tmp = self
+                .dir
+                .join(format!("{key}.{}.tmp", Uuid::now_v7().simple()));
+            let written = async {
+                let mut file = tokio::fs::File::create(&tmp).await?;
+                let mut hasher = Sha256::new();
+                let mut len = 0;
+                while let Some(chunk) = body.next().await {
+                    let chunk = chunk?;
+                    hasher.update(&chunk);
+                    len += chunk.len() as u64;
+                    file.write_all(&chunk).await?;
+                }
+                file.flush().await?;
+                anyhow::Ok((hex::encode(hasher.finalize()), len))
+            };
+            let (digest, len) = match written.await {
+                Ok(written) => written,
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&tmp).await;
+                    return Err(e);
+                }
+            };
+            let meta = CachedMedia {
+                content_type,
+                etag: format!("\"{}\"", &digest[..32]),
+                len,
+                used: 0,
+            };
+            if len > self.max_bytes {
+                let file = tokio::fs::File::open(&tmp).await?;
+                let _ = tokio::fs::remove_file(&tmp).await;
+                return Ok((meta, file));
+            }
+
+            let path = self.data_path(key);
+            tokio::fs::write(path.with_extension("json"), serde_json::to_vec(&meta)?).await?;
+            tokio::fs::rename(&tmp, &path).await?;
+            let file = tokio::fs::File::open(&path).await?;
+            let evicted = {
+                let mut cache = self.cache.lock().await;
+                cache.tick += 1;
+                let used = cache.tick;
+                if let Some(previous) = cache.entries.insert(
+                    key.to_string(),
+                    CachedMedia {
+                        used,
+                        ..meta.clone()
+                    },
+                ) {
+                    cache.total -= previous.len;
+                }
+                cache.total += len;
+                evict(&mut cache, self.max_bytes)
+            };
+            for key in evicted {
+                self.remove_files(&key);
+            }
+            Ok((meta, file))
+        }
+
+        /// One request fills a key, the others wait here and then find it cached.
+        async fn fill_lock(&self, key: &str) -> FillGuard<'_> {
+            let lock = self
+                .filling
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(key.to_string())
+                .or_default()
+                .clone();
+            FillGuard {
+                filling: &self.filling,
+                key: key.to_string(),
+                guard: Some(lock.lock_owned().await),
+            }
+        }
+
+        /// Scales an original down to `width` and caches it; `None` if it can't be
+        /// decoded here and is served as it is. Callers hold a `renders` permit.
+        async fn render(
+            &self,
+            key: &str,
+            original: Bytes,
+            width: u32,
+        ) -> anyhow::Result<Option<(CachedMedia, tokio::fs::File)>> {
+            let rendered =
+                tokio::task::spawn_blocking(move || render_variant(&original, width)).await?;
+            let (content_type, bytes) = match rendered {
+                Ok(rendered) => rendered,
+                Err(e) => {
+                    tracing::debug!(error = %e, "media: original not scaled");
+                    return Ok(None);
+                }
+            };
+            let body = futures::stream::iter([Ok::<_, std::io::Error>(Bytes::from(bytes))]);
+            self.store(key, content_type.to_string(), body)
+                .await
+                .map(Some)
+        }
+
+        /// The image leaf if it is in the user's tree. The index follows the cached
+        /// tree, so invalidating a tree also drops what it allowed here.
+        async fn image(
+            &self,
+            state: &AppState,
+            user_id: &str,
+            node_id: Uuid,
+        ) -> Result<Option<MediaLeaf>, Response> {
+            let tree = cached_tree(state, user_id, true).await?;
+            let current = self.indexes.lock().await.get(user_id).cloned();
+            if let Some(index) = current.filter(|index| Arc::ptr_eq(&index.tree, &tree)) {
+                return Ok(index.images.get(&node_id).cloned());
+            }
+            let index = Arc::new(MediaIndex {
+                images: image_leaves(&tree),
+                tree,
+            });
+            let mut indexes = self.indexes.lock().await;
+            // Trees no longer cached are only held here
+            indexes.retain(|_, index| Arc::strong_count(&index.tree) > 1);
+            indexes.insert(user_id.to_string(), index.clone());
+            Ok(index.images.get(&node_id).cloned())
+        }
+    }
+
+    /// SHA-256 of the object URL, with the variant for scaled ones.
+    fn cache_key(source: &str, variant: MediaVariant) -> String {
+        let digest = match variant {
+            MediaVariant::Full => Sha256::digest(source.as_bytes()),
+            _ => Sha256::digest(format!("{source}#{}", variant.as_str()).as_bytes()),
+        };
+        hex::encode(digest)
+    }
+
+    /// Decodes an image, turns it upright and scales it down to `width`. Encoded
+    /// as JPEG, or lossless WebP to keep transparency; metadata is not copied.
+    fn render_variant(bytes: &[u8], width: u32) -> image::ImageResult<(&'static str, Vec<u8>)> {
+        let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+        let mut limits = Limits::default();
+        limits.max_alloc = Some(MAX_RENDER_ALLOC);
+        reader.limits(limits);
+        let mut decoder = reader.into_decoder()?;
+        let orientation = decoder.orientation()?;
+        let mut image = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+        if image.width() > width {
+            image = image.thumbnail(width, u32::MAX);
+        }
+
+        let mut encoded = Vec::new();
+        if image.color().has_alpha() {
+            let rgba = image.to_rgba8();
+            WebPEncoder::new_lossless(&mut encoded).encode(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                ExtendedColorType::Rgba8,
+            )?;
+            Ok(("image/webp", encoded))
+        } else {
+            JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY)
+                .encode_image(&image.to_rgb8())?;
+            Ok(("image/jpeg", encoded))
+        }
+    }
+
+    /// [`perceptual_hash`] of an encoded image.
+    fn image_perceptual_hash(bytes: &[u8]) -> Option<u64> {
+        let samples = image::load_from_memory(bytes)
+            .ok()?
+            .resize_exact(
+                PERCEPTUAL_HASH_WIDTH,
+                PERCEPTUAL_HASH_HEIGHT,
+                image::imageops::FilterType::Triangle,
+            )
+            .to_rgba8();
+        perceptual_hash(samples.as_raw())
+    }
+
+    /// Removes the least recently served entries until the total fits.
+    fn evict(cache: &mut CacheState, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while cache.total > max_bytes {
+            let Some(key) = cache
+                .entries
+                .iter()
+                .min_by_key(|(_, meta)| meta.used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some(meta) = cache.entries.remove(&key) {
+                cache.total -= meta.len;
+            }
+            evicted.push(key);
+        }
+        evicted
+    }
+
+    /// Image leaves of a tree by id.
+    fn image_leaves(nodes: &[TreeNode]) -> HashMap<Uuid, MediaLeaf> {
+        nodes
+            .iter()
+            .filter(|node| node.node_type == NodeType::ImageLeaf)
+            .filter_map(|node| {
+                let image = serde_json::from_value(node.data.clone()).ok()?;
+                Some((
+                    node.id,
+                    MediaLeaf {
+                        image,
+                        own: node.own,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// The user's tree, with its leaves if `with_leafs`, as the agent has it now.
+    pub(crate) async fn agent_tree(
+        state: &AppState,
+        user_id: &str,
+        with_leafs: bool,
+    ) -> Result<Vec<TreeNode>, Response> {
+        let chat_config = &state.http_client.config.chat_config;
+        let url = if with_leafs {
+            format!(
+                "{}/agent/tree/{}?with_leafs=true",
+                chat_config.agent_api_url, user_id
+            )
+        } else {
+            format!("{}/agent/tree/{}", chat_config.agent_api_url, user_id)
+        };
+        let agent_secret = chat_config.agent_api_key.clone().unwrap_or_default();
+        let (timestamp, signature) = build_hmac(&agent_secret, &[]).map_err(|e| {
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Failed to sign request: {e}"),
+            )
+        })?;
+        let nodes = async {
+            state
+                .async_http_client
+                .get(&url)
+                .header("X-Timestamp", timestamp.to_string())
+                .header("X-Signature", signature)
+                .header("Accept", "application/json")
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Vec<TreeNode>>()
+                .await
+        };
+        nodes.await.map_err(|e| {
+            error_response(
+                StatusCode::BAD_GATEWAY,
+                &format!("Failed to fetch tree from agent: {e}"),
+            )
+        })
+    }
+
+    /// Declared type if it is a raster image type, else the type recorded on the node.
+    fn image_content_type(declared: Option<&str>, recorded: Option<&str>) -> String {
+        [declared, recorded]
+            .into_iter()
+            .flatten()
+            .map(|value| {
+                value
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_ascii_lowercase()
+            })
+            .find(|value| IMAGE_TYPES.contains(&value.as_str()))
+            .unwrap_or_else(|| "application/octet-stream".to_string())
+    }
+
+    /// Fetches an object from the agent or storage. Only the agent gets a
+    /// signature, anywhere else it could be replayed.
+    async fn fetch_object(state: &AppState, source: &str) -> Result<reqwest::Response, Response> {
+        let chat_config = &state.http_client.config.chat_config;
+        let agent_url = chat_config.agent_api_url.trim_end_matches('/');
+        let url = if source.starts_with('/') {
+            format!("{agent_url}{source}")
+        } else {
+            source.to_string()
+        };
+        let mut request = state.async_http_client.get(&url);
+        if url.starts_with(&format!("{agent_url}/")) {
+            let agent_secret = chat_config.agent_api_key.clone().unwrap_or_default();
+            let (timestamp, signature) = build_hmac(&agent_secret, &[]).map_err(|e| {
+                error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    &format!("Failed to sign request: {e}"),
+                )
+            })?;
+            request = request
+                .header("X-Timestamp", timestamp.to_string())
+                .header("X-Signature", signature);
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => Ok(response),
+            Ok(response) if response.status() == StatusCode::NOT_FOUND => {
+                Err(error_response(StatusCode::NOT_FOUND, "Image not found"))
+            }
+            Ok(response) => Err(error_response(
+                StatusCode::BAD_GATEWAY,
+                &format!("Image storage returned {}", response.status()),
+            )),
+            Err(e) => Err(error_response(
+                StatusCode::BAD_GATEWAY,
+                &format!("Failed to fetch image: {e}"),
+            )),
+        }
+    }
+
+    fn media_response(
+        meta: CachedMedia,
+        body: Body,
+        headers: &HeaderMap,
+        cache_control: &'static str,
+    ) -> Response {
+        let not_modified = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value.trim() == "*" || value.split(',').any(|tag| tag.trim() == meta.etag)
+            });
+        let mut response = if not_modified {
+            StatusCode::NOT_MODIFIED.into_response()
+        } else {
+            let mut response = body.into_response();
+            let headers = response.headers_mut();
+            if let Ok(value) = HeaderValue::from_str(&meta.content_type) {
+                headers.insert(header::CONTENT_TYPE, value);
+            }
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(meta.len));
+            response
+        };
+        let headers = response.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&meta.etag) {
+            headers.insert(header::ETAG, value);
+        }
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(cache_control),
+        );
+        response
+    }
+
+    /// `/media/{node_id}/{variant}`: an image of the session user's tree.
+    pub async fn media_handler(
+        State(state): State<AppState>,
+        Path((node_id, variant)): Path<(Uuid, MediaVariant)>,
+        jar: CookieJar,
+        headers: HeaderMap,
+    ) -> Response {
+        let Some(user_id) = agent_user_id(&state, &jar, None).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        let leaf = match state.media.image(&state, &user_id, node_id).await {
+            Ok(Some(leaf)) => leaf,
+            Ok(None) => return error_response(StatusCode::NOT_FOUND, "Image not found"),
+            Err(response) => return response,
+        };
+        let Some(source) = variant.source(&leaf.image) else {
+            return error_response(StatusCode::NOT_FOUND, "Image not found");
+        };
+        let cache_control = match leaf.own {
+            true => CACHE_CONTROL,
+            false => SHARED_CACHE_CONTROL,
+        };
+
+        match variant_media(&state, source, Some(&leaf.image), variant).await {
+            Ok((meta, body)) => media_response(meta, body, &headers, cache_control),
+            Err(response) => response,
+        }
+    }
+
+    /// A variant of an object, cached or made from its original.
+    async fn variant_media(
+        state: &AppState,
+        source: &str,
+        image: Option<&ImageData>,
+        variant: MediaVariant,
+    ) -> Result<(CachedMedia, Body), Response> {
+        let key = cache_key(source, variant);
+        if let Some((meta, file)) = state.media.cached(&key).await {
+            return Ok((meta, file_body(file)));
+        }
+        let _filling = state.media.fill_lock(&key).await;
+        if let Some((meta, file)) = state.media.cached(&key).await {
+            return Ok((meta, file_body(file)));
+        }
+        let (meta, mut file) = original(state, source, image).await?;
+        let Some(width) = variant
+            .width()
+            .filter(|_| meta.len <= MAX_RENDER_SOURCE_BYTES)
+        else {
+            return Ok((meta, file_body(file)));
+        };
+
+        // Taken before reading, so waiting requests don't hold originals in memory
+        let Ok(_permit) = state.media.renders.acquire().await else {
+            return Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to scale image",
+            ));
+        };
+        let mut bytes = Vec::with_capacity(meta.len as usize);
+        if let Err(e) = file.read_to_end(&mut bytes).await {
+            tracing::warn!(error = %e, "media: failed to read original");
+            return Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read image",
+            ));
+        }
+        let bytes = Bytes::from(bytes);
+        match state.media.render(&key, bytes.clone(), width).await {
+            Ok(Some((meta, file))) => Ok((meta, file_body(file))),
+            Ok(None) => Ok((meta, Body::from(bytes))),
+            Err(e) => {
+                tracing::warn!(error = %e, "media: failed to scale image");
+                Err(error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to scale image",
+                ))
+            }
+        }
+    }
+
+    /// Perceptual hash of an image leaf, taken from its thumbnail once.
+    pub(crate) async fn leaf_perceptual_hash(state: &AppState, image: &ImageData) -> Option<u64> {
+        let source = MediaVariant::Thumbnail.source(image)?;
+        let key = cache_key(source, MediaVariant::Thumbnail);
+        if let Some(hash) = state.media.hashes.lock().await.get(&key) {
+            return Some(*hash);
+        }
+        let (_, body) = variant_media(state, source, Some(image), MediaVariant::Thumbnail)
+            .await
+            .ok()?;
+        let bytes = axum::body::to_bytes(body, MAX_RENDER_SOURCE_BYTES as usize)
+            .await
+            .ok()?;
+        let hash = tokio::task::spawn_blocking(move || image_perceptual_hash(&bytes))
+            .await
+            .ok()??;
+        let mut hashes = state.media.hashes.lock().await;
+        if hashes.len() >= MAX_HASHES {
+            hashes.clear();
+        }
+        hashes.insert(key, hash);
+        Some(hash)
+    }
+
+    fn file_body(file: tokio::fs::File) -> Body {
+        Body::from_stream(ReaderStream::new(file))
+    }
+
+    /// The original object, from the cache or fetched and cached.
+    async fn original(
+        state: &AppState,
+        source: &str,
+        image: Option<&ImageData>,
+    ) -> Result<(CachedMedia, tokio::fs::File), Response> {
+        let key = cache_key(source, MediaVariant::Full);
+        if let Some(cached) = state.media.cached(&key).await {
+            return Ok(cached);
+        }
+        let response = fetch_object(state, source).await?;
+        let content_type = image_content_type(
+            response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok()),
+            image.and_then(|image| image.mime_type.as_deref()),
+        );
+        state
+            .media
+            .store(&key, content_type, response.bytes_stream())
+            .await
+            .map_err(|e| {
+                tracing::warn!(error = %e, "media: failed to fetch object");
+                error_response(StatusCode::BAD_GATEWAY, "Failed to fetch image")
+            })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::components::tree::test_node;
+
+        #[test]
+        fn shared_leaves_are_marked_for_revalidation() {
+            let leaf = |id, own| TreeNode {
+                data: serde_json::json!({ "url": format!("http://storage/{id}.jpg") }),
+                ..test_node(id, Some(1), NodeType::ImageLeaf, own)
+            };
+            let nodes = [
+                test_node(1, None, NodeType::Branch, true),
+                leaf(2, true),
+                leaf(3, false),
+            ];
+            let leaves = image_leaves(&nodes);
+
+            assert_eq!(leaves.len(), 2);
+            assert!(leaves[&Uuid::from_u128(2)].own);
+            assert!(!leaves[&Uuid::from_u128(3)].own);
+            assert_eq!(
+                leaves[&Uuid::from_u128(3)].image.url.as_deref(),
+                Some("http://storage/3.jpg")
+            );
+        }
+
+        async fn put(store: &MediaStore, key: &str, bytes: &'static [u8]) {
+            let body = futures::stream::iter([Ok::<_, std::io::Error>(Bytes::from_static(bytes))]);
+            store
+                .store(key, "image/png".to_string(), body)
+                .await
+                .unwrap();
+        }
+
+        #[tokio::test]
+        async fn a_key_is_filled_by_one_request_at_a_time() {
+            let dir = std::env::temp_dir().join(format!("gmr-media-{}", Uuid::now_v7()));
+            let store = Arc::new(MediaStore::open(&dir, 10));
+            let first = store.fill_lock("a").await;
+            let other = store.fill_lock("b").await;
+
+            let waiting = tokio::spawn({
+                let store = store.clone();
+                async move {
+                    let _second = store.fill_lock("a").await;
+                }
+            });
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            assert!(!waiting.is_finished());
+            drop(first);
+            waiting.await.unwrap();
+            drop(other);
+            assert!(store.filling.lock().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn cache_evicts_least_recently_served_and_survives_restart() {
+            let dir = std::env::temp_dir().join(format!("gmr-media-{}", Uuid::now_v7()));
+            let store = MediaStore::open(&dir, 10);
+            put(&store, "a", b"aaaa").await;
+            put(&store, "b", b"bbbb").await;
+            assert!(store.cached("a").await.is_some());
+            put(&store, "c", b"cccc").await;
+
+            assert!(store.cached("b").await.is_none());
+            assert!(!dir.join("b").exists());
+            let (meta, _) = store.cached("a").await.unwrap();
+            assert_eq!(meta.content_type, "image/png");
+            assert_eq!(meta.len, 4);
+
+            // Larger than the whole cache: served, not kept
+            put(&store, "d", b"ddddddddddddddd").await;
+            assert!(store.cached("d").await.is_none());
+
+            let reopened = MediaStore::open(&dir, 10);
+            assert_eq!(reopened.cached("a").await.unwrap().0.etag, meta.etag);
+            assert!(reopened.cached("c").await.is_some());
+            assert_eq!(reopened.cache.lock().await.total, 8);
+
+            let _ = std::fs::remove_dir_all(dir);
+        }
+
+        fn encoded(image: DynamicImage) -> Vec<u8> {
+            let mut bytes = Cursor::new(Vec::new());
+            image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+            bytes.into_inner()
+        }
+
+        #[test]
+        fn variants_are_scaled_down_and_keep_transparency() {
+            let photo = encoded(DynamicImage::new_rgb8(800, 400));
+            let (content_type, bytes) = render_variant(&photo, 320).unwrap();
+            assert_eq!(content_type, "image/jpeg");
+            let scaled = image::load_from_memory(&bytes).unwrap();
+            assert_eq!((scaled.width(), scaled.height()), (320, 160));
+
+            // Never scaled up
+            let (_, bytes) = render_variant(&photo, 1920).unwrap();
+            assert_eq!(image::load_from_memory(&bytes).unwrap().width(), 800);
+
+            let logo = encoded(DynamicImage::new_rgba8(64, 64));
+            assert_eq!(render_variant(&logo, 320).unwrap().0, "image/webp");
+
+            assert!(render_variant(b"\0\0\0\x18ftypheic", 320).is_err());
+            assert_ne!(
+                cache_key("http://storage/a.jpg", MediaVariant::Small),
+                cache_key("http://storage/a.jpg", MediaVariant::Full)
+            );
+        }
+
+        #[test]
+        fn only_raster_image_types_are_served() {
+            assert_eq!(
+                image_content_type(Some("image/JPEG; charset=binary"), None),
+                "image/jpeg"
+            );
+            assert_eq!(
+                image_content_type(Some("application/octet-stream"), Some("image/webp")),
+                "image/webp"
+            );
+            assert_eq!(
+                image_content_type(Some("text/html"), Some("image/svg+xml")),
+                "application/octet-stream"
+            );
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub use ssr::{MediaStore, media_handler};
+#[cfg(feature = "ssr")]
+pub(crate) use ssr::{agent_tree, leaf_perceptual_hash};
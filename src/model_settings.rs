@@ -57,13 +57,17 @@ pub struct UpdateModelsResponse {
 #[cfg(feature = "ssr")]
 mod ssr {
     use super::UpdateModelsRequest;
+    use crate::api_tokens::ApiPrincipal;
+    use crate::audit::{self, AuditAction, AuditActor};
+    use crate::ssr::correlation_id;
     use crate::{hmac::build_hmac, state::AppState};
     use axum::{
-        Json,
+        Extension, Json,
         extract::{Path, State},
-        http::StatusCode,
-        response::IntoResponse,
+        http::{HeaderMap, StatusCode},
+        response::{IntoResponse, Response},
     };
+    use axum_extra::extract::CookieJar;
     use serde_json::Value;
 
     pub async fn get_models_handler(
@@ -110,8 +114,33 @@ mod ssr {
     pub async fn update_models_handler(
         State(state): State<AppState>,
         Path(user_id): Path<String>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
         Json(req): Json<UpdateModelsRequest>,
-    ) -> impl IntoResponse {
+    ) -> Response {
+        let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+        let request_id = correlation_id(&headers);
+        let (response, agent_status) = update_models(&state, &user_id, &request_id, req).await;
+        audit::record(
+            &state,
+            actor,
+            AuditAction::ModelsUpdate,
+            &user_id,
+            request_id,
+            response.status(),
+            agent_status,
+        )
+        .await;
+        response
+    }
+
+    async fn update_models(
+        state: &AppState,
+        user_id: &str,
+        request_id: &str,
+        req: UpdateModelsRequest,
+    ) -> (Response, Option<StatusCode>) {
         let chat_config = &state.http_client.config.chat_config;
         let agent_url = format!("{}/agent/models/{}", chat_config.agent_api_url, user_id);
         let agent_secret = chat_config.agent_api_key.clone().unwrap_or_default();
@@ -120,12 +149,15 @@ mod ssr {
             Ok(body) => body,
             Err(e) => {
                 return (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({
-                        "error": format!("Failed to serialize request: {}", e)
-                    })),
-                )
-                    .into_response();
+                    (
+                        StatusCode::BAD_REQUEST,
+                        Json(serde_json::json!({
+                            "error": format!("Failed to serialize request: {}", e)
+                        })),
+                    )
+                        .into_response(),
+                    None,
+                );
             }
         };
 
@@ -133,18 +165,22 @@ mod ssr {
             Ok(value) => value,
             Err(e) => {
                 return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({
-                        "error": format!("Failed to sign request: {}", e)
-                    })),
-                )
-                    .into_response();
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(serde_json::json!({
+                            "error": format!("Failed to sign request: {}", e)
+                        })),
+                    )
+                        .into_response(),
+                    None,
+                );
             }
         };
 
         match state
             .async_http_client
             .put(&agent_url)
+            .header("X-Request-Id", request_id)
             .header("X-Timestamp", timestamp.to_string())
             .header("X-Signature", signature)
             .header("Content-Type", "application/json")
@@ -153,14 +189,20 @@ mod ssr {
             .send()
             .await
         {
-            Ok(response) => forward_json_response(response).await,
+            Ok(response) => {
+                let status = response.status();
+                (forward_json_response(response).await, Some(status))
+            }
             Err(e) => (
-                StatusCode::BAD_GATEWAY,
-                Json(serde_json::json!({
-                    "error": format!("Failed to update models through agent: {}", e)
-                })),
-            )
-                .into_response(),
+                (
+                    StatusCode::BAD_GATEWAY,
+                    Json(serde_json::json!({
+                        "error": format!("Failed to update models through agent: {}", e)
+                    })),
+                )
+                    .into_response(),
+                None,
+            ),
         }
     }
 
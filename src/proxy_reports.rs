@@ -1,12 +1,25 @@
+use crate::api_tokens::ApiPrincipal;
+use crate::audit::{self, AuditAction, AuditActor};
+use crate::hmac::{HmacSigner, TRAILER_SIGNATURE, TrailerSigner};
+use crate::proxy_tree::invalidate_node;
+use crate::sessions::error_response;
+use crate::sharing::require_edit;
+use crate::ssr::correlation_id;
+use crate::upload::{StagedImage, form_content_type, new_boundary, stream_upload};
 use crate::{hmac::build_hmac, state::AppState};
 use axum::{
-    Json,
+    Extension, Json,
     body::Bytes,
-    extract::{Path, State},
+    extract::{Multipart, Path, State},
     http::{HeaderMap, StatusCode, header},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
+use axum_extra::extract::CookieJar;
+use futures::StreamExt;
+use futures::channel::{mpsc, oneshot};
 use serde_json::Value;
+use tokio::io::AsyncReadExt;
+use tokio_util::io::ReaderStream;
 
 pub async fn proxy_reports_handler(
     State(state): State<AppState>,
@@ -52,8 +65,43 @@ pub async fn proxy_reports_handler(
 pub async fn proxy_update_report_handler(
     State(state): State<AppState>,
     Path(node_id): Path<String>,
+    jar: CookieJar,
+    principal: Option<Extension<ApiPrincipal>>,
+    headers: HeaderMap,
     Json(req): Json<Value>,
-) -> impl IntoResponse {
+) -> Response {
+    let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+    let request_id = correlation_id(&headers);
+    let (response, agent_status) =
+        match require_edit(&state, &jar, principal.as_deref(), &node_id).await {
+            Ok(user_id) => {
+                let result = update_report(&state, &node_id, &request_id, req).await;
+                if result.0.status().is_success() {
+                    invalidate_node(&state, &user_id, &node_id).await;
+                }
+                result
+            }
+            Err(response) => (response, None),
+        };
+    audit::record(
+        &state,
+        actor,
+        AuditAction::ReportUpdate,
+        &node_id,
+        request_id,
+        response.status(),
+        agent_status,
+    )
+    .await;
+    response
+}
+
+async fn update_report(
+    state: &AppState,
+    node_id: &str,
+    request_id: &str,
+    req: Value,
+) -> (Response, Option<StatusCode>) {
     let chat_config = &state.http_client.config.chat_config;
     let agent_url = format!("{}/agent/reports/{}", chat_config.agent_api_url, node_id);
     let agent_secret = chat_config.agent_api_key.clone().unwrap_or_default();
@@ -62,12 +110,15 @@ pub async fn proxy_update_report_handler(
         Ok(body) => body,
         Err(e) => {
             return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({
-                    "error": format!("Failed to serialize request: {}", e)
-                })),
-            )
-                .into_response();
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({
+                        "error": format!("Failed to serialize request: {}", e)
+                    })),
+                )
+                    .into_response(),
+                None,
+            );
         }
     };
 
@@ -75,18 +126,22 @@ pub async fn proxy_update_report_handler(
         Ok(value) => value,
         Err(e) => {
             return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": format!("Failed to sign request: {}", e)
-                })),
-            )
-                .into_response();
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({
+                        "error": format!("Failed to sign request: {}", e)
+                    })),
+                )
+                    .into_response(),
+                None,
+            );
         }
     };
 
     match state
         .async_http_client
         .put(&agent_url)
+        .header("X-Request-Id", request_id)
         .header("X-Timestamp", timestamp.to_string())
         .header("X-Signature", signature)
         .header("Content-Type", "application/json")
@@ -95,88 +150,379 @@ pub async fn proxy_update_report_handler(
         .send()
         .await
     {
-        Ok(response) => forward_empty_or_json_response(response).await,
+        Ok(response) => {
+            let status = response.status();
+            (forward_empty_or_json_response(response).await, Some(status))
+        }
         Err(e) => (
-            StatusCode::BAD_GATEWAY,
-            Json(serde_json::json!({
-                "error": format!("Failed to update report through agent: {}", e)
-            })),
-        )
-            .into_response(),
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({
+                    "error": format!("Failed to update report through agent: {}", e)
+                })),
+            )
+                .into_response(),
+            None,
+        ),
     }
 }
 pub async fn proxy_upload_image_handler(
     State(state): State<AppState>,
     Path(parent_id): Path<String>,
+    jar: CookieJar,
+    principal: Option<Extension<ApiPrincipal>>,
     headers: HeaderMap,
-    body: Bytes,
-) -> impl IntoResponse {
+    multipart: Multipart,
+) -> Response {
+    let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+    let request_id = correlation_id(&headers);
+    let (response, agent_status) =
+        match require_edit(&state, &jar, principal.as_deref(), &parent_id).await {
+            Ok(user_id) => {
+                let result = upload_image(&state, &parent_id, &request_id, multipart).await;
+                if result.0.status().is_success() {
+                    invalidate_node(&state, &user_id, &parent_id).await;
+                }
+                result
+            }
+            Err(response) => (response, None),
+        };
+    audit::record(
+        &state,
+        actor,
+        AuditAction::ImageUpload,
+        &parent_id,
+        request_id,
+        response.status(),
+        agent_status,
+    )
+    .await;
+    response
+}
+
+/// Pieces of the forwarded body in flight, the back-pressure on the client.
+const UPLOAD_BODY_BUFFER: usize = 4;
+
+/// Forwards the form to the agent. With `trailer_signature` it is streamed while it
+/// arrives and the signature follows the body (see `TRAILER_SIGNATURE`); otherwise
+/// it is collected first and signed up front.
+async fn upload_image(
+    state: &AppState,
+    parent_id: &str,
+    request_id: &str,
+    multipart: Multipart,
+) -> (Response, Option<StatusCode>) {
+    if !state.http_client.config.upload_config.trailer_signature {
+        return upload_buffered_image(state, parent_id, request_id, multipart).await;
+    }
+    let chat_config = &state.http_client.config.chat_config;
+    let agent_url = format!(
+        "{}/agent/images/upload/{}",
+        chat_config.agent_api_url, parent_id
+    );
+    let agent_secret = chat_config.agent_api_key.clone().unwrap_or_default();
+    let signer = match TrailerSigner::new(&agent_secret) {
+        Ok(signer) => signer,
+        Err(e) => {
+            return (
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({
+                        "error": format!("Failed to sign request: {}", e)
+                    })),
+                )
+                    .into_response(),
+                None,
+            );
+        }
+    };
+    let boundary = new_boundary();
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let (body_tx, body_rx) = mpsc::channel(UPLOAD_BODY_BUFFER);
+
+    let forwarded = stream_upload(
+        multipart,
+        &state.http_client.config.upload_config,
+        &boundary,
+        Some(signer),
+        ready_tx,
+        body_tx,
+    );
+    let sent = async {
+        // Not sent when the image head is rejected
+        ready_rx.await.ok()?;
+        Some(
+            state
+                .async_http_client
+                .post(&agent_url)
+                .header("X-Request-Id", request_id)
+                .header("X-Signature", TRAILER_SIGNATURE)
+                .header(header::CONTENT_TYPE, form_content_type(&boundary))
+                .body(reqwest::Body::wrap_stream(body_rx))
+                .send()
+                .await,
+        )
+    };
+    let (forwarded, sent) = tokio::join!(forwarded, sent);
+
+    if let Err(e) = forwarded {
+        tracing::warn!(request_id = %request_id, code = e.code(), "upload rejected");
+        return (e.into_response(), None);
+    }
+    match sent {
+        Some(Ok(response)) => {
+            let status = response.status();
+            (forward_json_response(response).await, Some(status))
+        }
+        Some(Err(e)) => (
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({
+                    "error": format!("Failed to upload image through agent: {}", e)
+                })),
+            )
+                .into_response(),
+            None,
+        ),
+        None => (
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": "Upload was not forwarded" })),
+            )
+                .into_response(),
+            None,
+        ),
+    }
+}
+
+/// Collects the scrubbed form, at most `max_bytes` of image, and sends it signed
+/// like every other agent request.
+async fn upload_buffered_image(
+    state: &AppState,
+    parent_id: &str,
+    request_id: &str,
+    multipart: Multipart,
+) -> (Response, Option<StatusCode>) {
     let chat_config = &state.http_client.config.chat_config;
     let agent_url = format!(
         "{}/agent/images/upload/{}",
         chat_config.agent_api_url, parent_id
     );
     let agent_secret = chat_config.agent_api_key.clone().unwrap_or_default();
+    let boundary = new_boundary();
+    let (ready_tx, _ready_rx) = oneshot::channel();
+    let (body_tx, body_rx) = mpsc::channel(UPLOAD_BODY_BUFFER);
+
+    let (forwarded, pieces) = tokio::join!(
+        stream_upload(
+            multipart,
+            &state.http_client.config.upload_config,
+            &boundary,
+            None,
+            ready_tx,
+            body_tx,
+        ),
+        body_rx.collect::<Vec<_>>()
+    );
+    if let Err(e) = forwarded {
+        tracing::warn!(request_id = %request_id, code = e.code(), "upload rejected");
+        return (e.into_response(), None);
+    }
+    let body: Vec<Bytes> = pieces.into_iter().filter_map(Result::ok).collect();
+    let body = body.concat();
 
     let (timestamp, signature) = match build_hmac(&agent_secret, &body) {
         Ok(value) => value,
         Err(e) => {
             return (
-                StatusCode::INTERNAL_SERVER_ERROR,
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({
+                        "error": format!("Failed to sign request: {}", e)
+                    })),
+                )
+                    .into_response(),
+                None,
+            );
+        }
+    };
+    match state
+        .async_http_client
+        .post(&agent_url)
+        .header("X-Request-Id", request_id)
+        .header("X-Timestamp", timestamp.to_string())
+        .header("X-Signature", signature)
+        .header(header::CONTENT_TYPE, form_content_type(&boundary))
+        .body(body)
+        .send()
+        .await
+    {
+        Ok(response) => {
+            let status = response.status();
+            (forward_json_response(response).await, Some(status))
+        }
+        Err(e) => (
+            (
+                StatusCode::BAD_GATEWAY,
                 Json(serde_json::json!({
-                    "error": format!("Failed to sign request: {}", e)
+                    "error": format!("Failed to upload image through agent: {}", e)
                 })),
             )
-                .into_response();
+                .into_response(),
+            None,
+        ),
+    }
+}
+
+/// Streams a staged upload to the agent. The body is signed in a first pass over
+/// the file, so it is never held in memory.
+pub(crate) async fn upload_staged_image(
+    state: &AppState,
+    parent_id: &str,
+    request_id: &str,
+    staged: &StagedImage,
+) -> (Response, Option<StatusCode>) {
+    let chat_config = &state.http_client.config.chat_config;
+    let agent_url = format!(
+        "{}/agent/images/upload/{}",
+        chat_config.agent_api_url, parent_id
+    );
+    let agent_secret = chat_config.agent_api_key.clone().unwrap_or_default();
+    let (content_type, head, tail) = staged.frame();
+
+    let signed = async {
+        let mut signer = HmacSigner::new(&agent_secret)?;
+        signer.update(&head);
+        let mut file = tokio::fs::File::open(&staged.path).await?;
+        let mut buffer = vec![0; 256 * 1024];
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            signer.update(&buffer[..read]);
+        }
+        signer.update(&tail);
+        anyhow::Ok((signer.finish(), tokio::fs::File::open(&staged.path).await?))
+    };
+    let ((timestamp, signature), file) = match signed.await {
+        Ok(value) => value,
+        Err(e) => {
+            return (
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({
+                        "error": format!("Failed to sign request: {}", e)
+                    })),
+                )
+                    .into_response(),
+                None,
+            );
         }
     };
 
-    let mut request = state
+    let content_length = head.len() as u64 + staged.len + tail.len() as u64;
+    let body = futures::stream::once(async { Ok(Bytes::from(head)) })
+        .chain(ReaderStream::new(file))
+        .chain(futures::stream::once(async { Ok(Bytes::from(tail)) }));
+
+    match state
         .async_http_client
         .post(&agent_url)
+        .header("X-Request-Id", request_id)
         .header("X-Timestamp", timestamp.to_string())
         .header("X-Signature", signature)
-        .body(body);
-    if let Some(content_type) = headers.get(header::CONTENT_TYPE) {
-        request = request.header(header::CONTENT_TYPE, content_type.clone());
-    }
-
-    match request.send().await {
-        Ok(response) => forward_json_response(response).await,
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, content_length)
+        .body(reqwest::Body::wrap_stream(body))
+        .send()
+        .await
+    {
+        Ok(response) => {
+            let status = response.status();
+            (forward_json_response(response).await, Some(status))
+        }
         Err(e) => (
-            StatusCode::BAD_GATEWAY,
-            Json(serde_json::json!({
-                "error": format!("Failed to upload image through agent: {}", e)
-            })),
-        )
-            .into_response(),
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({
+                    "error": format!("Failed to upload image through agent: {}", e)
+                })),
+            )
+                .into_response(),
+            None,
+        ),
     }
 }
 
 pub async fn proxy_delete_image_handler(
     State(state): State<AppState>,
     Path(node_id): Path<String>,
-) -> impl IntoResponse {
+    jar: CookieJar,
+    principal: Option<Extension<ApiPrincipal>>,
+    headers: HeaderMap,
+) -> Response {
+    let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+    let request_id = correlation_id(&headers);
+    let (response, agent_status) =
+        match require_edit(&state, &jar, principal.as_deref(), &node_id).await {
+            Ok(user_id) => {
+                let result = delete_image(&state, &user_id, &node_id, &request_id).await;
+                if result.0.status().is_success() {
+                    invalidate_node(&state, &user_id, &node_id).await;
+                }
+                result
+            }
+            Err(response) => (response, None),
+        };
+    audit::record(
+        &state,
+        actor,
+        AuditAction::ImageDelete,
+        &node_id,
+        request_id,
+        response.status(),
+        agent_status,
+    )
+    .await;
+    response
+}
+
+async fn delete_image(
+    state: &AppState,
+    user_id: &str,
+    node_id: &str,
+    request_id: &str,
+) -> (Response, Option<StatusCode>) {
     let chat_config = &state.http_client.config.chat_config;
-    let agent_url = format!("{}/agent/images/{}", chat_config.agent_api_url, node_id);
+    let agent_url = format!(
+        "{}/agent/images/{}?user_id={}",
+        chat_config.agent_api_url,
+        node_id,
+        percent_encoding::utf8_percent_encode(user_id, percent_encoding::NON_ALPHANUMERIC)
+    );
     let agent_secret = chat_config.agent_api_key.clone().unwrap_or_default();
 
     let (timestamp, signature) = match build_hmac(&agent_secret, &[]) {
         Ok(value) => value,
         Err(e) => {
             return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": format!("Failed to sign request: {}", e)
-                })),
-            )
-                .into_response();
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({
+                        "error": format!("Failed to sign request: {}", e)
+                    })),
+                )
+                    .into_response(),
+                None,
+            );
         }
     };
 
     match state
         .async_http_client
         .delete(&agent_url)
+        .header("X-Request-Id", request_id)
         .header("X-Timestamp", timestamp.to_string())
         .header("X-Signature", signature)
         .send()
@@ -185,17 +531,20 @@ pub async fn proxy_delete_image_handler(
         Ok(response) => {
             let status = response.status();
             if status == StatusCode::NO_CONTENT || status == StatusCode::OK {
-                return status.into_response();
+                return (status.into_response(), Some(status));
             }
-            forward_json_response(response).await
+            (forward_json_response(response).await, Some(status))
         }
         Err(e) => (
-            StatusCode::BAD_GATEWAY,
-            Json(serde_json::json!({
-                "error": format!("Failed to delete image through agent: {}", e)
-            })),
-        )
-            .into_response(),
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({
+                    "error": format!("Failed to delete image through agent: {}", e)
+                })),
+            )
+                .into_response(),
+            None,
+        ),
     }
 }
 
@@ -213,7 +562,65 @@ async fn forward_json_response(response: reqwest::Response) -> axum::response::R
     }
 }
 
-async fn forward_empty_or_json_response(response: reqwest::Response) -> axum::response::Response {
+/// Sends a request signed with the agent key, `body` as JSON. Also returns the
+/// agent's status, `None` when the agent was not reached.
+pub(crate) async fn forward_signed(
+    state: &AppState,
+    method: reqwest::Method,
+    url: &str,
+    request_id: &str,
+    body: Option<Vec<u8>>,
+) -> (Response, Option<StatusCode>) {
+    let agent_secret = state
+        .http_client
+        .config
+        .chat_config
+        .agent_api_key
+        .clone()
+        .unwrap_or_default();
+    let (timestamp, signature) =
+        match build_hmac(&agent_secret, body.as_deref().unwrap_or_default()) {
+            Ok(value) => value,
+            Err(e) => {
+                return (
+                    error_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        &format!("Failed to sign request: {e}"),
+                    ),
+                    None,
+                );
+            }
+        };
+    let mut request = state
+        .async_http_client
+        .request(method, url)
+        .header("X-Request-Id", request_id)
+        .header("X-Timestamp", timestamp.to_string())
+        .header("X-Signature", signature)
+        .header("Accept", "application/json");
+    if let Some(body) = body {
+        request = request
+            .header("Content-Type", "application/json")
+            .body(body);
+    }
+    match request.send().await {
+        Ok(response) => {
+            let status = response.status();
+            (forward_empty_or_json_response(response).await, Some(status))
+        }
+        Err(e) => (
+            error_response(
+                StatusCode::BAD_GATEWAY,
+                &format!("Failed to reach agent: {e}"),
+            ),
+            None,
+        ),
+    }
+}
+
+pub(crate) async fn forward_empty_or_json_response(
+    response: reqwest::Response,
+) -> axum::response::Response {
     let status = response.status();
     if status == StatusCode::NO_CONTENT || response.content_length() == Some(0) {
         return status.into_response();
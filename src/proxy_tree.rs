@@ -1,78 +1,365 @@
-use crate::{hmac::build_hmac, state::AppState};
+use crate::components::tree::{NodeType, TreeNode};
+use crate::media::agent_tree;
+use crate::state::AppState;
 use axum::{
-    Json,
-    extract::{Path, State},
-    http::{HeaderMap, StatusCode},
-    response::IntoResponse,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
 };
-use serde_json::Value;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
 
-/// Proxy handler for tree API
+/// How long a fetched tree is served without asking the agent again.
+const TREE_CACHE_TTL: Duration = Duration::from_secs(30);
+/// Browsers keep the tree but revalidate it with `If-None-Match` on every use.
+const CACHE_CONTROL: &str = "private, no-cache";
+
+/// A fetched tree and when it was fetched.
+type CachedTree = (Instant, Arc<Vec<TreeNode>>);
+
+#[derive(Default)]
+struct TreeCacheState {
+    /// Bumped by every change, so fetches that started before it are not kept.
+    generation: u64,
+    /// By user id and whether the leaves were fetched too.
+    trees: HashMap<(String, bool), CachedTree>,
+}
+
+/// Users' trees as last fetched from the agent.
+#[derive(Default)]
+pub struct TreeCache {
+    state: Mutex<TreeCacheState>,
+}
+
+impl TreeCache {
+    /// Drops every tree, for changes that reach users unknown here (a grant
+    /// to a group).
+    pub async fn clear(&self) {
+        let mut state = self.state.lock().await;
+        state.generation += 1;
+        state.trees.clear();
+    }
+
+    /// Drops the trees of `users` and every tree showing one of `ids` or one of
+    /// their ancestors in the trees of `users`. Shared branches are in the
+    /// trees of their owner and grantees, so both are reached.
+    pub async fn invalidate(&self, users: &[&str], ids: &[Uuid]) {
+        let mut state = self.state.lock().await;
+        state.generation += 1;
+        let is_user = |user_id: &str| users.iter().any(|u| u.eq_ignore_ascii_case(user_id));
+        let mut affected: HashSet<Uuid> = ids.iter().copied().collect();
+        for ((user_id, _), (_, nodes)) in &state.trees {
+            if is_user(user_id) {
+                affected.extend(with_ancestors(nodes, ids));
+            }
+        }
+        state.trees.retain(|(user_id, _), (_, nodes)| {
+            !is_user(user_id) && !nodes.iter().any(|node| affected.contains(&node.id))
+        });
+    }
+
+    /// A tree without leaves is served from the one with them as well.
+    async fn get(&self, user_id: &str, with_leafs: bool) -> Result<Arc<Vec<TreeNode>>, u64> {
+        let state = self.state.lock().await;
+        let fresh = |leaves: bool| {
+            state
+                .trees
+                .get(&(user_id.to_string(), leaves))
+                .filter(|(fetched, _)| fetched.elapsed() < TREE_CACHE_TTL)
+                .map(|(_, nodes)| nodes.clone())
+        };
+        let nodes = match with_leafs {
+            true => fresh(true),
+            false => fresh(false).or_else(|| fresh(true)),
+        };
+        nodes.ok_or(state.generation)
+    }
+
+    async fn put(
+        &self,
+        user_id: &str,
+        with_leafs: bool,
+        generation: u64,
+        nodes: Arc<Vec<TreeNode>>,
+    ) {
+        let mut state = self.state.lock().await;
+        if state.generation != generation {
+            return;
+        }
+        state
+            .trees
+            .retain(|_, (fetched, _)| fetched.elapsed() < TREE_CACHE_TTL);
+        state
+            .trees
+            .insert((user_id.to_string(), with_leafs), (Instant::now(), nodes));
+    }
+}
+
+/// `ids` and the nodes above them in `nodes`.
+pub(crate) fn with_ancestors(nodes: &[TreeNode], ids: &[Uuid]) -> HashSet<Uuid> {
+    let parents: HashMap<Uuid, Option<Uuid>> =
+        nodes.iter().map(|node| (node.id, node.parent_id)).collect();
+    let mut found = HashSet::new();
+    for id in ids {
+        let mut current = Some(*id);
+        // Bounded in case the agent ever returns a cycle
+        for _ in 0..=nodes.len() {
+            let Some(id) = current.filter(|id| found.insert(*id)) else {
+                break;
+            };
+            current = parents.get(&id).copied().flatten();
+        }
+    }
+    found
+}
+
+/// Drops the trees showing `node_id` after `user_id` changed it.
+pub(crate) async fn invalidate_node(state: &AppState, user_id: &str, node_id: &str) {
+    match Uuid::parse_str(node_id) {
+        Ok(node_id) => state.trees.invalidate(&[user_id], &[node_id]).await,
+        Err(_) => state.trees.clear().await,
+    }
+}
+
+/// The user's tree, with its leaves if `with_leafs`, at most `TREE_CACHE_TTL` old.
+/// Without `with_leafs` it may still contain leaves.
+pub(crate) async fn cached_tree(
+    state: &AppState,
+    user_id: &str,
+    with_leafs: bool,
+) -> Result<Arc<Vec<TreeNode>>, Response> {
+    let generation = match state.trees.get(user_id, with_leafs).await {
+        Ok(nodes) => return Ok(nodes),
+        Err(generation) => generation,
+    };
+    let nodes = Arc::new(agent_tree(state, user_id, with_leafs).await?);
+    state
+        .trees
+        .put(user_id, with_leafs, generation, nodes.clone())
+        .await;
+    Ok(nodes)
+}
+
+/// Tree request: the whole tree by default, or one level at a time.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TreeQuery {
+    pub with_leafs: bool,
+    /// Only the roots and the top-level nodes below them.
+    pub lazy: bool,
+    /// Only the direct children of this node.
+    pub parent: Option<Uuid>,
+}
+
+/// The requested part of `nodes`, each with `has_children` set.
+fn select_nodes(nodes: &[TreeNode], query: &TreeQuery) -> Vec<TreeNode> {
+    let shown = |node: &&TreeNode| query.with_leafs || node.node_type != NodeType::ImageLeaf;
+    let parents: HashSet<Uuid> = nodes
+        .iter()
+        .filter(shown)
+        .filter_map(|node| node.parent_id)
+        .collect();
+    let roots: HashSet<Uuid> = nodes
+        .iter()
+        .filter(|node| node.node_type == NodeType::Root)
+        .map(|node| node.id)
+        .collect();
+    let ids: HashSet<Uuid> = nodes.iter().map(|node| node.id).collect();
+    // Branches shared by other users hang below nodes missing from the tree
+    let top_level = |node: &TreeNode| {
+        node.parent_id
+            .is_none_or(|id| roots.contains(&id) || !ids.contains(&id))
+    };
+    let selected = |node: &TreeNode| match query.parent {
+        Some(parent) => node.parent_id == Some(parent),
+        None if query.lazy => roots.contains(&node.id) || top_level(node),
+        None => true,
+    };
+    nodes
+        .iter()
+        .filter(shown)
+        .filter(|node| selected(node))
+        .map(|node| TreeNode {
+            has_children: parents.contains(&node.id),
+            ..node.clone()
+        })
+        .collect()
+}
+
+fn etag(body: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(&Sha256::digest(body)[..16]))
+}
+
+fn matches_etag(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.trim() == "*"
+                || value
+                    .split(',')
+                    .any(|tag| tag.trim().trim_start_matches("W/") == etag)
+        })
+}
+
+/// Proxy handler for tree API. `?lazy=true` and `?parent=` serve one level,
+/// answers carry an `ETag` and are `304 Not Modified` when it still matches.
 pub async fn proxy_tree_handler(
     State(state): State<AppState>,
     Path(user_id): Path<String>,
+    Query(mut query): Query<TreeQuery>,
     headers: HeaderMap,
-) -> impl IntoResponse {
-    let chat_config = &state.http_client.config.chat_config;
-    let agent_api_url = &chat_config.agent_api_url;
-    let agent_secret = chat_config.agent_api_key.clone().unwrap_or_default();
-    // Extract query parameters (with_leafs)
-    let with_leafs = headers
+) -> Response {
+    // Older clients ask for leaves with a header
+    query.with_leafs |= headers
         .get("x-with-leafs")
         .and_then(|v| v.to_str().ok())
         .and_then(|v| v.parse::<bool>().ok())
         .unwrap_or(false);
 
-    let url = if with_leafs {
-        format!("{}/agent/tree/{}?with_leafs=true", agent_api_url, user_id)
-    } else {
-        format!("{}/agent/tree/{}", agent_api_url, user_id)
+    let nodes = match cached_tree(&state, &user_id, query.with_leafs).await {
+        Ok(nodes) => nodes,
+        Err(response) => return response,
     };
-
-    let (timestamp, signature) = match build_hmac(&agent_secret, &[]) {
-        Ok(value) => value,
+    let body = match serde_json::to_vec(&select_nodes(&nodes, &query)) {
+        Ok(body) => body,
         Err(e) => {
-            return (
+            return crate::sessions::error_response(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": format!("Failed to sign request: {}", e)
-                })),
-            )
-                .into_response();
+                &format!("Failed to encode tree: {e}"),
+            );
         }
     };
-
-    match state
-        .async_http_client
-        .get(&url)
-        .header("X-Timestamp", timestamp.to_string())
-        .header("X-Signature", signature)
-        .header("Accept", "application/json")
-        .send()
-        .await
-    {
-        Ok(response) => {
-            let status = response.status();
-            match response.json::<Value>().await {
-                Ok(json) => (status, Json(json)).into_response(),
-                Err(e) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({
-                        "error": format!("Failed to parse response: {}", e)
-                    })),
-                )
-                    .into_response(),
-            }
-        }
-        Err(e) => (
-            StatusCode::BAD_GATEWAY,
-            Json(serde_json::json!({
-                "error": format!("Failed to fetch from backend: {}", e)
-            })),
+    let etag = etag(&body);
+    let mut response = if matches_etag(&headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        (
+            [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            )],
+            body,
         )
-            .into_response(),
+            .into_response()
+    };
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
     }
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(CACHE_CONTROL),
+    );
+    response
 }
 
-// Add router entry in main.rs:
-// .route("/api/proxy/tree/:user_id", get(proxy_tree_handler))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::tree::test_node;
+
+    fn node(id: u128, parent: Option<u128>, node_type: NodeType) -> TreeNode {
+        test_node(id, parent, node_type, true)
+    }
+
+    fn ids(nodes: &[TreeNode]) -> Vec<(u128, bool)> {
+        nodes
+            .iter()
+            .map(|node| (node.id.as_u128(), node.has_children))
+            .collect()
+    }
+
+    #[test]
+    fn levels_are_selected_with_child_flags() {
+        let nodes = vec![
+            node(1, None, NodeType::Root),
+            node(2, Some(1), NodeType::Branch),
+            node(3, Some(2), NodeType::Branch),
+            node(4, Some(3), NodeType::ImageLeaf),
+            node(5, Some(1), NodeType::Branch),
+            node(6, Some(9), NodeType::Branch),
+            node(7, Some(6), NodeType::Branch),
+        ];
+        let lazy = TreeQuery {
+            lazy: true,
+            ..TreeQuery::default()
+        };
+        assert_eq!(
+            ids(&select_nodes(&nodes, &lazy)),
+            [(1, true), (2, true), (5, false), (6, true)]
+        );
+        let children = TreeQuery {
+            parent: Some(Uuid::from_u128(2)),
+            ..TreeQuery::default()
+        };
+        assert_eq!(ids(&select_nodes(&nodes, &children)), [(3, false)]);
+        let with_leafs = TreeQuery {
+            parent: Some(Uuid::from_u128(2)),
+            with_leafs: true,
+            ..TreeQuery::default()
+        };
+        assert_eq!(ids(&select_nodes(&nodes, &with_leafs)), [(3, true)]);
+        assert_eq!(select_nodes(&nodes, &TreeQuery::default()).len(), 6);
+
+        let tag = etag(b"[]");
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_str(&format!("\"other\", W/{tag}")).unwrap(),
+        );
+        assert!(matches_etag(&headers, &tag));
+        assert!(!matches_etag(&headers, &etag(b"[{}]")));
+    }
+
+    #[tokio::test]
+    async fn clearing_drops_trees_and_fetches_in_flight() {
+        let cache = TreeCache::default();
+        let generation = cache.get("alice", true).await.unwrap_err();
+        cache.clear().await;
+        cache
+            .put("alice", true, generation, Arc::new(Vec::new()))
+            .await;
+        let generation = cache.get("alice", true).await.unwrap_err();
+
+        cache
+            .put("alice", true, generation, Arc::new(Vec::new()))
+            .await;
+        assert!(cache.get("alice", true).await.is_ok());
+        assert!(cache.get("alice", false).await.is_ok());
+        cache.clear().await;
+        assert!(cache.get("alice", true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn invalidation_drops_only_trees_showing_the_change() {
+        let owner = vec![
+            node(1, None, NodeType::Root),
+            node(2, Some(1), NodeType::Branch),
+            node(3, Some(2), NodeType::Branch),
+            node(4, Some(1), NodeType::Branch),
+        ];
+        // Bob sees alice's shared branch 2, carol nothing of hers
+        let grantee = vec![
+            node(10, None, NodeType::Root),
+            node(2, None, NodeType::Branch),
+        ];
+        let other = vec![node(20, None, NodeType::Root)];
+        assert_eq!(with_ancestors(&owner, &[Uuid::from_u128(3)]).len(), 3);
+
+        let cache = TreeCache::default();
+        for (user, nodes) in [("alice", owner), ("bob", grantee), ("carol", other)] {
+            let generation = cache.get(user, false).await.unwrap_err();
+            cache.put(user, false, generation, Arc::new(nodes)).await;
+        }
+        cache.invalidate(&["Alice"], &[Uuid::from_u128(3)]).await;
+        assert!(cache.get("alice", false).await.is_err());
+        assert!(cache.get("bob", false).await.is_err());
+        assert!(cache.get("carol", false).await.is_ok());
+    }
+}
@@ -0,0 +1,421 @@
+/// `error` code of the 429 body, localized by the client as `rate-limited`.
+pub const RATE_LIMITED_ERROR: &str = "rate_limited";
+
+/// Seconds to wait when the response is a 429 from the rate limiter.
+pub fn retry_after(response: &web_sys::Response) -> Option<u64> {
+    if response.status() != 429 {
+        return None;
+    }
+    response
+        .headers()
+        .get("Retry-After")
+        .ok()
+        .flatten()
+        .and_then(|value| value.trim().parse().ok())
+        .or(Some(1))
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::RATE_LIMITED_ERROR;
+    use crate::api_tokens::ApiPrincipal;
+    use crate::auth::SESSION_ID;
+    use crate::state::AppState;
+    use axum::{
+        Json,
+        extract::{ConnectInfo, State},
+        http::{HeaderMap, HeaderValue, StatusCode, header},
+        middleware::Next,
+        response::{IntoResponse, Response},
+    };
+    use axum_extra::extract::CookieJar;
+    use std::collections::{BTreeMap, HashMap};
+    use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    /// Token bucket holding `burst` requests, one refilled every `refill_every`.
+    #[derive(Clone, Copy, Debug)]
+    pub struct RateLimitPolicy {
+        pub name: &'static str,
+        pub burst: u32,
+        pub refill_every: Duration,
+    }
+
+    pub const CHAT_POLICY: RateLimitPolicy = RateLimitPolicy {
+        name: "chat",
+        burst: 5,
+        refill_every: Duration::from_secs(12),
+    };
+    pub const UPLOAD_POLICY: RateLimitPolicy = RateLimitPolicy {
+        name: "upload",
+        burst: 10,
+        refill_every: Duration::from_secs(6),
+    };
+    /// Parts and completion of chunked uploads, whose creation counts as the upload.
+    /// A burst covers the largest staged upload in default 8 MiB parts.
+    pub const UPLOAD_PART_POLICY: RateLimitPolicy = RateLimitPolicy {
+        name: "upload-part",
+        burst: 64,
+        refill_every: Duration::from_millis(500),
+    };
+    pub const LOGIN_POLICY: RateLimitPolicy = RateLimitPolicy {
+        name: "login",
+        burst: 10,
+        refill_every: Duration::from_secs(30),
+    };
+
+    /// The least recently used bucket is dropped once the map reaches this size.
+    const MAX_BUCKETS: usize = 10_000;
+
+    type BucketKey = (&'static str, String);
+
+    #[derive(Clone, Copy, Debug)]
+    struct Bucket {
+        policy: RateLimitPolicy,
+        tokens: f64,
+        updated: Instant,
+        /// Position in `Buckets::by_use`.
+        used: u64,
+    }
+
+    /// Buckets with their order of last use, so eviction doesn't scan the map.
+    #[derive(Default)]
+    struct Buckets {
+        map: HashMap<BucketKey, Bucket>,
+        by_use: BTreeMap<u64, BucketKey>,
+        next_use: u64,
+    }
+
+    impl Bucket {
+        fn refilled(&self, now: Instant) -> f64 {
+            let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+            (self.tokens + elapsed / self.policy.refill_every.as_secs_f64())
+                .min(self.policy.burst as f64)
+        }
+    }
+
+    /// Proxy address or CIDR range allowed to set `X-Forwarded-For`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TrustedProxy {
+        addr: IpAddr,
+        prefix: u8,
+    }
+
+    impl TrustedProxy {
+        fn parse(value: &str) -> Option<Self> {
+            let (addr, prefix) = match value.split_once('/') {
+                Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, prefix.parse().ok()?),
+                None => {
+                    let addr = value.parse::<IpAddr>().ok()?;
+                    (addr, if addr.is_ipv4() { 32 } else { 128 })
+                }
+            };
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            (prefix <= max).then_some(Self { addr, prefix })
+        }
+
+        fn contains(&self, ip: IpAddr) -> bool {
+            match (self.addr, ip.to_canonical()) {
+                (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                    let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                    u32::from(net) & mask == u32::from(ip) & mask
+                }
+                (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                    let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                    u128::from(net) & mask == u128::from(ip) & mask
+                }
+                _ => false,
+            }
+        }
+    }
+
+    pub struct RateLimiter {
+        buckets: Mutex<Buckets>,
+        trusted_proxies: Vec<TrustedProxy>,
+    }
+
+    impl RateLimiter {
+        /// `trusted_proxies` is a comma separated list of addresses or CIDR ranges.
+        pub fn new(trusted_proxies: &str) -> Self {
+            let trusted_proxies = trusted_proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .filter_map(|value| {
+                    let proxy = TrustedProxy::parse(value);
+                    if proxy.is_none() {
+                        tracing::warn!(value, "rate limit: ignoring invalid trusted proxy");
+                    }
+                    proxy
+                })
+                .collect();
+            Self {
+                buckets: Mutex::new(Buckets::default()),
+                trusted_proxies,
+            }
+        }
+
+        /// Takes one token, or returns how long to wait for the next one.
+        pub fn check(&self, policy: &RateLimitPolicy, key: &str) -> Result<(), Duration> {
+            self.check_at(policy, key, Instant::now())
+        }
+
+        fn check_at(
+            &self,
+            policy: &RateLimitPolicy,
+            key: &str,
+            now: Instant,
+        ) -> Result<(), Duration> {
+            let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+            let Buckets {
+                map,
+                by_use,
+                next_use,
+            } = &mut *buckets;
+            let key = (policy.name, key.to_string());
+            if !map.contains_key(&key)
+                && map.len() >= MAX_BUCKETS
+                && let Some((_, oldest)) = by_use.pop_first()
+            {
+                map.remove(&oldest);
+            }
+            let bucket = map.entry(key.clone()).or_insert(Bucket {
+                policy: *policy,
+                tokens: policy.burst as f64,
+                updated: now,
+                used: 0,
+            });
+            by_use.remove(&bucket.used);
+            bucket.used = *next_use;
+            by_use.insert(*next_use, key);
+            *next_use += 1;
+            let tokens = bucket.refilled(now);
+            bucket.updated = now;
+            if tokens >= 1.0 {
+                bucket.tokens = tokens - 1.0;
+                Ok(())
+            } else {
+                bucket.tokens = tokens;
+                Err(policy.refill_every.mul_f64(1.0 - tokens))
+            }
+        }
+
+        /// Client address: the peer, or the first untrusted hop of `X-Forwarded-For`
+        /// when the request came through trusted proxies.
+        pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+            let trusted = |ip: IpAddr| self.trusted_proxies.iter().any(|p| p.contains(ip));
+            let mut client = peer?;
+            if !trusted(client) {
+                return Some(client);
+            }
+            let forwarded: Vec<IpAddr> = headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .filter_map(|hop| hop.trim().parse().ok())
+                .collect();
+            for hop in forwarded.into_iter().rev() {
+                client = hop;
+                if !trusted(hop) {
+                    break;
+                }
+            }
+            Some(client)
+        }
+    }
+
+    /// Rate limit key of a client address. IPv6 clients usually get a whole /64,
+    /// so they are limited per /64.
+    pub fn ip_key(ip: IpAddr) -> String {
+        match ip.to_canonical() {
+            IpAddr::V4(ip) => ip.to_string(),
+            IpAddr::V6(ip) => {
+                let prefix = u128::from(ip) & (u128::MAX << 64);
+                format!("{}/64", Ipv6Addr::from(prefix))
+            }
+        }
+    }
+
+    /// `Retry-After` value in whole seconds, at least one.
+    pub fn retry_after_secs(retry_after: Duration) -> u64 {
+        retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+
+    pub fn too_many_requests(retry_after: Duration) -> Response {
+        let secs = retry_after_secs(retry_after);
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(serde_json::json!({ "error": RATE_LIMITED_ERROR, "retry_after": secs })),
+        )
+            .into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        response
+    }
+
+    pub fn peer_ip(req: &axum::extract::Request) -> Option<IpAddr> {
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    }
+
+    async fn limit_key(
+        state: &AppState,
+        principal: Option<String>,
+        peer: Option<IpAddr>,
+        headers: &HeaderMap,
+    ) -> String {
+        if let Some(subject) = principal {
+            return format!("sub:{subject}");
+        }
+        if let Some(cookie) = CookieJar::from_headers(headers).get(SESSION_ID)
+            && let Some(subject) = state
+                .sessions
+                .lock()
+                .await
+                .get(cookie.value())
+                .and_then(|session| session.subject.clone())
+        {
+            return format!("sub:{subject}");
+        }
+        match state.rate_limiter.client_ip(peer, headers) {
+            Some(ip) => format!("ip:{}", ip_key(ip)),
+            None => "ip:unknown".to_string(),
+        }
+    }
+
+    /// Per-route limiter keyed by authenticated subject, else client IP.
+    pub async fn rate_limit(
+        State((state, policy)): State<(AppState, RateLimitPolicy)>,
+        req: axum::extract::Request,
+        next: Next,
+    ) -> Response {
+        let principal = req
+            .extensions()
+            .get::<ApiPrincipal>()
+            .map(|principal| principal.user.subject.clone());
+        let headers = req.headers().clone();
+        let key = limit_key(&state, principal, peer_ip(&req), &headers).await;
+        if let Err(retry_after) = state.rate_limiter.check(&policy, &key) {
+            tracing::warn!(policy = policy.name, key = %key, "rate limit: request rejected");
+            return too_many_requests(retry_after);
+        }
+        next.run(req).await
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const POLICY: RateLimitPolicy = RateLimitPolicy {
+            name: "test",
+            burst: 2,
+            refill_every: Duration::from_secs(10),
+        };
+
+        #[test]
+        fn bucket_allows_burst_then_refills() {
+            let limiter = RateLimiter::new("");
+            let start = Instant::now();
+
+            assert!(limiter.check_at(&POLICY, "a", start).is_ok());
+            assert!(limiter.check_at(&POLICY, "a", start).is_ok());
+            let wait = limiter.check_at(&POLICY, "a", start).unwrap_err();
+            assert_eq!(wait, Duration::from_secs(10));
+            assert!(limiter.check_at(&POLICY, "b", start).is_ok());
+
+            let later = start + Duration::from_secs(5);
+            assert_eq!(
+                limiter.check_at(&POLICY, "a", later).unwrap_err(),
+                Duration::from_secs(5)
+            );
+            assert!(
+                limiter
+                    .check_at(&POLICY, "a", start + Duration::from_secs(10))
+                    .is_ok()
+            );
+        }
+
+        #[test]
+        fn least_recently_used_bucket_is_evicted() {
+            let limiter = RateLimiter::new("");
+            let start = Instant::now();
+            for i in 0..MAX_BUCKETS {
+                assert!(limiter.check_at(&POLICY, &format!("k{i}"), start).is_ok());
+            }
+            assert!(limiter.check_at(&POLICY, "k0", start).is_ok());
+            assert!(limiter.check_at(&POLICY, "k0", start).is_err());
+
+            // A new client evicts k1, the bucket used longest ago
+            assert!(limiter.check_at(&POLICY, "new", start).is_ok());
+            let buckets = limiter.buckets.lock().unwrap();
+            assert_eq!(buckets.map.len(), MAX_BUCKETS);
+            assert_eq!(buckets.by_use.len(), MAX_BUCKETS);
+            assert!(!buckets.map.contains_key(&("test", "k1".to_string())));
+            drop(buckets);
+            assert!(limiter.check_at(&POLICY, "k0", start).is_err());
+        }
+
+        #[test]
+        fn ipv6_clients_are_grouped_by_64() {
+            let key = |ip: &str| ip_key(ip.parse().unwrap());
+            assert_eq!(key("2001:db8:1:2:aaaa::1"), "2001:db8:1:2::/64");
+            assert_eq!(key("2001:db8:1:2:bbbb::9"), key("2001:db8:1:2:aaaa::1"));
+            assert_ne!(key("2001:db8:1:3::1"), key("2001:db8:1:2::1"));
+            assert_eq!(key("198.51.100.7"), "198.51.100.7");
+            assert_eq!(key("::ffff:198.51.100.7"), "198.51.100.7");
+        }
+
+        #[test]
+        fn forwarded_for_is_honored_only_from_trusted_proxies() {
+            let limiter = RateLimiter::new("10.0.0.0/8, ::1");
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                "x-forwarded-for",
+                HeaderValue::from_static("198.51.100.7, 203.0.113.9, 10.1.2.3"),
+            );
+
+            let untrusted = "192.0.2.1".parse().ok();
+            assert_eq!(limiter.client_ip(untrusted, &headers), untrusted);
+            assert_eq!(
+                limiter.client_ip("10.0.0.1".parse().ok(), &headers),
+                "203.0.113.9".parse().ok()
+            );
+            assert_eq!(
+                limiter.client_ip("::1".parse().ok(), &HeaderMap::new()),
+                "::1".parse().ok()
+            );
+        }
+
+        #[test]
+        fn trusted_proxy_parsing() {
+            assert!(TrustedProxy::parse("10.0.0.0/33").is_none());
+            assert!(TrustedProxy::parse("proxy").is_none());
+            let net = TrustedProxy::parse("192.168.0.0/16").unwrap();
+            assert!(net.contains("192.168.44.1".parse().unwrap()));
+            assert!(net.contains("::ffff:192.168.44.1".parse().unwrap()));
+            assert!(!net.contains("192.169.0.1".parse().unwrap()));
+            assert!(
+                TrustedProxy::parse("0.0.0.0/0")
+                    .unwrap()
+                    .contains("8.8.8.8".parse().unwrap())
+            );
+        }
+
+        #[test]
+        fn too_many_requests_sets_retry_after() {
+            let response = too_many_requests(Duration::from_millis(1500));
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub use ssr::{
+    CHAT_POLICY, LOGIN_POLICY, RateLimitPolicy, RateLimiter, UPLOAD_PART_POLICY, UPLOAD_POLICY,
+    ip_key, peer_ip, rate_limit, retry_after_secs, too_many_requests,
+};
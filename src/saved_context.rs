@@ -0,0 +1,243 @@
+use crate::components::tree::NodeInfo;
+use crate::sharing::NodeAccess;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Largest saved context accepted from the browser.
+pub const MAX_SAVED_CONTEXT_BYTES: usize = 64 * 1024;
+/// Restored context older than this is flagged as possibly outdated.
+pub const STALE_AFTER_MS: i64 = 12 * 60 * 60 * 1000;
+
+/// Chat context of one chat as saved in the browser and with the conversation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedContext {
+    #[serde(default)]
+    pub parent: Option<NodeInfo>,
+    #[serde(default)]
+    pub prev_leaf: Option<NodeInfo>,
+    #[serde(default)]
+    pub next_leaf: Option<NodeInfo>,
+    /// Reports pinned for a timeline, oldest first.
+    #[serde(default)]
+    pub pinned: Vec<NodeInfo>,
+    /// Reports whose previews results of the chat show.
+    #[serde(default)]
+    pub report_media: Vec<NodeInfo>,
+    /// Unix milliseconds.
+    pub saved_at: i64,
+}
+
+impl SavedContext {
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_none()
+            && self.prev_leaf.is_none()
+            && self.next_leaf.is_none()
+            && self.pinned.is_empty()
+            && self.report_media.is_empty()
+    }
+
+    /// Drops nodes the user can no longer pick and returns how many were
+    /// dropped. Leaves only stay with their object and take its current access;
+    /// the saved access is never trusted.
+    pub fn retain_accessible(&mut self, access: impl Fn(Uuid) -> Option<NodeAccess>) -> usize {
+        let object_access = self.parent.as_ref().and_then(|parent| access(parent.id));
+        let exists = |id: Uuid| access(id).is_some();
+        let mut dropped = 0;
+        let mut keep = |node: &mut Option<NodeInfo>, valid: bool| {
+            if node.is_some() && !(valid && node.as_ref().is_some_and(|node| exists(node.id))) {
+                *node = None;
+                dropped += 1;
+            }
+        };
+        keep(&mut self.parent, true);
+        let has_parent = self.parent.is_some();
+        keep(&mut self.prev_leaf, has_parent);
+        keep(&mut self.next_leaf, has_parent);
+        // `ChatContext` keeps a single report in `prev_leaf`
+        if self.prev_leaf.is_none() {
+            self.prev_leaf = self.next_leaf.take();
+        }
+        let pinned = self.pinned.len();
+        if has_parent {
+            self.pinned.retain(|node| exists(node.id));
+        } else {
+            self.pinned.clear();
+        }
+        let before = self.report_media.len();
+        self.report_media.retain(|node| exists(node.id));
+        let object_access = object_access.unwrap_or_default();
+        for node in self
+            .parent
+            .iter_mut()
+            .chain(&mut self.prev_leaf)
+            .chain(&mut self.next_leaf)
+            .chain(&mut self.pinned)
+        {
+            node.access = object_access;
+        }
+        for node in &mut self.report_media {
+            node.access = access(node.id).unwrap_or_default();
+        }
+        dropped + pinned - self.pinned.len() + before - self.report_media.len()
+    }
+
+    pub fn is_stale(&self, now_ms: i64) -> bool {
+        now_ms - self.saved_at > STALE_AFTER_MS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::tree::NodeType;
+
+    fn node(id: u128) -> NodeInfo {
+        NodeInfo {
+            id: Uuid::from_u128(id),
+            parent_id: None,
+            name: None,
+            node_type: NodeType::ImageLeaf,
+            date_time: 0,
+            thumbnail_url: None,
+            full_url: None,
+            mime_type: None,
+            size: None,
+            access: NodeAccess::Own,
+        }
+    }
+
+    #[test]
+    fn restoring_drops_deleted_nodes() {
+        let saved = SavedContext {
+            parent: Some(node(1)),
+            prev_leaf: Some(node(2)),
+            next_leaf: Some(node(3)),
+            pinned: vec![node(2), node(4)],
+            report_media: vec![node(2), node(3), node(4)],
+            saved_at: 0,
+        };
+
+        let mut without_prev = saved.clone();
+        let exists = |id: Uuid| (id.as_u128() != 2).then_some(NodeAccess::Read);
+        assert_eq!(without_prev.retain_accessible(exists), 3);
+        assert_eq!(without_prev.pinned.len(), 1);
+        assert_eq!(
+            without_prev.prev_leaf.map(|node| node.id.as_u128()),
+            Some(3)
+        );
+        assert!(without_prev.next_leaf.is_none());
+        assert_eq!(without_prev.report_media.len(), 2);
+        // Access comes from the tree, not from what was saved
+        assert_eq!(
+            without_prev.parent.map(|node| node.access),
+            Some(NodeAccess::Read)
+        );
+        assert_eq!(without_prev.pinned[0].access, NodeAccess::Read);
+
+        let mut without_parent = saved.clone();
+        assert_eq!(
+            without_parent.retain_accessible(|id| (id.as_u128() != 1).then_some(NodeAccess::Own)),
+            5
+        );
+        assert!(without_parent.parent.is_none() && without_parent.prev_leaf.is_none());
+        assert_eq!(without_parent.report_media.len(), 3);
+
+        let mut all = saved;
+        assert_eq!(all.retain_accessible(|_| Some(NodeAccess::Edit)), 0);
+        assert_eq!(
+            all.prev_leaf.as_ref().map(|node| node.access),
+            Some(NodeAccess::Edit)
+        );
+        assert!(!all.is_stale(STALE_AFTER_MS));
+        assert!(all.is_stale(STALE_AFTER_MS + 1));
+        assert!(SavedContext::default().is_empty());
+    }
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::*;
+    use crate::api_tokens::{ApiPrincipal, agent_user_id};
+    use crate::proxy_reports::forward_signed;
+    use crate::sessions::error_response;
+    use crate::ssr::correlation_id;
+    use crate::state::AppState;
+    use axum::{
+        Extension, Json,
+        extract::{Path, State},
+        http::{HeaderMap, StatusCode},
+        response::Response,
+    };
+    use axum_extra::extract::CookieJar;
+
+    /// Sends the signed request to the conversation's context on the agent.
+    async fn forward_context(
+        state: &AppState,
+        user_id: &str,
+        chat_id: Uuid,
+        request_id: &str,
+        body: Option<Vec<u8>>,
+    ) -> Response {
+        let chat_config = &state.http_client.config.chat_config;
+        let agent_url = format!(
+            "{}/agent/chats/{user_id}/{chat_id}/context",
+            chat_config.agent_api_url
+        );
+        let method = match body {
+            Some(_) => reqwest::Method::PUT,
+            None => reqwest::Method::GET,
+        };
+        forward_signed(state, method, &agent_url, request_id, body)
+            .await
+            .0
+    }
+
+    /// `GET /api/chat_context/{chat_id}`: context saved with the conversation,
+    /// `404` when there is none.
+    pub async fn get_chat_context_handler(
+        State(state): State<AppState>,
+        Path(chat_id): Path<Uuid>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+    ) -> Response {
+        let Some(user_id) = agent_user_id(&state, &jar, principal.as_deref()).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        forward_context(&state, &user_id, chat_id, &correlation_id(&headers), None).await
+    }
+
+    /// `PUT /api/chat_context/{chat_id}`: saves the context with the conversation.
+    pub async fn put_chat_context_handler(
+        State(state): State<AppState>,
+        Path(chat_id): Path<Uuid>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+        Json(context): Json<SavedContext>,
+    ) -> Response {
+        let Some(user_id) = agent_user_id(&state, &jar, principal.as_deref()).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        let body = match serde_json::to_vec(&context) {
+            Ok(body) => body,
+            Err(e) => {
+                return error_response(
+                    StatusCode::BAD_REQUEST,
+                    &format!("Failed to serialize context: {e}"),
+                );
+            }
+        };
+        forward_context(
+            &state,
+            &user_id,
+            chat_id,
+            &correlation_id(&headers),
+            Some(body),
+        )
+        .await
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub use ssr::{get_chat_context_handler, put_chat_context_handler};
@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+
+/// A signed-in session as shown to its owner (or an admin).
+/// `handle` is a digest of the session id, never the id itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActiveSession {
+    pub handle: String,
+    pub subject: String,
+    pub name: Option<String>,
+    pub created_at: i64,
+    pub last_activity: i64,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub current: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionsQuery {
+    /// Admin only: list the sessions of another user.
+    pub subject: Option<String>,
+}
+
+/// Short "Browser / OS" label for a raw User-Agent header.
+pub fn describe_user_agent(user_agent: &str) -> String {
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ]
+    .into_iter()
+    .find(|(token, _)| user_agent.contains(token))
+    .map(|(_, name)| name);
+    let os = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .into_iter()
+    .find(|(token, _)| user_agent.contains(token))
+    .map(|(_, name)| name);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{browser} / {os}"),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        (None, None) => user_agent.chars().take(40).collect(),
+    }
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::{ActiveSession, SessionsQuery};
+    use crate::auth_ssr::{SessionData, session_user};
+    use crate::ssr::terminate_session;
+    use crate::state::AppState;
+    use axum::{
+        Json,
+        extract::{Path, Query, State},
+        http::StatusCode,
+        response::{IntoResponse, Response},
+    };
+    use axum_extra::extract::CookieJar;
+    use sha2::{Digest, Sha256};
+
+    pub fn session_handle(session_id: &str) -> String {
+        hex::encode(&Sha256::digest(session_id.as_bytes())[..16])
+    }
+
+    pub(crate) fn active_session(
+        session_id: &str,
+        session: &SessionData,
+        current_session_id: &str,
+    ) -> Option<ActiveSession> {
+        Some(ActiveSession {
+            handle: session_handle(session_id),
+            subject: session.subject.clone()?,
+            name: session.name.clone(),
+            created_at: session.created_at.timestamp(),
+            last_activity: session.last_activity.timestamp(),
+            user_agent: session.user_agent.clone(),
+            ip: session.ip.clone(),
+            current: session_id == current_session_id,
+        })
+    }
+
+    pub(crate) fn error_response(status: StatusCode, error: &str) -> Response {
+        (status, Json(serde_json::json!({ "error": error }))).into_response()
+    }
+
+    /// Rejection reasons of a proxy route, `code` doubles as the client's ftl key.
+    pub(crate) trait CodedError: Copy {
+        fn code(self) -> &'static str;
+        fn status(self) -> StatusCode;
+        fn message(self) -> &'static str;
+
+        /// `error_response` with the `code` added.
+        fn response(self) -> Response {
+            let body = serde_json::json!({
+                "error": self.message(),
+                "code": self.code(),
+            });
+            (self.status(), Json(body)).into_response()
+        }
+    }
+
+    pub async fn list_sessions_handler(
+        State(state): State<AppState>,
+        jar: CookieJar,
+        Query(query): Query<SessionsQuery>,
+    ) -> Response {
+        let Some((current_id, user)) = session_user(&state, &jar).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        let subject = match query.subject {
+            Some(subject) if subject != user.subject => {
+                if !user.is_admin() {
+                    return error_response(StatusCode::FORBIDDEN, "Admin role required");
+                }
+                subject
+            }
+            _ => user.subject,
+        };
+
+        let mut sessions: Vec<ActiveSession> = state
+            .sessions
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, session)| session.subject.as_deref() == Some(subject.as_str()))
+            .filter_map(|(id, session)| active_session(id, session, &current_id))
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_activity));
+
+        Json(sessions).into_response()
+    }
+
+    /// Ends a session by handle: the owner may revoke their own, admins any.
+    pub async fn revoke_session_handler(
+        State(state): State<AppState>,
+        jar: CookieJar,
+        Path(handle): Path<String>,
+    ) -> Response {
+        let Some((_, user)) = session_user(&state, &jar).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+
+        let target = state
+            .sessions
+            .lock()
+            .await
+            .iter()
+            .find(|(id, _)| session_handle(id) == handle)
+            .map(|(id, session)| (id.clone(), session.subject.clone()));
+        let Some((session_id, subject)) = target else {
+            return error_response(StatusCode::NOT_FOUND, "Session not found");
+        };
+        if subject.as_deref() != Some(user.subject.as_str()) && !user.is_admin() {
+            return error_response(StatusCode::FORBIDDEN, "Not allowed to revoke this session");
+        }
+
+        terminate_session(&state, &session_id).await;
+        tracing::info!(
+            revoked_by = %user.subject,
+            subject = ?subject,
+            "sessions: session revoked"
+        );
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) use ssr::{CodedError, active_session, error_response};
+#[cfg(feature = "ssr")]
+pub use ssr::{list_sessions_handler, revoke_session_handler, session_handle};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_common_user_agents() {
+        assert_eq!(
+            describe_user_agent(
+                "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0"
+            ),
+            "Firefox / Linux"
+        );
+        assert_eq!(
+            describe_user_agent(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0 Safari/537.36 Edg/126.0"
+            ),
+            "Edge / Windows"
+        );
+        assert_eq!(describe_user_agent("curl/8.5.0"), "curl/8.5.0");
+    }
+}
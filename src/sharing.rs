@@ -0,0 +1,503 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Longest user id (an e-mail address) or IdP group name a branch is shared with.
+pub const MAX_GRANTEE_CHARS: usize = 254;
+
+/// What a grantee may do with a shared branch and everything below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SharePermission {
+    Read,
+    Edit,
+}
+
+impl SharePermission {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Edit => "edit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GranteeKind {
+    User,
+    Group,
+}
+
+impl GranteeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Group => "group",
+        }
+    }
+}
+
+/// A user or IdP group a branch is shared with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Share {
+    pub kind: GranteeKind,
+    pub grantee: String,
+    pub permission: SharePermission,
+}
+
+/// Body of `PUT /api/proxy/shares/{node_id}/{kind}/{grantee}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareUpdate {
+    pub permission: SharePermission,
+}
+
+/// What the current user may do with a node. Unknown access is `Read`, so
+/// nothing is offered that the proxy would refuse.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeAccess {
+    Own,
+    Edit,
+    #[default]
+    Read,
+}
+
+impl NodeAccess {
+    /// `None` for nodes only shown as the way to something shared.
+    pub fn new(own: bool, permission: Option<SharePermission>) -> Option<Self> {
+        match (own, permission) {
+            (true, _) => Some(Self::Own),
+            (false, Some(SharePermission::Edit)) => Some(Self::Edit),
+            (false, Some(SharePermission::Read)) => Some(Self::Read),
+            (false, None) => None,
+        }
+    }
+
+    /// Uploading, deleting and editing reports.
+    pub fn can_edit(self) -> bool {
+        self != Self::Read
+    }
+}
+
+/// Trimmed grantee, or `None` if it cannot name a user or group.
+pub fn grantee_name(kind: GranteeKind, value: &str) -> Option<String> {
+    let value = value.trim();
+    let valid = !value.is_empty()
+        && value.chars().count() <= MAX_GRANTEE_CHARS
+        && !value
+            .chars()
+            .any(|c| c.is_control() || c.is_whitespace() || c == '/');
+    let valid = valid
+        && match kind {
+            GranteeKind::User => value
+                .split_once('@')
+                .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.')),
+            GranteeKind::Group => true,
+        };
+    valid.then(|| value.to_string())
+}
+
+/// Path of one grant below `/api/proxy/shares/` and `/agent/shares/`.
+pub fn share_path(node_id: Uuid, kind: GranteeKind, grantee: &str) -> String {
+    format!(
+        "{node_id}/{}/{}",
+        kind.as_str(),
+        percent_encoding::utf8_percent_encode(grantee, percent_encoding::NON_ALPHANUMERIC)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grantees_and_access_are_checked() {
+        assert_eq!(
+            grantee_name(GranteeKind::User, " bob@example.com "),
+            Some("bob@example.com".to_string())
+        );
+        assert_eq!(grantee_name(GranteeKind::User, "bob"), None);
+        assert_eq!(
+            grantee_name(GranteeKind::Group, "site-team"),
+            Some("site-team".to_string())
+        );
+        assert_eq!(grantee_name(GranteeKind::Group, "a/../b"), None);
+        assert_eq!(
+            share_path(Uuid::nil(), GranteeKind::User, "bob@example.com"),
+            "00000000-0000-0000-0000-000000000000/user/bob%40example%2Ecom"
+        );
+
+        assert_eq!(NodeAccess::new(false, None), None);
+        assert_eq!(NodeAccess::default(), NodeAccess::Read);
+        assert!(
+            NodeAccess::new(false, Some(SharePermission::Edit))
+                .unwrap()
+                .can_edit()
+        );
+        assert!(
+            !NodeAccess::new(false, Some(SharePermission::Read))
+                .unwrap()
+                .can_edit()
+        );
+        assert_eq!(
+            NodeAccess::new(true, Some(SharePermission::Read)),
+            Some(NodeAccess::Own)
+        );
+    }
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::*;
+    use crate::api_tokens::{ApiPrincipal, agent_user_id};
+    use crate::audit::{self, AuditAction, AuditActor};
+    use crate::components::tree::{NodeType, TreeNode};
+    use crate::media::agent_tree;
+    use crate::proxy_reports::forward_signed;
+    use crate::proxy_tree::{cached_tree, with_ancestors};
+    use crate::sessions::{CodedError, error_response};
+    use crate::ssr::correlation_id;
+    use crate::state::AppState;
+    use axum::{
+        Extension, Json,
+        extract::{Path, State},
+        http::{HeaderMap, StatusCode},
+        response::{IntoResponse, Response},
+    };
+    use axum_extra::extract::CookieJar;
+    use std::collections::HashSet;
+
+    /// Why a share change or a write to a shared node is refused.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ShareError {
+        NotFound,
+        NotOwned,
+        NotABranch,
+        InvalidGrantee,
+        SelfShare,
+        ReadOnly,
+    }
+
+    impl CodedError for ShareError {
+        fn code(self) -> &'static str {
+            match self {
+                Self::NotFound => "share-not-found",
+                Self::NotOwned => "share-not-owned",
+                Self::NotABranch => "share-not-a-branch",
+                Self::InvalidGrantee => "share-invalid-grantee",
+                Self::SelfShare => "share-self",
+                Self::ReadOnly => "share-read-only",
+            }
+        }
+
+        fn status(self) -> StatusCode {
+            match self {
+                Self::NotFound => StatusCode::NOT_FOUND,
+                Self::NotOwned | Self::ReadOnly => StatusCode::FORBIDDEN,
+                Self::NotABranch | Self::InvalidGrantee | Self::SelfShare => {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                }
+            }
+        }
+
+        fn message(self) -> &'static str {
+            match self {
+                Self::NotFound => "Node not found",
+                Self::NotOwned => "Node belongs to another user",
+                Self::NotABranch => "Only branches can be shared",
+                Self::InvalidGrantee => "Invalid user or group",
+                Self::SelfShare => "Branches cannot be shared with their owner",
+                Self::ReadOnly => "Node is shared read-only",
+            }
+        }
+    }
+
+    impl IntoResponse for ShareError {
+        fn into_response(self) -> Response {
+            self.response()
+        }
+    }
+
+    /// Only the owner of a branch manages who it is shared with.
+    fn own_branch(nodes: &[TreeNode], id: Uuid) -> Result<(), ShareError> {
+        let node = nodes
+            .iter()
+            .find(|node| node.id == id)
+            .ok_or(ShareError::NotFound)?;
+        if node.node_type != NodeType::Branch {
+            return Err(ShareError::NotABranch);
+        }
+        if !node.own {
+            return Err(ShareError::NotOwned);
+        }
+        Ok(())
+    }
+
+    /// Own nodes and those shared for editing; `node_id` comes from a path.
+    fn editable(nodes: &[TreeNode], node_id: &str) -> Result<(), ShareError> {
+        let node = Uuid::parse_str(node_id)
+            .ok()
+            .and_then(|id| nodes.iter().find(|node| node.id == id))
+            .ok_or(ShareError::NotFound)?;
+        match NodeAccess::new(node.own, node.permission) {
+            Some(access) if access.can_edit() => Ok(()),
+            _ => Err(ShareError::ReadOnly),
+        }
+    }
+
+    /// Rejects uploads, deletions and report edits on nodes the user may only
+    /// read, checked against the user's cached tree.
+    pub(crate) async fn require_edit(
+        state: &AppState,
+        jar: &CookieJar,
+        principal: Option<&ApiPrincipal>,
+        node_id: &str,
+    ) -> Result<String, Response> {
+        let Some(user_id) = agent_user_id(state, jar, principal).await else {
+            return Err(error_response(
+                StatusCode::UNAUTHORIZED,
+                "Not authenticated",
+            ));
+        };
+        let nodes = cached_tree(state, &user_id, true).await?;
+        editable(&nodes, node_id).map_err(IntoResponse::into_response)?;
+        Ok(user_id)
+    }
+
+    /// Normalized grantee of a grant or revocation by `user_id`.
+    fn check_grantee(
+        user_id: &str,
+        kind: GranteeKind,
+        grantee: &str,
+    ) -> Result<String, ShareError> {
+        let grantee = grantee_name(kind, grantee).ok_or(ShareError::InvalidGrantee)?;
+        if kind == GranteeKind::User && grantee.eq_ignore_ascii_case(user_id) {
+            return Err(ShareError::SelfShare);
+        }
+        Ok(grantee)
+    }
+
+    /// Checks that the user owns the branch, then sends the signed request on.
+    /// Also returns the branch and its ancestors.
+    async fn forward_share(
+        state: &AppState,
+        user_id: &str,
+        request_id: &str,
+        method: reqwest::Method,
+        node_id: Uuid,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> (Response, Option<StatusCode>, HashSet<Uuid>) {
+        let nodes = match agent_tree(state, user_id, false).await {
+            Ok(nodes) => nodes,
+            Err(response) => return (response, None, HashSet::new()),
+        };
+        if let Err(e) = own_branch(&nodes, node_id) {
+            return (e.into_response(), None, HashSet::new());
+        }
+        let touched = with_ancestors(&nodes, &[node_id]);
+
+        let chat_config = &state.http_client.config.chat_config;
+        let agent_url = format!("{}/agent/shares/{path}", chat_config.agent_api_url);
+        let (response, agent_status) =
+            forward_signed(state, method, &agent_url, request_id, body).await;
+        (response, agent_status, touched)
+    }
+
+    /// `GET /api/proxy/shares/{node_id}`: users and groups an own branch is shared with.
+    pub async fn list_shares_handler(
+        State(state): State<AppState>,
+        Path(node_id): Path<Uuid>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+    ) -> Response {
+        let Some(user_id) = agent_user_id(&state, &jar, principal.as_deref()).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        let request_id = correlation_id(&headers);
+        let path = node_id.to_string();
+        forward_share(
+            &state,
+            &user_id,
+            &request_id,
+            reqwest::Method::GET,
+            node_id,
+            &path,
+            None,
+        )
+        .await
+        .0
+    }
+
+    async fn audited_share(
+        state: AppState,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+        (node_id, kind, grantee): (Uuid, GranteeKind, String),
+        update: Option<ShareUpdate>,
+    ) -> Response {
+        let Some(user_id) = agent_user_id(&state, &jar, principal.as_deref()).await else {
+            return error_response(StatusCode::UNAUTHORIZED, "Not authenticated");
+        };
+        let actor = AuditActor::resolve(&state, &jar, principal.as_deref()).await;
+        let request_id = correlation_id(&headers);
+        let target = node_id.to_string();
+        let (response, agent_status) = match check_grantee(&user_id, kind, &grantee) {
+            Ok(grantee) => {
+                let path = share_path(node_id, kind, &grantee);
+                let (method, body) = match update {
+                    Some(update) => {
+                        let share = Share {
+                            kind,
+                            grantee: grantee.clone(),
+                            permission: update.permission,
+                        };
+                        (reqwest::Method::PUT, serde_json::to_vec(&share).ok())
+                    }
+                    None => (reqwest::Method::DELETE, None),
+                };
+                let (response, agent_status, touched) =
+                    forward_share(&state, &user_id, &request_id, method, node_id, &path, body)
+                        .await;
+                if response.status().is_success() {
+                    // Group members are not known here, so a group grant drops every tree
+                    match kind {
+                        GranteeKind::Group => state.trees.clear().await,
+                        GranteeKind::User => {
+                            let touched: Vec<Uuid> = touched.into_iter().collect();
+                            state
+                                .trees
+                                .invalidate(&[&user_id, &grantee], &touched)
+                                .await;
+                        }
+                    }
+                }
+                (response, agent_status)
+            }
+            Err(e) => (e.into_response(), None),
+        };
+        let action = match update {
+            Some(_) => AuditAction::ShareGrant,
+            None => AuditAction::ShareRevoke,
+        };
+        audit::record(
+            &state,
+            actor,
+            action,
+            &target,
+            request_id,
+            response.status(),
+            agent_status,
+        )
+        .await;
+        response
+    }
+
+    /// `PUT /api/proxy/shares/{node_id}/{kind}/{grantee}`: grants or changes access.
+    pub async fn grant_share_handler(
+        State(state): State<AppState>,
+        Path(grant): Path<(Uuid, GranteeKind, String)>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+        Json(update): Json<ShareUpdate>,
+    ) -> Response {
+        audited_share(state, jar, principal, headers, grant, Some(update)).await
+    }
+
+    /// `DELETE /api/proxy/shares/{node_id}/{kind}/{grantee}`: revokes access.
+    pub async fn revoke_share_handler(
+        State(state): State<AppState>,
+        Path(grant): Path<(Uuid, GranteeKind, String)>,
+        jar: CookieJar,
+        principal: Option<Extension<ApiPrincipal>>,
+        headers: HeaderMap,
+    ) -> Response {
+        audited_share(state, jar, principal, headers, grant, None).await
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::components::tree::test_node;
+
+        fn node(id: u128, node_type: NodeType, own: bool) -> TreeNode {
+            test_node(id, None, node_type, own)
+        }
+
+        #[test]
+        fn only_owners_share_branches_with_others() {
+            let nodes = vec![
+                node(1, NodeType::Root, true),
+                node(2, NodeType::Branch, true),
+                node(3, NodeType::Branch, false),
+                node(4, NodeType::ImageLeaf, true),
+            ];
+            assert_eq!(own_branch(&nodes, Uuid::from_u128(2)), Ok(()));
+            assert_eq!(
+                own_branch(&nodes, Uuid::from_u128(3)),
+                Err(ShareError::NotOwned)
+            );
+            assert_eq!(
+                own_branch(&nodes, Uuid::from_u128(4)),
+                Err(ShareError::NotABranch)
+            );
+            assert_eq!(
+                own_branch(&nodes, Uuid::from_u128(9)),
+                Err(ShareError::NotFound)
+            );
+
+            assert_eq!(
+                check_grantee("alice@example.com", GranteeKind::User, "Alice@Example.com"),
+                Err(ShareError::SelfShare)
+            );
+            assert_eq!(
+                check_grantee("alice@example.com", GranteeKind::Group, "alice@example.com"),
+                Ok("alice@example.com".to_string())
+            );
+            assert_eq!(
+                check_grantee("alice@example.com", GranteeKind::User, ""),
+                Err(ShareError::InvalidGrantee)
+            );
+        }
+
+        #[test]
+        fn read_grants_cannot_upload_or_delete() {
+            let shared = |id, permission| TreeNode {
+                permission: Some(permission),
+                ..node(id, NodeType::Branch, false)
+            };
+            let nodes = vec![
+                node(1, NodeType::Branch, true),
+                shared(2, SharePermission::Read),
+                TreeNode {
+                    node_type: NodeType::ImageLeaf,
+                    ..shared(3, SharePermission::Read)
+                },
+                shared(4, SharePermission::Edit),
+                node(5, NodeType::Branch, false),
+            ];
+            let id = |id: u128| Uuid::from_u128(id).to_string();
+
+            assert_eq!(editable(&nodes, &id(1)), Ok(()));
+            assert_eq!(editable(&nodes, &id(4)), Ok(()));
+            // Upload into a read-only branch and deletion of an image in it
+            for target in [2, 3] {
+                let rejected = editable(&nodes, &id(target)).unwrap_err();
+                assert_eq!(rejected, ShareError::ReadOnly);
+                assert_eq!(rejected.into_response().status(), StatusCode::FORBIDDEN);
+            }
+            // Only the way to a share
+            assert_eq!(editable(&nodes, &id(5)), Err(ShareError::ReadOnly));
+            assert_eq!(editable(&nodes, "not-a-node"), Err(ShareError::NotFound));
+            assert_eq!(editable(&nodes, &id(9)), Err(ShareError::NotFound));
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) use ssr::require_edit;
+#[cfg(feature = "ssr")]
+pub use ssr::{ShareError, grant_share_handler, list_shares_handler, revoke_share_handler};
@@ -2,16 +2,18 @@ use crate::app::shell;
 use crate::auth::*;
 use crate::auth_ssr::*;
 use crate::config::AppConfig;
+use crate::rate_limit::{LOGIN_POLICY, ip_key, retry_after_secs};
 use crate::state::{AppState, ChatSession};
 use axum::{
     Json,
-    extract::{FromRef, OriginalUri, Query, State},
+    extract::{ConnectInfo, FromRef, OriginalUri, Query, State},
     http::{HeaderValue, StatusCode},
     middleware::Next,
     response::{Html, IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::{CookieJar, cookie::Cookie};
 use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use chrono::Utc;
 use cookie::time::Duration as CookieDuration;
 use http::HeaderMap;
 use leptos::config::LeptosOptions;
@@ -26,16 +28,18 @@ use oauth2::{
 };
 use openidconnect::core::{
     CoreAuthDisplay, CoreAuthPrompt, CoreClient, CoreGenderClaim, CoreIdTokenClaims,
-    CoreIdTokenVerifier, CoreJsonWebKey, CoreJweContentEncryptionAlgorithm, CoreProviderMetadata,
-    CoreResponseType, CoreTokenIntrospectionResponse, CoreTokenResponse,
+    CoreIdTokenVerifier, CoreJsonWebKey, CoreJsonWebKeySet, CoreJweContentEncryptionAlgorithm,
+    CoreProviderMetadata, CoreResponseType, CoreTokenIntrospectionResponse, CoreTokenResponse,
 };
 use openidconnect::{
-    AuthenticationFlow, EmptyAdditionalClaims, IssuerUrl, Nonce, OAuth2TokenResponse,
+    AuthenticationFlow, EmptyAdditionalClaims, IssuerUrl, JsonWebKeySetUrl, Nonce,
+    OAuth2TokenResponse,
 };
 use serde::{Deserialize, Serialize, de::Error};
 use serde_json::Value;
 use serde_urlencoded::de::Error as UrlError;
 use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::Mutex;
@@ -112,6 +116,7 @@ struct ValidatedIdTokenData {
     roles: HashSet<Role>,
     name: String,
     email: Option<String>,
+    sid: Option<String>,
 }
 
 fn auth_language(jar: &CookieJar, query_language: Option<&str>) -> AuthLanguage {
@@ -170,6 +175,41 @@ fn callback_invalid_response(
     auth_error_response(language, request_id, status, title, message, retry, home)
 }
 
+fn login_rate_limited_response(
+    language: AuthLanguage,
+    request_id: &str,
+    retry_after: Duration,
+) -> Response {
+    let seconds = retry_after_secs(retry_after);
+    let (title, message, retry, home) = match language {
+        AuthLanguage::En => (
+            "Too many sign-in attempts",
+            format!("Please wait {seconds} seconds before trying again."),
+            "Try again",
+            "Home",
+        ),
+        AuthLanguage::De => (
+            "Zu viele Anmeldeversuche",
+            format!("Bitte warten Sie {seconds} Sekunden, bevor Sie es erneut versuchen."),
+            "Erneut versuchen",
+            "Startseite",
+        ),
+    };
+    let mut response = auth_error_response(
+        language,
+        request_id,
+        StatusCode::TOO_MANY_REQUESTS,
+        title,
+        &message,
+        retry,
+        home,
+    );
+    response
+        .headers_mut()
+        .insert(http::header::RETRY_AFTER, HeaderValue::from(seconds));
+    response
+}
+
 #[allow(clippy::too_many_arguments)]
 fn auth_error_response(
     language: AuthLanguage,
@@ -216,7 +256,7 @@ fn auth_error_response(
     (status, Html(html)).into_response()
 }
 
-fn correlation_id(headers: &HeaderMap) -> String {
+pub(crate) fn correlation_id(headers: &HeaderMap) -> String {
     ["x-request-id", "x-correlation-id"]
         .into_iter()
         .find_map(|name| {
@@ -236,6 +276,13 @@ fn correlation_id(headers: &HeaderMap) -> String {
         .unwrap_or_else(|| Uuid::now_v7().to_string())
 }
 
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(http::header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(256).collect())
+}
+
 fn validated_id_token_data(id_token: String, claims: &CoreIdTokenClaims) -> ValidatedIdTokenData {
     let expiry_system_time: SystemTime = claims.expiration().into();
     let expires_in = expiry_system_time
@@ -244,7 +291,6 @@ fn validated_id_token_data(id_token: String, claims: &CoreIdTokenClaims) -> Vali
     let claims_json = serde_json::to_value(claims).ok();
 
     ValidatedIdTokenData {
-        id_token,
         subject: claims.subject().to_string(),
         expires_in,
         roles: claims_json
@@ -256,6 +302,13 @@ fn validated_id_token_data(id_token: String, claims: &CoreIdTokenClaims) -> Vali
             .map(extract_name_from_claims)
             .unwrap_or_else(|| claims.subject().to_string()),
         email: claims_json.as_ref().and_then(extract_email_from_claims),
+        // `sid` is not part of the standard claims set, read it from the raw payload
+        sid: decode_jwt_payload(&id_token)
+            .as_ref()
+            .and_then(|claims| claims.get("sid"))
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        id_token,
     }
 }
 
@@ -282,6 +335,10 @@ pub struct ISPOidcClient {
         EndpointMaybeSet,
     >,
     pub config: AppConfig,
+    /// Provider JWKS endpoint and the key set fetched at discovery,
+    /// which seeds `AppState::jwks`
+    pub jwks_url: JsonWebKeySetUrl,
+    pub jwks: CoreJsonWebKeySet,
 }
 
 impl ISPOidcClient {
@@ -301,12 +358,16 @@ impl ISPOidcClient {
         let client_id = ClientId::new(config.oidc_client_id.clone());
         let client_secret = Some(ClientSecret::new(config.oidc_client_secret.clone()));
         let redirect_uri = RedirectUrl::new(config.oidc_redirect_uri.clone())?;
+        let jwks_url = provider_metadata.jwks_uri().clone();
+        let jwks = provider_metadata.jwks().clone();
 
         let inner = CoreClient::from_provider_metadata(provider_metadata, client_id, client_secret)
             .set_redirect_uri(redirect_uri);
         Ok(ISPOidcClient {
             client: inner,
             config,
+            jwks_url,
+            jwks,
         })
     }
 
@@ -412,6 +473,7 @@ fn apply_validated_id_token_claims(
     session.name = Some(validated.name);
     session.email = validated.email;
     session.id_token = Some(validated.id_token);
+    session.sid = validated.sid;
 
     let roles_extracted = !session.roles.is_empty();
     debug!(
@@ -440,6 +502,18 @@ async fn take_logout_state(
     (session, request_id)
 }
 
+/// Removes the session with its chat state and cancels the running agent request, if any.
+pub async fn terminate_session(state: &AppState, session_id: &str) -> Option<SessionData> {
+    let (session, request_id) =
+        take_logout_state(&state.sessions, &state.chat_sessions, session_id).await;
+
+    if let Some(request_id) = request_id {
+        crate::stop::cancel_request(state, &request_id);
+    }
+
+    session
+}
+
 pub async fn logout_handler(State(state): State<AppState>, jar: CookieJar) -> impl IntoResponse {
     let mut post_logout_redirect_uri = "/".to_string();
     let mut rauthy_logout_url = None;
@@ -447,21 +521,7 @@ pub async fn logout_handler(State(state): State<AppState>, jar: CookieJar) -> im
 
     if let Some(cookie) = jar.get(SESSION_ID) {
         let session_id = cookie.value().to_string();
-        let (session, request_id) =
-            take_logout_state(&state.sessions, &state.chat_sessions, &session_id).await;
-
-        if let Some(request_id) = request_id {
-            let client = state.async_http_client.clone();
-            let agent_api_url = state.http_client.config.chat_config.agent_api_url.clone();
-            let agent_secret = state
-                .http_client
-                .config
-                .chat_config
-                .agent_api_key
-                .clone()
-                .unwrap_or_default();
-            crate::stop::cancel_agent_request(&request_id, agent_api_url, agent_secret, client);
-        }
+        let session = terminate_session(&state, &session_id).await;
 
         if let Some(session) = session {
             if let Some(id_token) = session.id_token
@@ -525,10 +585,6 @@ pub async fn leptos_server_fn_handler(
     let auth_state = get_auth_state(state.clone(), headers).await;
     handle_server_fns_with_context(
         move || {
-            let client_config = crate::ClientConfig {
-                media_proxy: state.http_client.config.media_proxy.clone(),
-            };
-            provide_context(client_config);
             provide_context(state.sessions.clone());
             provide_context(jar.clone());
             provide_context(auth_state.clone());
@@ -545,6 +601,11 @@ pub async fn leptos_main_handler(
     req: axum::http::Request<axum::body::Body>,
 ) -> Response {
     let headers = req.headers().clone();
+    let csrf_token = crate::csrf::CsrfToken(
+        crate::csrf::session_csrf_token(&state, &headers)
+            .await
+            .unwrap_or_default(),
+    );
     let auth_state = get_auth_state(state.clone(), headers).await;
     let leptos_options = state.leptos_options.as_ref().clone();
     let nonce = req
@@ -554,13 +615,10 @@ pub async fn leptos_main_handler(
         .unwrap_or_else(leptos::nonce::Nonce::new);
     let handler = leptos_axum::render_app_to_stream_with_context(
         move || {
-            let client_config = crate::ClientConfig {
-                media_proxy: state.http_client.config.media_proxy.clone(),
-            };
-            provide_context(client_config);
             provide_context(jar.clone());
             provide_context(state.sessions.clone());
             provide_context(auth_state.clone());
+            provide_context(csrf_token.clone());
             provide_context(nonce.clone());
         },
         //move || view! { <App/> },
@@ -571,12 +629,20 @@ pub async fn leptos_main_handler(
 
 pub async fn login_handler(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     jar: CookieJar,
     Query(query): Query<LoginQuery>,
 ) -> impl IntoResponse {
     let request_id = correlation_id(&headers);
     let language = auth_language(&jar, query.lang.as_deref());
+    // Every login creates a pre-auth session, so limit per client address
+    let client_ip = state.rate_limiter.client_ip(Some(peer.ip()), &headers);
+    let limit_key = ip_key(client_ip.unwrap_or(peer.ip()));
+    if let Err(retry_after) = state.rate_limiter.check(&LOGIN_POLICY, &limit_key) {
+        warn!(request_id = %request_id, ip = %limit_key, "login: rate limited");
+        return login_rate_limited_response(language, &request_id, retry_after);
+    }
     let started_at = Instant::now();
     let discovery_url = oidc_discovery_url(&state.http_client.config.oidc_issuer_url);
     let sso_status = state
@@ -642,6 +708,12 @@ pub async fn login_handler(
             id_token_expires_at: None,
             is_refreshing: Mutex::new(false).into(),
             email: None,
+            sid: None,
+            created_at: Utc::now(),
+            last_activity: Utc::now(),
+            user_agent: user_agent(&headers),
+            ip: client_ip.map(|ip| ip.to_string()),
+            csrf_api_token: crate::csrf::new_csrf_token(),
         },
     );
 
@@ -1039,14 +1111,15 @@ mod auth_reliability_tests {
 
 /// Extract claims from Access Token, (if it is JWT).
 pub fn extract_claims_from_access_token(token: &AccessToken) -> Option<Value> {
-    let token_str = token.secret();
+    decode_jwt_payload(token.secret())
+}
+
+/// Decode the payload of a compact JWT without verifying it.
+pub fn decode_jwt_payload(token_str: &str) -> Option<Value> {
     let parts: Vec<&str> = token_str.split('.').collect();
 
     if parts.len() != 3 {
-        warn!(
-            "Access Token is not JWT (must b 3 part, found: {})",
-            parts.len()
-        );
+        warn!("Token is not JWT (must b 3 part, found: {})", parts.len());
         return None;
     }
 
@@ -1056,12 +1129,12 @@ pub fn extract_claims_from_access_token(token: &AccessToken) -> Option<Value> {
         Ok(decoded_bytes) => match serde_json::from_slice(&decoded_bytes) {
             Ok(claims_value) => Some(claims_value),
             Err(e) => {
-                warn!("Failed to deserialize Claims from Access Token: {}", e);
+                warn!("Failed to deserialize Claims from Token: {}", e);
                 None
             }
         },
         Err(e) => {
-            warn!("Unable to decode Base64 Payload from Access Token: {}", e);
+            warn!("Unable to decode Base64 Payload from Token: {}", e);
             None
         }
     }
@@ -1108,7 +1181,7 @@ pub async fn security_headers(
                 frame-ancestors 'none'; \
                 script-src 'self' 'unsafe-inline' 'unsafe-eval' 'wasm-unsafe-eval' 'nonce-{}'; \
                 style-src 'self' 'unsafe-inline' {trust_data_list} 'nonce-{}'; \
-                img-src 'self' data: blob: {trust_data_list}; \
+                img-src 'self' data: blob:; \
                 font-src 'self' data: {trust_data_list}; \
                 connect-src 'self' ws: wss: {trust_connect_list}",
             nonce, nonce
@@ -1121,7 +1194,7 @@ pub async fn security_headers(
                  frame-ancestors 'none'; \
                  script-src 'self' 'nonce-{}' 'wasm-unsafe-eval'; \
                  style-src 'self' {trust_data_list} 'nonce-{}'; \
-                 img-src 'self' data: blob: {trust_connect_list}; \
+                 img-src 'self' data: blob:; \
                  font-src 'self' data: {trust_data_list}; \
                  connect-src 'self' {trust_connect_list}",
             nonce, nonce
@@ -1180,5 +1253,6 @@ fn is_static(uri: String) -> bool {
         || uri.starts_with("/login")
         || uri.starts_with("/logout")
         || uri.starts_with("/callback")
+        || uri.starts_with("/backchannel-logout")
         || uri.starts_with("/local")
 }
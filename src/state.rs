@@ -1,4 +1,11 @@
+use crate::api_tokens::ApiTokenStore;
+use crate::audit::{AuditSink, JsonlAuditSink};
 use crate::auth_ssr::SessionData;
+use crate::backchannel::JwksCache;
+use crate::chunked_upload::ChunkedUploadStore;
+use crate::media::MediaStore;
+use crate::proxy_tree::TreeCache;
+use crate::rate_limit::RateLimiter;
 use crate::ssr::ISPOidcClient;
 use leptos::config::LeptosOptions;
 use std::collections::HashMap;
@@ -14,6 +21,13 @@ pub struct AppState {
     pub sso_http_client: reqwest::Client,
     pub chat_sessions: Arc<Mutex<HashMap<String, Arc<ChatSession>>>>,
     pub agent_max_retries: usize,
+    pub api_tokens: Arc<ApiTokenStore>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub audit: Arc<dyn AuditSink>,
+    pub chunked_uploads: Arc<ChunkedUploadStore>,
+    pub media: Arc<MediaStore>,
+    pub trees: Arc<TreeCache>,
+    pub jwks: Arc<JwksCache>,
 }
 pub struct ChatSession {
     pub current_request_id: tokio::sync::RwLock<Option<String>>,
@@ -42,6 +56,20 @@ impl AppState {
         let leptos_options = conf.leptos_options;
         // 3. Initialize OIDC Client
         let oidc_client = ISPOidcClient::new(&sso_http_client).await?;
+        let api_tokens = ApiTokenStore::load(&oidc_client.config.api_tokens_path);
+        let rate_limiter = RateLimiter::new(&oidc_client.config.trusted_proxies);
+        let audit = JsonlAuditSink::new(&oidc_client.config.audit_log_path);
+        let upload_config = &oidc_client.config.upload_config;
+        let chunked_uploads = Arc::new(ChunkedUploadStore::new(
+            &upload_config.staging_dir,
+            upload_config.max_staging_total_bytes,
+        ));
+        ChunkedUploadStore::spawn_sweeper(&chunked_uploads);
+        let jwks = JwksCache::new(oidc_client.jwks.clone());
+        let media = MediaStore::open(
+            &oidc_client.config.media_cache_dir,
+            oidc_client.config.media_cache_max_bytes,
+        );
 
         // 4. Construct AppState
         let state = AppState {
@@ -59,6 +87,17 @@ impl AppState {
             chat_sessions: Arc::new(Mutex::new(HashMap::new())),
 
             agent_max_retries: 0,
+
+            api_tokens: Arc::new(api_tokens),
+
+            rate_limiter: Arc::new(rate_limiter),
+
+            audit: Arc::new(audit),
+
+            chunked_uploads,
+            media: Arc::new(media),
+            trees: Arc::new(TreeCache::default()),
+            jwks: Arc::new(jwks),
         };
 
         Ok(state)
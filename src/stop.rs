@@ -1,16 +1,22 @@
+use crate::api_tokens::ApiPrincipal;
 use crate::state::AppState;
 use crate::{auth::SESSION_ID, hmac::build_hmac};
-use axum::{extract::State, response::IntoResponse};
+use axum::{Extension, extract::State, response::IntoResponse};
 use axum_extra::extract::CookieJar;
 use reqwest::{Client, StatusCode};
 use std::time::Duration;
 
 // Stop handler - extracts session_id from cookie
-pub async fn stop_handler(State(state): State<AppState>, jar: CookieJar) -> impl IntoResponse {
-    // Extract session_id from cookie
-    let session_id = match jar.get(SESSION_ID) {
-        Some(cookie) => cookie.value().to_string(),
-        None => {
+pub async fn stop_handler(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    principal: Option<Extension<ApiPrincipal>>,
+) -> impl IntoResponse {
+    // Extract session_id from cookie, or the API token key
+    let session_id = match (principal, jar.get(SESSION_ID)) {
+        (Some(Extension(principal)), _) => principal.session_key(),
+        (None, Some(cookie)) => cookie.value().to_string(),
+        (None, None) => {
             tracing::error!("No session_id in cookie");
             return (StatusCode::UNAUTHORIZED, "No session to exit");
         }
@@ -23,16 +29,7 @@ pub async fn stop_handler(State(state): State<AppState>, jar: CookieJar) -> impl
     if let Some(chat_session) = sessions.get_mut(&session_id) {
         // Cancel on agent if active request exists
         if let Some(request_id) = &chat_session.current_request_id.read().await.clone() {
-            let agent_api_url = state.http_client.config.chat_config.agent_api_url.clone();
-            let agent_secret = state
-                .http_client
-                .config
-                .chat_config
-                .agent_api_key
-                .clone()
-                .unwrap_or_default();
-            let client = state.async_http_client.clone();
-            cancel_agent_request(request_id, agent_api_url, agent_secret, client);
+            cancel_request(&state, request_id);
         }
 
         (StatusCode::OK, "stopped")
@@ -42,6 +39,17 @@ pub async fn stop_handler(State(state): State<AppState>, jar: CookieJar) -> impl
     }
 }
 
+/// Cancels an agent request using the configured agent URL and secret.
+pub fn cancel_request(state: &AppState, request_id: &String) {
+    let chat_config = &state.http_client.config.chat_config;
+    cancel_agent_request(
+        request_id,
+        chat_config.agent_api_url.clone(),
+        chat_config.agent_api_key.clone().unwrap_or_default(),
+        state.async_http_client.clone(),
+    );
+}
+
 pub fn cancel_agent_request(
     request_id: &String,
     agent_api_url: String,
@@ -0,0 +1,1651 @@
+use crate::config::{ExifPolicy, UploadConfig};
+use crate::duplicates::{HASH_FIELD, PERCEPTUAL_HASH_FIELD, parse_perceptual_hash};
+use crate::exif::CaptureTime;
+use crate::hmac::{SIGNATURE_FIELD, TIMESTAMP_FIELD, TrailerSigner};
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Multipart, multipart::Field},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures::SinkExt;
+use futures::channel::{mpsc, oneshot};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+const IMAGE_FIELD: &str = "image";
+const DATETIME_FIELD: &str = "berlin_datetime";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    WebP,
+    Heic,
+}
+
+impl ImageFormat {
+    pub fn mime(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::WebP => "image/webp",
+            Self::Heic => "image/heic",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::WebP => "webp",
+            Self::Heic => "heic",
+        }
+    }
+}
+
+/// Rejection reasons, `code` doubles as the client's ftl key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadError {
+    InvalidForm,
+    MissingFile,
+    UnsupportedType,
+    TooLarge { max_bytes: usize },
+    TooManyPixels { max_pixels: u64 },
+    InvalidImage,
+}
+
+impl UploadError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidForm => "upload-invalid-form",
+            Self::MissingFile => "upload-missing-file",
+            Self::UnsupportedType => "upload-unsupported-type",
+            Self::TooLarge { .. } => "upload-too-large",
+            Self::TooManyPixels { .. } => "upload-too-many-pixels",
+            Self::InvalidImage => "upload-invalid-image",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::InvalidForm | Self::MissingFile => StatusCode::BAD_REQUEST,
+            Self::UnsupportedType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::TooManyPixels { .. } | Self::InvalidImage => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            Self::InvalidForm => "Invalid upload form",
+            Self::MissingFile => "No image in upload",
+            Self::UnsupportedType => "Unsupported image type",
+            Self::TooLarge { .. } => "Image file is too large",
+            Self::TooManyPixels { .. } => "Image resolution is too large",
+            Self::InvalidImage => "Image file is damaged",
+        }
+    }
+}
+
+impl IntoResponse for UploadError {
+    fn into_response(self) -> Response {
+        let mut body = serde_json::json!({
+            "error": self.message(),
+            "code": self.code(),
+        });
+        match self {
+            Self::TooLarge { max_bytes } => {
+                body["max_mb"] = (max_bytes / (1024 * 1024)).into();
+            }
+            Self::TooManyPixels { max_pixels } => {
+                body["max_megapixels"] = (max_pixels / 1_000_000).into();
+            }
+            _ => {}
+        }
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+pub fn sniff(bytes: &[u8]) -> Option<ImageFormat> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some(ImageFormat::Jpeg);
+    }
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(ImageFormat::Png);
+    }
+    if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some(ImageFormat::WebP);
+    }
+    if bytes.len() >= 16 && &bytes[4..8] == b"ftyp" {
+        let size = (be_u32(bytes, 0)? as usize).clamp(16, bytes.len());
+        let heic = |brand: &[u8]| {
+            matches!(
+                brand,
+                b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx"
+            )
+        };
+        let major = &bytes[8..12];
+        // Compatible brands follow the major brand and minor version
+        if heic(major) || bytes[16..size].chunks_exact(4).any(heic) {
+            return Some(ImageFormat::Heic);
+        }
+    }
+    None
+}
+
+/// Pixel size read from the container headers.
+pub fn dimensions(format: ImageFormat, bytes: &[u8]) -> Option<(u32, u32)> {
+    match format {
+        ImageFormat::Jpeg => jpeg_dimensions(bytes),
+        ImageFormat::Png => {
+            (bytes.get(12..16)? == b"IHDR").then_some((be_u32(bytes, 16)?, be_u32(bytes, 20)?))
+        }
+        ImageFormat::WebP => webp_dimensions(bytes),
+        ImageFormat::Heic => heic_dimensions(bytes),
+    }
+}
+
+/// Checks type and limits before anything reaches the agent.
+pub fn validate_image(bytes: &[u8], config: &UploadConfig) -> Result<ImageFormat, UploadError> {
+    if bytes.len() > config.max_bytes {
+        return Err(UploadError::TooLarge {
+            max_bytes: config.max_bytes,
+        });
+    }
+    validate_head(bytes, config)
+}
+
+/// Type and resolution checks, which only need the container headers.
+pub fn validate_head(head: &[u8], config: &UploadConfig) -> Result<ImageFormat, UploadError> {
+    let format = sniff(head).ok_or(UploadError::UnsupportedType)?;
+    let (width, height) = dimensions(format, head)
+        .filter(|(width, height)| *width > 0 && *height > 0)
+        .ok_or(UploadError::InvalidImage)?;
+    if width as u64 * height as u64 > config.max_pixels {
+        return Err(UploadError::TooManyPixels {
+            max_pixels: config.max_pixels,
+        });
+    }
+    Ok(format)
+}
+
+/// Applies the EXIF policy. Metadata is blanked in place so container offsets stay
+/// valid; XMP packets, which may repeat the location, go too unless kept.
+pub fn normalize_metadata(
+    format: ImageFormat,
+    mut bytes: Vec<u8>,
+    policy: ExifPolicy,
+) -> Result<Vec<u8>, UploadError> {
+    if policy == ExifPolicy::Keep {
+        return Ok(bytes);
+    }
+    match format {
+        ImageFormat::Jpeg => normalize_jpeg(&bytes, policy),
+        ImageFormat::Png => normalize_png(&bytes, policy),
+        ImageFormat::WebP => {
+            normalize_webp(&mut bytes, policy)?;
+            Ok(bytes)
+        }
+        ImageFormat::Heic => {
+            normalize_heic(&mut bytes, policy)?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// Body of an upload forwarded to the agent; an error aborts the request.
+pub type BodySender = mpsc::Sender<std::io::Result<Bytes>>;
+
+/// Forwards an upload form to the agent as it arrives, signed by trailing
+/// `TIMESTAMP_FIELD` and `SIGNATURE_FIELD` parts when a `signer` is given. The image
+/// head is validated before `ready` fires so a rejected upload never reaches the
+/// agent; after that the image passes through a `StreamScrubber` and `body` applies
+/// back-pressure.
+pub async fn stream_upload(
+    multipart: Multipart,
+    config: &UploadConfig,
+    boundary: &str,
+    signer: Option<TrailerSigner>,
+    ready: oneshot::Sender<()>,
+    mut body: BodySender,
+) -> Result<(), UploadError> {
+    let forwarded = forward_form(multipart, config, boundary, signer, ready, &mut body).await;
+    if let Err(e) = &forwarded {
+        let _ = body.send(Err(std::io::Error::other(e.message()))).await;
+    }
+    forwarded
+}
+
+async fn forward_form(
+    mut multipart: Multipart,
+    config: &UploadConfig,
+    boundary: &str,
+    mut signer: Option<TrailerSigner>,
+    ready: oneshot::Sender<()>,
+    body: &mut BodySender,
+) -> Result<(), UploadError> {
+    let mut fields = Vec::new();
+    let mut image = loop {
+        let field = multipart
+            .next_field()
+            .await
+            .map_err(|_| UploadError::InvalidForm)?
+            .ok_or(UploadError::MissingFile)?;
+        if field.name() == Some(IMAGE_FIELD) {
+            break field;
+        }
+        let name = field.name().unwrap_or_default().to_string();
+        let value = field.text().await.map_err(|_| UploadError::InvalidForm)?;
+        fields.push((name, value));
+    };
+
+    let mut received = 0;
+    let mut head = Vec::new();
+    while head.len() < STAGED_HEAD_BYTES {
+        match next_chunk(&mut image, &mut received, config).await? {
+            Some(chunk) => head.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    let format = validate_head(&head, config)?;
+    let captured = crate::exif::capture_time(&head);
+    let file_name = safe_file_name(image.file_name(), format);
+    let _ = ready.send(());
+
+    let mut content = Sha256::new();
+    content.update(&head);
+    let mut scrubber = StreamScrubber::new(format, config.exif_policy);
+    let opening = image_part(boundary, &file_name, format).into_bytes();
+    let mut forwarding = forward(body, &mut signer, opening).await
+        && forward(body, &mut signer, scrubber.push(&head)?).await;
+    drop(head);
+    while forwarding && let Some(chunk) = next_chunk(&mut image, &mut received, config).await? {
+        content.update(&chunk);
+        forwarding = forward(body, &mut signer, scrubber.push(&chunk)?).await;
+    }
+    // The agent has stopped reading, its response tells why
+    if !forwarding || !forward(body, &mut signer, scrubber.finish()?).await {
+        return Ok(());
+    }
+    drop(image);
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| UploadError::InvalidForm)?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == IMAGE_FIELD {
+            return Err(UploadError::InvalidForm);
+        }
+        let value = field.text().await.map_err(|_| UploadError::InvalidForm)?;
+        fields.push((name, value));
+    }
+    fill_capture_time(&mut fields, captured);
+    fill_hashes(&mut fields, hex::encode(content.finalize()));
+    let mut rest = String::from("\r\n");
+    for (name, value) in &fields {
+        rest.push_str(&form_part(boundary, name, value));
+    }
+    if forward(body, &mut signer, rest.into_bytes()).await {
+        let mut trailer = String::new();
+        if let Some(signer) = signer {
+            let (timestamp, signature) = signer.finish();
+            trailer.push_str(&form_part(
+                boundary,
+                TIMESTAMP_FIELD,
+                &timestamp.to_string(),
+            ));
+            trailer.push_str(&form_part(boundary, SIGNATURE_FIELD, &signature));
+        }
+        trailer.push_str(&format!("--{boundary}--\r\n"));
+        let _ = body.send(Ok(Bytes::from(trailer))).await;
+    }
+    Ok(())
+}
+
+/// Next piece of the image field, counted against the size limit.
+async fn next_chunk(
+    field: &mut Field<'_>,
+    received: &mut usize,
+    config: &UploadConfig,
+) -> Result<Option<Bytes>, UploadError> {
+    let chunk = field.chunk().await.map_err(|e| {
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            UploadError::TooLarge {
+                max_bytes: config.max_bytes,
+            }
+        } else {
+            UploadError::InvalidForm
+        }
+    })?;
+    *received += chunk.as_ref().map_or(0, Bytes::len);
+    if *received > config.max_bytes {
+        return Err(UploadError::TooLarge {
+            max_bytes: config.max_bytes,
+        });
+    }
+    Ok(chunk)
+}
+
+/// Signs and sends `bytes`; false once the agent has closed the body.
+async fn forward(
+    body: &mut BodySender,
+    signer: &mut Option<TrailerSigner>,
+    bytes: Vec<u8>,
+) -> bool {
+    if bytes.is_empty() {
+        return true;
+    }
+    if let Some(signer) = signer {
+        signer.update(&bytes);
+    }
+    body.send(Ok(Bytes::from(bytes))).await.is_ok()
+}
+
+/// A resumable upload staged on disk, validated and with the EXIF policy applied.
+pub struct StagedImage {
+    pub path: PathBuf,
+    pub len: u64,
+    pub format: ImageFormat,
+    pub file_name: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl StagedImage {
+    /// Checks the headers and scrubs metadata in place; only the head is read
+    /// into memory.
+    pub async fn prepare(
+        path: PathBuf,
+        file_name: &str,
+        mut fields: Vec<(String, String)>,
+        config: &UploadConfig,
+    ) -> Result<Self, UploadError> {
+        let config = config.clone();
+        let file_name = file_name.to_string();
+        tokio::task::spawn_blocking(move || {
+            let mut file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&path)
+                .map_err(|_| UploadError::MissingFile)?;
+            let len = file.metadata().map_err(|_| UploadError::MissingFile)?.len();
+            let head = read_at(&mut file, 0, len.min(STAGED_HEAD_BYTES as u64) as usize)?;
+            let format = validate_head(&head, &config)?;
+            fill_capture_time(&mut fields, crate::exif::capture_time(&head));
+            fill_hashes(&mut fields, content_hash(&mut file)?);
+            normalize_staged(&mut file, format, config.exif_policy)?;
+            Ok(Self {
+                path,
+                len,
+                format,
+                file_name: safe_file_name(Some(&file_name), format),
+                fields,
+            })
+        })
+        .await
+        .map_err(|_| UploadError::InvalidImage)?
+    }
+
+    /// `multipart_frame` around the staged file.
+    pub fn frame(&self) -> (String, Vec<u8>, Vec<u8>) {
+        multipart_frame(&self.fields, &self.file_name, self.format)
+    }
+}
+
+/// Content type plus the bytes before and after the image of a `multipart/form-data`
+/// body holding `fields` and the image part.
+pub fn multipart_frame(
+    fields: &[(String, String)],
+    file_name: &str,
+    format: ImageFormat,
+) -> (String, Vec<u8>, Vec<u8>) {
+    let boundary = new_boundary();
+    let mut head = String::with_capacity(512);
+    for (name, value) in fields {
+        head.push_str(&form_part(&boundary, name, value));
+    }
+    head.push_str(&image_part(&boundary, file_name, format));
+    let tail = format!("\r\n--{boundary}--\r\n").into_bytes();
+    (form_content_type(&boundary), head.into_bytes(), tail)
+}
+
+pub fn new_boundary() -> String {
+    format!("gmr-{}", uuid::Uuid::now_v7().simple())
+}
+
+pub fn form_content_type(boundary: &str) -> String {
+    format!("multipart/form-data; boundary={boundary}")
+}
+
+fn form_part(boundary: &str, name: &str, value: &str) -> String {
+    format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{value}\r\n",
+        quote_safe(name)
+    )
+}
+
+/// Opening of the image part, its bytes follow.
+fn image_part(boundary: &str, file_name: &str, format: ImageFormat) -> String {
+    format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"{IMAGE_FIELD}\"; filename=\"{file_name}\"\r\nContent-Type: {}\r\n\r\n",
+        format.mime()
+    )
+}
+
+/// Defaults a missing report timestamp to the EXIF capture time, read before the
+/// metadata is stripped.
+fn fill_capture_time(fields: &mut Vec<(String, String)>, captured: Option<CaptureTime>) {
+    let has_datetime = fields
+        .iter()
+        .any(|(name, value)| name == DATETIME_FIELD && !value.trim().is_empty());
+    if has_datetime {
+        return;
+    }
+    if let Some(captured) = captured {
+        fields.retain(|(name, _)| name != DATETIME_FIELD);
+        fields.push((DATETIME_FIELD.to_string(), captured.agent_datetime()));
+    }
+}
+
+/// Records the SHA-256 of the image as received, before metadata is stripped, for
+/// later duplicate checks. A perceptual hash from the client is kept if well-formed.
+fn fill_hashes(fields: &mut Vec<(String, String)>, hash: String) {
+    fields.retain(|(name, value)| {
+        name != HASH_FIELD
+            && (name != PERCEPTUAL_HASH_FIELD || parse_perceptual_hash(value).is_some())
+    });
+    fields.push((HASH_FIELD.to_string(), hash));
+}
+
+/// Hex SHA-256 of a whole file.
+fn content_hash<F: Read + Seek>(file: &mut F) -> Result<String, UploadError> {
+    file.seek(SeekFrom::Start(0))
+        .map_err(|_| UploadError::InvalidImage)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 256 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|_| UploadError::InvalidImage)?;
+        if read == 0 {
+            return Ok(hex::encode(hasher.finalize()));
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+fn quote_safe(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !matches!(c, '"' | '\r' | '\n' | '\\'))
+        .collect()
+}
+
+/// Original stem limited to safe characters, extension from the sniffed type.
+fn safe_file_name(file_name: Option<&str>, format: ImageFormat) -> String {
+    let stem: String = file_name
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .map(|name| name.rsplit_once('.').map_or(name, |(stem, _)| stem))
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '))
+        .take(100)
+        .collect();
+    let stem = stem.trim_matches(['.', ' ']);
+    let stem = if stem.is_empty() { "upload" } else { stem };
+    format!("{stem}.{}", format.extension())
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_u24(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 3)?;
+    Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+}
+
+struct JpegSegment {
+    marker: u8,
+    /// Offset of the `0xFF` marker byte.
+    start: usize,
+    /// Payload after the length field.
+    data: std::ops::Range<usize>,
+}
+
+/// JPEG marker segments up to and including the start of scan.
+fn jpeg_segments(bytes: &[u8]) -> Option<Vec<JpegSegment>> {
+    let mut segments = Vec::new();
+    let mut at = 2;
+    loop {
+        if *bytes.get(at)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(at + 1)?;
+        if marker == 0xFF {
+            at += 1;
+            continue;
+        }
+        if matches!(marker, 0x01 | 0xD0..=0xD7) {
+            at += 2;
+            continue;
+        }
+        let length = be_u16(bytes, at + 2)? as usize;
+        let end = at + 2 + length;
+        if length < 2 || end > bytes.len() {
+            return None;
+        }
+        segments.push(JpegSegment {
+            marker,
+            start: at,
+            data: at + 4..end,
+        });
+        if marker == 0xDA {
+            return Some(segments);
+        }
+        at = end;
+    }
+}
+
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    jpeg_segments(bytes)?.into_iter().find_map(|segment| {
+        let marker = segment.marker;
+        let is_sof = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        is_sof.then(|| {
+            let height = be_u16(bytes, segment.data.start + 1)? as u32;
+            let width = be_u16(bytes, segment.data.start + 3)? as u32;
+            Some((width, height))
+        })?
+    })
+}
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp\0";
+
+fn normalize_jpeg(bytes: &[u8], policy: ExifPolicy) -> Result<Vec<u8>, UploadError> {
+    let segments = jpeg_segments(bytes).ok_or(UploadError::InvalidImage)?;
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[..2]);
+    let mut copied = 2;
+    for JpegSegment {
+        marker,
+        start,
+        data,
+    } in segments
+    {
+        if marker != 0xE1 {
+            continue;
+        }
+        out.extend_from_slice(&bytes[copied..start]);
+        copied = data.end;
+        let payload = &bytes[data.clone()];
+        if payload.starts_with(XMP_HEADER) {
+            continue;
+        }
+        let mut segment = bytes[start..data.end].to_vec();
+        if payload.starts_with(EXIF_HEADER) {
+            scrub_exif(&mut segment[4 + EXIF_HEADER.len()..], policy);
+        }
+        out.extend_from_slice(&segment);
+    }
+    out.extend_from_slice(&bytes[copied..]);
+    Ok(out)
+}
+
+fn normalize_png(bytes: &[u8], policy: ExifPolicy) -> Result<Vec<u8>, UploadError> {
+    let mut out = bytes[..8].to_vec();
+    let mut at = 8;
+    while at < bytes.len() {
+        let length = be_u32(bytes, at).ok_or(UploadError::InvalidImage)? as usize;
+        let end = at
+            .checked_add(12 + length)
+            .filter(|end| *end <= bytes.len())
+            .ok_or(UploadError::InvalidImage)?;
+        let kind = &bytes[at + 4..at + 8];
+        let data = &bytes[at + 8..at + 8 + length];
+        match kind {
+            b"iTXt" if data.starts_with(PNG_XMP_KEYWORD) => {}
+            b"eXIf" => {
+                let mut chunk = bytes[at..end].to_vec();
+                scrub_exif(&mut chunk[8..8 + length], policy);
+                let crc = crc32(&chunk[4..8 + length]);
+                chunk[8 + length..].copy_from_slice(&crc.to_be_bytes());
+                out.extend_from_slice(&chunk);
+            }
+            _ => out.extend_from_slice(&bytes[at..end]),
+        }
+        at = end;
+        if kind == b"IEND" {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+fn webp_chunks(bytes: &[u8]) -> Vec<([u8; 4], std::ops::Range<usize>)> {
+    let mut chunks = Vec::new();
+    let mut at = 12;
+    while let (Some(kind), Some(length)) = (bytes.get(at..at + 4), le_u32(bytes, at + 4)) {
+        let start = at + 8;
+        let Some(end) = start
+            .checked_add(length as usize)
+            .filter(|end| *end <= bytes.len())
+        else {
+            break;
+        };
+        chunks.push((kind.try_into().unwrap_or_default(), start..end));
+        at = end + (length as usize & 1);
+    }
+    chunks
+}
+
+fn webp_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let (kind, data) = webp_chunks(bytes).into_iter().next()?;
+    let data = bytes.get(data)?;
+    match &kind {
+        b"VP8 " => {
+            (data.get(3..6)? == [0x9D, 0x01, 0x2A]).then_some(())?;
+            let width = u16::from_le_bytes(data.get(6..8)?.try_into().ok()?) & 0x3FFF;
+            let height = u16::from_le_bytes(data.get(8..10)?.try_into().ok()?) & 0x3FFF;
+            Some((width as u32, height as u32))
+        }
+        b"VP8L" => {
+            (*data.first()? == 0x2F).then_some(())?;
+            let bits = le_u32(data, 1)?;
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8X" => Some((le_u24(data, 4)? + 1, le_u24(data, 7)? + 1)),
+        _ => None,
+    }
+}
+
+fn normalize_webp(bytes: &mut [u8], policy: ExifPolicy) -> Result<(), UploadError> {
+    for (kind, data) in webp_chunks(bytes) {
+        match &kind {
+            b"EXIF" => {
+                let chunk = &mut bytes[data];
+                let tiff = if chunk.starts_with(EXIF_HEADER) {
+                    &mut chunk[EXIF_HEADER.len()..]
+                } else {
+                    chunk
+                };
+                scrub_exif(tiff, policy);
+            }
+            b"XMP " => bytes[data].fill(b' '),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Leading bytes read to validate a staged upload. They hold the JPEG markers up
+/// to the start of scan and the PNG, WebP and HEIC headers.
+pub const STAGED_HEAD_BYTES: usize = 4 * 1024 * 1024;
+/// Largest metadata block held in memory for scrubbing.
+const MAX_METADATA_BYTES: usize = 1024 * 1024;
+
+fn read_at<F: Read + Seek>(file: &mut F, at: u64, len: usize) -> Result<Vec<u8>, UploadError> {
+    let mut bytes = vec![0; len];
+    file.seek(SeekFrom::Start(at))
+        .and_then(|_| file.read_exact(&mut bytes))
+        .map_err(|_| UploadError::InvalidImage)?;
+    Ok(bytes)
+}
+
+fn write_at<F: Write + Seek>(file: &mut F, at: u64, bytes: &[u8]) -> Result<(), UploadError> {
+    file.seek(SeekFrom::Start(at))
+        .and_then(|_| file.write_all(bytes))
+        .map_err(|_| UploadError::InvalidImage)
+}
+
+/// `normalize_metadata` for an upload staged on disk. Metadata blocks are located
+/// from the container structure and blanked in place without reading the image data.
+pub fn normalize_staged<F: Read + Write + Seek>(
+    file: &mut F,
+    format: ImageFormat,
+    policy: ExifPolicy,
+) -> Result<(), UploadError> {
+    if policy == ExifPolicy::Keep {
+        return Ok(());
+    }
+    let len = file
+        .seek(SeekFrom::End(0))
+        .map_err(|_| UploadError::InvalidImage)?;
+    match format {
+        ImageFormat::Jpeg => {
+            let head = read_at(file, 0, len.min(STAGED_HEAD_BYTES as u64) as usize)?;
+            for segment in jpeg_segments(&head).ok_or(UploadError::InvalidImage)? {
+                let mut payload = head[segment.data.clone()].to_vec();
+                if scrub_jpeg_segment(segment.marker, &mut payload, policy) {
+                    write_at(file, segment.data.start as u64, &payload)?;
+                }
+            }
+        }
+        ImageFormat::Png => {
+            let mut at = 8;
+            while at + 12 <= len {
+                let header = read_at(file, at, 8)?;
+                let length = be_u32(&header, 0).ok_or(UploadError::InvalidImage)? as u64;
+                let end = at + 12 + length;
+                if end > len {
+                    return Err(UploadError::InvalidImage);
+                }
+                match &header[4..8] {
+                    b"eXIf" | b"iTXt" => {
+                        // Type and data, the CRC covers both
+                        let mut chunk = read_at(file, at + 4, 4 + length as usize)?;
+                        let rewrite = if &header[4..8] == b"eXIf" {
+                            scrub_exif(&mut chunk[4..], policy);
+                            true
+                        } else if chunk[4..].starts_with(PNG_XMP_KEYWORD) {
+                            blank_itxt(&mut chunk[4..]);
+                            true
+                        } else {
+                            false
+                        };
+                        if rewrite {
+                            let crc = crc32(&chunk);
+                            chunk.extend_from_slice(&crc.to_be_bytes());
+                            write_at(file, at + 4, &chunk)?;
+                        }
+                    }
+                    b"IEND" => break,
+                    _ => {}
+                }
+                at = end;
+            }
+        }
+        ImageFormat::WebP => {
+            let mut at = 12;
+            while at + 8 <= len {
+                let header = read_at(file, at, 8)?;
+                let length = le_u32(&header, 4).ok_or(UploadError::InvalidImage)? as u64;
+                let start = at + 8;
+                if start + length > len {
+                    break;
+                }
+                match &header[..4] {
+                    b"EXIF" => {
+                        let mut chunk = read_at(file, start, length as usize)?;
+                        let skip = if chunk.starts_with(EXIF_HEADER) {
+                            EXIF_HEADER.len()
+                        } else {
+                            0
+                        };
+                        scrub_exif(&mut chunk[skip..], policy);
+                        write_at(file, start, &chunk)?;
+                    }
+                    b"XMP " => write_at(file, start, &vec![b' '; length as usize])?,
+                    _ => {}
+                }
+                at = start + length + (length & 1);
+            }
+        }
+        ImageFormat::Heic => {
+            // Windows overlap by one marker so none is split
+            let marker = EXIF_HEADER.len() + 4;
+            let mut starts = Vec::new();
+            let mut offset = 0;
+            while offset < len {
+                let window = read_at(
+                    file,
+                    offset,
+                    (len - offset).min(STAGED_HEAD_BYTES as u64) as usize,
+                )?;
+                starts.extend(
+                    window
+                        .windows(marker)
+                        .enumerate()
+                        .filter(|(_, window)| is_exif_marker(window))
+                        .map(|(at, _)| offset + (at + EXIF_HEADER.len()) as u64),
+                );
+                if offset + window.len() as u64 >= len {
+                    break;
+                }
+                offset += (window.len() - (marker - 1)) as u64;
+            }
+            for start in starts {
+                let mut tiff = read_at(
+                    file,
+                    start,
+                    (len - start).min(MAX_METADATA_BYTES as u64) as usize,
+                )?;
+                if !scrub_tiff(&mut tiff, policy) {
+                    return Err(UploadError::InvalidImage);
+                }
+                write_at(file, start, &tiff)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Scrubs an APP1 payload in place, returning whether it held EXIF or XMP.
+fn scrub_jpeg_segment(marker: u8, payload: &mut [u8], policy: ExifPolicy) -> bool {
+    if marker != 0xE1 {
+        false
+    } else if payload.starts_with(EXIF_HEADER) {
+        scrub_exif(&mut payload[EXIF_HEADER.len()..], policy);
+        true
+    } else if payload.starts_with(XMP_HEADER) {
+        payload.fill(b' ');
+        true
+    } else {
+        false
+    }
+}
+
+/// `Exif\0\0` followed by a TIFF header, as found in HEIC item data.
+fn is_exif_marker(window: &[u8]) -> bool {
+    window.starts_with(EXIF_HEADER)
+        && matches!(
+            window.get(EXIF_HEADER.len()..EXIF_HEADER.len() + 4),
+            Some(b"II*\0" | b"MM\0*")
+        )
+}
+
+/// `normalize_staged` for an image passing through. Bytes are released once no
+/// metadata block can start in them, so at most one block, or the JPEG markers up
+/// to the start of scan, is held in memory.
+pub struct StreamScrubber {
+    format: ImageFormat,
+    policy: ExifPolicy,
+    held: Vec<u8>,
+    /// Image offset of `held[0]`.
+    offset: u64,
+    /// Image offset of the next chunk header or marker to inspect.
+    next: u64,
+    done: bool,
+}
+
+impl StreamScrubber {
+    pub fn new(format: ImageFormat, policy: ExifPolicy) -> Self {
+        Self {
+            format,
+            policy,
+            held: Vec::new(),
+            offset: 0,
+            next: match format {
+                ImageFormat::Png => 8,
+                ImageFormat::WebP => 12,
+                ImageFormat::Jpeg | ImageFormat::Heic => 0,
+            },
+            done: policy == ExifPolicy::Keep,
+        }
+    }
+
+    /// Takes the next bytes of the image and returns those ready to forward.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<u8>, UploadError> {
+        self.held.extend_from_slice(bytes);
+        if !self.done {
+            self.scan(false)?;
+        }
+        let ready = if self.done {
+            self.held.len()
+        } else {
+            ((self.next - self.offset) as usize).min(self.held.len())
+        };
+        self.offset += ready as u64;
+        let rest = self.held.split_off(ready);
+        Ok(std::mem::replace(&mut self.held, rest))
+    }
+
+    /// Bytes still held once the image has ended.
+    pub fn finish(mut self) -> Result<Vec<u8>, UploadError> {
+        if !self.done {
+            self.scan(true)?;
+        }
+        Ok(self.held)
+    }
+
+    fn scan(&mut self, ended: bool) -> Result<(), UploadError> {
+        match self.format {
+            ImageFormat::Jpeg => self.scan_jpeg(ended),
+            ImageFormat::Png | ImageFormat::WebP => self.scan_chunks(ended),
+            ImageFormat::Heic => self.scan_heic(ended),
+        }
+    }
+
+    /// EXIF and XMP segments all precede the start of scan.
+    fn scan_jpeg(&mut self, ended: bool) -> Result<(), UploadError> {
+        let Some(segments) = jpeg_segments(&self.held) else {
+            if ended || self.held.len() > STAGED_HEAD_BYTES {
+                return Err(UploadError::InvalidImage);
+            }
+            return Ok(());
+        };
+        for segment in segments {
+            scrub_jpeg_segment(segment.marker, &mut self.held[segment.data], self.policy);
+        }
+        self.done = true;
+        Ok(())
+    }
+
+    fn scan_chunks(&mut self, ended: bool) -> Result<(), UploadError> {
+        let png = self.format == ImageFormat::Png;
+        loop {
+            let at = (self.next - self.offset) as usize;
+            let Some(header) = self.held.get(at..at + 8) else {
+                return Ok(());
+            };
+            let (kind, length) = if png {
+                (&header[4..8], be_u32(header, 0))
+            } else {
+                (&header[..4], le_u32(header, 4))
+            };
+            let kind: [u8; 4] = kind.try_into().map_err(|_| UploadError::InvalidImage)?;
+            let length = length.ok_or(UploadError::InvalidImage)? as usize;
+            let data = at + 8;
+            let metadata = match &kind {
+                b"IEND" if png => {
+                    self.done = true;
+                    return Ok(());
+                }
+                b"eXIf" if png => true,
+                // Too short to hold the keyword and the blanked header
+                b"iTXt" if png && length < PNG_XMP_KEYWORD.len() => false,
+                b"iTXt" if png => {
+                    let keyword = data + PNG_XMP_KEYWORD.len();
+                    if self.held.len() < keyword {
+                        return Ok(());
+                    }
+                    &self.held[data..keyword] == PNG_XMP_KEYWORD
+                }
+                b"EXIF" | b"XMP " => !png,
+                _ => false,
+            };
+            // PNG chunks end with a CRC, RIFF chunks are padded to even length
+            let end = if png {
+                data + length + 4
+            } else {
+                data + length
+            };
+            if metadata {
+                if end - at > MAX_METADATA_BYTES {
+                    return Err(UploadError::InvalidImage);
+                }
+                if self.held.len() < end {
+                    return if ended {
+                        Err(UploadError::InvalidImage)
+                    } else {
+                        Ok(())
+                    };
+                }
+                self.scrub_chunk(&kind == b"eXIf" || &kind == b"EXIF", at, length);
+            }
+            let end = if png { end } else { end + (length & 1) };
+            self.next = self.offset + end as u64;
+        }
+    }
+
+    fn scrub_chunk(&mut self, exif: bool, at: usize, length: usize) {
+        let data = at + 8..at + 8 + length;
+        match self.format {
+            ImageFormat::Png => {
+                if exif {
+                    scrub_exif(&mut self.held[data.clone()], self.policy);
+                } else {
+                    blank_itxt(&mut self.held[data.clone()]);
+                }
+                let crc = crc32(&self.held[at + 4..data.end]);
+                self.held[data.end..data.end + 4].copy_from_slice(&crc.to_be_bytes());
+            }
+            _ if exif => {
+                let chunk = &mut self.held[data];
+                let skip = if chunk.starts_with(EXIF_HEADER) {
+                    EXIF_HEADER.len()
+                } else {
+                    0
+                };
+                scrub_exif(&mut chunk[skip..], self.policy);
+            }
+            _ => self.held[data].fill(b' '),
+        }
+    }
+
+    /// Searches for EXIF markers, holding the bytes that may begin one.
+    fn scan_heic(&mut self, ended: bool) -> Result<(), UploadError> {
+        let marker = EXIF_HEADER.len() + 4;
+        loop {
+            let from = (self.next - self.offset) as usize;
+            let found = self.held[from..]
+                .windows(marker)
+                .position(is_exif_marker)
+                .map(|at| from + at);
+            let Some(at) = found else {
+                let safe = self.held.len().saturating_sub(marker - 1).max(from);
+                self.next = self.offset + safe as u64;
+                return Ok(());
+            };
+            self.next = self.offset + at as u64;
+            let start = at + EXIF_HEADER.len();
+            if self.held.len() < start + MAX_METADATA_BYTES && !ended {
+                return Ok(());
+            }
+            let end = self.held.len().min(start + MAX_METADATA_BYTES);
+            if !scrub_tiff(&mut self.held[start..end], self.policy) {
+                return Err(UploadError::InvalidImage);
+            }
+            self.next = self.offset + start as u64 + 4;
+        }
+    }
+}
+
+/// Rewrites an XMP `iTXt` chunk in place as an uncompressed comment of spaces.
+fn blank_itxt(data: &mut [u8]) {
+    // Keyword, compression flag and method, empty language and translated keyword
+    const COMMENT: &[u8] = b"Comment\0\0\0\0\0";
+    data.fill(b' ');
+    data[..COMMENT.len()].copy_from_slice(COMMENT);
+}
+
+/// Largest `ispe` (image spatial extents) property, the primary image or its grid.
+fn heic_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    bytes
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == b"ispe")
+        .filter_map(|(at, _)| Some((be_u32(bytes, at + 8)?, be_u32(bytes, at + 12)?)))
+        .max_by_key(|(width, height)| *width as u64 * *height as u64)
+}
+
+/// HEIC stores EXIF as an item payload located through `iloc`; it is found by its
+/// `Exif\0\0` + TIFF header and scrubbed in place.
+fn normalize_heic(bytes: &mut [u8], policy: ExifPolicy) -> Result<(), UploadError> {
+    let starts: Vec<usize> = bytes
+        .windows(EXIF_HEADER.len() + 4)
+        .enumerate()
+        .filter(|(_, window)| is_exif_marker(window))
+        .map(|(at, _)| at + EXIF_HEADER.len())
+        .collect();
+    for start in starts {
+        // Without the item length the TIFF cannot be blanked wholesale
+        if !scrub_tiff(&mut bytes[start..], policy) {
+            return Err(UploadError::InvalidImage);
+        }
+    }
+    Ok(())
+}
+
+/// Scrubs a TIFF block of known length, blanking it when it cannot be parsed.
+fn scrub_exif(tiff: &mut [u8], policy: ExifPolicy) {
+    if !scrub_tiff(tiff, policy) {
+        tiff.fill(0);
+    }
+}
+
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_INTEROP_IFD: u16 = 0xA005;
+const TAG_THUMBNAIL_OFFSET: u16 = 0x0201;
+const TAG_THUMBNAIL_LENGTH: u16 = 0x0202;
+
+struct Tiff<'a> {
+    bytes: &'a mut [u8],
+    little_endian: bool,
+    visited: HashSet<usize>,
+}
+
+impl Tiff<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let raw: [u8; 2] = self.bytes.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(raw)
+        } else {
+            u16::from_be_bytes(raw)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let raw: [u8; 4] = self.bytes.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(raw)
+        } else {
+            u32::from_be_bytes(raw)
+        })
+    }
+
+    fn put_u16(&mut self, at: usize, value: u16) {
+        let raw = if self.little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        };
+        self.bytes[at..at + 2].copy_from_slice(&raw);
+    }
+
+    fn put_u32(&mut self, at: usize, value: u32) {
+        let raw = if self.little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        };
+        self.bytes[at..at + 4].copy_from_slice(&raw);
+    }
+
+    fn zero(&mut self, at: usize, len: usize) -> Option<()> {
+        self.bytes.get_mut(at..at.checked_add(len)?)?.fill(0);
+        Some(())
+    }
+
+    /// Drops entries not accepted by `keep`, blanking their out-of-line values and
+    /// any sub-IFD or thumbnail they point to. Returns the next IFD offset.
+    fn retain(&mut self, offset: usize, keep: &dyn Fn(u16) -> bool) -> Option<u32> {
+        if !self.visited.insert(offset) {
+            return None;
+        }
+        let count = self.u16(offset)? as usize;
+        let entries = offset + 2;
+        let next_at = entries + count * 12;
+        let next = self.u32(next_at)?;
+        let mut kept = Vec::new();
+        let mut thumbnail = (None, None);
+        for index in 0..count {
+            let at = entries + index * 12;
+            let tag = self.u16(at)?;
+            let entry: [u8; 12] = self.bytes[at..at + 12].try_into().ok()?;
+            if keep(tag) {
+                kept.push(entry);
+                continue;
+            }
+            let kind = self.u16(at + 2)?;
+            let size = match kind {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 | 13 => 4,
+                5 | 10 | 12 => 8,
+                _ => 0,
+            } * self.u32(at + 4)? as usize;
+            let value = self.u32(at + 8)?;
+            if size > 4 {
+                self.zero(value as usize, size)?;
+            }
+            match tag {
+                TAG_EXIF_IFD | TAG_GPS_IFD | TAG_INTEROP_IFD => {
+                    self.clear(value as usize)?;
+                }
+                TAG_THUMBNAIL_OFFSET => thumbnail.0 = Some(value as usize),
+                TAG_THUMBNAIL_LENGTH => thumbnail.1 = Some(value as usize),
+                _ => {}
+            }
+        }
+        if let (Some(start), Some(len)) = thumbnail {
+            self.zero(start, len)?;
+        }
+        // Compact the kept entries, the next IFD pointer follows them
+        self.zero(entries, count * 12 + 4)?;
+        for (index, entry) in kept.iter().enumerate() {
+            let at = entries + index * 12;
+            self.bytes[at..at + 12].copy_from_slice(entry);
+        }
+        self.put_u16(offset, kept.len() as u16);
+        self.put_u32(entries + kept.len() * 12, next);
+        Some(next)
+    }
+
+    fn clear(&mut self, offset: usize) -> Option<()> {
+        self.retain(offset, &|_| false).map(|_| ())
+    }
+}
+
+/// Returns false when the TIFF structure is malformed.
+fn scrub_tiff(bytes: &mut [u8], policy: ExifPolicy) -> bool {
+    let little_endian = match bytes.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return false,
+    };
+    let mut tiff = Tiff {
+        bytes,
+        little_endian,
+        visited: HashSet::new(),
+    };
+    let scrubbed = (|| {
+        if tiff.u16(2)? != 42 {
+            return None;
+        }
+        let ifd0 = tiff.u32(4)? as usize;
+        match policy {
+            ExifPolicy::Keep => Some(()),
+            ExifPolicy::StripGps => tiff.retain(ifd0, &|tag| tag != TAG_GPS_IFD).map(|_| ()),
+            ExifPolicy::Strip => {
+                let mut ifd = tiff.retain(ifd0, &|tag| tag == TAG_ORIENTATION)? as usize;
+                let next_at = ifd0 + 2 + tiff.u16(ifd0)? as usize * 12;
+                tiff.put_u32(next_at, 0);
+                // IFD1 and later hold the thumbnail
+                while ifd != 0 {
+                    ifd = tiff.retain(ifd, &|_| false)? as usize;
+                }
+                Some(())
+            }
+        }
+    })();
+    scrubbed.is_some()
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian TIFF: Orientation, Make and a GPS IFD with one rational triple.
+    fn tiff() -> Vec<u8> {
+        let mut t = b"II\x2a\x00\x08\x00\x00\x00".to_vec();
+        t.extend_from_slice(&3u16.to_le_bytes());
+        for (tag, kind, count, value) in [
+            (0x0112u16, 3u16, 1u32, 6u32),
+            (0x010F, 2, 10, 50),
+            (0x8825, 4, 1, 60),
+        ] {
+            t.extend_from_slice(&tag.to_le_bytes());
+            t.extend_from_slice(&kind.to_le_bytes());
+            t.extend_from_slice(&count.to_le_bytes());
+            t.extend_from_slice(&value.to_le_bytes());
+        }
+        t.extend_from_slice(&0u32.to_le_bytes());
+        t.extend_from_slice(b"CanonEOS!!");
+        t.extend_from_slice(&1u16.to_le_bytes());
+        t.extend_from_slice(&0x0002u16.to_le_bytes());
+        t.extend_from_slice(&5u16.to_le_bytes());
+        t.extend_from_slice(&3u32.to_le_bytes());
+        t.extend_from_slice(&80u32.to_le_bytes());
+        t.extend_from_slice(&[0; 6]);
+        t.extend_from_slice(&[0x11; 24]);
+        assert_eq!(t.len(), 104);
+        t
+    }
+
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut j = vec![0xFF, 0xD8];
+        let exif = [EXIF_HEADER, &tiff()].concat();
+        j.extend_from_slice(&[0xFF, 0xE1]);
+        j.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        j.extend_from_slice(&exif);
+        let xmp = [XMP_HEADER, b"<x:xmpmeta/>"].concat();
+        j.extend_from_slice(&[0xFF, 0xE1]);
+        j.extend_from_slice(&(xmp.len() as u16 + 2).to_be_bytes());
+        j.extend_from_slice(&xmp);
+        j.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08]);
+        j.extend_from_slice(&height.to_be_bytes());
+        j.extend_from_slice(&width.to_be_bytes());
+        j.extend_from_slice(&[3, 1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        j.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x08, 1, 2, 3, 4, 5, 6, 0xAB, 0xCD]);
+        j.extend_from_slice(&[0xFF, 0xD9]);
+        j
+    }
+
+    fn exif_of(jpeg: &[u8]) -> &[u8] {
+        let at = jpeg.windows(6).position(|w| w == EXIF_HEADER).unwrap() + 6;
+        &jpeg[at..at + 104]
+    }
+
+    fn config() -> UploadConfig {
+        UploadConfig {
+            max_bytes: 1024,
+            max_pixels: 10_000,
+            exif_policy: ExifPolicy::StripGps,
+            ..UploadConfig::default()
+        }
+    }
+
+    #[test]
+    fn sniffs_allowed_formats_only() {
+        assert_eq!(sniff(&jpeg(1, 1)), Some(ImageFormat::Jpeg));
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n...."), Some(ImageFormat::Png));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8X"), Some(ImageFormat::WebP));
+        assert_eq!(
+            sniff(b"\0\0\0\x18ftypmif1\0\0\0\0mif1heic"),
+            Some(ImageFormat::Heic)
+        );
+        assert_eq!(sniff(b"\0\0\0\x14ftypavif\0\0\0\0avif"), None);
+        assert_eq!(sniff(b"GIF89a......"), None);
+        assert_eq!(sniff(b"<svg xmlns=\"http://www.w3.org/2000/svg\">"), None);
+    }
+
+    #[test]
+    fn reads_dimensions_and_enforces_limits() {
+        assert_eq!(
+            dimensions(ImageFormat::Jpeg, &jpeg(640, 480)),
+            Some((640, 480))
+        );
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+        webp.extend_from_slice(&[0x7F, 0x02, 0x00, 0xDF, 0x01, 0x00]);
+        assert_eq!(dimensions(ImageFormat::WebP, &webp), Some((640, 480)));
+
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 0, 100, 0, 0, 0, 50]);
+        assert_eq!(dimensions(ImageFormat::Png, &png), Some((100, 50)));
+
+        assert_eq!(
+            validate_image(&jpeg(100, 100), &config()),
+            Ok(ImageFormat::Jpeg)
+        );
+        assert_eq!(
+            validate_image(&jpeg(101, 100), &config()),
+            Err(UploadError::TooManyPixels { max_pixels: 10_000 })
+        );
+        assert_eq!(
+            validate_image(&[0xFF; 2048], &config()),
+            Err(UploadError::TooLarge { max_bytes: 1024 })
+        );
+        assert_eq!(
+            validate_image(&jpeg(0, 10), &config()),
+            Err(UploadError::InvalidImage)
+        );
+    }
+
+    #[test]
+    fn strip_gps_keeps_other_tags() {
+        let out = normalize_metadata(ImageFormat::Jpeg, jpeg(8, 8), ExifPolicy::StripGps).unwrap();
+        let tiff = exif_of(&out);
+
+        assert_eq!(u16::from_le_bytes([tiff[8], tiff[9]]), 2);
+        assert_eq!(&tiff[50..60], b"CanonEOS!!");
+        assert!(tiff[60..].iter().all(|byte| *byte == 0));
+        assert!(!out.windows(XMP_HEADER.len()).any(|w| w == XMP_HEADER));
+        assert_eq!(dimensions(ImageFormat::Jpeg, &out), Some((8, 8)));
+        assert!(out.ends_with(&[0xAB, 0xCD, 0xFF, 0xD9]));
+    }
+
+    #[test]
+    fn strip_keeps_only_orientation() {
+        let out = normalize_metadata(ImageFormat::Jpeg, jpeg(8, 8), ExifPolicy::Strip).unwrap();
+        let tiff = exif_of(&out);
+
+        assert_eq!(u16::from_le_bytes([tiff[8], tiff[9]]), 1);
+        assert_eq!(u16::from_le_bytes([tiff[10], tiff[11]]), TAG_ORIENTATION);
+        assert_eq!(u16::from_le_bytes([tiff[18], tiff[19]]), 6);
+        assert!(tiff[22..].iter().all(|byte| *byte == 0));
+
+        let kept = normalize_metadata(ImageFormat::Jpeg, jpeg(8, 8), ExifPolicy::Keep).unwrap();
+        assert_eq!(kept, jpeg(8, 8));
+    }
+
+    #[test]
+    fn staged_jpeg_is_scrubbed_in_place() {
+        let original = jpeg(8, 8);
+        let mut staged = std::io::Cursor::new(original.clone());
+        normalize_staged(&mut staged, ImageFormat::Jpeg, ExifPolicy::StripGps).unwrap();
+        let out = staged.into_inner();
+        let tiff = exif_of(&out);
+
+        assert_eq!(out.len(), original.len());
+        assert_eq!(u16::from_le_bytes([tiff[8], tiff[9]]), 2);
+        assert_eq!(&tiff[50..60], b"CanonEOS!!");
+        assert!(tiff[60..].iter().all(|byte| *byte == 0));
+        assert!(!out.windows(XMP_HEADER.len()).any(|w| w == XMP_HEADER));
+        assert_eq!(validate_head(&out, &config()), Ok(ImageFormat::Jpeg));
+    }
+
+    #[test]
+    fn png_exif_chunk_is_scrubbed_with_valid_crc() {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let tiff = tiff();
+        png.extend_from_slice(&(tiff.len() as u32).to_be_bytes());
+        png.extend_from_slice(b"eXIf");
+        png.extend_from_slice(&tiff);
+        png.extend_from_slice(&crc32(&[b"eXIf".as_slice(), &tiff].concat()).to_be_bytes());
+        png.extend_from_slice(&[0, 0, 0, 0]);
+        png.extend_from_slice(b"IEND");
+        png.extend_from_slice(&crc32(b"IEND").to_be_bytes());
+
+        let out = normalize_metadata(ImageFormat::Png, png, ExifPolicy::StripGps).unwrap();
+        let data = &out[16..16 + 104];
+        assert!(data[60..].iter().all(|byte| *byte == 0));
+        let crc = u32::from_be_bytes(out[120..124].try_into().unwrap());
+        assert_eq!(crc, crc32(&out[12..120]));
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn multipart_body_uses_sniffed_type_and_safe_name() {
+        assert_eq!(
+            safe_file_name(Some("C:\\fotos\\Ri\"ss 1.PNG"), ImageFormat::Jpeg),
+            "Riss 1.jpg"
+        );
+        assert_eq!(
+            safe_file_name(Some("../.."), ImageFormat::Png),
+            "upload.png"
+        );
+
+        let fields = vec![(
+            "berlin_datetime".to_string(),
+            "2026-01-01 10:00".to_string(),
+        )];
+        let (content_type, head, tail) = multipart_frame(&fields, "a.webp", ImageFormat::WebP);
+        let body = [head.as_slice(), &[1, 2, 3], &tail].concat();
+        let boundary = content_type.split_once("boundary=").unwrap().1;
+        let body = String::from_utf8_lossy(&body);
+
+        assert!(body.starts_with(&format!("--{boundary}\r\n")));
+        assert!(body.contains("name=\"berlin_datetime\"\r\n\r\n2026-01-01 10:00\r\n"));
+        assert!(body.contains("filename=\"a.webp\"\r\nContent-Type: image/webp\r\n\r\n"));
+        assert!(body.ends_with(&format!("\r\n--{boundary}--\r\n")));
+    }
+
+    fn png_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&crc32(&[kind, data].concat()).to_be_bytes());
+        chunk
+    }
+
+    fn png(idat: &[u8]) -> Vec<u8> {
+        let ihdr = [
+            &8u32.to_be_bytes()[..],
+            &8u32.to_be_bytes(),
+            &[8, 2, 0, 0, 0],
+        ]
+        .concat();
+        [
+            b"\x89PNG\r\n\x1a\n".to_vec(),
+            png_chunk(b"IHDR", &ihdr),
+            png_chunk(
+                b"iTXt",
+                &[PNG_XMP_KEYWORD, b"\0\0\0\0<x:xmpmeta/>"].concat(),
+            ),
+            png_chunk(b"IDAT", idat),
+            png_chunk(b"eXIf", &tiff()),
+            png_chunk(b"IEND", &[]),
+        ]
+        .concat()
+    }
+
+    fn scrub_streamed(format: ImageFormat, image: &[u8], piece: usize) -> Vec<u8> {
+        let mut scrubber = StreamScrubber::new(format, ExifPolicy::StripGps);
+        let mut out = Vec::new();
+        for bytes in image.chunks(piece) {
+            out.extend(scrubber.push(bytes).unwrap());
+        }
+        out.extend(scrubber.finish().unwrap());
+        out
+    }
+
+    #[test]
+    fn streamed_scrub_matches_staged_scrub() {
+        let webp = {
+            let exif = [EXIF_HEADER, &tiff()].concat();
+            let mut riff = b"WEBP".to_vec();
+            for (kind, data) in [
+                (b"VP8X", [0u8; 10].as_slice()),
+                (b"EXIF", &exif),
+                (b"XMP ", b"<x:xmpmeta/>!"),
+            ] {
+                riff.extend_from_slice(kind);
+                riff.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                riff.extend_from_slice(data);
+                if data.len() % 2 == 1 {
+                    riff.push(0);
+                }
+            }
+            [b"RIFF", &(riff.len() as u32).to_le_bytes()[..], &riff].concat()
+        };
+        let heic = [
+            b"\0\0\0\x18ftypheic\0\0\0\0mif1heic".as_slice(),
+            &[7; 300],
+            EXIF_HEADER,
+            &tiff(),
+            &[9; 300],
+        ]
+        .concat();
+
+        for (format, image) in [
+            (ImageFormat::Jpeg, jpeg(8, 8)),
+            (ImageFormat::Png, png(&[5; 1000])),
+            (ImageFormat::WebP, webp),
+            (ImageFormat::Heic, heic),
+        ] {
+            let mut staged = std::io::Cursor::new(image.clone());
+            normalize_staged(&mut staged, format, ExifPolicy::StripGps).unwrap();
+            let staged = staged.into_inner();
+
+            assert_ne!(staged, image, "{format:?}");
+            for piece in [1, 7, image.len()] {
+                assert_eq!(scrub_streamed(format, &image, piece), staged, "{format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn short_itxt_chunk_is_not_taken_for_xmp() {
+        let image = png(&[]);
+        let idat_at = image.windows(4).position(|w| w == b"IDAT").unwrap() - 4;
+        // A 4 byte iTXt chunk followed by bytes that spell the XMP keyword
+        let crafted = [
+            &image[..idat_at],
+            &4u32.to_be_bytes(),
+            b"iTXt",
+            PNG_XMP_KEYWORD,
+            &image[idat_at..],
+        ]
+        .concat();
+
+        let mut scrubber = StreamScrubber::new(ImageFormat::Png, ExifPolicy::StripGps);
+        let pushed = scrubber.push(&crafted).and_then(|mut out| {
+            out.extend(scrubber.finish()?);
+            Ok(out)
+        });
+        if let Ok(out) = pushed {
+            assert!(
+                out.windows(PNG_XMP_KEYWORD.len())
+                    .any(|w| w == PNG_XMP_KEYWORD)
+            );
+        }
+    }
+
+    #[test]
+    fn streamed_scrub_holds_one_block_of_large_image() {
+        const PIECE: usize = 64 * 1024;
+        let image = png(&[]);
+        let idat_at = image.windows(4).position(|w| w == b"IDAT").unwrap() - 4;
+        let (start, end) = (&image[..idat_at], &image[idat_at + 12..]);
+        let idat = png_chunk(b"IDAT", &[5; PIECE - 12]);
+        let mut scrubber = StreamScrubber::new(ImageFormat::Png, ExifPolicy::StripGps);
+        let (mut total, mut peak) = (0, 0);
+        let mut push = |bytes: &[u8]| {
+            let out = scrubber.push(bytes).unwrap();
+            peak = peak.max(scrubber.held.len());
+            total += out.len();
+            out
+        };
+
+        push(start);
+        // 64 MiB of image data
+        for _ in 0..1024 {
+            push(&idat);
+        }
+        let tail = push(end);
+
+        assert!(scrubber.finish().unwrap().is_empty());
+        assert!(peak <= PIECE, "held {peak} bytes");
+        assert_eq!(total, start.len() + 1024 * PIECE + end.len());
+        let exif = tail.windows(4).position(|w| w == b"eXIf").unwrap() + 4;
+        assert!(tail[exif + 60..exif + 104].iter().all(|byte| *byte == 0));
+    }
+
+    #[tokio::test]
+    async fn upload_stream_is_signed_by_trailer() {
+        use axum::extract::FromRequest;
+
+        let image = png(&[5; 512 * 1024]);
+        let form = [
+            b"--b\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nRiss\r\n".as_slice(),
+            b"--b\r\nContent-Disposition: form-data; name=\"image\"; filename=\"x.png\"\r\n\r\n",
+            &image,
+            b"\r\n--b\r\nContent-Disposition: form-data; name=\"berlin_datetime\"\r\n\r\n01.02.2026 10:00:00\r\n--b--\r\n",
+        ]
+        .concat();
+        let request = axum::http::Request::builder()
+            .header("content-type", "multipart/form-data; boundary=b")
+            .body(axum::body::Body::from(form))
+            .unwrap();
+        let multipart = Multipart::from_request(request, &()).await.unwrap();
+        let signer = TrailerSigner::new("demo-secret").unwrap();
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let (body_tx, body_rx) = mpsc::channel(1);
+        let config = UploadConfig {
+            max_bytes: 1024 * 1024,
+            ..UploadConfig::default()
+        };
+
+        let (forwarded, pieces) = tokio::join!(
+            stream_upload(multipart, &config, "out", Some(signer), ready_tx, body_tx),
+            futures::StreamExt::collect::<Vec<_>>(body_rx)
+        );
+        forwarded.unwrap();
+        ready_rx.await.unwrap();
+        let mut pieces: Vec<Bytes> = pieces.into_iter().map(Result::unwrap).collect();
+        let trailer = String::from_utf8(pieces.pop().unwrap().to_vec()).unwrap();
+        let signed = pieces.concat();
+        let timestamp: i64 = trailer
+            .strip_prefix(&format!(
+                "--out\r\nContent-Disposition: form-data; name=\"{TIMESTAMP_FIELD}\"\r\n\r\n"
+            ))
+            .and_then(|rest| rest.split("\r\n").next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut verifier = TrailerSigner::new("demo-secret").unwrap();
+        verifier.update(&signed);
+        let (_, signature) = verifier.finish_at(timestamp);
+
+        assert!(trailer.ends_with(&format!(
+            "{timestamp}\r\n--out\r\nContent-Disposition: form-data; name=\"{SIGNATURE_FIELD}\"\r\n\r\n{signature}\r\n--out--\r\n"
+        )));
+        let signed = String::from_utf8_lossy(&signed);
+        assert!(signed.starts_with("--out\r\nContent-Disposition: form-data; name=\"image\"; filename=\"x.png\"\r\nContent-Type: image/png\r\n\r\n"));
+        let hash = hex::encode(Sha256::digest(&image));
+        assert!(signed.ends_with(&format!("\r\n--out\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nRiss\r\n--out\r\nContent-Disposition: form-data; name=\"berlin_datetime\"\r\n\r\n01.02.2026 10:00:00\r\n--out\r\nContent-Disposition: form-data; name=\"hash\"\r\n\r\n{hash}\r\n")));
+    }
+}